@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// decoding through `deserialize_any`, as self-describing targets do, must never panic
+fuzz_target!(|data: &[u8]| {
+    let _ = velocypack::from_bytes::<serde_json::Value>(data);
+    let _ = velocypack::from_bytes::<serde::de::IgnoredAny>(data);
+    let _ = velocypack::from_bytes::<std::collections::BTreeMap<char, Vec<char>>>(data);
+    let _ = velocypack::validate(data);
+});
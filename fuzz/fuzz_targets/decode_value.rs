@@ -0,0 +1,9 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// decoding into a `Value`, which keeps every value type, must never panic
+fuzz_target!(|data: &[u8]| {
+    let _ = velocypack::from_bytes::<velocypack::Value>(data);
+    let _ = velocypack::Value::from_bytes(data);
+    let _ = velocypack::to_json_bytes(data);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// anything which decodes must encode again, and decode to a value encoding to the same bytes
+fuzz_target!(|data: &[u8]| {
+    let value = match velocypack::from_bytes::<velocypack::Value>(data) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    let bytes = value.to_bytes().expect("decoded value failed to encode");
+    let decoded = velocypack::from_bytes::<velocypack::Value>(&bytes).expect("encoded value failed to decode");
+    assert_eq!(decoded.to_bytes().unwrap(), bytes);
+});
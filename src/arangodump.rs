@@ -0,0 +1,363 @@
+//! Reading of directories written by ArangoDB's `arangodump` tool.
+//!
+//! A dump directory contains a `<collection>.structure.json` file for each collection (the file
+//! name may also include a hash of the collection name, e.g. `users_5a4b….structure.json`),
+//! along with its data stored either as one JSON document per line (`.data.json`) or as
+//! concatenated VelocyPack values (`.data.vpack`). Compressed data files aren't supported.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::raw;
+
+const STRUCTURE_SUFFIX: &str = ".structure.json";
+
+// legacy dump formats wrap each line as `{"type": <marker type>, "data": <document>}`
+const MARKER_DOCUMENT: u64 = 2300;
+const MARKER_REMOVE: u64 = 2302;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollectionType {
+    Document,
+    Edge,
+}
+
+/// A single record read from a collection's data file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record<T> {
+    Document(T),
+    /// Document from an edge collection, along with its `_from` and `_to` attributes.
+    Edge { from: String, to: String, document: T },
+}
+
+/// A collection found in a dump directory.
+#[derive(Clone, Debug)]
+pub struct Collection {
+    name: String,
+    collection_type: CollectionType,
+    structure: serde_json::Value,
+    data_path: Option<PathBuf>,
+}
+
+impl Collection {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn collection_type(&self) -> CollectionType {
+        self.collection_type
+    }
+
+    /// Contents of the collection's structure file, i.e. its parameters and indexes.
+    pub fn structure(&self) -> &serde_json::Value {
+        &self.structure
+    }
+
+    /// Stream the records of this collection from its data file, decoding each into `T`.
+    ///
+    /// Collections dumped without data (e.g. with `--dump-data false`) yield no records.
+    pub fn records<T: DeserializeOwned>(&self) -> Result<Records<T>> {
+        let source = match self.data_path {
+            Some(ref path) if path.extension().is_some_and(|e| e == "vpack") => {
+                Source::VPack(VPackReader::new(BufReader::new(File::open(path)?)))
+            },
+            Some(ref path) => Source::Json(BufReader::new(File::open(path)?).lines()),
+            None => Source::Empty,
+        };
+        Ok(Records { source, collection_type: self.collection_type, _marker: PhantomData })
+    }
+}
+
+/// Collections of a dump directory.
+#[derive(Clone, Debug)]
+pub struct Dump {
+    collections: Vec<Collection>,
+}
+
+impl Dump {
+    /// Read the structure files of the dump in `dir`.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let mut collections = Vec::new();
+        for entry in fs::read_dir(dir.as_ref())? {
+            let path = entry?.path();
+            let prefix = match path.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_suffix(STRUCTURE_SUFFIX)) {
+                Some(prefix) => prefix.to_owned(),
+                None => continue,
+            };
+
+            let structure: serde_json::Value = serde_json::from_slice(&fs::read(&path)?)
+                .map_err(|e| ErrorKind::Message(format!("invalid structure file {}: {}", path.display(), e)))?;
+            let parameters = &structure["parameters"];
+            let name = match parameters["name"].as_str() {
+                Some(name) => name.to_owned(),
+                None => return Err(ErrorKind::Message(format!("missing collection name in {}", path.display())).into()),
+            };
+            let collection_type = match parameters["type"].as_u64() {
+                Some(3) => CollectionType::Edge,
+                _ => CollectionType::Document,
+            };
+
+            let data_path = ["vpack", "json"].iter()
+                .map(|ext| path.with_file_name(format!("{}.data.{}", prefix, ext)))
+                .find(|p| p.is_file());
+
+            collections.push(Collection { name, collection_type, structure, data_path });
+        }
+        collections.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Self { collections })
+    }
+
+    pub fn collections(&self) -> &[Collection] {
+        &self.collections
+    }
+
+    pub fn collection(&self, name: &str) -> Option<&Collection> {
+        self.collections.iter().find(|c| c.name == name)
+    }
+}
+
+enum Source {
+    Json(io::Lines<BufReader<File>>),
+    VPack(VPackReader<BufReader<File>>),
+    Empty,
+}
+
+/// Iterator over the records of a collection, see [`Collection::records`].
+pub struct Records<T> {
+    source: Source,
+    collection_type: CollectionType,
+    _marker: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Records<T> {
+    // encoded bytes of the next document, skipping removal markers and blank lines
+    fn next_document(&mut self) -> Option<Result<Vec<u8>>> {
+        loop {
+            match self.source {
+                Source::Json(ref mut lines) => {
+                    let line = match lines.next()? {
+                        Ok(line) => line,
+                        Err(e) => return Some(Err(e.into())),
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+
+                    let mut value: serde_json::Value = match serde_json::from_str(&line) {
+                        Ok(value) => value,
+                        Err(e) => return Some(Err(ErrorKind::Message(format!("invalid JSON document: {}", e)).into())),
+                    };
+                    if let Some(marker) = value.get("type").and_then(|t| t.as_u64()) {
+                        if value.get("data").is_some() {
+                            match marker {
+                                MARKER_DOCUMENT => value = value["data"].take(),
+                                MARKER_REMOVE => continue,
+                                _ => {},
+                            }
+                        }
+                    }
+                    return Some(crate::to_bytes(&value));
+                },
+                Source::VPack(ref mut reader) => return reader.next(),
+                Source::Empty => return None,
+            }
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Record<T>> {
+        let document = crate::from_bytes(bytes)?;
+        match self.collection_type {
+            CollectionType::Document => Ok(Record::Document(document)),
+            CollectionType::Edge => {
+                let attribute = |name| match raw::object_get(bytes, name)? {
+                    Some(v) => Ok(raw::read_str(v)?.to_owned()),
+                    None => Err(Error::from(ErrorKind::Message(format!("edge is missing attribute {}", name)))),
+                };
+                Ok(Record::Edge { from: attribute("_from")?, to: attribute("_to")?, document })
+            },
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for Records<T> {
+    type Item = Result<Record<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = match self.next_document()? {
+            Ok(bytes) => bytes,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(self.decode(&bytes))
+    }
+}
+
+/// Reads concatenated VelocyPack values from a reader, one value at a time.
+struct VPackReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> VPackReader<R> {
+    fn new(reader: R) -> Self {
+        Self { reader, buf: Vec::new(), eof: false }
+    }
+
+    // read another chunk into the buffer, returning false if the reader is exhausted
+    fn fill(&mut self) -> Result<bool> {
+        let mut chunk = [0; 64 * 1024];
+        let n = self.reader.read(&mut chunk)?;
+        self.buf.extend_from_slice(&chunk[..n]);
+        self.eof = n == 0;
+        Ok(!self.eof)
+    }
+
+    fn next_value(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            // zero bytes between values are padding
+            let padding = self.buf.iter().take_while(|b| **b == 0x00).count();
+            self.buf.drain(..padding);
+
+            if self.buf.is_empty() {
+                if self.eof || !self.fill()? {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            match raw::value_byte_length(&self.buf) {
+                Ok(length) => {
+                    let rest = self.buf.split_off(length);
+                    return Ok(Some(std::mem::replace(&mut self.buf, rest)));
+                },
+                Err(ref e) if e.kind() == &ErrorKind::Eof && !self.eof => {
+                    self.fill()?;
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for VPackReader<R> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_value().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct User {
+        _key: String,
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Follows {
+        _key: String,
+        since: u32,
+    }
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("velocypack-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn write(&self, name: &str, contents: &[u8]) {
+            fs::write(self.0.join(name), contents).unwrap();
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn read_json_dump() {
+        let dir = TempDir::new("json-dump");
+        dir.write("users_abc123.structure.json", br#"{"indexes": [], "parameters": {"name": "users", "type": 2}}"#);
+        dir.write("users_abc123.data.json", b"{\"_key\":\"a\",\"name\":\"Alice\"}\n\n{\"_key\":\"b\",\"name\":\"Bob\"}\n");
+        dir.write("follows.structure.json", br#"{"indexes": [], "parameters": {"name": "follows", "type": 3}}"#);
+        dir.write("follows.data.json", concat!(
+            r#"{"type":2300,"data":{"_key":"1","_from":"users/a","_to":"users/b","since":2019}}"#, "\n",
+            r#"{"type":2302,"data":{"_key":"0"}}"#, "\n",
+        ).as_bytes());
+        dir.write("empty.structure.json", br#"{"parameters": {"name": "empty", "type": 2}}"#);
+        dir.write("dump.json", b"{}");
+
+        let dump = Dump::open(&dir.0).unwrap();
+        let names: Vec<_> = dump.collections().iter().map(|c| c.name()).collect();
+        assert_eq!(names, vec!["empty", "follows", "users"]);
+
+        let users = dump.collection("users").unwrap();
+        assert_eq!(users.collection_type(), CollectionType::Document);
+        assert_eq!(users.structure()["indexes"], serde_json::json!([]));
+        let records: Vec<_> = users.records::<User>().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(records, vec![
+            Record::Document(User { _key: "a".to_owned(), name: "Alice".to_owned() }),
+            Record::Document(User { _key: "b".to_owned(), name: "Bob".to_owned() }),
+        ]);
+
+        let follows = dump.collection("follows").unwrap();
+        assert_eq!(follows.collection_type(), CollectionType::Edge);
+        let records: Vec<_> = follows.records::<Follows>().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(records, vec![Record::Edge {
+            from: "users/a".to_owned(),
+            to: "users/b".to_owned(),
+            document: Follows { _key: "1".to_owned(), since: 2019 },
+        }]);
+
+        assert_eq!(dump.collection("empty").unwrap().records::<User>().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn read_vpack_dump() {
+        let dir = TempDir::new("vpack-dump");
+        dir.write("users.structure.json", br#"{"parameters": {"name": "users", "type": 2}}"#);
+        let mut data = Vec::new();
+        for (key, name) in &[("a", "Alice"), ("b", "Bob")] {
+            let doc = serde_json::json!({"_key": key, "name": name});
+            data.extend(crate::to_bytes(&doc).unwrap());
+            data.push(0x00);
+        }
+        dir.write("users.data.vpack", &data);
+
+        let dump = Dump::open(&dir.0).unwrap();
+        let records: Vec<_> = dump.collection("users").unwrap().records::<User>().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1], Record::Document(User { _key: "b".to_owned(), name: "Bob".to_owned() }));
+    }
+
+    #[test]
+    fn vpack_reader_chunks() {
+        // values spanning more than one read
+        let big = "x".repeat(100_000);
+        let mut data = crate::to_bytes(&big).unwrap();
+        data.extend(crate::to_bytes(&1u8).unwrap());
+        let values: Vec<_> = VPackReader::new(&data[..]).collect::<Result<_>>().unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(crate::from_bytes::<String>(&values[0]).unwrap(), big);
+        assert_eq!(values[1], vec![0x31]);
+
+        let truncated = &data[..1000];
+        let mut reader = VPackReader::new(truncated);
+        assert_eq!(reader.next(), Some(Err(ErrorKind::Eof.into())));
+    }
+}
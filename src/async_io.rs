@@ -0,0 +1,76 @@
+//! Reading and writing values with tokio's `AsyncRead` and `AsyncWrite`, the async versions of
+//! [`read_value`](crate::read_value) and [`write_value`](crate::write_value). Needs the `tokio`
+//! feature.
+
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::error::{ErrorKind, Result};
+use crate::io::value_byte_length;
+
+/// Read exactly one value from `reader`, using its header and byte length to read no further, so
+/// that the reader is left at the start of whatever follows (e.g. the next value of a stream).
+/// Returns `None` if the reader is already at its end.
+pub async fn read_value<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut bytes = Vec::new();
+    loop {
+        match value_byte_length(&bytes) {
+            Ok(length) if length > bytes.len() => {
+                // grow the buffer only as bytes arrive, rather than trusting the byte length
+                let remaining = (length - bytes.len()) as u64;
+                (&mut *reader).take(remaining).read_to_end(&mut bytes).await?;
+                if bytes.len() != length {
+                    return Err(ErrorKind::Eof.into());
+                }
+                return Ok(Some(bytes));
+            },
+            Ok(length) if length == bytes.len() => return Ok(Some(bytes)),
+            Ok(_) => return Err(ErrorKind::Eof.into()),
+            Err(ref e) if e.kind() == &ErrorKind::Eof => {
+                let mut b = [0x00];
+                if reader.read(&mut b).await? == 0 {
+                    return if bytes.is_empty() { Ok(None) } else { Err(ErrorKind::Eof.into()) };
+                }
+                bytes.push(b[0]);
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Serialize `value` and write it to `writer`.
+pub async fn write_value<W: AsyncWrite + Unpin, T: ?Sized + Serialize>(writer: &mut W, value: &T) -> Result<()> {
+    writer.write_all(&crate::to_bytes(value)?).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn read_write() {
+        let mut stream = Vec::new();
+        write_value(&mut stream, &vec!["a", "b"]).await.unwrap();
+        write_value(&mut stream, &1).await.unwrap();
+        stream.extend_from_slice(&[0xee, 0x01, 0x41, 0x61]);
+
+        let mut reader = &stream[..];
+        assert_eq!(read_value(&mut reader).await.unwrap().unwrap(), crate::to_bytes(&vec!["a", "b"]).unwrap());
+        assert_eq!(read_value(&mut reader).await.unwrap().unwrap(), &[0x31]);
+        assert_eq!(read_value(&mut reader).await.unwrap().unwrap(), &[0xee, 0x01, 0x41, 0x61]);
+        assert_eq!(read_value(&mut reader).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn truncated() {
+        assert_eq!(read_value(&mut &[0x02, 0x04, 0x31][..]).await, Err(ErrorKind::Eof.into()));
+        assert_eq!(read_value(&mut &[0xbf, 0, 0, 0, 0, 0, 0, 0, 0x7f, 0x61][..]).await, Err(ErrorKind::Eof.into()));
+        assert_eq!(read_value(&mut &[0x15][..]).await, Err(ErrorKind::Unimplemented(0x15).into()));
+
+        // nothing past the value is read
+        let mut reader = &[0x0b, 0x07, 0x01, 0x41, 0x61, 0x31, 0x03, 0xff][..];
+        read_value(&mut reader).await.unwrap();
+        assert_eq!(reader, &[0xff]);
+    }
+}
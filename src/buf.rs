@@ -0,0 +1,51 @@
+use bytes::{Buf, BufMut};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::ser::SerializerOptions;
+
+/// Deserialize a single VelocyPack from the bytes remaining in `buf`, consuming them. Buffers
+/// holding their bytes as a `Bytes` (e.g. `Bytes` and `BytesMut`) are decoded without copying,
+/// as by [`from_bytes_shared`](crate::from_bytes_shared); others are first copied into one
+/// contiguous buffer.
+pub fn from_buf<T: DeserializeOwned>(mut buf: impl Buf) -> Result<T> {
+    let remaining = buf.remaining();
+    crate::from_bytes_shared(buf.copy_to_bytes(remaining))
+}
+
+/// Serialize `value` onto the end of `buf`.
+pub fn to_buf<T: ?Sized + Serialize>(value: &T, buf: &mut impl BufMut) -> Result<()> {
+    to_buf_with_options(value, buf, &SerializerOptions::default())
+}
+
+/// Serialize `value` onto the end of `buf` like [`to_buf`], with the encoding chosen by
+/// `options`.
+pub fn to_buf_with_options<T: ?Sized + Serialize>(value: &T, buf: &mut impl BufMut, options: &SerializerOptions) -> Result<()> {
+    buf.put_slice(&crate::to_bytes_with_options(value, options)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::{Bytes, BytesMut};
+
+    #[test]
+    fn buf() {
+        let mut out = BytesMut::new();
+        to_buf(&vec!["a", "b"], &mut out).unwrap();
+        assert_eq!(&out[..], &crate::to_bytes(&vec!["a", "b"]).unwrap()[..]);
+        let options = SerializerOptions::new().compact_containers(true);
+        let mut compact = Vec::new();
+        to_buf_with_options(&vec!["a", "b"], &mut compact, &options).unwrap();
+        assert_eq!(compact, crate::to_bytes_with_options(&vec!["a", "b"], &options).unwrap());
+
+        assert_eq!(from_buf::<Vec<String>>(out.clone()).unwrap(), vec!["a", "b"]);
+        assert_eq!(from_buf::<Vec<String>>(out.freeze()).unwrap(), vec!["a", "b"]);
+        // split over two chunks
+        let chained = Bytes::from_static(&[0x02, 0x05, 0x31]).chain(Bytes::from_static(&[0x41, 0x61]));
+        assert_eq!(from_buf::<(u8, String)>(chained).unwrap(), (1, "a".to_owned()));
+        assert!(from_buf::<u8>(&[0x31, 0x32][..]).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use serde::Serialize;
+
+use crate::error::{ErrorKind, Result};
+use crate::ser::{self, SerializerOptions};
+
+/// Builds VelocyPack step by step rather than from a `Serialize` type, for documents whose shape
+/// is only known at runtime:
+///
+/// ```
+/// let mut builder = velocypack::Builder::new();
+/// builder.open_object()?;
+/// builder.add("name", "Bob")?;
+/// builder.key("tags")?;
+/// builder.open_array()?;
+/// builder.push(&1)?;
+/// builder.push(&2)?;
+/// builder.close()?;
+/// builder.close()?;
+/// let bytes = builder.finish()?;
+/// assert_eq!(velocypack::from_bytes::<serde_json::Value>(&bytes)?, serde_json::json!({"name": "Bob", "tags": [1, 2]}));
+/// # Ok::<(), velocypack::Error>(())
+/// ```
+///
+/// Values are added with [`push`](Builder::push), which takes anything `Serialize` (including
+/// [`RawVPack`](crate::RawVPack) values, which are copied as-is). Inside an object each value
+/// needs a key, set with [`key`](Builder::key) or by adding the member with
+/// [`add`](Builder::add). Values added outside of any container are written one after another.
+#[derive(Default)]
+pub struct Builder {
+    output: Vec<u8>,
+    options: SerializerOptions,
+    // containers currently open, innermost last
+    open: Vec<Open>,
+    // key of the next value added to the innermost object
+    key: Option<Vec<u8>>,
+}
+
+// an open container, with its key if it's a member of an object
+struct Open {
+    key: Option<Vec<u8>>,
+    items: Vec<Vec<u8>>,
+    // keys of an object's members, `None` for arrays
+    keys: Option<Vec<Vec<u8>>>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encode values, and arrays and objects when they are closed, with `options`.
+    pub fn with_options(options: SerializerOptions) -> Self {
+        Builder { options, ..Self::default() }
+    }
+
+    /// Write values after the existing contents of `buffer`, e.g. a protocol header, rather than
+    /// into a new buffer. The buffer can be taken back without copying with
+    /// [`finish`](Builder::finish) or [`into_inner`](Builder::into_inner).
+    pub fn append_to(mut self, buffer: Vec<u8>) -> Self {
+        self.output = buffer;
+        self
+    }
+
+    pub fn open_array(&mut self) -> Result<()> {
+        self.open(None)
+    }
+
+    pub fn open_object(&mut self) -> Result<()> {
+        self.open(Some(Vec::new()))
+    }
+
+    fn open(&mut self, keys: Option<Vec<Vec<u8>>>) -> Result<()> {
+        let key = self.take_key()?;
+        self.open.push(Open { key, items: Vec::new(), keys });
+        Ok(())
+    }
+
+    /// Set the key of the next value, or container opened, in the current object.
+    pub fn key<K: ?Sized + Serialize>(&mut self, key: &K) -> Result<()> {
+        match self.open.last() {
+            Some(Open { keys: Some(_), .. }) => {},
+            _ => return Err(ErrorKind::Message("key set outside of an object".to_owned()).into()),
+        }
+        if self.key.is_some() {
+            return Err(ErrorKind::Message("key set twice without adding a value".to_owned()).into());
+        }
+        self.key = Some(ser::encode_key(key, &self.options)?);
+        Ok(())
+    }
+
+    /// Add a member to the current object.
+    pub fn add<K: ?Sized + Serialize, V: ?Sized + Serialize>(&mut self, key: &K, value: &V) -> Result<()> {
+        self.key(key)?;
+        self.push(value)
+    }
+
+    /// Add a value to the current array or object, or after the values written so far if no
+    /// container is open.
+    pub fn push<V: ?Sized + Serialize>(&mut self, value: &V) -> Result<()> {
+        let bytes = crate::to_bytes_with_options(value, &self.options)?;
+        self.write(bytes)
+    }
+
+    /// Close the innermost open array or object.
+    pub fn close(&mut self) -> Result<()> {
+        if self.key.is_some() {
+            return Err(ErrorKind::Message("object closed after a key without value".to_owned()).into());
+        }
+        let open = self.open.pop().ok_or_else(|| ErrorKind::Message("no array or object to close".to_owned()))?;
+        let mut bytes = Vec::new();
+        match open.keys {
+            Some(keys) => ser::write_object(&mut bytes, keys, open.items, &self.options)?,
+            None => ser::write_array(&mut bytes, open.items, &self.options)?,
+        }
+        self.key = open.key;
+        self.write(bytes)
+    }
+
+    /// Whether all arrays and objects opened have been closed.
+    pub fn is_closed(&self) -> bool {
+        self.open.is_empty()
+    }
+
+    /// Get the values built, failing if any array or object is still open.
+    pub fn finish(self) -> Result<Vec<u8>> {
+        if !self.is_closed() {
+            return Err(ErrorKind::Message(format!("{} arrays or objects still open", self.open.len())).into());
+        }
+        Ok(self.output)
+    }
+
+    /// The values completed so far, leaving out any still open array or object.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Take the values completed so far, see [`as_slice`](Builder::as_slice).
+    pub fn into_inner(self) -> Vec<u8> {
+        self.output
+    }
+
+    /// Clear everything built so far, including open arrays and objects, keeping the allocated
+    /// buffer to build more values into.
+    pub fn reset(&mut self) {
+        self.output.clear();
+        self.open.clear();
+        self.key = None;
+    }
+
+    fn write(&mut self, bytes: Vec<u8>) -> Result<()> {
+        let key = self.take_key()?;
+        match self.open.last_mut() {
+            Some(open) => {
+                if let (Some(keys), Some(key)) = (open.keys.as_mut(), key) {
+                    keys.push(key);
+                }
+                open.items.push(bytes);
+            },
+            None => self.output.extend_from_slice(&bytes),
+        }
+        Ok(())
+    }
+
+    // take the key of a value about to be added, which objects need and arrays don't
+    fn take_key(&mut self) -> Result<Option<Vec<u8>>> {
+        match self.open.last() {
+            Some(Open { keys: Some(_), .. }) => match self.key.take() {
+                Some(key) => Ok(Some(key)),
+                None => Err(ErrorKind::Message("value added to an object without a key".to_owned()).into()),
+            },
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{to_bytes, to_bytes_with_options, RawVPack};
+    use serde_json::json;
+
+    #[test]
+    fn build() {
+        let mut builder = Builder::new();
+        builder.open_object().unwrap();
+        builder.add("b", &1_u64).unwrap();
+        builder.key("a").unwrap();
+        builder.open_array().unwrap();
+        builder.push("x").unwrap();
+        builder.open_object().unwrap();
+        builder.close().unwrap();
+        builder.push(&RawVPack::from_slice(&[0x1a]).unwrap()).unwrap();
+        builder.close().unwrap();
+        assert!(!builder.is_closed());
+        builder.close().unwrap();
+        let bytes = builder.finish().unwrap();
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&bytes).unwrap(), json!({"b": 1, "a": ["x", {}, true]}));
+        // members keep the order they were added in, with a sorted index table
+        assert_eq!(&bytes[3..6], &[0x41, 0x62, 0x31]);
+
+        let mut builder = Builder::new();
+        builder.push(&1).unwrap();
+        builder.open_array().unwrap();
+        builder.close().unwrap();
+        assert_eq!(builder.finish().unwrap(), &[0x31, 0x01]);
+    }
+
+    #[test]
+    fn options() {
+        let options = crate::SerializerOptions::new().compact_containers(true);
+        let mut builder = Builder::with_options(options.clone());
+        builder.open_array().unwrap();
+        builder.push(&[1, 2]).unwrap();
+        builder.close().unwrap();
+        assert_eq!(builder.finish().unwrap(), to_bytes_with_options(&[[1, 2]], &options).unwrap());
+
+        let options = crate::SerializerOptions::new().padding(true).compact_objects(true);
+        let mut builder = Builder::with_options(options);
+        builder.open_array().unwrap();
+        builder.open_object().unwrap();
+        builder.add("a", &1).unwrap();
+        builder.close().unwrap();
+        builder.close().unwrap();
+        assert_eq!(builder.finish().unwrap(), &[0x02, 0x0f, 0, 0, 0, 0, 0, 0, 0, 0x14, 0x06, 0x41, 0x61, 0x31, 0x01]);
+    }
+
+    #[test]
+    fn reuse() {
+        let mut builder = Builder::new();
+        builder.push(&1).unwrap();
+        builder.open_object().unwrap();
+        builder.key("a").unwrap();
+        assert_eq!(builder.as_slice(), &[0x31]);
+        builder.reset();
+        assert!(builder.is_closed());
+        builder.open_array().unwrap();
+        builder.push(&2).unwrap();
+        builder.close().unwrap();
+        assert_eq!(builder.as_slice(), &[0x02, 0x03, 0x32]);
+        builder.open_array().unwrap();
+        assert_eq!(builder.into_inner(), &[0x02, 0x03, 0x32]);
+
+        let mut builder = Builder::new().append_to(vec![0x01, 0x00, 0x00, 0x00]);
+        builder.push("a").unwrap();
+        assert_eq!(builder.finish().unwrap(), &[0x01, 0x00, 0x00, 0x00, 0x41, 0x61]);
+    }
+
+    #[test]
+    fn misuse() {
+        let mut builder = Builder::new();
+        assert!(builder.close().is_err());
+        assert!(builder.key("a").is_err());
+        builder.open_object().unwrap();
+        assert!(builder.push(&1).is_err());
+        assert!(builder.open_array().is_err());
+        builder.key("a").unwrap();
+        assert!(builder.key("b").is_err());
+        assert!(builder.close().is_err());
+        builder.push(&1).unwrap();
+        builder.close().unwrap();
+        assert_eq!(builder.finish().unwrap(), to_bytes(&json!({"a": 1})).unwrap());
+
+        let mut builder = Builder::new();
+        builder.open_array().unwrap();
+        assert!(builder.key("a").is_err());
+        assert!(builder.finish().is_err());
+    }
+}
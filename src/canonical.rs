@@ -0,0 +1,147 @@
+use std::borrow::Cow;
+
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use crate::error::{ErrorKind, Result};
+use crate::raw;
+use crate::raw_vpack::RawVPack;
+
+/// Re-encode the value at the start of `bytes` in a normal form, so that equal documents give
+/// equal bytes whichever writer produced them: objects have their members sorted by key and an
+/// index table, integers, strings and binary values use their shortest encoding, and arrays and
+/// objects the smallest header and offset sizes.
+///
+/// Other values, including tagged values, are copied as-is. Arrays and objects nested more than
+/// [`DEFAULT_MAX_DEPTH`](crate::DEFAULT_MAX_DEPTH) deep fail with `ErrorKind::DepthLimitExceeded`.
+pub fn canonicalize(bytes: &[u8]) -> Result<Vec<u8>> {
+    crate::to_bytes(&Node::parse(bytes, 0)?)
+}
+
+// a decoded value, with the parts needing re-encoding split out
+enum Node<'a> {
+    Raw(&'a [u8]),
+    Int(i64),
+    UInt(u64),
+    Str(&'a str),
+    Binary(&'a [u8]),
+    Array(Vec<Node<'a>>),
+    Object(Vec<(Node<'a>, Node<'a>)>),
+}
+
+impl<'a> Node<'a> {
+    fn parse(bytes: &'a [u8], depth: usize) -> Result<Self> {
+        let (value, _) = raw::split_value(bytes)?;
+        let header = value[0];
+        if matches!(header, 0x01..=0x14) && depth >= crate::DEFAULT_MAX_DEPTH {
+            return Err(ErrorKind::DepthLimitExceeded.into());
+        }
+        let node = match header {
+            0x01..=0x09 | 0x13 => {
+                let items = raw::Container::new(value)?.items();
+                Node::Array(items.map(|item| Node::parse(item?, depth + 1)).collect::<Result<_>>()?)
+            },
+            0x0a..=0x12 | 0x14 => {
+                let mut members: Vec<(Cow<str>, Node, Node)> = Vec::new();
+                for member in raw::Container::new(value)?.members() {
+                    let (key, value) = member?;
+                    members.push((raw::read_key(key)?, Node::parse(key, depth + 1)?, Node::parse(value, depth + 1)?));
+                }
+                members.sort_by(|a, b| a.0.cmp(&b.0));
+                Node::Object(members.into_iter().map(|(_, key, value)| (key, value)).collect())
+            },
+            0x20..=0x27 => Node::Int(raw::read_int(&value[1..], (header - 0x1f) as usize)?),
+            0x28..=0x2f => Node::UInt(raw::read_uint(&value[1..], (header - 0x27) as usize)?),
+            0x40..=0xbf => Node::Str(raw::read_str(value)?),
+            0xc0..=0xc7 => Node::Binary(&value[1 + (header - 0xbf) as usize..]),
+            _ => Node::Raw(value),
+        };
+        Ok(node)
+    }
+}
+
+impl Serialize for Node<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Node::Raw(bytes) => RawVPack::from_slice(bytes).map_err(ser::Error::custom)?.serialize(serializer),
+            Node::Int(v) => serializer.serialize_i64(*v),
+            Node::UInt(v) => serializer.serialize_u64(*v),
+            Node::Str(v) => serializer.serialize_str(v),
+            Node::Binary(v) => serializer.serialize_bytes(v),
+            Node::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            },
+            Node::Object(members) => {
+                let mut map = serializer.serialize_map(Some(members.len()))?;
+                for (key, value) in members {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{to_bytes, to_bytes_with_options, ErrorKind, IndexWidth, SerializerOptions};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn normal_form() {
+        let mut doc = BTreeMap::new();
+        doc.insert("b", vec![1, 2, 300]);
+        doc.insert("a", vec![-5]);
+        doc.insert("abc", vec![]);
+        let expected = to_bytes(&doc).unwrap();
+        assert_eq!(canonicalize(&expected).unwrap(), expected);
+
+        for options in [
+            SerializerOptions::new().unsorted_objects(true),
+            SerializerOptions::new().compact_containers(true),
+            SerializerOptions::new().index_width(IndexWidth::Eight),
+        ] {
+            let bytes = to_bytes_with_options(&doc, &options).unwrap();
+            assert_ne!(bytes, expected);
+            assert_eq!(canonicalize(&bytes).unwrap(), expected);
+        }
+
+        // member order
+        let json: serde_json::Value = serde_json::from_str(r#"{"b": 1, "a": 2}"#).unwrap();
+        let options = SerializerOptions::new().unsorted_objects(true);
+        assert_eq!(canonicalize(&to_bytes_with_options(&json, &options).unwrap()).unwrap(), canonicalize(&to_bytes(&json).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(canonicalize(&[0x27, 0x01, 0, 0, 0, 0, 0, 0, 0]).unwrap(), &[0x31]);
+        assert_eq!(canonicalize(&[0x21, 0xff, 0xff]).unwrap(), &[0x3f]);
+        assert_eq!(canonicalize(&[0x29, 0xe8, 0x03]).unwrap(), &[0x29, 0xe8, 0x03]);
+        assert_eq!(canonicalize(&[0xbf, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x61]).unwrap(), &[0x41, 0x61]);
+        assert_eq!(canonicalize(&[0xc1, 0x01, 0x00, 0xab]).unwrap(), &[0xc0, 0x01, 0xab]);
+        assert_eq!(canonicalize(&[0x1a]).unwrap(), &[0x1a]);
+        assert_eq!(canonicalize(&[0xee, 0x01, 0x31]).unwrap(), &[0xee, 0x01, 0x31]);
+        assert!(matches!(canonicalize(&[0x02, 0x05, 0x31]).unwrap_err().kind(), ErrorKind::Eof));
+    }
+
+    #[test]
+    fn depth() {
+        // arrays nested `n` deep, around an empty array
+        let nested = |n: usize| {
+            let mut data = Vec::new();
+            for i in 0..n {
+                data.push(0x05);
+                data.extend_from_slice(&(9 * (n - i) as u64 + 1).to_le_bytes());
+            }
+            data.push(0x01);
+            data
+        };
+        assert!(canonicalize(&nested(crate::DEFAULT_MAX_DEPTH - 1)).is_ok());
+        assert_eq!(canonicalize(&nested(crate::DEFAULT_MAX_DEPTH)).unwrap_err().kind(), &ErrorKind::DepthLimitExceeded);
+        assert_eq!(canonicalize(&nested(100_000)).unwrap_err().kind(), &ErrorKind::DepthLimitExceeded);
+    }
+}
@@ -0,0 +1,63 @@
+use serde::Deserialize;
+
+use crate::error::{ErrorKind, Result};
+use crate::raw;
+
+/// Decode a single attribute from every object in the encoded array `array`, e.g. the `price`
+/// of each document in a cursor result, without decoding anything else.
+///
+/// Elements which aren't objects, don't contain the attribute, or have it set to null give
+/// `None`.
+pub fn column<'a, T: Deserialize<'a>>(array: &'a [u8], attribute: &str) -> Result<Vec<Option<T>>> {
+    match array.first() {
+        Some(0x01..=0x09) | Some(0x13) => {},
+        Some(_) => return Err(ErrorKind::ExpectedArray.into()),
+        None => return Err(ErrorKind::Eof.into()),
+    }
+
+    let container = raw::Container::new(array)?;
+    let mut values = Vec::new();
+    for item in container.items() {
+        let item = item?;
+        let value = match item[0] {
+            0x0a..=0x12 | 0x14 => raw::object_get(item, attribute)?,
+            _ => None,
+        };
+        values.push(match value {
+            Some([0x18]) | None => None,
+            Some(value) => Some(crate::from_bytes(value)?),
+        });
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn extract_column() {
+        let docs = json!([
+            {"_key": "a", "price": 1.5, "tags": ["x"]},
+            {"price": 10.0, "_key": "b"},
+            {"_key": "c"},
+            {"_key": "d", "price": null},
+            "not an object",
+        ]);
+        let data = crate::to_bytes(&docs).unwrap();
+        assert_eq!(column::<f64>(&data, "price").unwrap(), vec![Some(1.5), Some(10.0), None, None, None]);
+        assert_eq!(column::<&str>(&data, "_key").unwrap(), vec![Some("a"), Some("b"), Some("c"), Some("d"), None]);
+        assert_eq!(column::<Vec<String>>(&data, "tags").unwrap(), vec![Some(vec!["x".to_owned()]), None, None, None, None]);
+        assert!(column::<u8>(&data, "price").is_err());
+    }
+
+    #[test]
+    fn other_arrays() {
+        assert_eq!(column::<u8>(&[0x01], "a").unwrap(), vec![]);
+        // compact array of compact objects
+        let data = [0x13, 0x0a, 0x14, 0x06, 0x41, 0x61, 0x31, 0x01, 0x01, 0x02];
+        assert_eq!(column::<u8>(&data, "a").unwrap(), vec![Some(1), None]);
+        assert_eq!(column::<u8>(&[0x0a], "a"), Err(ErrorKind::ExpectedArray.into()));
+    }
+}
@@ -0,0 +1,213 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use crate::error::{ErrorKind, Result};
+use crate::number::Number;
+use crate::raw;
+use crate::slice::Slice;
+
+const NULL: &[u8] = &[0x18];
+
+/// Compare the values at the start of `a` and `b` the way ArangoDB sorts values:
+///
+/// - by type first: min key < null < bool < number < string < binary < array < object < other
+///   types < max key
+/// - integers and doubles by their numeric value, UTC dates as their milliseconds
+/// - strings and binary values by their bytes
+/// - arrays item by item, the shorter array compared as if padded with nulls
+/// - objects by the values of all their keys in sorted order, missing members compared as nulls
+///
+/// Tagged values are compared by the values they wrap, and values of other types (custom, BCD,
+/// external) by their bytes. If either value is malformed, or has arrays or objects nested more
+/// than [`DEFAULT_MAX_DEPTH`](crate::DEFAULT_MAX_DEPTH) deep, their bytes are compared.
+pub fn compare(a: &[u8], b: &[u8]) -> Ordering {
+    compare_values(a, b, 0).unwrap_or_else(|_| a.cmp(b))
+}
+
+fn weight(header: u8) -> u8 {
+    match header {
+        0x1e => 0,
+        0x00 | 0x17 | 0x18 => 1,
+        0x19 | 0x1a => 2,
+        0x1b | 0x1c | 0x20..=0x3f => 3,
+        0x40..=0xbf => 4,
+        0xc0..=0xc7 => 5,
+        0x01..=0x09 | 0x13 => 6,
+        0x0a..=0x12 | 0x14 => 7,
+        0x1f => 9,
+        _ => 8,
+    }
+}
+
+fn compare_values(a: &[u8], b: &[u8], depth: usize) -> Result<Ordering> {
+    let a = Slice::new(a).untagged()?.value_bytes()?;
+    let b = Slice::new(b).untagged()?.value_bytes()?;
+    let weight_a = weight(a[0]);
+    let ordering = weight_a.cmp(&weight(b[0]));
+    if ordering != Ordering::Equal {
+        return Ok(ordering);
+    }
+    if matches!(weight_a, 6 | 7) && depth >= crate::DEFAULT_MAX_DEPTH {
+        return Err(ErrorKind::DepthLimitExceeded.into());
+    }
+    match weight_a {
+        2 => Ok(a[0].cmp(&b[0])),
+        3 => Ok(compare_numbers(number(a)?, number(b)?)),
+        4 => Ok(raw::read_str(a)?.as_bytes().cmp(raw::read_str(b)?.as_bytes())),
+        5 => Ok(binary(a).cmp(binary(b))),
+        6 => {
+            let mut items_a = raw::Container::new(a)?.items();
+            let mut items_b = raw::Container::new(b)?.items();
+            loop {
+                let ordering = match (items_a.next().transpose()?, items_b.next().transpose()?) {
+                    (None, None) => return Ok(Ordering::Equal),
+                    (a, b) => compare_values(a.unwrap_or(NULL), b.unwrap_or(NULL), depth + 1)?,
+                };
+                if ordering != Ordering::Equal {
+                    return Ok(ordering);
+                }
+            }
+        },
+        7 => {
+            let members_a = members(a)?;
+            let members_b = members(b)?;
+            let mut keys: Vec<&Cow<str>> = members_a.keys().chain(members_b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let value_a = members_a.get(key).copied().unwrap_or(NULL);
+                let value_b = members_b.get(key).copied().unwrap_or(NULL);
+                let ordering = compare_values(value_a, value_b, depth + 1)?;
+                if ordering != Ordering::Equal {
+                    return Ok(ordering);
+                }
+            }
+            Ok(Ordering::Equal)
+        },
+        8 => Ok(a.cmp(b)),
+        _ => Ok(Ordering::Equal),
+    }
+}
+
+enum Numeric {
+    Int(i128),
+    Double(f64),
+}
+
+fn number(bytes: &[u8]) -> Result<Numeric> {
+    if bytes[0] == 0x1c {
+        return Ok(Numeric::Int(raw::read_int(&bytes[1..], 8)? as i128));
+    }
+    let n = Number::from_bytes(bytes)?;
+    Ok(match (n.as_i64(), n.as_u64()) {
+        (Some(v), _) => Numeric::Int(v as i128),
+        (_, Some(v)) => Numeric::Int(v as i128),
+        _ => Numeric::Double(n.as_f64()),
+    })
+}
+
+fn compare_numbers(a: Numeric, b: Numeric) -> Ordering {
+    let (a, b) = match (a, b) {
+        (Numeric::Int(a), Numeric::Int(b)) => return a.cmp(&b),
+        (Numeric::Int(a), Numeric::Double(b)) => (a as f64, b),
+        (Numeric::Double(a), Numeric::Int(b)) => (a, b as f64),
+        (Numeric::Double(a), Numeric::Double(b)) => (a, b),
+    };
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+fn binary(bytes: &[u8]) -> &[u8] {
+    &bytes[1 + (bytes[0] - 0xbf) as usize..]
+}
+
+fn members(bytes: &[u8]) -> Result<BTreeMap<Cow<'_, str>, &[u8]>> {
+    let mut members = BTreeMap::new();
+    for member in raw::Container::new(bytes)?.members() {
+        let (key, value) = member?;
+        members.insert(raw::read_key(key)?, value);
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_bytes;
+    use serde_json::json;
+
+    fn cmp(a: serde_json::Value, b: serde_json::Value) -> Ordering {
+        compare(&to_bytes(&a).unwrap(), &to_bytes(&b).unwrap())
+    }
+
+    #[test]
+    fn types() {
+        let sorted = [json!(null), json!(false), json!(true), json!(-1.5), json!(0), json!(u64::MAX), json!(""), json!("a"), json!([]), json!({})];
+        for (i, a) in sorted.iter().enumerate() {
+            for (j, b) in sorted.iter().enumerate() {
+                assert_eq!(cmp(a.clone(), b.clone()), i.cmp(&j), "{} <=> {}", a, b);
+            }
+        }
+        assert_eq!(compare(&[0x1e], &[0x18]), Ordering::Less);
+        assert_eq!(compare(&[0x1f], &[0x0a]), Ordering::Greater);
+        assert_eq!(compare(&[0xc0, 0x01, 0x00], &[0x41, 0x61]), Ordering::Greater);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(compare(&[0x31], &[0x28, 0x01]), Ordering::Equal);
+        assert_eq!(compare(&[0x1b, 0, 0, 0, 0, 0, 0, 0xf8, 0x3f], &[0x32]), Ordering::Less);
+        assert_eq!(cmp(json!(-3), json!(2.5)), Ordering::Less);
+        assert_eq!(cmp(json!(u64::MAX), json!(i64::MAX)), Ordering::Greater);
+        let date = [0x1c, 0x05, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(compare(&date, &[0x35]), Ordering::Equal);
+        assert_eq!(compare(&date, &[0x36]), Ordering::Less);
+    }
+
+    #[test]
+    fn containers() {
+        assert_eq!(cmp(json!([1, 2]), json!([1, 2, 3])), Ordering::Less);
+        assert_eq!(cmp(json!([1, null]), json!([1])), Ordering::Equal);
+        assert_eq!(cmp(json!([2]), json!([1, 5])), Ordering::Greater);
+        assert_eq!(cmp(json!({"a": 1}), json!({"a": 1, "b": null})), Ordering::Equal);
+        assert_eq!(cmp(json!({"a": 1}), json!({"b": 1})), Ordering::Greater);
+        assert_eq!(cmp(json!({"a": 1, "b": 2}), json!({"b": 2, "a": 1})), Ordering::Equal);
+        let options = crate::SerializerOptions::new().compact_containers(true);
+        let compact = crate::to_bytes_with_options(&json!({"x": [1, "y"]}), &options).unwrap();
+        assert_eq!(compare(&compact, &to_bytes(&json!({"x": [1, "y"]})).unwrap()), Ordering::Equal);
+    }
+
+    #[test]
+    fn tagged_and_malformed() {
+        assert_eq!(compare(&[0xee, 0x01, 0x31], &[0x31]), Ordering::Equal);
+        assert_eq!(compare(&[0x43, 0x61], &[0x41, 0x61]), [0x43, 0x61].cmp(&[0x41, 0x61]));
+
+        let mut docs: Vec<Vec<u8>> = [json!("b"), json!(3), json!(null), json!([0])].iter().map(|v| to_bytes(v).unwrap()).collect();
+        docs.sort_by(|a, b| compare(a, b));
+        let docs: Vec<serde_json::Value> = docs.iter().map(|d| crate::from_bytes(d).unwrap()).collect();
+        assert_eq!(docs, [json!(null), json!(3), json!("b"), json!([0])]);
+    }
+
+    #[test]
+    fn depth() {
+        // arrays nested `n` deep, around an array of `last`
+        let nested = |n: usize, last: &[u8]| {
+            let mut data = Vec::new();
+            for i in 0..n {
+                data.push(0x05);
+                data.extend_from_slice(&(9 * (n - i) as u64 + 2 + last.len() as u64).to_le_bytes());
+            }
+            data.extend_from_slice(&[0x02, 2 + last.len() as u8]);
+            data.extend_from_slice(last);
+            data
+        };
+        let limit = crate::DEFAULT_MAX_DEPTH;
+        // 5 as a small int and as a one byte uint
+        assert_eq!(compare(&nested(limit - 1, &[0x35]), &nested(limit - 1, &[0x28, 0x05])), Ordering::Equal);
+        // past the limit the bytes are compared
+        let (a, b) = (nested(limit, &[0x35]), nested(limit, &[0x28, 0x05]));
+        assert_eq!(compare(&a, &b), a.cmp(&b));
+        assert_ne!(compare(&a, &b), Ordering::Equal);
+        assert_eq!(compare(&nested(100_000, &[0x31]), &nested(100_000, &[0x31])), Ordering::Equal);
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::error::{ErrorKind, Result};
+use crate::hook::Rewrite;
+use crate::raw;
+
+type Decoder = Box<dyn Fn(&[u8]) -> Option<Rewrite> + Send + Sync>;
+type Encoder = Box<dyn Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync>;
+
+/// Application specific handlers for custom types (0xf0 to 0xff), e.g. ArangoDB's internal
+/// types, so that they can be decoded into, and encoded from, ordinary values.
+///
+/// Decoders are used by a deserializer set up with
+/// [`Deserializer::with_custom_types`](crate::Deserializer::with_custom_types), encoders by
+/// serializers with [`SerializerOptions::custom_types`](crate::SerializerOptions::custom_types).
+/// Custom values without a decoder are decoded as their bytes, as without a registry.
+#[derive(Default)]
+pub struct CustomTypes {
+    decoders: HashMap<u8, Decoder>,
+    // by newtype struct name, with the header to encode values with
+    encoders: HashMap<&'static str, (u8, Encoder)>,
+}
+
+impl CustomTypes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode values of custom type `header` by passing their payload (the bytes after the
+    /// header and any length) to `decoder`, which returns the value to decode instead, or `None`
+    /// to decode the value's bytes.
+    ///
+    /// Panics if `header` isn't a custom type.
+    pub fn decoder<F>(mut self, header: u8, decoder: F) -> Self where
+        F: Fn(&[u8]) -> Option<Rewrite> + Send + Sync + 'static {
+        assert!(header >= 0xf0, "0x{:02x} isn't a custom type", header);
+        self.decoders.insert(header, Box::new(decoder));
+        self
+    }
+
+    /// Encode newtype structs named `name` as custom type `header`, by passing the encoded value
+    /// they wrap to `encoder`, which returns the payload to write. Payloads of types 0xf0 to
+    /// 0xf3 must be 1, 2, 4 or 8 bytes long respectively.
+    ///
+    /// Panics if `header` isn't a custom type.
+    pub fn encoder<F>(mut self, name: &'static str, header: u8, encoder: F) -> Self where
+        F: Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync + 'static {
+        assert!(header >= 0xf0, "0x{:02x} isn't a custom type", header);
+        self.encoders.insert(name, (header, Box::new(encoder)));
+        self
+    }
+
+    pub(crate) fn encodes(&self, name: &str) -> bool {
+        self.encoders.contains_key(name)
+    }
+
+    /// Decode the custom value in `bytes` with its type's decoder, if there is one.
+    pub(crate) fn decode(&self, bytes: &[u8]) -> Option<Rewrite> {
+        let decoder = self.decoders.get(bytes.first()?)?;
+        decoder(payload(bytes).ok()?)
+    }
+
+    /// Encode `value`, the encoded value wrapped by newtype struct `name`, as a custom value, if
+    /// there is an encoder for `name`.
+    pub(crate) fn encode(&self, name: &str, value: &[u8]) -> Option<Result<Vec<u8>>> {
+        let (header, encoder) = self.encoders.get(name)?;
+        Some(encoder(value).and_then(|payload| custom_value(*header, &payload)))
+    }
+}
+
+impl fmt::Debug for CustomTypes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut decoders: Vec<_> = self.decoders.keys().collect();
+        decoders.sort();
+        let mut encoders: Vec<_> = self.encoders.iter().map(|(name, (header, _))| (name, header)).collect();
+        encoders.sort();
+        f.debug_struct("CustomTypes").field("decoders", &decoders).field("encoders", &encoders).finish()
+    }
+}
+
+/// Rewrite the ArangoDB documents in `bytes` (or any arrays and objects holding them) with each
+/// `_id` attribute stored as ArangoDB's custom type 0xf3, a collection id, replaced by the
+/// `"collection/key"` string arangosh shows, taking the collection's name from `resolver` and
+/// the key from the document's `_key`. Collections `resolver` doesn't know are shown as
+/// `_unknown`, as ArangoDB does.
+///
+/// `_key` and `_id` may be stored as strings or as their attribute ids (see
+/// [`AttributeTranslator::arangodb`](crate::AttributeTranslator::arangodb)). Containers holding
+/// no such `_id` are copied as they are.
+pub fn resolve_ids<F>(bytes: &[u8], resolver: F) -> Result<Vec<u8>> where
+    F: Fn(u64) -> Option<String> {
+    let value = raw::split_value(bytes)?.0;
+    Ok(resolve(value, &resolver)?.unwrap_or_else(|| value.to_vec()))
+}
+
+// the value with `_id`s resolved, or `None` if there were none
+fn resolve(value: &[u8], resolver: &dyn Fn(u64) -> Option<String>) -> Result<Option<Vec<u8>>> {
+    let options = crate::SerializerOptions::default();
+    match value[0] {
+        0x02..=0x09 | 0x13 => {
+            let items = raw::Container::new(value)?.items().collect::<Result<Vec<_>>>()?;
+            let resolved = items.iter().map(|item| resolve(item, resolver)).collect::<Result<Vec<_>>>()?;
+            if resolved.iter().all(Option::is_none) {
+                return Ok(None);
+            }
+            let items = items.iter().zip(resolved).map(|(item, r)| r.unwrap_or_else(|| item.to_vec())).collect();
+            let mut out = Vec::new();
+            crate::ser::write_array(&mut out, items, &options)?;
+            Ok(Some(out))
+        },
+        0x0b..=0x12 | 0x14 => {
+            let members = raw::Container::new(value)?.members().collect::<Result<Vec<_>>>()?;
+            let key = members.iter().find(|(k, _)| is_attribute(k, "_key", 0x31)).map(|(_, v)| raw::read_str(v)).transpose()?;
+            let mut changed = false;
+            let mut values = Vec::with_capacity(members.len());
+            for (k, v) in &members {
+                let resolved = match key {
+                    Some(key) if v[0] == 0xf3 && is_attribute(k, "_id", 0x33) => {
+                        let cid = raw::read_uint(&v[1..], 8)?;
+                        let collection = resolver(cid).unwrap_or_else(|| "_unknown".to_owned());
+                        Some(crate::to_bytes(&format!("{}/{}", collection, key))?)
+                    },
+                    _ => resolve(v, resolver)?,
+                };
+                changed |= resolved.is_some();
+                values.push(resolved.unwrap_or_else(|| v.to_vec()));
+            }
+            if !changed {
+                return Ok(None);
+            }
+            let keys = members.iter().map(|(k, _)| k.to_vec()).collect();
+            let mut out = Vec::new();
+            crate::ser::write_object(&mut out, keys, values, &options)?;
+            Ok(Some(out))
+        },
+        _ => Ok(None),
+    }
+}
+
+// whether object key `key` is attribute `name`, stored as a string or as its id
+fn is_attribute(key: &[u8], name: &str, id: u8) -> bool {
+    key == [id] || raw::read_str(key).ok() == Some(name)
+}
+
+// the payload of the custom value in `bytes`
+fn payload(bytes: &[u8]) -> Result<&[u8]> {
+    let value = raw::split_value(bytes)?.0;
+    Ok(match value[0] {
+        0xf0..=0xf3 => &value[1..],
+        header => &value[1 + (1 << ((header - 0xf4) / 3))..],
+    })
+}
+
+// a custom value of type `header` holding `payload`
+fn custom_value(header: u8, payload: &[u8]) -> Result<Vec<u8>> {
+    let mut out = vec![header];
+    match header {
+        0xf0..=0xf3 => {
+            if payload.len() != 1 << (header - 0xf0) {
+                return Err(ErrorKind::Message(format!("custom type 0x{:02x} needs {} bytes, got {}", header, 1 << (header - 0xf0), payload.len())).into());
+            }
+        },
+        _ => {
+            let n_bytes = 1 << ((header - 0xf4) / 3);
+            if n_bytes < 8 && payload.len() >= 1 << (n_bytes * 8) {
+                return Err(ErrorKind::Message(format!("{} bytes too long for custom type 0x{:02x}", payload.len(), header)).into());
+            }
+            out.extend_from_slice(&(payload.len() as u64).to_le_bytes()[..n_bytes]);
+        },
+    }
+    out.extend_from_slice(payload);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+    use std::sync::Arc;
+
+    use serde::{Deserialize, Serialize};
+
+    // a point packed into a u64, stored as its 8 little endian bytes in custom type 0xf3
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename = "Point")]
+    struct Point(u64);
+
+    fn types() -> Arc<CustomTypes> {
+        Arc::new(CustomTypes::new()
+            .encoder("Point", 0xf3, |value| Ok(crate::from_bytes::<u64>(value)?.to_le_bytes().to_vec()))
+            .decoder(0xf3, |payload| Some(Rewrite::UInt(u64::from_le_bytes(payload.try_into().ok()?))))
+            .encoder("Blob", 0xf4, |value| Ok(value.to_vec()))
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        let options = crate::SerializerOptions::new().custom_types(types());
+        let bytes = crate::to_bytes_with_options(&vec![Point(7)], &options).unwrap();
+        assert_eq!(bytes, &[0x02, 0x0b, 0xf3, 7, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(crate::serialized_size_with_options(&vec![Point(7)], &options).unwrap(), bytes.len());
+
+        let mut de = crate::Deserializer::from_bytes(&bytes).with_custom_types(types());
+        assert_eq!(Vec::<Point>::deserialize(&mut de).unwrap(), vec![Point(7)]);
+        let mut de = crate::Deserializer::from_bytes(&bytes).with_custom_types(types());
+        assert_eq!(serde_json::Value::deserialize(&mut de).unwrap(), serde_json::json!([7]));
+
+        // without a registry, custom values are passed along as bytes
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&bytes[2..]).unwrap(), serde_json::json!([0xf3, 7, 0, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(crate::to_bytes(&Point(7)).unwrap(), &[0x37]);
+    }
+
+    #[test]
+    fn ids() {
+        let resolver = |cid| if cid == 42 { Some("users".to_owned()) } else { None };
+        let mut builder = crate::Builder::new();
+        builder.open_array().unwrap();
+        for (cid, key) in [(42_u64, "1"), (7, "2")] {
+            let mut id = vec![0xf3];
+            id.extend_from_slice(&cid.to_le_bytes());
+            builder.open_object().unwrap();
+            builder.add(&1, &key).unwrap();
+            builder.add(&3, &crate::RawVPack::from_slice(&id).unwrap()).unwrap();
+            builder.add("name", "x").unwrap();
+            builder.close().unwrap();
+        }
+        builder.push(&42).unwrap();
+        builder.close().unwrap();
+        let bytes = builder.finish().unwrap();
+
+        let resolved = resolve_ids(&bytes, resolver).unwrap();
+        let translator = Arc::new(crate::AttributeTranslator::arangodb());
+        let mut de = crate::Deserializer::from_bytes(&resolved).with_attribute_translator(translator);
+        assert_eq!(serde_json::Value::deserialize(&mut de).unwrap(), serde_json::json!([
+            {"_key": "1", "_id": "users/1", "name": "x"},
+            {"_key": "2", "_id": "_unknown/2", "name": "x"},
+            42,
+        ]));
+
+        let plain = crate::to_bytes(&serde_json::json!({"_id": "users/1", "a": [1]})).unwrap();
+        assert_eq!(resolve_ids(&plain, resolver).unwrap(), plain);
+    }
+
+    #[test]
+    fn lengths() {
+        assert_eq!(custom_value(0xf4, b"ab").unwrap(), &[0xf4, 0x02, b'a', b'b']);
+        assert_eq!(payload(&[0xf4, 0x02, b'a', b'b']).unwrap(), b"ab");
+        assert_eq!(custom_value(0xf7, b"a").unwrap(), &[0xf7, 0x01, 0x00, b'a']);
+        assert_eq!(payload(&[0xf0, 0x01]).unwrap(), &[0x01]);
+        assert!(custom_value(0xf1, &[1]).is_err());
+        assert!(custom_value(0xf4, &[0; 256]).is_err());
+
+        #[derive(Serialize)]
+        #[serde(rename = "Blob")]
+        struct Blob(u8);
+        let options = crate::SerializerOptions::new().custom_types(types());
+        assert_eq!(crate::to_bytes_with_options(&Blob(1), &options).unwrap(), &[0xf4, 0x01, 0x31]);
+    }
+}
@@ -1,85 +1,664 @@
 use log::debug;
 use serde::Deserialize;
+use serde::de::DeserializeOwned;
 use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
-
-use crate::error::{Error, Result};
+use serde::de::value::SeqDeserializer;
+
+use crate::custom::CustomTypes;
+use crate::dictionary::StringDictionary;
+use crate::error::{Error, ErrorKind, Result};
+use crate::hook::{Hooks, Rewrite};
+use crate::raw;
+use crate::translator::AttributeTranslator;
+use crate::value_type::ValueType;
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::convert::TryFrom;
-use crate::{U8_SIZE, U16_SIZE, U32_SIZE, U64_SIZE};
+use crate::{U8_SIZE, U64_SIZE};
 use std::slice::SliceIndex;
-use bitvec::order;
-use bitvec::prelude::Lsb0;
-use bitvec::array::BitArray;
-use bitvec::slice::BitSlice;
+use std::sync::Arc;
+
+// pass the next value to any registered hooks, visiting its replacement if it was rewritten,
+// then skip any tags so that the tagged value is decoded (and passed to hooks) in their place
+macro_rules! rewrite {
+    ($de:expr, $visitor:expr) => {
+        loop {
+            $de.resolve_external()?;
+            $de.check_document_size()?;
+            if let Some(rewrite) = $de.rewrite()? {
+                return rewrite.visit($visitor);
+            }
+            if $de.decoding_value || $de.read_tag()?.is_none() {
+                break;
+            }
+        }
+    };
+}
+
+/// Default for [`Deserializer::max_depth`].
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Called with the pointer held by an external value, returns the encoded value it points to.
+type Resolver<'de> = Box<dyn Fn(usize) -> Option<&'de [u8]> + 'de>;
+
+/// Whether objects may hold the same key more than once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Decode objects as they are, leaving duplicates to the type being decoded.
+    #[default]
+    Allow,
+    /// Fail with `ErrorKind::DuplicateKey`.
+    Error,
+}
+
+/// Whether structs may be decoded from objects with members which aren't fields of the struct.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownFields {
+    /// Skip them, unless the struct denies unknown fields itself.
+    #[default]
+    Ignore,
+    /// Fail with `ErrorKind::UnknownField`.
+    Error,
+}
+
+/// Options controlling how values are decoded, for use with [`from_bytes_with_options`] and
+/// [`Deserializer::with_options`]. Each option is described on the `Deserializer` method of the
+/// same name. The defaults are those of [`from_bytes`]:
+///
+/// ```
+/// use velocypack::{DeserializerOptions, DuplicateKeys, ErrorKind};
+///
+/// let options = DeserializerOptions::new()
+///     .max_depth(16)
+///     .max_string_length(1 << 20)
+///     .strict(true)
+///     .duplicate_keys(DuplicateKeys::Error);
+/// // {"a": 1, "a": 2}
+/// let bytes = [0x0b, 0x0b, 0x02, 0x41, 0x61, 0x31, 0x41, 0x61, 0x32, 0x03, 0x06];
+/// let error = velocypack::from_bytes_with_options::<serde_json::Value>(&bytes, &options).unwrap_err();
+/// assert_eq!(error.kind(), &ErrorKind::DuplicateKey("a".to_owned()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DeserializerOptions {
+    max_depth: usize,
+    max_string_length: Option<usize>,
+    max_container_len: Option<usize>,
+    max_document_size: Option<usize>,
+    budget: Option<u64>,
+    strict: bool,
+    trusted_utf8: bool,
+    positional_structs: bool,
+    lookup_fields: bool,
+    attribute_translator: Option<Arc<AttributeTranslator>>,
+    duplicate_keys: DuplicateKeys,
+    unknown_fields: UnknownFields,
+}
+
+impl Default for DeserializerOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_string_length: None,
+            max_container_len: None,
+            max_document_size: None,
+            budget: None,
+            strict: false,
+            trusted_utf8: false,
+            positional_structs: false,
+            lookup_fields: false,
+            attribute_translator: None,
+            duplicate_keys: DuplicateKeys::default(),
+            unknown_fields: UnknownFields::default(),
+        }
+    }
+}
+
+impl DeserializerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// See [`Deserializer::max_depth`].
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// See [`Deserializer::max_string_length`].
+    pub fn max_string_length(mut self, length: usize) -> Self {
+        self.max_string_length = Some(length);
+        self
+    }
+
+    /// See [`Deserializer::max_container_len`].
+    pub fn max_container_len(mut self, len: usize) -> Self {
+        self.max_container_len = Some(len);
+        self
+    }
+
+    /// See [`Deserializer::max_document_size`].
+    pub fn max_document_size(mut self, size: usize) -> Self {
+        self.max_document_size = Some(size);
+        self
+    }
+
+    /// See [`Deserializer::budget`].
+    pub fn budget(mut self, budget: u64) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// See [`Deserializer::strict`].
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    /// See [`Deserializer::assume_valid_utf8`].
+    ///
+    /// # Safety
+    ///
+    /// Every string in the input decoded with these options must be valid UTF-8.
+    pub unsafe fn assume_valid_utf8(mut self) -> Self {
+        self.trusted_utf8 = true;
+        self
+    }
+
+    /// See [`Deserializer::positional_structs`].
+    pub fn positional_structs(mut self, enabled: bool) -> Self {
+        self.positional_structs = enabled;
+        self
+    }
+
+    /// See [`Deserializer::lookup_fields`].
+    pub fn lookup_fields(mut self, enabled: bool) -> Self {
+        self.lookup_fields = enabled;
+        self
+    }
+
+    /// See [`Deserializer::with_attribute_translator`].
+    pub fn attribute_translator(mut self, translator: Arc<AttributeTranslator>) -> Self {
+        self.attribute_translator = Some(translator);
+        self
+    }
+
+    /// See [`Deserializer::duplicate_keys`].
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// See [`Deserializer::unknown_fields`].
+    pub fn unknown_fields(mut self, policy: UnknownFields) -> Self {
+        self.unknown_fields = policy;
+        self
+    }
+}
 
 pub struct Deserializer<'de> {
     input: &'de [u8],
+    // the input as given, for reporting offsets in errors
+    document: &'de [u8],
+    hooks: Hooks,
+    // length of remaining input when hooks were last run, so that a value is only passed to
+    // hooks once when deserialize methods delegate to each other
+    hooked_at: Option<usize>,
+    positional_structs: bool,
+    lookup_fields: bool,
+    strict: bool,
+    // skip checking that strings are UTF-8, see `assume_valid_utf8`
+    trusted_utf8: bool,
+    resolver: Option<Resolver<'de>>,
+    // number of values which may still be decoded
+    budget: Option<u64>,
+    // number of arrays, objects and tags the next value is nested within, and the limit on it
+    depth: usize,
+    max_depth: usize,
+    // optional limits on the byte length of strings and binary values, the number of items or
+    // members of containers and the byte length of top-level values
+    max_string_length: Option<usize>,
+    max_container_len: Option<usize>,
+    max_document_size: Option<usize>,
+    translator: Option<Arc<AttributeTranslator>>,
+    duplicate_keys: DuplicateKeys,
+    unknown_fields: UnknownFields,
+    // input following each external value currently being decoded, innermost last
+    resume: Vec<&'de [u8]>,
+    // set while decoding a `Value`: binary values are visited as bytes rather than sequences, and
+    // numbers, UTC dates, min/max keys and custom values as single `RAW_TOKEN` members holding
+    // their encoded bytes
+    decoding_value: bool,
+    // set while decoding an enum's variant, the only identifiers which may be integers
+    decoding_variant: bool,
 }
 
 impl<'de> Deserializer<'de> {
     pub fn from_bytes(input: &'de [u8]) -> Self {
-        Self { input }
+        Self::with_options(input, &DeserializerOptions::default())
+    }
+
+    /// Deserializer for `input` configured by `options`. Hooks and external value resolvers,
+    /// which aren't options, can be added afterwards.
+    pub fn with_options(input: &'de [u8], options: &DeserializerOptions) -> Self {
+        Self {
+            input,
+            document: input,
+            hooks: Hooks::default(),
+            hooked_at: None,
+            positional_structs: options.positional_structs,
+            lookup_fields: options.lookup_fields,
+            strict: options.strict,
+            trusted_utf8: options.trusted_utf8,
+            resolver: None,
+            resume: Vec::new(),
+            budget: options.budget,
+            depth: 0,
+            max_depth: options.max_depth,
+            max_string_length: options.max_string_length,
+            max_container_len: options.max_container_len,
+            max_document_size: options.max_document_size,
+            translator: options.attribute_translator.clone(),
+            duplicate_keys: options.duplicate_keys,
+            unknown_fields: options.unknown_fields,
+            decoding_value: false,
+            decoding_variant: false,
+        }
     }
 
-    fn peek_byte(&self) -> Result<u8> {
-        match self.input.get(0) {
-            Some(b) => Ok(*b),
-            None => Err(Error::Eof),
+    /// Register a hook which is passed the encoded bytes of every value of type `value_type`
+    /// before it is decoded. Returning `Some` replaces the value with the given rewrite, e.g.
+    /// to turn UTC dates into strings or drop binary data, without writing a custom visitor for
+    /// every target type. Hooks for the same type run in the order they were added.
+    pub fn with_hook<F>(mut self, value_type: ValueType, hook: F) -> Self where
+        F: Fn(&[u8]) -> Option<Rewrite> + 'static {
+        self.hooks.add(value_type, Box::new(hook));
+        self
+    }
+
+    /// Decode tagged values referencing `dictionary` (as written by a serializer using the same
+    /// dictionary) into the strings they reference.
+    pub fn with_dictionary(self, dictionary: Arc<StringDictionary>) -> Self {
+        self.with_hook(ValueType::Tagged, move |b| dictionary.decode(b).map(|s| Rewrite::String(s.to_owned())))
+    }
+
+    /// Decode custom types (0xf0 to 0xff) with a decoder in `custom_types` into the values it
+    /// returns for them.
+    pub fn with_custom_types(self, custom_types: Arc<CustomTypes>) -> Self {
+        self.with_hook(ValueType::Custom, move |b| custom_types.decode(b))
+    }
+
+    /// Decode integer object keys found in `translator` into the attribute names they stand for.
+    /// Keys which aren't found are decoded as integers.
+    pub fn with_attribute_translator(mut self, translator: Arc<AttributeTranslator>) -> Self {
+        self.translator = Some(translator);
+        self
+    }
+
+    /// Allow structs to be decoded from arrays, taking fields in the order they are declared, as
+    /// well as from objects.
+    pub fn positional_structs(mut self, enabled: bool) -> Self {
+        self.positional_structs = enabled;
+        self
+    }
+
+    /// Decode structs from sorted objects (0x0b to 0x0e) by looking each of their fields up in
+    /// the object's index table, rather than decoding every member in turn. Much faster when only
+    /// a few of many attributes are needed, e.g. 3 fields of an 80 attribute document.
+    ///
+    /// Members which aren't fields of the struct are never seen, so
+    /// `#[serde(deny_unknown_fields)]` doesn't reject them, and hand-written `Deserialize` impls
+    /// must pass every field they accept to `deserialize_struct`. Objects with integer keys are
+    /// decoded member by member when an attribute translator is set.
+    pub fn lookup_fields(mut self, enabled: bool) -> Self {
+        self.lookup_fields = enabled;
+        self
+    }
+
+    /// Check that the index table of every array and object points at the items being decoded,
+    /// and that sorted objects list their keys in order, failing with `ErrorKind::Invalid` otherwise.
+    /// Decoding only follows the items themselves, so without this a corrupt index table from
+    /// another encoder goes unnoticed, to be misread by readers relying on it.
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    /// Whether objects may hold the same key more than once. When allowed, maps keep the last
+    /// value for a key and derived structs fail with a duplicate field error.
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// Whether structs may be decoded from objects with members which aren't fields of the
+    /// struct, like `#[serde(deny_unknown_fields)]` but for every struct. Applies to all structs
+    /// decoded, including those of other crates, but not to maps or structs with flattened fields.
+    pub fn unknown_fields(mut self, policy: UnknownFields) -> Self {
+        self.unknown_fields = policy;
+        self
+    }
+
+    /// Decode strings without checking that they are valid UTF-8, which is a large part of the
+    /// time taken to decode documents made up mostly of strings.
+    ///
+    /// # Safety
+    ///
+    /// Every string in the input must be valid UTF-8, e.g. because the input was written by this
+    /// crate or already passed [`validate`](crate::validate). Decoding a string which isn't is
+    /// undefined behaviour.
+    pub unsafe fn assume_valid_utf8(mut self) -> Self {
+        self.trusted_utf8 = true;
+        self
+    }
+
+    /// Decode external values (0x1d), which hold a pointer to a value elsewhere in memory, by
+    /// passing the pointer to `resolver` and decoding the value in the slice it returns instead.
+    ///
+    /// Only useful to embedders which control the memory layout the pointers refer to, e.g. by
+    /// mapping pointers back to offsets in a buffer they built. Returning `None` fails
+    /// deserialization with `ErrorKind::UnresolvedExternal`. Without a resolver, external values fail
+    /// with `ErrorKind::Unsupported`.
+    pub fn with_external_resolver<F>(mut self, resolver: F) -> Self where
+        F: Fn(usize) -> Option<&'de [u8]> + 'de {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+
+    /// Fail with `ErrorKind::DepthLimitExceeded` on arrays, objects and tagged values nested more
+    /// than `max_depth` deep, rather than recursing until the stack overflows. Defaults to
+    /// [`DEFAULT_MAX_DEPTH`]; raising it risks overflowing the stack on untrusted input.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Fail with `ErrorKind::LimitExceeded` on strings and binary values longer than `length` bytes.
+    pub fn max_string_length(mut self, length: usize) -> Self {
+        self.max_string_length = Some(length);
+        self
+    }
+
+    /// Fail with `ErrorKind::LimitExceeded` on arrays and objects with more than `len` items or
+    /// members.
+    pub fn max_container_len(mut self, len: usize) -> Self {
+        self.max_container_len = Some(len);
+        self
+    }
+
+    /// Fail with `ErrorKind::LimitExceeded` on documents (top-level values) larger than `size` bytes,
+    /// before decoding any of them.
+    pub fn max_document_size(mut self, size: usize) -> Self {
+        self.max_document_size = Some(size);
+        self
+    }
+
+    /// Abort decoding with `ErrorKind::BudgetExceeded` after `budget` values nested within the
+    /// top-level one (array items, object keys and values) have been decoded, to bound the work
+    /// done for documents engineered to be expensive to decode.
+    pub fn budget(mut self, budget: u64) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Check that all input has been consumed, for use after deserializing a value from a
+    /// manually constructed `Deserializer`.
+    pub fn end(&self) -> Result<()> {
+        let remaining = self.remaining();
+        if remaining.is_empty() {
+            Ok(())
+        } else {
+            Err(ErrorKind::TrailingBytes(remaining.len()).into())
         }
     }
 
-    fn peek_bytes<I: SliceIndex<[u8]>>(&self, index: I) -> Result<&<I as SliceIndex<[u8]>>::Output> {
-        match self.input.get(index) {
-            Some(b) => Ok(b),
-            None => Err(Error::Eof),
+    fn remaining(&self) -> &'de [u8] {
+        self.resume.first().copied().unwrap_or(self.input)
+    }
+
+    // if the next value is external, continue with the value it points to as input, until
+    // `deserialize_value` finishes decoding it. Each external pointing to another counts towards
+    // the budget and depth limit, so that cycles of them fail rather than never ending.
+    fn resolve_external(&mut self) -> Result<()> {
+        if self.input.first() != Some(&0x1d) {
+            return Ok(());
+        }
+        if self.resolver.is_none() {
+            return Err(ErrorKind::Unsupported(ValueType::External).into());
+        }
+
+        let rest = &self.input[raw::value_byte_length(self.input)?..];
+        let mut hops = 0;
+        while self.input.first() == Some(&0x1d) {
+            debug!("0x1d -> resolving external value");
+            if hops > 0 {
+                self.spend_budget()?;
+            }
+            if self.depth + hops >= self.max_depth {
+                return Err(ErrorKind::DepthLimitExceeded.into());
+            }
+            hops += 1;
+            let ptr = raw::to_usize(raw::read_uint(&self.input[1..], std::mem::size_of::<usize>())?)?;
+            let resolver = self.resolver.as_ref().ok_or(ErrorKind::Unsupported(ValueType::External))?;
+            let target = resolver(ptr).ok_or(ErrorKind::UnresolvedExternal(ptr))?;
+            self.input = &target[..raw::value_byte_length(target)?];
+        }
+        self.resume.push(rest);
+        self.hooked_at = None;
+        Ok(())
+    }
+
+    fn spend_budget(&mut self) -> Result<()> {
+        if let Some(budget) = &mut self.budget {
+            *budget = budget.checked_sub(1).ok_or(ErrorKind::BudgetExceeded)?;
+        }
+        Ok(())
+    }
+
+    // if the next value is an integer attribute id known to the translator, consume it and
+    // return the id
+    fn translate_key(&mut self) -> Result<Option<u64>> {
+        let translator = match (&self.translator, self.input.first()) {
+            (Some(translator), Some(0x28..=0x39)) => translator,
+            _ => return Ok(None),
+        };
+        let length = raw::value_byte_length(self.input)?;
+        let id: u64 = from_bytes(&self.input[..length])?;
+        if translator.name(id).is_none() {
+            return Ok(None);
+        }
+        self.consume_bytes(length)?;
+        Ok(Some(id))
+    }
+
+    // decode a complete value, e.g. an array item, returning to the original input afterwards if
+    // it was external
+    fn deserialize_value<T>(&mut self, seed: T) -> Result<T::Value> where
+        T: DeserializeSeed<'de> {
+        self.deserialize_value_with(|de| seed.deserialize(de))
+    }
+
+    fn deserialize_value_with<T, F>(&mut self, f: F) -> Result<T> where
+        F: FnOnce(&mut Self) -> Result<T> {
+        self.spend_budget()?;
+        let depth = self.resume.len();
+        let value = f(self)?;
+        if self.resume.len() > depth {
+            self.input = self.resume[depth];
+            self.resume.truncate(depth);
+        }
+        Ok(value)
+    }
+
+    // if the next value is tagged, consume the tag and return it
+    fn read_tag(&mut self) -> Result<Option<u64>> {
+        let n_bytes = match self.input.first() {
+            Some(0xee) => U8_SIZE,
+            Some(0xef) => U64_SIZE,
+            _ => return Ok(None),
+        };
+        let tag = raw::read_uint(&self.input[1..], n_bytes)?;
+        self.consume_bytes(1 + n_bytes)?;
+        Ok(Some(tag))
+    }
+
+    fn rewrite(&mut self) -> Result<Option<Rewrite>> {
+        if self.hooks.is_empty() || self.hooked_at == Some(self.input.len()) {
+            return Ok(None);
+        }
+        self.hooked_at = Some(self.input.len());
+
+        let length = raw::value_byte_length(self.input)?;
+        let rewrite = self.hooks.run(&self.input[..length])?;
+        if rewrite.is_some() {
+            self.consume_bytes(length)?;
+        }
+        Ok(rewrite)
+    }
+
+    // decode an array or object, then skip past anything the visitor didn't consume, e.g. the
+    // index table, or remaining items when decoding a fixed number of them
+    fn visit_container<T, F>(&mut self, f: F) -> Result<T> where
+        F: FnOnce(&mut Self) -> Result<T> {
+        let input = self.input;
+        let length = raw::value_byte_length(input)?;
+        if let Some(max) = self.max_container_len {
+            check_limit(raw::Container::new(input)?.len(), max, "container length")?;
         }
+        if self.strict {
+            crate::validate::validate_container(&input[..length])?;
+        }
+        if self.duplicate_keys == DuplicateKeys::Error && ValueType::from_header(input[0]) == ValueType::Object {
+            self.check_duplicate_keys(&input[..length])?;
+        }
+        let value = self.nested(f)?;
+        self.input = &input[length..];
+        Ok(value)
+    }
+
+    fn check_duplicate_keys(&self, object: &[u8]) -> Result<()> {
+        let mut keys = HashSet::new();
+        for member in raw::Container::new(object)?.members() {
+            let key = self.key_name(member?.0)?;
+            if !keys.insert(key.clone()) {
+                return Err(ErrorKind::DuplicateKey(key.into_owned()).into());
+            }
+        }
+        Ok(())
     }
 
-    fn consume_padding(&mut self) -> Result<()> {
-        while self.peek_byte()? == 0x00 {
-            self.consume_bytes(1);
+    // check that every member of `object` is one of a struct's `fields`
+    fn check_fields(&self, object: &[u8], fields: &[&str]) -> Result<()> {
+        for member in raw::Container::new(object)?.members() {
+            let key = self.key_name(member?.0)?;
+            if !fields.contains(&&*key) {
+                return Err(ErrorKind::UnknownField(key.into_owned()).into());
+            }
         }
         Ok(())
     }
 
-    fn next_byte(&mut self) -> Result<u8> {
-        let b = self.peek_byte()?;
-        self.consume_bytes(1);
-        Ok(b)
+    // name of an encoded object key, translating integer keys known to the translator
+    fn key_name<'k>(&self, key: &'k [u8]) -> Result<Cow<'k, str>> {
+        let name = raw::read_key(key)?;
+        // integer keys known to the translator are reported by the attribute name
+        let translated = match (&self.translator, key[0]) {
+            (Some(translator), 0x28..=0x39) => name.parse().ok().and_then(|id| translator.name(id)),
+            _ => None,
+        };
+        Ok(translated.map_or(name, |t| Cow::Owned(t.to_owned())))
     }
 
-    fn consume_bytes(&mut self, n: usize) {
-        self.input = &self.input[n..];
+    fn check_document_size(&self) -> Result<()> {
+        match self.max_document_size {
+            Some(max) if self.depth == 0 && self.resume.is_empty() => {
+                check_limit(raw::value_byte_length(self.input)?, max, "document size")
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn check_string_length(&self, length: usize) -> Result<()> {
+        match self.max_string_length {
+            Some(max) => check_limit(length, max, "string length"),
+            None => Ok(()),
+        }
+    }
+
+    // move to the first item, or key, of the array or object at the start of the input, returning
+    // the number of items or members. Index tables are left unread, since items follow each other
+    // directly, and are skipped by `visit_container` afterwards.
+    fn enter_container(&mut self) -> Result<usize> {
+        let container = raw::Container::new(self.input)?;
+        if container.len() > 0 {
+            let offset = container.data().as_ptr() as usize - self.input.as_ptr() as usize;
+            self.consume_bytes(offset)?;
+        }
+        Ok(container.len())
+    }
+
+    // decode values nested one level deeper, e.g. the items of an array
+    fn nested<T, F>(&mut self, f: F) -> Result<T> where
+        F: FnOnce(&mut Self) -> Result<T> {
+        if self.depth >= self.max_depth {
+            return Err(ErrorKind::DepthLimitExceeded.into());
+        }
+        self.depth += 1;
+        let value = f(self);
+        self.depth -= 1;
+        value
+    }
+
+    // `error` for a value of the wrong type at the start of the input, with the header byte found
+    // and its offset within the document, if the input is still within it rather than an
+    // external value
+    fn mismatch(&self, kind: ErrorKind) -> Error {
+        let found = match self.input.first() {
+            Some(b) => *b,
+            None => return ErrorKind::Eof.into(),
+        };
+        let start = self.document.as_ptr() as usize;
+        let offset = (self.input.as_ptr() as usize).checked_sub(start).filter(|o| *o <= self.document.len());
+        Error::from(kind).at_value(found, offset)
+    }
+
+    fn peek_byte(&self) -> Result<u8> {
+        match self.input.first() {
+            Some(b) => Ok(*b),
+            None => Err(ErrorKind::Eof.into()),
+        }
     }
 
-    fn consume_header(&mut self) {
-        self.consume_bytes(1);
+    fn peek_bytes<I: SliceIndex<[u8]>>(&self, index: I) -> Result<&<I as SliceIndex<[u8]>>::Output> {
+        match self.input.get(index) {
+            Some(b) => Ok(b),
+            None => Err(ErrorKind::Eof.into()),
+        }
     }
 
-    fn consume_u8(&mut self) -> Result<u8> {
-        let mut bytes: [u8; U8_SIZE] = Default::default();
-        bytes.copy_from_slice(self.peek_bytes(..U8_SIZE)?);
-        self.consume_bytes(U8_SIZE);
-        Ok(u8::from_le_bytes(bytes))
+    // every read of the input goes through here or `peek_bytes`, which fail on truncated input
+    // rather than panicking
+    fn consume_bytes(&mut self, n: usize) -> Result<()> {
+        self.input = self.input.get(n..).ok_or(ErrorKind::Eof)?;
+        Ok(())
     }
 
-    fn consume_u16(&mut self) -> Result<u16> {
-        let mut bytes: [u8; U16_SIZE] = Default::default();
-        bytes.copy_from_slice(self.peek_bytes(..U16_SIZE)?);
-        self.consume_bytes(U16_SIZE);
-        Ok(u16::from_le_bytes(bytes))
+    // consume the next value in one step using its byte length, without decoding any of it (or
+    // anything nested in it), returning its encoded bytes
+    fn skip_value(&mut self) -> Result<&'de [u8]> {
+        let input = self.input;
+        let length = raw::value_byte_length(input)?;
+        self.consume_bytes(length)?;
+        Ok(&input[..length])
     }
 
-    fn consume_u32(&mut self) -> Result<u32> {
-        let mut bytes: [u8; U32_SIZE] = Default::default();
-        bytes.copy_from_slice(self.peek_bytes(..U32_SIZE)?);
-        self.consume_bytes(U32_SIZE);
-        Ok(u32::from_le_bytes(bytes))
+    fn consume_header(&mut self) -> Result<()> {
+        self.consume_bytes(1)
     }
 
     fn consume_u64(&mut self) -> Result<u64> {
         let mut bytes: [u8; U64_SIZE] = Default::default();
         bytes.copy_from_slice(self.peek_bytes(..U64_SIZE)?);
-        self.consume_bytes(U64_SIZE);
+        self.consume_bytes(U64_SIZE)?;
         Ok(u64::from_le_bytes(bytes))
     }
 
@@ -87,162 +666,202 @@ impl<'de> Deserializer<'de> {
         match self.peek_byte()? {
             0x19 =>  {
                 debug!("0x19 -> deserializing boolean [false]");
-                self.consume_bytes(1);
+                self.consume_bytes(1)?;
                 Ok(false)
             },
             0x1a => {
                 debug!("0x1a -> deserializing boolean [true]");
-                self.consume_bytes(1);
+                self.consume_bytes(1)?;
                 Ok(true)
             },
-            _   => Err(Error::ExpectedBoolean),
+            _   => Err(self.mismatch(ErrorKind::ExpectedBoolean)),
         }
     }
 
     fn parse_double(&mut self) -> Result<f64> {
         match self.peek_byte()? {
+            0xc8..=0xd7 => return self.parse_bcd()?.parse().map_err(|_| ErrorKind::ExpectedDouble.into()),
+            // integers, e.g. as written for integral doubles by `SerializerOptions::integral_doubles_as_ints`
+            0x20..=0x27 | 0x3a..=0x3f => return Ok(self.parse_signed::<i64>()? as f64),
+            0x28..=0x39 => return Ok(self.parse_unsigned::<u64>()? as f64),
             0x1b => {
                 debug!("0x1b -> deserializing double");
-                self.consume_bytes(1)
+                self.consume_bytes(1)?
             },
-            _    => return Err(Error::ExpectedDouble),
+            _    => return Err(self.mismatch(ErrorKind::ExpectedDouble)),
         }
 
         let mut bytes: [u8; 8] = Default::default();
-        bytes.copy_from_slice(&self.input[..8]);
+        bytes.copy_from_slice(self.peek_bytes(..8)?);
 
         let v = f64::from_bits(u64::from_le_bytes(bytes));
-        self.consume_bytes(8);
+        self.consume_bytes(8)?;
         Ok(v)
     }
 
     fn parse_signed<T: TryFrom<i64> + TryFrom<u64>>(&mut self) -> Result<T> {
         match self.peek_byte()? {
-            b if b >= 0x3a && b <= 0x3f => {
+            b @ 0x3a..=0x3f => {
                 debug!("0x{:x?} -> deserializing small negative integer", b);
-                self.consume_bytes(1);
-                Ok(T::try_from(-(0x40 - (b as i64))).unwrap_or_else(|_| panic!("Unable to convert to signed")))
+                self.consume_bytes(1)?;
+                T::try_from(-(0x40 - (b as i64))).map_err(|_| ErrorKind::NumberTooLarge.into())
             },
-            b if b >= 0x20 && b <= 0x27 => {
+            b @ 0x20..=0x27 => {
                 debug!("0x{:x?} -> deserializing signed integer (1 to 8 bytes)", b);
                 let n_bytes = (b - 0x1f) as usize;
-                self.consume_header();
-
-                let v: i64 = match n_bytes {
-                    1 => {
-                        let mut le_bytes: [u8; 1] = [0x00; 1];
-                        le_bytes[..n_bytes].copy_from_slice(&self.input[..n_bytes]);
-                        i8::from_le_bytes(le_bytes) as i64
-                    },
-                    2 => {
-                        let mut le_bytes: [u8; 2] = [0x00; 2];
-                        le_bytes[..n_bytes].copy_from_slice(&self.input[..n_bytes]);
-                        i16::from_le_bytes(le_bytes) as i64
-                    },
-                    4 => {
-                        let mut le_bytes: [u8; 4] = [0x00; 4];
-                        le_bytes[..n_bytes].copy_from_slice(&self.input[..n_bytes]);
-                        i32::from_le_bytes(le_bytes) as i64
-                    },
-                    8 => {
-                        let mut le_bytes: [u8; 8] = [0x00; 8];
-                        le_bytes[..n_bytes].copy_from_slice(&self.input[..n_bytes]);
-                        i64::from_le_bytes(le_bytes)
-                    },
-                    n => {
-                        let msg = format!("Invalid byte length for signed integer: {} (valid: 1, 2, 4, 8)", n);
-                        return Err(Error::Message(msg));
-                    },
-                };
+                self.consume_header()?;
+
+                let v = raw::read_int(self.input, n_bytes)?;
 
                 let value = match T::try_from(v) {
                     Ok(v) => v,
-                    Err(_) => return Err(Error::NumberTooLarge),
+                    Err(_) => return Err(ErrorKind::NumberTooLarge.into()),
                 };
-                self.consume_bytes(n_bytes); // number of bytes header plus bytes
+                self.consume_bytes(n_bytes)?; // number of bytes header plus bytes
                 Ok(value)
             },
             _ => {
                 // else parse into a u64, then attempt to fit into current signed type
                 let v_u64: u64 = self.parse_unsigned()?;
-                T::try_from(v_u64).map_err(|_| Error::NumberTooLarge)
+                T::try_from(v_u64).map_err(|_| ErrorKind::NumberTooLarge.into())
             }
         }
     }
 
     fn parse_unsigned<T: TryFrom<u64>>(&mut self) -> Result<T> {
         match self.peek_byte()? {
-            b if b >= 0x28 && b <= 0x2f => {
+            b @ 0x28..=0x2f => {
                 debug!("0x{:x?} -> deserializing unsigned integer (1 to 8 bytes)", b);
                 let n_bytes = (b - 0x27) as usize;
-                self.consume_bytes(1);
+                self.consume_bytes(1)?;
 
-                let mut le_bytes: [u8; 8] = [0; 8];
-                le_bytes[..n_bytes].copy_from_slice(&self.input[..n_bytes]);
-                let v = match T::try_from(u64::from_le_bytes(le_bytes)) {
+                let v = match T::try_from(raw::read_uint(self.input, n_bytes)?) {
                     Ok(v) => v,
-                    Err(_) => return Err(Error::NumberTooLarge),
+                    Err(_) => return Err(ErrorKind::NumberTooLarge.into()),
                 };
-                self.consume_bytes(n_bytes); // number of bytes header plus bytes
+                self.consume_bytes(n_bytes)?; // number of bytes header plus bytes
                 Ok(v)
             },
-            b if b >= 0x30 && b <= 0x39 => {
+            b @ 0x30..=0x39 => {
                 debug!("0x{:x?} -> deserializing unsigned integer (1 to 9)", b);
                 let v = match T::try_from((b - 0x30) as u64) {
                     Ok(v) => v,
-                    Err(_) => return Err(Error::NumberTooLarge),
+                    Err(_) => return Err(ErrorKind::NumberTooLarge.into()),
                 };
-                self.consume_bytes(1);
+                self.consume_bytes(1)?;
                 Ok(v)
             },
-            _ => Err(Error::ExpectedInteger),
+            _ => Err(self.mismatch(ErrorKind::ExpectedInteger)),
         }
     }
 
-    fn parse_string(&mut self) -> Result<String> {
-        match self.peek_byte()? {
+    fn parse_str(&mut self) -> Result<&'de str> {
+        let length = match self.peek_byte()? {
             0xbf => {
-                self.consume_bytes(1);
-                let mut le_bytes: [u8; 8] = [0; 8];
-                le_bytes[..8].copy_from_slice(&self.input[..8]);
-                let length = u64::from_le_bytes(le_bytes) as usize;
-                self.consume_bytes(8);
-                match std::str::from_utf8(&self.input[..length]) {
-                    Ok(s) => {
-                        self.consume_bytes(length);
-                        Ok(s.to_owned())
-                    },
-                    Err(utf8err) => Err(Error::InvalidUtf8(utf8err)),
-                }
+                debug!("0xbf -> deserializing long string");
+                self.consume_header()?;
+                raw::to_usize(self.consume_u64()?)?
             },
-            b if b >= 0x40 && b <= 0xbe => {
-                self.consume_header();
-                let length = (b - 0x40) as usize;
-                if length == 0 {
-                    return Ok(String::new())
-                }
-
-                match std::str::from_utf8(&self.input[..length]) {
-                    Ok(s) => {
-                        self.consume_bytes(length);
-                        Ok(s.to_owned())
-                    },
-                    Err(utf8err) => Err(Error::InvalidUtf8(utf8err)),
-                }
+            b @ 0x40..=0xbe => {
+                debug!("0x{:x?} -> deserializing short string", b);
+                self.consume_header()?;
+                (b - 0x40) as usize
+            },
+            _ => return Err(self.mismatch(ErrorKind::ExpectedString)),
+        };
+        self.check_string_length(length)?;
+
+        // borrow from the input itself rather than self, so the str lives for 'de
+        let input = self.input;
+        let bytes = input.get(..length).ok_or(ErrorKind::Eof)?;
+        if self.trusted_utf8 {
+            // SAFETY: the caller of `assume_valid_utf8` guarantees strings are valid UTF-8
+            let s = unsafe { std::str::from_utf8_unchecked(bytes) };
+            self.consume_bytes(length)?;
+            return Ok(s);
+        }
+        match std::str::from_utf8(bytes) {
+            Ok(s) => {
+                self.consume_bytes(length)?;
+                Ok(s)
             },
-            _ => Err(Error::ExpectedString),
+            Err(utf8err) => Err(utf8err.into()),
         }
     }
+
+    fn parse_bcd(&mut self) -> Result<String> {
+        debug!("0x{:x?} -> deserializing bcd", self.peek_byte()?);
+        let s = raw::read_bcd(self.input)?;
+        self.consume_bytes(raw::value_byte_length(self.input)?)?;
+        Ok(s)
+    }
+
+    fn parse_utc_date(&mut self) -> Result<i64> {
+        debug!("0x1c -> deserializing utc date");
+        self.consume_header()?;
+        let ms = raw::read_int(self.input, U64_SIZE)?;
+        self.consume_bytes(U64_SIZE)?;
+        Ok(ms)
+    }
+
+    fn parse_binary(&mut self) -> Result<&'de [u8]> {
+        let length = match self.peek_byte()? {
+            b @ 0xc0..=0xc7 => {
+                debug!("0x{:x?} -> deserializing binary", b);
+                let n_bytes = (b - 0xbf) as usize;
+                self.consume_header()?;
+                let length = raw::read_uint(self.input, n_bytes)?;
+                self.consume_bytes(n_bytes)?;
+                usize::try_from(length).map_err(|_| ErrorKind::NumberTooLarge)?
+            },
+            _ => return Err(self.mismatch(ErrorKind::ExpectedBinary)),
+        };
+        self.check_string_length(length)?;
+
+        let input = self.input;
+        let bytes = input.get(..length).ok_or(ErrorKind::Eof)?;
+        self.consume_bytes(length)?;
+        Ok(bytes)
+    }
 }
 
 /// Deserialize a single VelocyPack's bytes into a struct.
+///
+/// Malformed input of any kind fails with an error, as do arrays, objects and tagged values nested
+/// more than [`DEFAULT_MAX_DEPTH`] deep; decoding never panics, whatever `s` holds.
 pub fn from_bytes<'a, T: Deserialize<'a>>(s: &'a [u8]) -> Result<T> {
-    let (t, remaining_bytes) = first_from_bytes(s)?;
-    if remaining_bytes.is_empty() {
-        Ok(t)
-    } else {
-        Err(Error::TrailingBytes(remaining_bytes.len()))
-    }
+    from_bytes_with_options(s, &DeserializerOptions::default())
+}
+
+/// Deserialize a single VelocyPack's bytes with the decoding chosen by `options`.
+pub fn from_bytes_with_options<'a, T: Deserialize<'a>>(s: &'a [u8], options: &DeserializerOptions) -> Result<T> {
+    let mut deserializer = Deserializer::with_options(s, options);
+    let t = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserialize a single VelocyPack's bytes into a value which doesn't borrow from them, taking
+/// ownership of the buffer, e.g. to avoid lifetimes when passing decoded values between tasks.
+pub fn from_bytes_owned<T: DeserializeOwned>(bytes: Vec<u8>) -> Result<T> {
+    from_bytes(&bytes)
+}
+
+/// Deserialize a single VelocyPack's bytes, allowing structs to be decoded from arrays by field
+/// position (e.g. `[1, "Bob"]` into `struct { id: u64, name: String }`) as well as from objects.
+pub fn from_array_bytes<'a, T: Deserialize<'a>>(s: &'a [u8]) -> Result<T> {
+    let mut deserializer = Deserializer::from_bytes(s).positional_structs(true);
+    let t = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserialize the value starting at `offset` in `bytes`, returning it along with the offset just
+/// past its end. Values are read byte by byte, so needn't be aligned in memory.
+pub fn from_bytes_at<'a, T: Deserialize<'a>>(bytes: &'a [u8], offset: usize) -> Result<(T, usize)> {
+    let input = bytes.get(offset..).ok_or(ErrorKind::Eof)?;
+    let (t, remaining) = first_from_bytes(input)?;
+    Ok((t, bytes.len() - remaining.len()))
 }
 
 /// Deserialize the first VelocyPack found in given bytes, and return it along with any remaining
@@ -252,176 +871,343 @@ pub fn from_bytes<'a, T: Deserialize<'a>>(s: &'a [u8]) -> Result<T> {
 pub fn first_from_bytes<'a, T: Deserialize<'a>>(s: &'a [u8]) -> Result<(T, &'a [u8])> {
     let mut deserializer = Deserializer::from_bytes(s);
     let t = T::deserialize(&mut deserializer)?;
-    Ok((t, deserializer.input))
+    Ok((t, deserializer.remaining()))
 }
 
-impl<'de> Deserializer<'de> {
+/// Deserialize each of the VelocyPacks packed back to back in `s`, as in VelocyStream bodies or
+/// batch responses. Iteration stops after the first error.
+pub fn iter_from_bytes<'a, T: Deserialize<'a>>(mut s: &'a [u8]) -> impl Iterator<Item = Result<T>> + 'a {
+    std::iter::from_fn(move || {
+        if s.is_empty() {
+            return None;
+        }
+        match first_from_bytes(s) {
+            Ok((t, remaining)) => {
+                s = remaining;
+                Some(Ok(t))
+            },
+            Err(e) => {
+                s = &[];
+                Some(Err(e))
+            },
+        }
+    })
+}
 
+fn check_limit(value: usize, max: usize, limit: &'static str) -> Result<()> {
+    if value > max {
+        return Err(ErrorKind::LimitExceeded(limit).into());
+    }
+    Ok(())
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        match self.peek_byte()? {
-            b if (b >= 0x01 && b <= 0x09) || b == 0x13 => self.deserialize_seq(visitor),
-            b if (b >= 0x0a && b <= 0x12) || b == 0x14 => self.deserialize_map(visitor),
-            0x18 => self.deserialize_unit(visitor),
-            0x19 | 0x1a => self.deserialize_bool(visitor),
-            0x1b => self.deserialize_f64(visitor),
-            b if (b >= 0x20 && b <= 0x27) || (b >= 0x3a && b <= 0x3f) => self.deserialize_i64(visitor),
-            b if b >= 0x28 && b <= 0x39 => self.deserialize_u64(visitor),
-            b if b >= 0x40 && b <= 0xbf => self.deserialize_string(visitor),
-            b => Err(Error::Unimplemented(b)),
+        rewrite!(self, visitor);
+        let header = self.peek_byte()?;
+        match ValueType::from_header(header) {
+            ValueType::Array => self.deserialize_seq(visitor),
+            ValueType::Object => self.deserialize_map(visitor),
+            ValueType::Null => self.deserialize_unit(visitor),
+            ValueType::Bool => self.deserialize_bool(visitor),
+            ValueType::Double | ValueType::UtcDate | ValueType::MinKey | ValueType::MaxKey | ValueType::Int |
+            ValueType::UInt | ValueType::SmallInt | ValueType::Custom if self.decoding_value => {
+                let value = std::iter::once((crate::RAW_TOKEN, self.skip_value()?));
+                visitor.visit_map(de::value::MapDeserializer::<_, Error>::new(value))
+            },
+            ValueType::Double => self.deserialize_f64(visitor),
+            ValueType::Int => self.deserialize_i64(visitor),
+            ValueType::SmallInt if header >= 0x3a => self.deserialize_i64(visitor),
+            ValueType::UInt | ValueType::SmallInt => self.deserialize_u64(visitor),
+            ValueType::String | ValueType::Bcd => self.deserialize_string(visitor),
+            // self-describing targets such as serde_json::Value can't take bytes, so binary
+            // values are visited as a sequence of u8
+            ValueType::Binary => {
+                let bytes = self.parse_binary()?;
+                if self.decoding_value {
+                    return visitor.visit_borrowed_bytes(bytes);
+                }
+                visitor.visit_seq(SeqDeserializer::new(bytes.iter().copied()))
+            },
+            ValueType::UtcDate => visitor.visit_i64(self.parse_utc_date()?),
+            ValueType::Illegal | ValueType::MinKey | ValueType::MaxKey => {
+                debug!("0x{:x?} -> deserializing illegal/min key/max key as unit", header);
+                self.consume_header()?;
+                visitor.visit_unit()
+            },
+            ValueType::Tagged => {
+                // only reached when decoding a `Value`, which keeps the tag as a single
+                // `TAGGED_TOKEN` member holding the tag and value
+                debug!("0x{:x?} -> deserializing tagged value", header);
+                self.nested(|de| visitor.visit_map(TaggedDeserializer { de, tag: None, done: false }))
+            },
+            ValueType::Custom => {
+                // custom types are application specific, pass along the value's bytes as-is
+                debug!("0x{:x?} -> deserializing custom type as bytes", header);
+                visitor.visit_seq(SeqDeserializer::new(self.skip_value()?.iter().copied()))
+            },
+            // external values are resolved by `rewrite!` before getting here
+            ValueType::External | ValueType::None => Err(ErrorKind::Unimplemented(header).into()),
         }
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         visitor.visit_bool(self.parse_bool()?)
     }
 
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         visitor.visit_i8(self.parse_signed()?)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         visitor.visit_i16(self.parse_signed()?)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         visitor.visit_i32(self.parse_signed()?)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         visitor.visit_i64(self.parse_signed()?)
     }
 
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value> where
+        V: Visitor<'de> {
+        rewrite!(self, visitor);
+        visitor.visit_i128(self.parse_signed()?)
+    }
+
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         visitor.visit_u8(self.parse_unsigned()?)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         visitor.visit_u16(self.parse_unsigned()?)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         visitor.visit_u32(self.parse_unsigned()?)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         visitor.visit_u64(self.parse_unsigned()?)
     }
 
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value> where
+        V: Visitor<'de> {
+        rewrite!(self, visitor);
+        visitor.visit_u128(self.parse_unsigned()?)
+    }
+
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         visitor.visit_f32(self.parse_double()? as f32)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         visitor.visit_f64(self.parse_double()?)
     }
 
-    fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value> where
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        unimplemented!()
+        rewrite!(self, visitor);
+        let s = self.parse_str()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(de::Error::invalid_value(de::Unexpected::Str(s), &"a single character")),
+        }
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        visitor.visit_string(self.parse_string()?)
+        rewrite!(self, visitor);
+        match self.peek_byte()? {
+            // decimal numbers of arbitrary precision, so best represented as strings
+            0xc8..=0xd7 => visitor.visit_string(self.parse_bcd()?),
+            _ => visitor.visit_borrowed_str(self.parse_str()?),
+        }
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        visitor.visit_string(self.parse_string()?)
+        rewrite!(self, visitor);
+        self.deserialize_str(visitor)
     }
 
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value> where
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        unimplemented!()
+        rewrite!(self, visitor);
+        visitor.visit_borrowed_bytes(self.parse_binary()?)
     }
 
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value> where
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        unimplemented!()
+        rewrite!(self, visitor);
+        self.deserialize_bytes(visitor)
     }
 
-    fn deserialize_option<V>(self, _visitor: V) -> Result<V::Value> where
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        unimplemented!()
+        self.resolve_external()?;
+        match self.rewrite()? {
+            Some(Rewrite::Null) => visitor.visit_none(),
+            Some(rewrite) => visitor.visit_some(rewrite),
+            None if self.peek_byte()? == 0x18 => {
+                debug!("0x18 -> deserializing none");
+                self.consume_header()?;
+                visitor.visit_none()
+            },
+            None => visitor.visit_some(self),
+        }
     }
 
     fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         match self.peek_byte()? {
             0x18 => {
                 debug!("0x18 -> deserializing null");
-                self.consume_bytes(1);
+                self.consume_bytes(1)?;
                 visitor.visit_unit()
             },
-            _    => Err(Error::ExpectedNull)
+            _    => Err(self.mismatch(ErrorKind::ExpectedNull))
         }
     }
 
     fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
+        rewrite!(self, visitor);
         self.deserialize_unit(visitor)
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, _visitor: V) -> Result<V::Value> where
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        unimplemented!()
+        self.resolve_external()?;
+        self.check_document_size()?;
+        if let Some(rewrite) = self.rewrite()? {
+            // this crate's own types take the replacement as is, others wrap it
+            if name.starts_with("$velocypack::private::") {
+                return rewrite.visit(visitor);
+            }
+            return visitor.visit_newtype_struct(rewrite);
+        }
+        if name == crate::UTC_DATE_TOKEN && self.peek_byte()? == 0x1c {
+            return visitor.visit_i64(self.parse_utc_date()?);
+        }
+        if name == crate::TAGGED_TOKEN {
+            let tag = self.read_tag()?.ok_or_else(|| self.mismatch(ErrorKind::ExpectedTagged))?;
+            return visitor.visit_seq(TaggedDeserializer { de: self, tag: Some(tag), done: false });
+        }
+        if name == crate::RAW_TOKEN {
+            return visitor.visit_borrowed_bytes(self.skip_value()?);
+        }
+        if name == crate::VALUE_TOKEN {
+            let decoding_value = std::mem::replace(&mut self.decoding_value, true);
+            let value = visitor.visit_newtype_struct(&mut *self);
+            self.decoding_value = decoding_value;
+            return value;
+        }
+        visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(mut self, visitor: V) -> Result<V::Value> where
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        visitor.visit_seq(ArrayDeserializer::new(&mut self))
+        rewrite!(self, visitor);
+        self.visit_container(|de| visitor.visit_seq(ArrayDeserializer::new(de)))
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value> where
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        unimplemented!()
+        rewrite!(self, visitor);
+        self.deserialize_seq(visitor)
     }
 
-    fn deserialize_tuple_struct<V>(self, _name: &'static str, _len: usize, _visitor: V) -> Result<V::Value> where
+    fn deserialize_tuple_struct<V>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        unimplemented!()
+        rewrite!(self, visitor);
+        self.deserialize_seq(visitor)
     }
 
-    fn deserialize_map<V>(mut self, visitor: V) -> Result<V::Value> where
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        visitor.visit_map(MapDeserializer::new(&mut self))
+        rewrite!(self, visitor);
+        self.visit_container(|de| visitor.visit_map(MapDeserializer::new(de)))
     }
 
-    fn deserialize_struct<V>(mut self, _name: &'static str, _fields: &'static [&'static str], visitor: V) -> Result<V::Value> where
+    fn deserialize_struct<V>(self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        visitor.visit_map(MapDeserializer::new(&mut self))
+        rewrite!(self, visitor);
+        let header = self.peek_byte()?;
+        if self.positional_structs && ValueType::from_header(header) == ValueType::Array {
+            return self.visit_container(|de| visitor.visit_seq(ArrayDeserializer::new(de)));
+        }
+        if self.unknown_fields == UnknownFields::Error && ValueType::from_header(header) == ValueType::Object {
+            self.check_fields(self.input, fields)?;
+        }
+        if self.lookup_fields && self.translator.is_none() && (0x0b..=0x0e).contains(&header) {
+            debug!("0x{:x?} -> deserializing struct fields by index table", header);
+            let mut members = Vec::with_capacity(fields.len());
+            for field in fields {
+                if let Some((_, value)) = raw::sorted_object_find(self.input, field)? {
+                    members.push((*field, value));
+                }
+            }
+            return self.visit_container(|de| visitor.visit_map(FieldsDeserializer { de, members: members.into_iter(), current: None }));
+        }
+        self.visit_container(|de| visitor.visit_map(MapDeserializer::new(de)))
     }
 
-    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str], _visitor: V) -> Result<V::Value> where
+    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        unimplemented!()
+        rewrite!(self, visitor);
+        match self.peek_byte()? {
+            // integers or strings
+            0x20..=0xbf => visitor.visit_enum(UnitVariantDeserializer { de: self }),
+            _ => Err(self.mismatch(ErrorKind::ExpectedString)),
+        }
     }
 
+    // unit variants may be encoded by index, see `SerializerOptions::unit_variants`, but struct
+    // fields are always named
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        self.deserialize_string(visitor)
+        rewrite!(self, visitor);
+        match self.peek_byte()? {
+            0x20..=0x3f if self.decoding_variant => self.deserialize_u64(visitor),
+            _ => self.deserialize_string(visitor),
+        }
     }
 
-    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value> where
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value> where
         V: Visitor<'de> {
-        unimplemented!()
+        rewrite!(self, visitor);
+        self.skip_value()?;
+        visitor.visit_unit()
     }
 
     fn is_human_readable(&self) -> bool {
@@ -431,13 +1217,22 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
 struct MapDeserializer<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
-    index_size: Option<usize>,
     remaining_items: Option<usize>,
+    // input starting with the key of the current member, for the path of errors in its value
+    key: &'de [u8],
 }
 
 impl<'a, 'de> MapDeserializer<'a, 'de> {
     fn new(de: &'a mut Deserializer<'de>) -> Self {
-        Self { de, index_size: None, remaining_items: None }
+        Self { de, remaining_items: None, key: &[] }
+    }
+
+    // add the current member's key to the path of `error`
+    fn at_key(&self, error: Error) -> Error {
+        match self.de.key_name(self.key) {
+            Ok(name) => error.at_key(&name),
+            Err(_) => error,
+        }
     }
 }
 
@@ -446,132 +1241,127 @@ impl<'de, 'a> MapAccess<'de> for MapDeserializer<'a, 'de> {
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>> where
         K: DeserializeSeed<'de> {
-        if self.remaining_items.is_none() {
-            match self.de.peek_byte()? {
-                0x0a => {
-                    self.de.consume_header();
-                    return Ok(None);
-                },
-                0x0b | 0x0f => {
-                    self.de.consume_header();
-                    let _byte_len = self.de.consume_u8()? as usize - 1 - 2*U8_SIZE; // sub header, bytelen, nitems
-                    let num_items = self.de.consume_u8()? as usize;
-                    self.remaining_items = Some(num_items);
-                    self.index_size = Some(U8_SIZE * num_items);
-                    self.de.consume_padding()?;
-                },
-                0x0c | 0x10 => {
-                    self.de.consume_header();
-                    let _byte_len = self.de.consume_u16()? as usize - 1 - 2*U16_SIZE; // sub header, bytelen, nitems
-                    let num_items = self.de.consume_u16()? as usize;
-                    self.remaining_items = Some(num_items);
-                    self.index_size = Some(U16_SIZE * num_items);
-                    self.de.consume_padding()?;
-                },
-                0x0d | 0x11 => {
-                    self.de.consume_header();
-                    let _byte_len = self.de.consume_u32()? as usize - 1 - 2*U32_SIZE; // sub header, bytelen, nitems
-                    let num_items = self.de.consume_u32()? as usize;
-                    self.remaining_items = Some(num_items);
-                    self.index_size = Some(U32_SIZE * num_items);
-                    self.de.consume_padding()?;
-                },
-                0x0e | 0x12 => {
-                    // FIXME: num items is at end
-                    self.de.consume_header();
-                    let _byte_len = self.de.consume_u64()? as usize - 1 - 2*U64_SIZE; // sub header, bytelen, nitems
-                    let num_items = self.de.consume_u64()? as usize;
-                    self.remaining_items = Some(num_items);
-                    self.index_size = Some(U64_SIZE * num_items);
-                    self.de.consume_padding()?;
-                },
-                0x14 => {
-                    // compact object
-                    self.de.consume_header();
-
-                    let mut buf: [u8; 8] = [0; 8];
-                    let length_bits: &mut BitSlice<Lsb0, u8> = bitvec::slice::BitSlice::<Lsb0, u8>::from_slice_mut(&mut buf[..]).unwrap();
-
-                    let mut header_size = 1; // header, increment with bytelen bytes
-                    let mut idx = 0;
-                    loop {
-                        let b = self.de.next_byte()?;
-                        for n in 0..7 {
-                            if (b & (1 << n)) != 0 {
-                                length_bits.set(idx, true);
-                            }
-                            idx += 1;
-                        }
-
-                        header_size += 1;
-
-                        if (b & (1 << 7)) == 0 { // check high bit set
-                            break;
-                        }
-                    }
+        let remaining_items = match self.remaining_items {
+            Some(n) => n,
+            None => {
+                match self.de.peek_byte()? {
+                    b @ 0x0a..=0x12 | b @ 0x14 => debug!("0x{:x?} -> deserializing object", b),
+                    _ => return Err(self.de.mismatch(ErrorKind::ExpectedObject)),
+                }
+                self.de.enter_container()?
+            },
+        };
+        if remaining_items == 0 {
+            self.remaining_items = Some(0);
+            return Ok(None);
+        }
 
-                    let bytelength = u64::from_le_bytes(buf) as usize;
+        self.remaining_items = Some(remaining_items - 1);
+        self.key = self.de.input;
+        if let Some(id) = self.de.translate_key()? {
+            self.de.spend_budget()?;
+            // borrowed from the translator, rather than copied for every key
+            let name = self.de.translator.as_ref().and_then(|t| t.name(id)).unwrap_or_default();
+            return seed.deserialize(de::value::StrDeserializer::<Error>::new(name)).map(Some);
+        }
+        self.de.deserialize_value_with(|de| seed.deserialize(KeyDeserializer { de })).map(Some).map_err(|e| self.at_key(e))
+    }
 
-                    let remaining_bytes = bytelength - header_size;
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value> where
+        V: DeserializeSeed<'de> {
+        self.de.deserialize_value(seed).map_err(|e| self.at_key(e))
+    }
+}
 
-                    let mut buf: [u8; 8] = [0; 8];
-                    let length_bits: &mut BitSlice<Lsb0, u8> = bitvec::slice::BitSlice::<Lsb0, u8>::from_slice_mut(&mut buf[..]).unwrap();
+// the members of a sorted object named by a struct's fields, found through the object's index
+// table by `Deserializer::lookup_fields`, along with their values
+struct FieldsDeserializer<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    members: std::vec::IntoIter<(&'static str, &'de [u8])>,
+    current: Option<(&'static str, &'de [u8])>,
+}
 
-                    let mut index_size = 0;
-                    let mut idx = 0;
+impl<'de, 'a> MapAccess<'de> for FieldsDeserializer<'a, 'de> {
+    type Error = Error;
 
-                    for b in self.de.input[..remaining_bytes].iter().rev() {
-                        for n in 0..7 {
-                            if (b & (1 << n)) != 0 {
-                                length_bits.set(idx, true);
-                            }
-                            idx += 1;
-                        }
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>> where
+        K: DeserializeSeed<'de> {
+        self.current = self.members.next();
+        match self.current {
+            Some((field, _)) => {
+                self.de.spend_budget()?;
+                seed.deserialize(de::value::BorrowedStrDeserializer::<Error>::new(field)).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
 
-                        index_size += 1;
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value> where
+        V: DeserializeSeed<'de> {
+        let (field, value) = self.current.take().ok_or(ErrorKind::Invalid("value requested before key"))?;
+        self.de.input = value;
+        self.de.deserialize_value(seed).map_err(|e| e.at_key(field))
+    }
 
-                        if (b & (1 << 7)) == 0 { // check high bit set
-                            break;
-                        }
-                    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.members.len())
+    }
+}
 
-                    let num_items = u64::from_le_bytes(buf) as usize;
-                    self.remaining_items = Some(num_items);
-                    self.index_size = Some(index_size);
-                },
-                _ => return Err(Error::ExpectedObject)
-            }
-        }
+// a tagged value, as the sequence of its tag and value once the tag is read, or otherwise as a
+// map with the single key `TAGGED_TOKEN` and the whole tagged value as value
+struct TaggedDeserializer<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    tag: Option<u64>,
+    done: bool,
+}
 
-        let remaining_items = self.remaining_items.unwrap();
-        if remaining_items == 0 {
-            if let Some(index_size) = self.index_size {
-                // index is unused, but consume bytes
-                self.de.consume_bytes(index_size as usize);
-            }
+impl<'de, 'a> SeqAccess<'de> for TaggedDeserializer<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>> where
+        T: DeserializeSeed<'de> {
+        if let Some(tag) = self.tag.take() {
+            return seed.deserialize(de::value::U64Deserializer::new(tag)).map(Some);
+        }
+        if std::mem::replace(&mut self.done, true) {
             return Ok(None);
         }
+        self.de.deserialize_value(seed).map(Some)
+    }
 
-        let v = seed.deserialize(&mut *self.de).map(Some);
-        self.remaining_items = Some(remaining_items - 1);
-        v
+    fn size_hint(&self) -> Option<usize> {
+        Some(if self.done { 0 } else { 1 + self.tag.is_some() as usize })
+    }
+}
+
+impl<'de, 'a> MapAccess<'de> for TaggedDeserializer<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>> where
+        K: DeserializeSeed<'de> {
+        if self.done {
+            return Ok(None);
+        }
+        seed.deserialize(de::value::BorrowedStrDeserializer::new(crate::TAGGED_TOKEN)).map(Some)
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value> where
         V: DeserializeSeed<'de> {
+        self.done = true;
         seed.deserialize(&mut *self.de)
     }
 }
 
 struct ArrayDeserializer<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
-    index_size: Option<usize>,
     remaining_items: Option<usize>,
+    index: usize,
 }
 
 impl<'a, 'de> ArrayDeserializer<'a, 'de> {
     fn new(de: &'a mut Deserializer<'de>) -> Self {
-        Self { de, index_size: None, remaining_items: None }
+        Self { de, remaining_items: None, index: 0 }
     }
 }
 
@@ -580,184 +1370,161 @@ impl <'de, 'a> SeqAccess<'de> for ArrayDeserializer<'a, 'de> {
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>> where
         T: DeserializeSeed<'de> {
-        if self.remaining_items.is_none() {
-            match self.de.peek_byte()? {
-                0x01 => {
-                    debug!("0x01 -> deserializing empty array");
-                    self.de.consume_header();
-                    self.remaining_items = Some(0);
-                },
-                0x02 => {
-                    debug!("0x02 -> deserializing array without index table (1 byte length)");
-                    self.de.consume_header();
-                    let byte_length = self.de.consume_u8()? as usize - 1 - U8_SIZE; // sub header + bytelen
-                    self.de.consume_padding()?;
-
-                    // num items is unknown until first item is consumed
-                    let old_size = self.de.input.len();
-                    let v = seed.deserialize(&mut *self.de).map(Some);
-                    let item_size = old_size - self.de.input.len();
-                    let n_items = byte_length / item_size;
-                    self.remaining_items = Some(n_items - 1);
-                    return v;
-                },
-                0x03 => {
-                    debug!("0x03 -> deserializing array without index table (2 byte length)");
-                    self.de.consume_header();
-                    let byte_length = self.de.consume_u16()? as usize - 1 - U16_SIZE; // header + bytelen
-                    self.de.consume_padding()?;
-
-                    // num items is unknown until first item is consumed
-                    let old_size = self.de.input.len();
-                    let v = seed.deserialize(&mut *self.de).map(Some);
-                    let item_size = old_size - self.de.input.len();
-                    let n_items = byte_length / item_size;
-                    self.remaining_items = Some(n_items - 1);
-                    return v;
-                },
-                0x04 => {
-                    debug!("0x04 -> deserializing array without index table (4 byte length)");
-                    self.de.consume_header();
-                    let byte_length = self.de.consume_u32()? as usize - 1 - U32_SIZE; // header + bytelen
-                    self.de.consume_padding()?;
-
-                    // num items is unknown until first item is consumed
-                    let old_size = self.de.input.len();
-                    let v = seed.deserialize(&mut *self.de).map(Some);
-                    let item_size = old_size - self.de.input.len();
-                    let n_items = byte_length / item_size;
-                    self.remaining_items = Some(n_items - 1);
-                    return v;
-                },
-                0x05 => {
-                    debug!("0x05 -> deserializing array without index table (8 byte length)");
-                    self.de.consume_header();
-                    let byte_length = self.de.consume_u64()? as usize - 1 - U64_SIZE; // header + bytelen
-                    self.de.consume_padding()?;
-
-                    // num items is unknown until first item is consumed
-                    let old_size = self.de.input.len();
-                    let v = seed.deserialize(&mut *self.de).map(Some);
-                    let item_size = old_size - self.de.input.len();
-                    let n_items = byte_length / item_size;
-                    self.remaining_items = Some(n_items - 1);
-                    return v;
-                },
-                0x06 => {
-                    debug!("0x06 -> deserializing array with index table (1 byte length)");
-                    self.de.consume_bytes(1 + U8_SIZE); // header + bytelength (unused)
-
-                    let length = self.de.consume_u8()? as usize;
-                    self.de.consume_padding()?;
-
-                    self.remaining_items = Some(length);
-                    self.index_size = Some(length * U8_SIZE);
-                },
-                0x07 => {
-                    debug!("0x07 -> deserializing array with index table (2 byte length)");
-                    self.de.consume_bytes(1 + U16_SIZE); // header + bytelength (unused)
+        let remaining_items = match self.remaining_items {
+            Some(n) => n,
+            None => {
+                match self.de.peek_byte()? {
+                    b @ 0x01..=0x09 | b @ 0x13 => debug!("0x{:x?} -> deserializing array", b),
+                    _ => return Err(self.de.mismatch(ErrorKind::ExpectedArray)),
+                }
+                self.de.enter_container()?
+            },
+        };
+        if remaining_items == 0 {
+            self.remaining_items = Some(0);
+            return Ok(None);
+        }
 
-                    let length = self.de.consume_u16()? as usize;
-                    self.de.consume_padding()?;
+        let v = self.de.deserialize_value(seed).map(Some).map_err(|e| e.at_index(self.index));
+        self.remaining_items = Some(remaining_items - 1);
+        self.index += 1;
+        v
+    }
+}
+
+// object keys, which may be numbers or bools written as strings, see
+// `SerializerOptions::non_string_keys`
+struct KeyDeserializer<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
 
-                    self.remaining_items = Some(length);
-                    self.index_size = Some(length * U16_SIZE);
+macro_rules! deserialize_parsed_key {
+    ($method:ident, $visit:ident) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value> where
+            V: Visitor<'de> {
+            match self.de.peek_byte()? {
+                0x40..=0xbf => {
+                    let s = self.de.parse_str()?;
+                    let v = s.parse().map_err(|_| <Error as de::Error>::invalid_value(de::Unexpected::Str(s), &"a number or bool"))?;
+                    visitor.$visit(v)
                 },
-                0x08 => {
-                    debug!("0x08 -> deserializing array with index table (4 byte length)");
-                    self.de.consume_bytes(1 + U32_SIZE); // header + bytelength (unused)
+                _ => self.de.$method(visitor),
+            }
+        }
+    };
+}
 
-                    let length = self.de.consume_u32()? as usize;
-                    self.de.consume_padding()?;
+macro_rules! forward_key {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value> where
+                V: Visitor<'de> {
+                self.de.$method(visitor)
+            }
+        )*
+    };
+}
 
-                    self.remaining_items = Some(length);
-                    self.index_size = Some(length * U32_SIZE);
-                },
-                0x09 => {
-                    debug!("0x09 -> deserializing array with index table (8 byte length)");
-                    // nritems at end of data for 8-byte case
-                    self.de.consume_header();
+impl<'de, 'a> de::Deserializer<'de> for KeyDeserializer<'a, 'de> {
+    type Error = Error;
 
-                    let bytelength = self.de.consume_u64()? - 1 - 8; // sub header and bytelength
-                    let start = (bytelength - 8) as usize;
-                    let end = bytelength as usize;
+    deserialize_parsed_key!(deserialize_bool, visit_bool);
+    deserialize_parsed_key!(deserialize_i8, visit_i8);
+    deserialize_parsed_key!(deserialize_i16, visit_i16);
+    deserialize_parsed_key!(deserialize_i32, visit_i32);
+    deserialize_parsed_key!(deserialize_i64, visit_i64);
+    deserialize_parsed_key!(deserialize_i128, visit_i128);
+    deserialize_parsed_key!(deserialize_u8, visit_u8);
+    deserialize_parsed_key!(deserialize_u16, visit_u16);
+    deserialize_parsed_key!(deserialize_u32, visit_u32);
+    deserialize_parsed_key!(deserialize_u64, visit_u64);
+    deserialize_parsed_key!(deserialize_u128, visit_u128);
+    deserialize_parsed_key!(deserialize_f32, visit_f32);
+    deserialize_parsed_key!(deserialize_f64, visit_f64);
+
+    forward_key! {
+        deserialize_any deserialize_char deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_option deserialize_unit deserialize_seq deserialize_map
+        deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value> where
+        V: Visitor<'de> {
+        self.de.deserialize_unit_struct(name, visitor)
+    }
 
-                    let mut bytes: [u8; U64_SIZE] = Default::default();
-                    bytes.copy_from_slice(&self.de.input[start..end]);
-                    let length = u64::from_le_bytes(bytes) as usize;
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value> where
+        V: Visitor<'de> {
+        if name == crate::UTC_DATE_TOKEN || name == crate::RAW_TOKEN {
+            return self.de.deserialize_newtype_struct(name, visitor);
+        }
+        // so that newtypes around numbers are parsed from strings too
+        visitor.visit_newtype_struct(self)
+    }
 
-                    self.remaining_items = Some(length);
-                    self.index_size = Some((length * U64_SIZE) + U64_SIZE); // consume nritems
-                },
-                0x13 => {
-                    self.de.consume_header();
-
-                    let mut buf: [u8; 8] = [0; 8];
-                    let length_bits: &mut BitSlice<Lsb0, u8> = bitvec::slice::BitSlice::<Lsb0, u8>::from_slice_mut(&mut buf).unwrap();
-
-                    let mut header_size = 1; // header, increment with bytelen bytes
-                    let mut idx = 0;
-                    loop {
-                        let b = self.de.next_byte()?;
-                        for n in 0..7 {
-                            if (b & (1 << n)) != 0 {
-                                length_bits.set(idx, true);
-                            }
-                            idx += 1;
-                        }
-
-                        header_size += 1;
-
-                        if (b & (1 << 7)) == 0 { // check high bit set
-                            break;
-                        }
-                    }
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value> where
+        V: Visitor<'de> {
+        self.de.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(self, name: &'static str, len: usize, visitor: V) -> Result<V::Value> where
+        V: Visitor<'de> {
+        self.de.deserialize_tuple_struct(name, len, visitor)
+    }
+
+    fn deserialize_struct<V>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value> where
+        V: Visitor<'de> {
+        self.de.deserialize_struct(name, fields, visitor)
+    }
 
-                    let bytelength = u64::from_le_bytes(buf) as usize;
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value> where
+        V: Visitor<'de> {
+        self.de.deserialize_enum(name, variants, visitor)
+    }
 
-                    let remaining_bytes = bytelength - header_size;
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
 
-                    let mut buf: [u8; 8] = [0; 8];
-                    let length_bits: &mut BitSlice<Lsb0, u8> = bitvec::slice::BitSlice::<Lsb0, u8>::from_slice_mut(&mut buf).unwrap();
+// only unit variants, encoded as either their name or index, are supported so far
+struct UnitVariantDeserializer<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
 
-                    let mut index_size = 0;
+impl<'de, 'a> de::EnumAccess<'de> for UnitVariantDeserializer<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
 
-                    let mut idx = 0;
-                    for b in self.de.input[..remaining_bytes].iter().rev() {
-                        for n in 0..7 {
-                            if (b & (1 << n)) != 0 {
-                                length_bits.set(idx, true);
-                            }
-                            idx += 1;
-                        }
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)> where
+        V: DeserializeSeed<'de> {
+        self.de.decoding_variant = true;
+        let variant = self.de.deserialize_value(seed);
+        self.de.decoding_variant = false;
+        Ok((variant?, self))
+    }
+}
 
-                        index_size += 1;
+impl<'de, 'a> de::VariantAccess<'de> for UnitVariantDeserializer<'a, 'de> {
+    type Error = Error;
 
-                        if (b & (1 << 7)) == 0 { // check high bit set
-                            break;
-                        }
-                    }
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
 
-                    let num_items = u64::from_le_bytes(buf) as usize;
-                    self.remaining_items = Some(num_items);
-                    self.index_size = Some(index_size);
-                }
-                _ => return Err(Error::ExpectedArray)
-            }
-        }
+    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value> where
+        T: DeserializeSeed<'de> {
+        Err(de::Error::invalid_type(de::Unexpected::UnitVariant, &"newtype variant"))
+    }
 
-        let remaining_items = self.remaining_items.unwrap();
-        if remaining_items == 0 {
-            if let Some(index_size) = self.index_size {
-                // index is unused, but consume bytes
-                self.de.consume_bytes(index_size as usize);
-            }
-            return Ok(None);
-        }
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value> where
+        V: Visitor<'de> {
+        Err(de::Error::invalid_type(de::Unexpected::UnitVariant, &"tuple variant"))
+    }
 
-        let v = seed.deserialize(&mut *self.de).map(Some);
-        self.remaining_items = Some(remaining_items - 1);
-        v
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value> where
+        V: Visitor<'de> {
+        Err(de::Error::invalid_type(de::Unexpected::UnitVariant, &"struct variant"))
     }
 }
 
@@ -765,16 +1532,17 @@ impl <'de, 'a> SeqAccess<'de> for ArrayDeserializer<'a, 'de> {
 mod tests {
     use super::*;
     use std::collections::HashMap;
+    use serde::Serialize;
     use serde_json::json;
 
     #[test]
     fn bool_false() {
-        assert_eq!(from_bytes::<bool>(&[0x19]).unwrap(), false);
+        assert!(!from_bytes::<bool>(&[0x19]).unwrap());
     }
 
     #[test]
     fn bool_true() {
-        assert_eq!(from_bytes::<bool>(&[0x1a]).unwrap(), true);
+        assert!(from_bytes::<bool>(&[0x1a]).unwrap());
     }
 
     #[test]
@@ -804,7 +1572,7 @@ mod tests {
 
         // uint, little endian, 1 byte
         assert_eq!(from_bytes::<u8>(&[0x28, 0x0a]).unwrap(), 10);
-        assert_eq!(from_bytes::<u8>(&[0x28, 0xff]).unwrap(), std::u8::MAX);
+        assert_eq!(from_bytes::<u8>(&[0x28, 0xff]).unwrap(), u8::MAX);
     }
 
     #[test]
@@ -814,7 +1582,7 @@ mod tests {
         }
 
         assert_eq!(from_bytes::<u64>(&[0x28, 0x0a]).unwrap(), 10);
-        assert_eq!(from_bytes::<u64>(&[0x2f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap(), std::u64::MAX);
+        assert_eq!(from_bytes::<u64>(&[0x2f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap(), u64::MAX);
     }
 
     #[test]
@@ -829,8 +1597,8 @@ mod tests {
         }
 
         // signed int, little endian, 1 byte
-        assert_eq!(from_bytes::<i8>(&[0x20, 0x80]).unwrap(), std::i8::MIN);
-        assert_eq!(from_bytes::<i8>(&[0x28, 0x7f]).unwrap(), std::i8::MAX);
+        assert_eq!(from_bytes::<i8>(&[0x20, 0x80]).unwrap(), i8::MIN);
+        assert_eq!(from_bytes::<i8>(&[0x28, 0x7f]).unwrap(), i8::MAX);
         assert_eq!(from_bytes::<i8>(&[0x20, 0xf9]).unwrap(), -7_i8);
         assert_eq!(from_bytes::<i8>(&[0x28, 0x0a]).unwrap(), 10_i8);
     }
@@ -847,17 +1615,43 @@ mod tests {
         }
 
         // signed int, little endian, 1 byte
-        assert_eq!(from_bytes::<i16>(&[0x20, 0x80]).unwrap(), std::i8::MIN as i16);
-        assert_eq!(from_bytes::<i16>(&[0x28, 0x7f]).unwrap(), std::i8::MAX as i16);
+        assert_eq!(from_bytes::<i16>(&[0x20, 0x80]).unwrap(), i8::MIN as i16);
+        assert_eq!(from_bytes::<i16>(&[0x28, 0x7f]).unwrap(), i8::MAX as i16);
         assert_eq!(from_bytes::<i16>(&[0x20, 0xf9]).unwrap(), -7_i16);
         assert_eq!(from_bytes::<i16>(&[0x28, 0x0a]).unwrap(), 10_i16);
 
         // signed int, little endian, 2 bytes
-        assert_eq!(from_bytes::<i16>(&[0x21, 0x00, 0x80]).unwrap(), std::i16::MIN);
-        assert_eq!(from_bytes::<i16>(&[0x29, 0xff, 0x7f]).unwrap(), std::i16::MAX);
+        assert_eq!(from_bytes::<i16>(&[0x21, 0x00, 0x80]).unwrap(), i16::MIN);
+        assert_eq!(from_bytes::<i16>(&[0x29, 0xff, 0x7f]).unwrap(), i16::MAX);
         assert_eq!(from_bytes::<i16>(&[0x21, 0xc8, 0x00]).unwrap(), 200_i16);
     }
 
+    #[test]
+    fn i64_odd_byte_lengths() {
+        assert_eq!(from_bytes::<i64>(&[0x22, 0xff, 0xff, 0x7f]).unwrap(), 0x7fffff);
+        assert_eq!(from_bytes::<i64>(&[0x22, 0x00, 0x00, 0x80]).unwrap(), -0x800000);
+        assert_eq!(from_bytes::<i64>(&[0x26, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap(), -2);
+        assert_eq!(from_bytes::<u64>(&[0x2a, 0x01, 0x02, 0x03]).unwrap(), 0x030201);
+        assert_eq!(from_bytes::<i64>(&[0x23, 0x01, 0x02]), Err(ErrorKind::Eof.into()));
+        assert_eq!(from_bytes::<u64>(&[0x2b, 0x01]), Err(ErrorKind::Eof.into()));
+    }
+
+    #[test]
+    fn i128() {
+        assert_eq!(from_bytes::<i128>(&[0x35]).unwrap(), 5);
+        assert_eq!(from_bytes::<i128>(&[0x3a]).unwrap(), -6);
+        assert_eq!(from_bytes::<i128>(&[0x27, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80]).unwrap(), i64::MIN as i128);
+        assert_eq!(from_bytes::<i128>(&[0x2f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap(), u64::MAX as i128);
+        assert_eq!(from_bytes::<i128>(&[0x41, 0x31]).map_err(|e| e.kind().clone()), Err(ErrorKind::ExpectedInteger));
+    }
+
+    #[test]
+    fn u128() {
+        assert_eq!(from_bytes::<u128>(&[0x30]).unwrap(), 0);
+        assert_eq!(from_bytes::<u128>(&[0x2f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap(), u64::MAX as u128);
+        assert_eq!(from_bytes::<u128>(&[0x3f]).map_err(|e| e.kind().clone()), Err(ErrorKind::ExpectedInteger));
+    }
+
     #[test]
     fn string() {
         assert_eq!(from_bytes::<String>(&[0x40]).unwrap(), "".to_owned());
@@ -882,6 +1676,160 @@ mod tests {
             0x61, 0x61, 0x61, 0x61, 0x61, 0x61, 0x61, 0x61, 0x61, 0x61, 0x61, 0x61, 0x61, 0x61]).unwrap(), "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_owned());
     }
 
+    #[test]
+    fn char() {
+        assert_eq!(from_bytes::<char>(&[0x41, 0x61]).unwrap(), 'a');
+        assert_eq!(from_bytes::<char>(&[0x43, 0xe2, 0x88, 0x80]).unwrap(), '∀');
+        assert_eq!(from_bytes::<char>(&[0xbf, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x62]).unwrap(), 'b');
+        assert_eq!(from_bytes::<Vec<char>>(&[0x02, 0x06, 0x41, 0x78, 0x41, 0x79]).unwrap(), ['x', 'y']);
+        assert_eq!(from_bytes::<char>(&[0x40]).unwrap_err().to_string(), "invalid value: string \"\", expected a single character");
+        assert_eq!(from_bytes::<char>(&[0x42, 0x61, 0x62]).unwrap_err().to_string(), "invalid value: string \"ab\", expected a single character");
+        assert_eq!(from_bytes::<char>(&[0x31]).map_err(|e| e.kind().clone()), Err(ErrorKind::ExpectedString));
+    }
+
+    #[test]
+    fn borrowed_str() {
+        let data = [0x43, 0x66, 0x6f, 0x6f];
+        let s: &str = from_bytes(&data).unwrap();
+        assert_eq!(s, "foo");
+        assert_eq!(s.as_ptr(), data[1..].as_ptr());
+
+        let data = [0xbf, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x62, 0x61, 0x72];
+        assert_eq!(from_bytes::<&str>(&data).unwrap(), "bar");
+
+        assert_eq!(from_bytes::<&str>(&[0x43, 0x66, 0x6f]), Err(ErrorKind::Eof.into()));
+        assert!(matches!(from_bytes::<&str>(&[0x41, 0xff]).unwrap_err().kind(), ErrorKind::InvalidUtf8));
+    }
+
+    #[test]
+    fn borrowed_str_fields() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Person<'a> {
+            name: &'a str,
+            #[serde(borrow)]
+            nickname: std::borrow::Cow<'a, str>,
+        }
+
+        let data = [0x0b, 0x1d, 0x02, 0x44, 0x6e, 0x61, 0x6d, 0x65, 0x43, 0x42, 0x6f, 0x62, 0x48, 0x6e, 0x69, 0x63, 0x6b,
+            0x6e, 0x61, 0x6d, 0x65, 0x45, 0x42, 0x6f, 0x62, 0x62, 0x79, 0x03, 0x0c];
+        let p: Person = from_bytes(&data).unwrap();
+        assert_eq!(p, Person { name: "Bob", nickname: "Bobby".into() });
+        assert!(matches!(p.nickname, std::borrow::Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn identifiers_not_copied() {
+        // field names which fail to decode if passed as owned strings
+        #[derive(PartialEq, Eq, Hash)]
+        struct Field(String);
+
+        impl<'de> Deserialize<'de> for Field {
+            fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+                struct FieldVisitor;
+
+                impl<'de> Visitor<'de> for FieldVisitor {
+                    type Value = Field;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        f.write_str("a field name")
+                    }
+
+                    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Field, E> {
+                        Ok(Field(v.to_owned()))
+                    }
+
+                    fn visit_string<E: de::Error>(self, _v: String) -> std::result::Result<Field, E> {
+                        Err(E::custom("field name was copied"))
+                    }
+                }
+
+                deserializer.deserialize_identifier(FieldVisitor)
+            }
+        }
+
+        let fields = |data: &[u8], translator: Option<Arc<crate::AttributeTranslator>>| {
+            let mut de = Deserializer::from_bytes(data);
+            if let Some(translator) = translator {
+                de = de.with_attribute_translator(translator);
+            }
+            HashMap::<Field, u8>::deserialize(&mut de).map(|m| m.into_keys().map(|f| f.0).collect::<Vec<_>>())
+        };
+        assert_eq!(fields(&[0x14, 0x06, 0x41, 0x61, 0x31, 0x01], None).unwrap(), vec!["a"]);
+        let translator = Arc::new(crate::AttributeTranslator::new(vec![(1, "a")]));
+        assert_eq!(fields(&[0x14, 0x05, 0x31, 0x31, 0x01], Some(translator)).unwrap(), vec!["a"]);
+    }
+
+    #[test]
+    fn cow_str() {
+        use std::borrow::Cow;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Request<'a> {
+            #[serde(borrow)]
+            path: Cow<'a, str>,
+            #[serde(borrow)]
+            body: Cow<'a, str>,
+            // without `borrow`, Cow fields are always owned
+            method: Cow<'a, str>,
+        }
+
+        let body = "b".repeat(200);
+        let mut m = HashMap::new();
+        m.insert("path", "/_api/document");
+        m.insert("body", &body);
+        m.insert("method", "GET");
+        let data = crate::to_bytes(&m).unwrap();
+
+        let r: Request = from_bytes(&data).unwrap();
+        assert_eq!(r.path, "/_api/document");
+        assert_eq!(r.body, body);
+        assert_eq!(r.method, "GET");
+        assert!(matches!(r.path, Cow::Borrowed(_)));
+        // long (0xbf) strings are contiguous in the input, so can be borrowed as well
+        assert!(matches!(r.body, Cow::Borrowed(_)));
+        assert!(matches!(r.method, Cow::Owned(_)));
+
+        // values replaced by a hook have to be owned
+        let mut de = Deserializer::from_bytes(&data).with_hook(ValueType::String, |b| match b.len() {
+            4 => Some(Rewrite::String("PUT".to_owned())),
+            _ => None,
+        });
+        let r = Request::deserialize(&mut de).unwrap();
+        assert_eq!(r.method, "PUT");
+        assert!(matches!(r.path, Cow::Borrowed(_)));
+
+        let mut de = Deserializer::from_bytes(&data).with_hook(ValueType::String, |b| match b.len() {
+            15 => Some(Rewrite::String("/".to_owned())),
+            _ => None,
+        });
+        let r = Request::deserialize(&mut de).unwrap();
+        assert_eq!(r.path, "/");
+        assert!(matches!(r.path, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn borrowed_bytes() {
+        let data = [0xc0, 0x03, 0x01, 0x02, 0x03];
+        let b: &[u8] = from_bytes(&data).unwrap();
+        assert_eq!(b, &[0x01, 0x02, 0x03]);
+        assert_eq!(b.as_ptr(), data[2..].as_ptr());
+
+        assert_eq!(from_bytes::<&[u8]>(&[0xc0, 0x00]).unwrap(), &[] as &[u8]);
+        assert_eq!(from_bytes::<&[u8]>(&[0xc1, 0x02, 0x00, 0xaa, 0xbb]).unwrap(), &[0xaa, 0xbb]);
+        assert_eq!(from_bytes::<&[u8]>(&[0xc0, 0x03, 0x01]), Err(ErrorKind::Eof.into()));
+        assert_eq!(from_bytes::<&[u8]>(&[0x31]).map_err(|e| e.kind().clone()), Err(ErrorKind::ExpectedBinary));
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Blob<'a> {
+            #[serde(borrow)]
+            data: std::borrow::Cow<'a, [u8]>,
+        }
+        let data = [0x0b, 0x0d, 0x01, 0x44, 0x64, 0x61, 0x74, 0x61, 0xc0, 0x02, 0xff, 0xfe, 0x03];
+        let blob: Blob = from_bytes(&data).unwrap();
+        assert_eq!(&blob.data[..], &[0xff, 0xfe]);
+        assert!(matches!(blob.data, std::borrow::Cow::Borrowed(_)));
+    }
+
     #[test]
     fn array_empty() {
         assert_eq!(from_bytes::<Vec<u32>>(&[0x01]).unwrap(), Vec::<u32>::new());
@@ -922,6 +1870,24 @@ mod tests {
 
     }
 
+    #[test]
+    fn array_empty_forms() {
+        let empty: Vec<Vec<u8>> = vec![
+            vec![0x01],
+            vec![0x02, 0x02],
+            vec![0x03, 0x03, 0x00],
+            vec![0x02, 0x05, 0x00, 0x00, 0x00],
+            vec![0x06, 0x03, 0x00],
+            vec![0x13, 0x03, 0x00],
+        ];
+        for bytes in empty {
+            assert_eq!(from_bytes::<Vec<u32>>(&bytes).unwrap(), Vec::<u32>::new(), "{:x?}", bytes);
+        }
+
+        // padding between the byte length and the first item
+        assert_eq!(from_bytes::<Vec<u8>>(&[0x03, 0x07, 0x00, 0x00, 0x00, 0x31, 0x32]).unwrap(), vec![1, 2]);
+    }
+
     #[test]
     fn array_with_index() {
         assert_eq!(from_bytes::<Vec<u16>>(&[0x06, 0x09, 0x02, 0x31, 0x29, 0x00, 0x01, 0x03, 0x04]).unwrap(), vec![1, 256]);
@@ -1026,6 +1992,19 @@ mod tests {
         assert_eq!(from_bytes::<HashMap<String, u8>>(&[0x0a]).unwrap(), HashMap::new());
     }
 
+    #[test]
+    fn object_empty_forms() {
+        let empty: Vec<Vec<u8>> = vec![
+            vec![0x0a],
+            vec![0x0b, 0x03, 0x00],
+            vec![0x0f, 0x03, 0x00],
+            vec![0x14, 0x03, 0x00],
+        ];
+        for bytes in empty {
+            assert_eq!(from_bytes::<HashMap<String, u32>>(&bytes).unwrap(), HashMap::new(), "{:x?}", bytes);
+        }
+    }
+
     #[test]
     fn object_1byte() {
         let mut m = HashMap::new();
@@ -1053,6 +2032,659 @@ mod tests {
         assert_eq!(from_bytes::<HashMap<String, u8>>(&[0x14, 0x06, 0x41, 0x61, 0x31, 0x01]).unwrap(), expected);
     }
 
+    #[test]
+    fn ignored_fields() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Test {
+            b: u8,
+        }
+        let bytes = crate::to_bytes(&json!({"a": [1, "x"], "b": 2, "c": {"d": null}})).unwrap();
+        assert_eq!(from_bytes::<Test>(&bytes).unwrap(), Test { b: 2 });
+    }
+
+    #[test]
+    fn flatten() {
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct Meta {
+            _key: String,
+            _rev: String,
+            #[serde(default)]
+            _id: Option<String>,
+        }
+
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct Doc {
+            #[serde(flatten)]
+            meta: Meta,
+            name: String,
+            count: u32,
+            scores: Vec<i64>,
+        }
+
+        let doc = Doc {
+            meta: Meta { _key: "k".to_owned(), _rev: "r".to_owned(), _id: Some("docs/k".to_owned()) },
+            name: "x".to_owned(),
+            count: 300,
+            scores: vec![-1, 2],
+        };
+        let bytes = crate::to_bytes(&doc).unwrap();
+        assert_eq!(crate::to_json_bytes(&bytes).unwrap(), br#"{"_key":"k","_rev":"r","_id":"docs/k","name":"x","count":300,"scores":[-1,2]}"#.to_vec());
+        assert_eq!(from_bytes::<Doc>(&bytes).unwrap(), doc);
+
+        // flattened maps collect any remaining attributes
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct Extra {
+            _key: String,
+            #[serde(flatten)]
+            rest: HashMap<String, serde_json::Value>,
+        }
+        let extra: Extra = from_bytes(&bytes).unwrap();
+        assert_eq!(extra._key, "k");
+        assert_eq!(extra.rest["count"], json!(300));
+        assert_eq!(extra.rest["scores"], json!([-1, 2]));
+        assert_eq!(from_bytes::<Extra>(&crate::to_bytes(&extra).unwrap()).unwrap(), extra);
+    }
+
+    #[test]
+    fn tuples() {
+        assert_eq!(from_bytes::<(u8, String)>(&[0x06, 0x08, 0x02, 0x31, 0x41, 0x61, 0x03, 0x04]).unwrap(), (1, "a".to_owned()));
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Point(i32, i32);
+        assert_eq!(from_bytes::<Point>(&[0x02, 0x04, 0x31, 0x3f]).unwrap(), Point(1, -1));
+    }
+
+    #[test]
+    fn positional_structs() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct User {
+            id: u64,
+            name: String,
+            tags: Vec<String>,
+        }
+
+        let data = crate::to_bytes(&json!([[7, "Bob", ["a"]], {"id": 8, "name": "Alice", "tags": []}])).unwrap();
+        let users: Vec<User> = from_array_bytes(&data).unwrap();
+        assert_eq!(users, vec![
+            User { id: 7, name: "Bob".to_owned(), tags: vec!["a".to_owned()] },
+            User { id: 8, name: "Alice".to_owned(), tags: vec![] },
+        ]);
+
+        // compact arrays, as often returned by AQL
+        let data = [0x13, 0x0a, 0x35, 0x43, 0x42, 0x6f, 0x62, 0x01, 0x03, 0x03];
+        assert_eq!(from_array_bytes::<User>(&data).unwrap(), User { id: 5, name: "Bob".to_owned(), tags: vec![] });
+
+        // too few elements
+        assert!(from_array_bytes::<User>(&[0x02, 0x03, 0x31]).is_err());
+
+        // not enabled by default
+        assert_eq!(from_bytes::<User>(&data).map_err(|e| e.kind().clone()), Err(ErrorKind::ExpectedObject));
+    }
+
+    #[test]
+    fn owned() {
+        #[derive(Debug, Deserialize)]
+        struct Doc {
+            name: String,
+            extra: crate::RawVPackBuf,
+        }
+
+        let data = crate::to_bytes(&json!({"name": "x", "extra": [1, 2]})).unwrap();
+        let doc: Doc = from_bytes_owned(data).unwrap();
+        assert_eq!(doc.name, "x");
+        assert_eq!(doc.extra.as_raw_vpack().deserialize::<Vec<u8>>().unwrap(), vec![1, 2]);
+
+        let handle = std::thread::spawn(move || doc.name);
+        assert_eq!(handle.join().unwrap(), "x");
+    }
+
+    #[test]
+    fn iter() {
+        let values: Result<Vec<u32>> = iter_from_bytes(&[0x31, 0x28, 0xff, 0x32]).collect();
+        assert_eq!(values.unwrap(), vec![1, 255, 2]);
+        assert_eq!(iter_from_bytes::<u32>(&[]).count(), 0);
+
+        // stops after the first error
+        let mut values = iter_from_bytes::<u32>(&[0x31, 0x41, 0x61, 0x32]);
+        assert_eq!(values.next(), Some(Ok(1)));
+        assert!(values.next().unwrap().is_err());
+        assert_eq!(values.next(), None);
+    }
+
+    #[test]
+    fn unit_variants() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Status { Active, Inactive }
+        assert_eq!(from_bytes::<Status>(&[0x46, 0x41, 0x63, 0x74, 0x69, 0x76, 0x65]).unwrap(), Status::Active);
+        assert_eq!(from_bytes::<Status>(&[0x31]).unwrap(), Status::Inactive);
+        assert_eq!(from_bytes::<Status>(&[0x28, 0x01]).unwrap(), Status::Inactive);
+        assert!(from_bytes::<Status>(&[0x32]).is_err());
+        assert!(from_bytes::<Status>(&[0x43, 0x66, 0x6f, 0x6f]).is_err());
+        assert_eq!(from_bytes::<Status>(&[0x18]).map_err(|e| e.kind().clone()), Err(ErrorKind::ExpectedString));
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Shape { Point, Circle(f64) }
+        assert!(from_bytes::<Shape>(&[0x31]).is_err());
+        assert_eq!(from_bytes::<Vec<Shape>>(&[0x02, 0x04, 0x30, 0x30]).unwrap(), vec![Shape::Point, Shape::Point]);
+
+        // struct fields aren't named by index, only variants are
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct S { a: u8, b: u8 }
+        let data = [0x14, 0x08, 0x30, 0x37, 0x41, 0x62, 0x39, 0x02];
+        assert_eq!(from_bytes::<S>(&data).map_err(|e| e.kind().clone()), Err(ErrorKind::ExpectedString));
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Account { status: Status, a: u8 }
+        let data = crate::to_bytes(&json!({"status": 1, "a": 2})).unwrap();
+        assert_eq!(from_bytes::<Account>(&data).unwrap(), Account { status: Status::Inactive, a: 2 });
+    }
+
+    #[test]
+    fn single_byte_members() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Flags {
+            a: Option<bool>,
+            b: bool,
+            c: bool,
+        }
+        let members: &[(&str, &[u8])] = &[("b", &[0x1a]), ("a", &[0x18]), ("c", &[0x19])];
+        for offset_size in [1, 2, 4, 8] {
+            let bytes = raw::object_with_offset_size(offset_size, members);
+            assert_eq!(from_bytes::<Flags>(&bytes).unwrap(), Flags { a: None, b: true, c: false });
+            assert_eq!(from_bytes::<serde_json::Value>(&bytes).unwrap(), json!({"a": null, "b": true, "c": false}));
+            assert_eq!(crate::to_json_bytes(&bytes).unwrap(), br#"{"b":true,"a":null,"c":false}"#);
+
+            // as a member of an array, so the object's index table must be skipped
+            let mut array = vec![0x02, 0x00];
+            array.extend(&bytes);
+            array.extend(&bytes);
+            array[1] = array.len() as u8;
+            assert_eq!(from_bytes::<Vec<Flags>>(&array).unwrap().len(), 2);
+        }
+
+        // large enough for the serializer to use 2 byte offsets
+        let flags: HashMap<String, bool> = (0..100).map(|i| (format!("f{}", i), i % 3 == 0)).collect();
+        let bytes = crate::to_bytes(&flags).unwrap();
+        assert_eq!(bytes[0], 0x0c);
+        assert_eq!(from_bytes::<HashMap<String, bool>>(&bytes).unwrap(), flags);
+        let nulls: HashMap<String, ()> = (0..100).map(|i| (format!("f{}", i), ())).collect();
+        assert_eq!(from_bytes::<HashMap<String, ()>>(&crate::to_bytes(&nulls).unwrap()).unwrap(), nulls);
+    }
+
+    #[test]
+    fn double_from_int() {
+        assert_eq!(from_bytes::<f64>(&[0x35]).unwrap(), 5.0);
+        assert_eq!(from_bytes::<f64>(&[0x3e]).unwrap(), -2.0);
+        assert_eq!(from_bytes::<f32>(&[0x29, 0xe8, 0x03]).unwrap(), 1000.0);
+        assert_eq!(from_bytes::<f64>(&[0x21, 0x18, 0xfc]).unwrap(), -1000.0);
+        assert_eq!(from_bytes::<f64>(&[0x18]).map_err(|e| e.kind().clone()), Err(ErrorKind::ExpectedDouble));
+    }
+
+    #[test]
+    fn bcd() {
+        let data = [0xd0, 0x02, 0xfe, 0xff, 0xff, 0xff, 0x01, 0x25];
+        assert_eq!(from_bytes::<String>(&data).unwrap(), "-1.25");
+        assert_eq!(from_bytes::<f64>(&data).unwrap(), -1.25);
+        assert_eq!(from_bytes::<serde_json::Value>(&data).unwrap(), json!("-1.25"));
+        assert!(from_bytes::<&str>(&data).is_err());
+        assert_eq!(from_bytes::<String>(&data[..7]), Err(ErrorKind::Eof.into()));
+    }
+
+    #[test]
+    fn at_offset() {
+        let mut bytes = vec![0xff];
+        bytes.extend(crate::to_bytes(&vec![1.5f64, -2.25]).unwrap());
+        bytes.push(0x31);
+        let (v, end) = from_bytes_at::<Vec<f64>>(&bytes, 1).unwrap();
+        assert_eq!(v, vec![1.5, -2.25]);
+        assert_eq!(from_bytes_at::<u8>(&bytes, end).unwrap(), (1, bytes.len()));
+        assert_eq!(from_bytes_at::<u8>(&bytes, bytes.len()), Err(ErrorKind::Eof.into()));
+        assert_eq!(from_bytes_at::<u8>(&bytes, bytes.len() + 1), Err(ErrorKind::Eof.into()));
+    }
+
+    #[test]
+    fn ignored_fields_skipped() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Id {
+            id: u64,
+        }
+
+        // the ignored field holds an invalid string, which is never decoded, nor counted
+        // against the budget
+        let mut data = vec![0x0b, 0x00, 0x02, 0x41, 0x61, 0x02, 0x05, 0x31, 0x41, 0xff, 0x42, 0x69, 0x64, 0x35, 0x03, 0x0a];
+        data[1] = data.len() as u8;
+        let mut de = Deserializer::from_bytes(&data).budget(4);
+        assert_eq!(Id::deserialize(&mut de).unwrap(), Id { id: 5 });
+        de.end().unwrap();
+    }
+
+    #[test]
+    fn lookup_fields() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Doc<'a> {
+            #[serde(rename = "_key")]
+            key: &'a str,
+            #[serde(alias = "n")]
+            count: u64,
+            missing: Option<u8>,
+        }
+
+        let mut doc: HashMap<String, serde_json::Value> = (0..80).map(|i| (format!("attr{}", i), json!([i, "x"]))).collect();
+        doc.insert("_key".to_owned(), json!("abc"));
+        doc.insert("n".to_owned(), json!(42));
+        let data = crate::to_bytes(&doc).unwrap();
+        let expected = Doc { key: "abc", count: 42, missing: None };
+        assert_eq!(from_bytes::<Doc>(&data).unwrap(), expected);
+        let mut de = Deserializer::from_bytes(&data).lookup_fields(true).strict(true);
+        assert_eq!(Doc::deserialize(&mut de).unwrap(), expected);
+        de.end().unwrap();
+
+        // only the fields are decoded
+        let mut de = Deserializer::from_bytes(&data).lookup_fields(true).budget(4);
+        assert_eq!(Doc::deserialize(&mut de).unwrap(), expected);
+
+        doc.insert("count".to_owned(), json!("many"));
+        let data = crate::to_bytes(&doc).unwrap();
+        let mut de = Deserializer::from_bytes(&data).lookup_fields(true);
+        let e = Doc::deserialize(&mut de).unwrap_err();
+        assert_eq!(e.path(), Some("count"));
+
+        // unsorted objects are decoded member by member
+        let options = crate::SerializerOptions::new().unsorted_objects(true);
+        let data = crate::to_bytes_with_options(&json!({"n": 1, "_key": "a"}), &options).unwrap();
+        let mut de = Deserializer::from_bytes(&data).lookup_fields(true);
+        assert_eq!(Doc::deserialize(&mut de).unwrap(), Doc { key: "a", count: 1, missing: None });
+    }
+
+    #[test]
+    fn options() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        let data = crate::to_bytes(&json!({"x": 1, "y": 2, "z": 3})).unwrap();
+        assert_eq!(from_bytes::<Point>(&data).unwrap(), Point { x: 1, y: 2 });
+        let options = DeserializerOptions::new().unknown_fields(UnknownFields::Error);
+        assert_eq!(from_bytes_with_options::<Point>(&data, &options).map_err(|e| e.kind().clone()), Err(ErrorKind::UnknownField("z".to_owned())));
+        // maps take any keys
+        assert!(from_bytes_with_options::<HashMap<String, i32>>(&data, &options).is_ok());
+        let options = options.lookup_fields(true);
+        assert!(from_bytes_with_options::<Point>(&data, &options).is_err());
+
+        // {"x": 1, "y": 2, "x": 3}
+        let data = [0x0b, 0x0f, 0x03, 0x41, 0x78, 0x31, 0x41, 0x79, 0x32, 0x41, 0x78, 0x33, 0x03, 0x09, 0x06];
+        let options = DeserializerOptions::new().duplicate_keys(DuplicateKeys::Error);
+        let e = from_bytes_with_options::<HashMap<String, i32>>(&data, &options).unwrap_err();
+        assert_eq!(e.kind(), &ErrorKind::DuplicateKey("x".to_owned()));
+        assert_eq!(e.code(), 1007);
+        assert_eq!(from_bytes::<HashMap<String, i32>>(&data).unwrap()["x"], 3);
+        assert!(from_bytes::<Point>(&data).is_err());
+
+        // nested objects are checked too, and the limits apply
+        let data = crate::to_bytes(&json!([{"a": "abc"}])).unwrap();
+        assert!(from_bytes_with_options::<serde_json::Value>(&data, &options).is_ok());
+        let options = options.max_string_length(2);
+        assert_eq!(from_bytes_with_options::<serde_json::Value>(&data, &options).map_err(|e| e.kind().clone()), Err(ErrorKind::LimitExceeded("string length")));
+        let options = DeserializerOptions::new().max_depth(1);
+        assert_eq!(from_bytes_with_options::<serde_json::Value>(&data, &options).map_err(|e| e.kind().clone()), Err(ErrorKind::DepthLimitExceeded));
+        assert_eq!(from_bytes_with_options::<u8>(&[0x31, 0x31], &options), Err(ErrorKind::TrailingBytes(1).into()));
+    }
+
+    #[test]
+    fn budget() {
+        let data = crate::to_bytes(&json!([{"a": 1}, [2, 3]])).unwrap();
+        let decode = |budget| {
+            let mut de = Deserializer::from_bytes(&data).budget(budget);
+            serde_json::Value::deserialize(&mut de)
+        };
+        assert_eq!(decode(6).unwrap(), json!([{"a": 1}, [2, 3]]));
+        assert_eq!(decode(5).map_err(|e| e.kind().clone()), Err(ErrorKind::BudgetExceeded));
+        assert_eq!(decode(0).map_err(|e| e.kind().clone()), Err(ErrorKind::BudgetExceeded));
+
+        let mut de = Deserializer::from_bytes(&data).budget(0);
+        assert_eq!(serde::de::IgnoredAny::deserialize(&mut de), Ok(serde::de::IgnoredAny));
+    }
+
+    #[test]
+    fn max_depth() {
+        // arrays nested `n` deep, around an empty array
+        let nested = |n: usize| {
+            let mut data = Vec::new();
+            for i in 0..n {
+                data.push(0x05);
+                data.extend_from_slice(&(9 * (n - i) as u64 + 1).to_le_bytes());
+            }
+            data.push(0x01);
+            data
+        };
+        let data = nested(DEFAULT_MAX_DEPTH - 1);
+        assert!(from_bytes::<serde_json::Value>(&data).is_ok());
+        assert!(from_bytes::<crate::Value>(&data).is_ok());
+        let data = nested(DEFAULT_MAX_DEPTH);
+        assert_eq!(from_bytes::<serde_json::Value>(&data).map_err(|e| e.kind().clone()), Err(ErrorKind::DepthLimitExceeded));
+        // skipped without being decoded
+        assert!(from_bytes::<serde::de::IgnoredAny>(&data).is_ok());
+        let mut de = Deserializer::from_bytes(&data).max_depth(DEFAULT_MAX_DEPTH + 1);
+        assert!(serde_json::Value::deserialize(&mut de).is_ok());
+
+        // without a limit this would overflow the stack
+        assert_eq!(from_bytes::<crate::Value>(&nested(100_000)).map_err(|e| e.kind().clone()), Err(ErrorKind::DepthLimitExceeded));
+        let mut tags = [0xee, 0x01].repeat(100_000);
+        tags.push(0x18);
+        assert_eq!(from_bytes::<crate::Value>(&tags).map_err(|e| e.kind().clone()), Err(ErrorKind::DepthLimitExceeded));
+        assert_eq!(from_bytes::<()>(&tags), Ok(()));
+    }
+
+    #[test]
+    fn limits() {
+        let data = crate::to_bytes(&json!({"a": ["bc", "def"], "b": [1, 2, 3, 4]})).unwrap();
+        let decode = |de: Deserializer| {
+            let mut de = de;
+            serde_json::Value::deserialize(&mut de)
+        };
+        assert!(decode(Deserializer::from_bytes(&data).max_string_length(3).max_container_len(4).max_document_size(data.len())).is_ok());
+        let error = decode(Deserializer::from_bytes(&data).max_string_length(2)).unwrap_err();
+        assert_eq!((error.path(), error.kind()), (Some("a[1]"), &ErrorKind::LimitExceeded("string length")));
+        assert_eq!(decode(Deserializer::from_bytes(&data).max_container_len(3)).map_err(|e| e.kind().clone()), Err(ErrorKind::LimitExceeded("container length")));
+        assert_eq!(decode(Deserializer::from_bytes(&data).max_document_size(data.len() - 1)), Err(ErrorKind::LimitExceeded("document size").into()));
+
+        // checked before the claimed bytes are needed
+        let mut de = Deserializer::from_bytes(&[0xbf, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]).max_string_length(1 << 20);
+        assert_eq!(String::deserialize(&mut de), Err(ErrorKind::LimitExceeded("string length").into()));
+        let mut de = Deserializer::from_bytes(&[0xc0, 0x02, 0x01, 0x02]).max_string_length(1);
+        assert_eq!(crate::Value::deserialize(&mut de), Err(ErrorKind::LimitExceeded("string length").into()));
+    }
+
+    #[test]
+    fn malformed() {
+        // compact containers claiming more data than they hold, and an indexed array with a
+        // byte length too short for its item count
+        for data in [&[0x14, 0x01, 0xc0, 0x72, 0xae, 0x02][..], &[0x13, 0x01, 0xc0, 0xa3, 0x01], &[0x09, 0x03, 0, 0, 0, 0, 0, 0, 0]] {
+            assert!(from_bytes::<serde_json::Value>(data).is_err());
+            assert!(from_bytes::<crate::Value>(data).is_err());
+        }
+        // every truncation of a document fails rather than panicking
+        let data = crate::to_bytes(&json!({"a": [1, "bc", 1.5], "b": {"c": u64::MAX, "d": -300}})).unwrap();
+        for n in 0..data.len() {
+            assert!(from_bytes::<serde_json::Value>(&data[..n]).is_err());
+        }
+    }
+
+    #[test]
+    fn strict() {
+        let decode = |data: &[u8]| {
+            let mut de = Deserializer::from_bytes(data).strict(true);
+            serde_json::Value::deserialize(&mut de)
+        };
+        let data = crate::to_bytes(&json!({"b": [1, "x"], "a": {"d": 1, "c": 2}})).unwrap();
+        assert_eq!(decode(&data).unwrap(), json!({"b": [1, "x"], "a": {"d": 1, "c": 2}}));
+
+        // index table pointing at the second item twice
+        let data = [0x06, 0x07, 0x02, 0x31, 0x32, 0x04, 0x04];
+        assert_eq!(from_bytes::<Vec<u8>>(&data).unwrap(), vec![1, 2]);
+        assert_eq!(decode(&data), Err(ErrorKind::Invalid("array items don't match their index table or lengths").into()));
+        // sorted object with "b" indexed before "a"
+        let data = [0x0b, 0x0b, 0x02, 0x41, 0x62, 0x31, 0x41, 0x61, 0x32, 0x03, 0x06];
+        assert!(from_bytes::<serde_json::Value>(&data).is_ok());
+        assert_eq!(decode(&data), Err(ErrorKind::Invalid("sorted object keys are out of order").into()));
+    }
+
+    #[test]
+    fn trusted_utf8() {
+        let data = crate::to_bytes(&json!({"name": "Zoë", "tags": ["a", "bc"]})).unwrap();
+        let mut de = unsafe { Deserializer::from_bytes(&data).assume_valid_utf8() };
+        assert_eq!(serde_json::Value::deserialize(&mut de).unwrap(), json!({"name": "Zoë", "tags": ["a", "bc"]}));
+    }
+
+    #[test]
+    fn never_panics() {
+        // randomly corrupted and truncated documents, see also the targets under fuzz/
+        let doc = json!({"a": [1, 2, "x", {"b": null}], "c": 1.5, "d": "long enough string", "e": [[1, 2], [3, 4]], "f": -300});
+        let corpus: Vec<Vec<u8>> = [
+            crate::SerializerOptions::new(),
+            crate::SerializerOptions::new().compact_containers(true),
+            crate::SerializerOptions::new().index_width(crate::IndexWidth::Eight),
+        ].iter().map(|options| crate::to_bytes_with_options(&doc, options).unwrap()).collect();
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        for _ in 0..20_000 {
+            let mut data = corpus[random() % corpus.len()].clone();
+            for _ in 0..1 + random() % 4 {
+                let i = random() % data.len();
+                data[i] = random() as u8;
+            }
+            data.truncate(random() % (data.len() + 1));
+            let _ = from_bytes::<serde_json::Value>(&data);
+            let _ = from_bytes::<crate::Value>(&data);
+            let _ = from_bytes::<std::collections::BTreeMap<char, Vec<char>>>(&data);
+            let _ = crate::Value::from_bytes(&data);
+            let _ = crate::to_json_bytes(&data);
+        }
+    }
+
+    #[test]
+    fn error_path() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Address {
+            port: u16,
+        }
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Person {
+            friends: Vec<Person>,
+            address: Option<Address>,
+        }
+        let data = crate::to_bytes(&json!({"friends": [{"friends": []}, {"friends": [], "address": {"port": "80"}}]})).unwrap();
+        let error = from_bytes::<Person>(&data).unwrap_err();
+        assert_eq!(error.path(), Some("friends[1].address.port"));
+        assert_eq!(error.kind(), &ErrorKind::ExpectedInteger);
+        assert_eq!(from_bytes::<Vec<u8>>(&[0x02, 0x04, 0x31, 0x18]).unwrap_err().path(), Some("[1]"));
+
+        // translated keys are reported by name
+        let translator = Arc::new(crate::AttributeTranslator::new(vec![(1, "port")]));
+        let mut de = Deserializer::from_bytes(&[0x14, 0x05, 0x31, 0x18, 0x01]).with_attribute_translator(translator);
+        assert_eq!(Address::deserialize(&mut de).unwrap_err().path(), Some("port"));
+    }
+
+    #[test]
+    fn mismatch() {
+        let error = from_bytes::<Vec<u8>>(&[0x06, 0x08, 0x02, 0x31, 0x41, 0x61, 0x03, 0x04]).unwrap_err();
+        assert_eq!(error.to_string(), "expected integer, found string (0x41) at offset 4 at [1]");
+        assert_eq!(error.found(), Some(0x41));
+        assert_eq!(error.offset(), Some(4));
+        assert_eq!(from_bytes::<bool>(&[0x18]).unwrap_err().to_string(), "expected boolean, found null (0x18) at offset 0");
+    }
+
+    #[test]
+    fn external() {
+        fn external(ptr: usize) -> Vec<u8> {
+            let mut out = vec![0x1d];
+            out.extend_from_slice(&ptr.to_le_bytes());
+            out
+        }
+        let values: HashMap<usize, Vec<u8>> = vec![
+            (0x1000, vec![0x02, 0x04, 0x31, 0x32]),
+            (0x2000, vec![0x43, 0x66, 0x6f, 0x6f, 0xff]),
+            (0x3000, external(0x2000)),
+        ].into_iter().collect();
+        let single = external(0x1000);
+        let resolve = |ptr| values.get(&ptr).map(Vec::as_slice);
+
+        // compact array of [external, 3, external]
+        let mut data = vec![0x13, 0x00];
+        data.extend(external(0x1000));
+        data.push(0x33);
+        data.extend(external(0x3000));
+        data.push(0x03);
+        data[1] = data.len() as u8;
+
+        let mut de = Deserializer::from_bytes(&data).with_external_resolver(resolve);
+        let v = serde_json::Value::deserialize(&mut de).unwrap();
+        de.end().unwrap();
+        assert_eq!(v, json!([[1, 2], 3, "foo"]));
+
+        let mut de = Deserializer::from_bytes(&data).with_external_resolver(resolve);
+        let v = <(Vec<u8>, u8, &str)>::deserialize(&mut de).unwrap();
+        assert_eq!(v, (vec![1, 2], 3, "foo"));
+
+        let mut de = Deserializer::from_bytes(&single).with_external_resolver(resolve);
+        assert_eq!(Option::<Vec<u8>>::deserialize(&mut de).unwrap(), Some(vec![1, 2]));
+        de.end().unwrap();
+
+        let mut de = Deserializer::from_bytes(&single).with_external_resolver(|_| None);
+        assert_eq!(u8::deserialize(&mut de), Err(ErrorKind::UnresolvedExternal(0x1000).into()));
+        assert_eq!(from_bytes::<u8>(&single), Err(ErrorKind::Unsupported(ValueType::External).into()));
+
+        // externals pointing to each other
+        let cycle: HashMap<usize, Vec<u8>> = vec![(0x1000, external(0x2000)), (0x2000, external(0x1000))].into_iter().collect();
+        let resolve = |ptr| cycle.get(&ptr).map(Vec::as_slice);
+        let mut de = Deserializer::from_bytes(&single).with_external_resolver(resolve);
+        assert_eq!(u8::deserialize(&mut de).map_err(|e| e.kind().clone()), Err(ErrorKind::DepthLimitExceeded));
+        let mut de = Deserializer::from_bytes(&single).with_external_resolver(resolve).budget(10);
+        assert_eq!(serde_json::Value::deserialize(&mut de).map_err(|e| e.kind().clone()), Err(ErrorKind::BudgetExceeded));
+        let self_referential = |_| Some(&single[..]);
+        let mut de = Deserializer::from_bytes(&single).with_external_resolver(self_referential);
+        assert_eq!(serde_json::Value::deserialize(&mut de).map_err(|e| e.kind().clone()), Err(ErrorKind::DepthLimitExceeded));
+    }
+
+    #[test]
+    fn any() {
+        use serde_json::Value;
+        let any = |b: &[u8]| from_bytes::<Value>(b);
+        assert_eq!(any(&[0x17]).unwrap(), Value::Null);
+        assert_eq!(any(&[0x1e]).unwrap(), Value::Null);
+        assert_eq!(any(&[0x1f]).unwrap(), Value::Null);
+        assert_eq!(any(&[0x1c, 0x00, 0xe8, 0x66, 0x5e, 0x6f, 0x01, 0x00, 0x00]).unwrap(), json!(1577836800000_i64));
+        assert_eq!(any(&[0xee, 0x01, 0x43, 0x66, 0x6f, 0x6f]).unwrap(), json!("foo"));
+        assert_eq!(any(&[0xef, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x02, 0x04, 0x31, 0x32]).unwrap(), json!([1, 2]));
+        assert_eq!(any(&[0xc0, 0x02, 0x01, 0x02]).unwrap(), json!([1, 2]));
+        assert_eq!(any(&[0xf0, 0xaa]).unwrap(), json!([0xf0, 0xaa]));
+        assert_eq!(any(&[0xf4, 0x01, 0xbb]).unwrap(), json!([0xf4, 0x01, 0xbb]));
+        assert_eq!(any(&[0xee, 0x01]), Err(ErrorKind::Eof.into()));
+
+        // mixed values inside containers
+        let data = [0x06, 0x0b, 0x03, 0x1e, 0xee, 0x05, 0x35, 0x1f, 0x03, 0x04, 0x07];
+        assert_eq!(any(&data).unwrap(), json!([null, 5, null]));
+    }
+
+    #[test]
+    fn option() {
+        assert_eq!(from_bytes::<Option<u8>>(&[0x18]).unwrap(), None);
+        assert_eq!(from_bytes::<Option<u8>>(&[0x35]).unwrap(), Some(5));
+        assert_eq!(from_bytes::<Option<Option<u8>>>(&[0x35]).unwrap(), Some(Some(5)));
+        assert_eq!(from_bytes::<Vec<Option<&str>>>(&[0x02, 0x04, 0x18, 0x40]).unwrap(), vec![None, Some("")]);
+    }
+
+    #[test]
+    fn missing_optional_fields() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Doc {
+            _key: String,
+            name: Option<String>,
+            age: Option<u8>,
+            #[serde(default)]
+            tags: Vec<String>,
+        }
+
+        let doc = |v: serde_json::Value| from_bytes::<Doc>(&crate::to_bytes(&v).unwrap());
+        assert_eq!(doc(json!({"_key": "a"})).unwrap(), Doc { _key: "a".to_owned(), name: None, age: None, tags: vec![] });
+        assert_eq!(doc(json!({"_key": "a", "name": null, "age": 3})).unwrap(),
+                   Doc { _key: "a".to_owned(), name: None, age: Some(3), tags: vec![] });
+        assert_eq!(doc(json!({"age": 3, "tags": ["x"], "_key": "b", "other": 1})).unwrap(),
+                   Doc { _key: "b".to_owned(), name: None, age: Some(3), tags: vec!["x".to_owned()] });
+        assert_eq!(doc(json!({"name": "x"})).unwrap_err(), ErrorKind::Message("missing field `_key`".to_owned()).into());
+        assert!(doc(json!({"_key": "a", "age": "x"})).is_err());
+    }
+
+    #[test]
+    fn option_hooks() {
+        let data = [0x02, 0x05, 0x31, 0x35, 0x18];
+        let mut de = Deserializer::from_bytes(&data).with_hook(ValueType::SmallInt, |b| match b[0] {
+            0x35 => Some(Rewrite::Null),
+            _ => Some(Rewrite::String("one".to_owned())),
+        });
+        assert_eq!(Vec::<Option<String>>::deserialize(&mut de).unwrap(), vec![Some("one".to_owned()), None, None]);
+    }
+
+    #[test]
+    fn hook_rewrite() {
+        // UTC date 2020-01-01T00:00:00Z
+        let data = [0x1c, 0x00, 0xe8, 0x66, 0x5e, 0x6f, 0x01, 0x00, 0x00];
+        let mut de = Deserializer::from_bytes(&data).with_hook(ValueType::UtcDate, |b| {
+            let mut ms = [0; 8];
+            ms.copy_from_slice(&b[1..9]);
+            Some(Rewrite::String(i64::from_le_bytes(ms).to_string()))
+        });
+        assert_eq!(String::deserialize(&mut de).unwrap(), "1577836800000");
+        assert_eq!(de.end(), Ok(()));
+
+        // strip binary from an array of mixed values
+        let data = [0x06, 0x0a, 0x02, 0x31, 0xc0, 0x02, 0xff, 0xff, 0x03, 0x04];
+        let mut de = Deserializer::from_bytes(&data).with_hook(ValueType::Binary, |_| Some(Rewrite::Null));
+        assert_eq!(serde_json::Value::deserialize(&mut de).unwrap(), json!([1, null]));
+    }
+
+    #[test]
+    fn dictionary() {
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct Task {
+            status: String,
+            owner: String,
+        }
+
+        let tasks = vec![
+            Task { status: "done".to_owned(), owner: "alice".to_owned() },
+            Task { status: "status".to_owned(), owner: "bob".to_owned() },
+        ];
+        let dictionary = Arc::new(StringDictionary::new(7, vec!["done", "status", "alice"]));
+        let options = crate::SerializerOptions::new().dictionary(dictionary.clone());
+        let data = crate::to_bytes_with_options(&tasks, &options).unwrap();
+
+        // keys are left alone, values are replaced by tagged references
+        assert!(data.windows(7).any(|w| w == b"\x46status"));
+        assert!(!data.windows(5).any(|w| w == b"\x44done"));
+        assert!(data.windows(3).any(|w| w == [0xee, 0x07, 0x30]));
+        assert!(data.len() < crate::to_bytes(&tasks).unwrap().len());
+
+        let mut de = Deserializer::from_bytes(&data).with_dictionary(dictionary);
+        assert_eq!(Vec::<Task>::deserialize(&mut de).unwrap(), tasks);
+    }
+
+    #[test]
+    fn hook_observe() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let seen = Rc::new(Cell::new(0));
+        let counter = seen.clone();
+        let data = [0x02, 0x05, 0x31, 0x32, 0x33];
+        let mut de = Deserializer::from_bytes(&data).with_hook(ValueType::SmallInt, move |_| {
+            counter.set(counter.get() + 1);
+            None
+        });
+        assert_eq!(Vec::<u8>::deserialize(&mut de).unwrap(), vec![1, 2, 3]);
+        assert_eq!(seen.get(), 3);
+
+        // hooks only apply to their own type
+        let mut de = Deserializer::from_bytes(&[0x43, 0x66, 0x6f, 0x6f]).with_hook(ValueType::Int, |_| Some(Rewrite::Null));
+        assert_eq!(String::deserialize(&mut de).unwrap(), "foo");
+    }
+
+    #[test]
+    fn end_trailing_bytes() {
+        let data = [0x31, 0x32];
+        let mut de = Deserializer::from_bytes(&data);
+        assert_eq!(u8::deserialize(&mut de).unwrap(), 1);
+        assert_eq!(de.end(), Err(ErrorKind::TrailingBytes(1).into()));
+    }
+
     #[test]
     fn vst_header() {
         // VelocyStream header returned by ArangoDB 3.5.3 for /_admin/echo query
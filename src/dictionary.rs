@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use crate::raw;
+
+/// Dictionary of frequently repeated strings, for (experimental) compression of attribute
+/// values.
+///
+/// When set with [`SerializerOptions::dictionary`](crate::SerializerOptions::dictionary), string
+/// values found in the dictionary are encoded as a tagged value holding the string's position in
+/// the dictionary instead of the string itself. Such values are still valid VelocyPack, but can
+/// only be decoded back into strings by a deserializer using the same dictionary, see
+/// [`Deserializer::with_dictionary`](crate::Deserializer::with_dictionary).
+#[derive(Clone, Debug)]
+pub struct StringDictionary {
+    tag: u64,
+    strings: Vec<String>,
+    positions: HashMap<String, u64>,
+}
+
+impl StringDictionary {
+    /// Create a dictionary of `strings`, marking encoded values with `tag`. Later duplicates of a
+    /// string are ignored.
+    pub fn new<I, S>(tag: u64, strings: I) -> Self where
+        I: IntoIterator<Item = S>, S: Into<String> {
+        let mut dictionary = Self { tag, strings: Vec::new(), positions: HashMap::new() };
+        for s in strings {
+            let s = s.into();
+            if !dictionary.positions.contains_key(&s) {
+                dictionary.positions.insert(s.clone(), dictionary.strings.len() as u64);
+                dictionary.strings.push(s);
+            }
+        }
+        dictionary
+    }
+
+    pub fn tag(&self) -> u64 {
+        self.tag
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Get the string at position `n`.
+    pub fn get(&self, n: u64) -> Option<&str> {
+        self.strings.get(usize::try_from(n).ok()?).map(String::as_str)
+    }
+
+    /// Get the position of `s` in the dictionary.
+    pub fn position(&self, s: &str) -> Option<u64> {
+        self.positions.get(s).copied()
+    }
+
+    /// Encode `s` as a tagged dictionary reference, if it is in the dictionary.
+    pub(crate) fn encode(&self, s: &str) -> Option<Vec<u8>> {
+        let n = self.position(s)?;
+        let mut out = if self.tag <= u8::MAX as u64 {
+            vec![0xee, self.tag as u8]
+        } else {
+            let mut out = vec![0xef];
+            out.extend_from_slice(&self.tag.to_le_bytes());
+            out
+        };
+        out.extend(crate::to_bytes(&n).ok()?);
+        Some(out)
+    }
+
+    /// Decode the string referenced by the tagged value in `bytes`, if it carries this
+    /// dictionary's tag.
+    pub(crate) fn decode(&self, bytes: &[u8]) -> Option<&str> {
+        let (tag, value) = match *bytes.first()? {
+            0xee => (*bytes.get(1)? as u64, bytes.get(2..)?),
+            0xef => (raw::read_uint(bytes.get(1..)?, 8).ok()?, bytes.get(9..)?),
+            _ => return None,
+        };
+        if tag != self.tag {
+            return None;
+        }
+        self.get(crate::from_bytes(value).ok()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        let dictionary = StringDictionary::new(42, vec!["active", "inactive", "active"]);
+        assert_eq!(dictionary.len(), 2);
+        assert_eq!(dictionary.position("inactive"), Some(1));
+        assert_eq!(dictionary.get(2), None);
+
+        assert_eq!(dictionary.encode("active").unwrap(), &[0xee, 0x2a, 0x30]);
+        assert_eq!(dictionary.encode("other"), None);
+        assert_eq!(dictionary.decode(&[0xee, 0x2a, 0x31]), Some("inactive"));
+        assert_eq!(dictionary.decode(&[0xee, 0x2b, 0x31]), None);
+        assert_eq!(dictionary.decode(&[0xee, 0x2a, 0x35]), None);
+        assert_eq!(dictionary.decode(&[0xee, 0x2a]), None);
+
+        let dictionary = StringDictionary::new(1000, vec!["a"]);
+        let encoded = dictionary.encode("a").unwrap();
+        assert_eq!(encoded, &[0xef, 0xe8, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x30]);
+        assert_eq!(dictionary.decode(&encoded), Some("a"));
+    }
+}
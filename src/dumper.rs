@@ -0,0 +1,456 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::error::{ErrorKind, Result};
+use crate::raw;
+use crate::ser::NonFinite;
+use crate::translator::AttributeTranslator;
+use crate::value_type::ValueType;
+
+/// What to do with values which have no JSON equivalent (binary, BCD, external, min/max key,
+/// custom and illegal values).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Unsupported {
+    /// Fail with `ErrorKind::Unsupported`.
+    #[default]
+    Error,
+    /// Output `null` in place of the value.
+    Null,
+    /// Output the given string (as a JSON string) in place of the value.
+    Replace(&'static str),
+}
+
+/// Options for converting VelocyPack to JSON.
+#[derive(Clone, Debug)]
+pub struct DumperOptions {
+    pretty: bool,
+    indent: String,
+    sort_keys: bool,
+    trailing_newline: bool,
+    escape_unicode: bool,
+    escape_forward_slashes: bool,
+    dates_as_integers: bool,
+    unsupported: Unsupported,
+    non_finite: NonFinite,
+    attribute_translator: Option<Arc<AttributeTranslator>>,
+}
+
+impl Default for DumperOptions {
+    fn default() -> Self {
+        Self {
+            pretty: false,
+            indent: "  ".to_owned(),
+            sort_keys: false,
+            trailing_newline: false,
+            escape_unicode: false,
+            escape_forward_slashes: false,
+            dates_as_integers: true,
+            unsupported: Unsupported::Error,
+            non_finite: NonFinite::Null,
+            attribute_translator: None,
+        }
+    }
+}
+
+impl DumperOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indent nested values, one per line, by two spaces unless set otherwise with
+    /// [`indent`](DumperOptions::indent).
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// String to indent nested values with, once per level, when pretty printing, e.g. `"\t"`.
+    pub fn indent(mut self, indent: &str) -> Self {
+        self.indent = indent.to_owned();
+        self
+    }
+
+    /// Write object members in order of their keys, rather than in the order they are stored,
+    /// e.g. for output that can be compared between runs.
+    pub fn sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// End the output with a newline.
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    /// Escape all non-ASCII characters in strings as `\uXXXX`.
+    pub fn escape_unicode(mut self, escape_unicode: bool) -> Self {
+        self.escape_unicode = escape_unicode;
+        self
+    }
+
+    /// Escape `/` in strings as `\/`, e.g. for JSON embedded in HTML.
+    pub fn escape_forward_slashes(mut self, escape_forward_slashes: bool) -> Self {
+        self.escape_forward_slashes = escape_forward_slashes;
+        self
+    }
+
+    /// Output UTC dates as milliseconds since the epoch (the default), or otherwise treat them as
+    /// unsupported.
+    pub fn dates_as_integers(mut self, dates_as_integers: bool) -> Self {
+        self.dates_as_integers = dates_as_integers;
+        self
+    }
+
+    pub fn unsupported(mut self, unsupported: Unsupported) -> Self {
+        self.unsupported = unsupported;
+        self
+    }
+
+    /// Write integer object keys found in `translator` as the attribute names they stand for.
+    /// Other integer keys are written as strings of their digits.
+    pub fn attribute_translator(mut self, translator: Arc<AttributeTranslator>) -> Self {
+        self.attribute_translator = Some(translator);
+        self
+    }
+
+    /// What to do with NaN and infinite doubles, null by default. `NonFinite::Allow` writes them
+    /// as `NaN`, `Infinity` and `-Infinity`, which many JSON parsers reject.
+    pub fn non_finite(mut self, non_finite: NonFinite) -> Self {
+        self.non_finite = non_finite;
+        self
+    }
+}
+
+/// Convert the VelocyPack value at the start of `bytes` to JSON.
+///
+/// UTC dates are output as milliseconds since the epoch, tagged values as the value they tag,
+/// and NaN/infinite doubles as `null` unless set otherwise with
+/// [`DumperOptions::non_finite`]. Arrays and objects nested more than
+/// [`DEFAULT_MAX_DEPTH`](crate::DEFAULT_MAX_DEPTH) deep fail with `ErrorKind::DepthLimitExceeded`.
+pub fn to_json_bytes(bytes: &[u8]) -> Result<Vec<u8>> {
+    to_json_bytes_with_options(bytes, &DumperOptions::default())
+}
+
+pub fn to_json_bytes_with_options(bytes: &[u8], options: &DumperOptions) -> Result<Vec<u8>> {
+    let mut dumper = Dumper { options, output: String::new(), depth: 0 };
+    dumper.dump(bytes)?;
+    if options.trailing_newline {
+        dumper.output.push('\n');
+    }
+    Ok(dumper.output.into_bytes())
+}
+
+struct Dumper<'a> {
+    options: &'a DumperOptions,
+    output: String,
+    depth: usize,
+}
+
+impl<'a> Dumper<'a> {
+    fn dump(&mut self, bytes: &[u8]) -> Result<()> {
+        // tags are dropped, so skip over them without recursing
+        let mut bytes = bytes;
+        while let Some(&header @ (0xee | 0xef)) = bytes.first() {
+            let n_bytes = if header == 0xee { 1 } else { 8 };
+            bytes = bytes.get(1 + n_bytes..).ok_or(ErrorKind::Eof)?;
+        }
+        let header = *bytes.first().ok_or(ErrorKind::Eof)?;
+        match header {
+            0x01..=0x09 | 0x13 => self.dump_array(bytes),
+            0x0a..=0x12 | 0x14 => self.dump_object(bytes),
+            0x18 => self.write("null"),
+            0x19 => self.write("false"),
+            0x1a => self.write("true"),
+            0x1b => {
+                let v = f64::from_bits(raw::read_uint(&bytes[1..], 8)?);
+                if v.is_finite() {
+                    self.write_display(v)
+                } else {
+                    match self.options.non_finite {
+                        NonFinite::Allow if v.is_nan() => self.write("NaN"),
+                        NonFinite::Allow if v > 0.0 => self.write("Infinity"),
+                        NonFinite::Allow => self.write("-Infinity"),
+                        NonFinite::Error => Err(ErrorKind::NonFiniteDouble.into()),
+                        NonFinite::Null => self.write("null"),
+                    }
+                }
+            },
+            0x1c if self.options.dates_as_integers => self.write_display(raw::read_int(&bytes[1..], 8)?),
+            0x20..=0x27 => self.write_display(raw::read_int(&bytes[1..], (header - 0x1f) as usize)?),
+            0x28..=0x2f => self.write_display(raw::read_uint(&bytes[1..], (header - 0x27) as usize)?),
+            0x30..=0x39 => self.write_display(header - 0x30),
+            0x3a..=0x3f => self.write_display(header as i64 - 0x40),
+            0x40..=0xbf => self.dump_str(raw::read_str(bytes)?),
+            0xc8..=0xd7 => self.write(&raw::read_bcd(bytes)?),
+            b => match ValueType::from_header(b) {
+                ValueType::None => Err(ErrorKind::Unimplemented(b).into()),
+                value_type => match self.options.unsupported {
+                    Unsupported::Error => Err(ErrorKind::Unsupported(value_type).into()),
+                    Unsupported::Null => self.write("null"),
+                    Unsupported::Replace(s) => self.dump_str(s),
+                },
+            },
+        }
+    }
+
+    fn dump_array(&mut self, bytes: &[u8]) -> Result<()> {
+        self.enter()?;
+        self.output.push('[');
+        let mut empty = true;
+        for (i, item) in raw::Container::new(bytes)?.items().enumerate() {
+            if i > 0 {
+                self.output.push(',');
+            }
+            self.newline();
+            self.dump(item?)?;
+            empty = false;
+        }
+        self.depth -= 1;
+        if !empty {
+            self.newline();
+        }
+        self.output.push(']');
+        Ok(())
+    }
+
+    fn dump_object(&mut self, bytes: &[u8]) -> Result<()> {
+        self.enter()?;
+        self.output.push('{');
+        let mut members = Vec::new();
+        for member in raw::Container::new(bytes)?.members() {
+            let (key, value) = member?;
+            let name = match (&self.options.attribute_translator, key[0]) {
+                (Some(translator), 0x28..=0x39) => translator.name(crate::from_bytes(key)?).map(Cow::Borrowed),
+                _ => None,
+            };
+            members.push((name.map_or_else(|| raw::read_key(key), Ok)?, value));
+        }
+        if self.options.sort_keys {
+            members.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        let mut empty = true;
+        for (i, (key, value)) in members.into_iter().enumerate() {
+            if i > 0 {
+                self.output.push(',');
+            }
+            self.newline();
+            self.dump_str(&key)?;
+            self.output.push(':');
+            if self.options.pretty {
+                self.output.push(' ');
+            }
+            self.dump(value)?;
+            empty = false;
+        }
+        self.depth -= 1;
+        if !empty {
+            self.newline();
+        }
+        self.output.push('}');
+        Ok(())
+    }
+
+    fn dump_str(&mut self, s: &str) -> Result<()> {
+        self.output.reserve(s.len() + 2);
+        let start = self.output.len();
+        write_json_str(&mut self.output, s, self.options.escape_unicode).expect("writing to string");
+        if self.options.escape_forward_slashes && s.contains('/') {
+            // no other escape sequence contains a slash
+            let escaped = self.output[start..].replace('/', "\\/");
+            self.output.truncate(start);
+            self.output.push_str(&escaped);
+        }
+        Ok(())
+    }
+
+    // one level deeper into arrays and objects, failing past the limit
+    fn enter(&mut self) -> Result<()> {
+        if self.depth >= crate::DEFAULT_MAX_DEPTH {
+            return Err(ErrorKind::DepthLimitExceeded.into());
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn newline(&mut self) {
+        if self.options.pretty {
+            self.output.push('\n');
+            for _ in 0..self.depth {
+                self.output.push_str(&self.options.indent);
+            }
+        }
+    }
+
+    fn write(&mut self, s: &str) -> Result<()> {
+        self.output.push_str(s);
+        Ok(())
+    }
+
+    fn write_display<T: std::fmt::Display>(&mut self, v: T) -> Result<()> {
+        write!(self.output, "{}", v).expect("writing to string");
+        Ok(())
+    }
+}
+
+/// Write `s` as a quoted JSON string, escaping all non-ASCII characters if `escape_unicode` is set.
+pub(crate) fn write_json_str<W: Write>(out: &mut W, s: &str, escape_unicode: bool) -> std::fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            '\u{08}' => out.write_str("\\b")?,
+            '\u{0c}' => out.write_str("\\f")?,
+            c if (c as u32) < 0x20 || (escape_unicode && !c.is_ascii()) => {
+                let mut buf = [0; 2];
+                for unit in c.encode_utf16(&mut buf) {
+                    write!(out, "\\u{:04x}", unit)?;
+                }
+            },
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    fn json(bytes: &[u8]) -> String {
+        String::from_utf8(to_json_bytes(bytes).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(json(&[0x18]), "null");
+        assert_eq!(json(&[0x19]), "false");
+        assert_eq!(json(&[0x1a]), "true");
+        assert_eq!(json(&[0x35]), "5");
+        assert_eq!(json(&[0x3a]), "-6");
+        assert_eq!(json(&[0x20, 0xf9]), "-7");
+        assert_eq!(json(&[0x29, 0xe8, 0x03]), "1000");
+        assert_eq!(json(&[0x1b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8, 0x3f]), "1.5");
+        assert_eq!(json(&[0x1b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8, 0x7f]), "null");
+        assert_eq!(json(&[0x1c, 0x00, 0xe8, 0x66, 0x5e, 0x6f, 0x01, 0x00, 0x00]), "1577836800000");
+        assert_eq!(json(&[0xee, 0x01, 0x31]), "1");
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(json(&[0x40]), r#""""#);
+        assert_eq!(json(&[0x43, 0x61, 0x22, 0x0a]), r#""a\"\n""#);
+        assert_eq!(json(&[0x42, 0x5c, 0x01]), r#""\\\u0001""#);
+        assert_eq!(json(&crate::to_bytes(&"é😀").unwrap()), "\"é😀\"");
+
+        let options = DumperOptions::new().escape_forward_slashes(true);
+        let out = to_json_bytes_with_options(&crate::to_bytes(&"users/1").unwrap(), &options).unwrap();
+        assert_eq!(out, br#""users\/1""#);
+
+        let options = DumperOptions::new().escape_unicode(true);
+        let out = to_json_bytes_with_options(&crate::to_bytes(&"é😀").unwrap(), &options).unwrap();
+        assert_eq!(out, br#""\u00e9\ud83d\ude00""#);
+    }
+
+    #[test]
+    fn containers() {
+        #[derive(Serialize)]
+        struct Person {
+            name: String,
+            age: u8,
+            tags: Vec<&'static str>,
+        }
+        let bytes = crate::to_bytes(&Person { name: "Bob".to_owned(), age: 23, tags: vec!["a", "b"] }).unwrap();
+        assert_eq!(json(&bytes), r#"{"name":"Bob","age":23,"tags":["a","b"]}"#);
+
+        let options = DumperOptions::new().pretty(true);
+        let out = String::from_utf8(to_json_bytes_with_options(&bytes, &options).unwrap()).unwrap();
+        assert_eq!(out, "{\n  \"name\": \"Bob\",\n  \"age\": 23,\n  \"tags\": [\n    \"a\",\n    \"b\"\n  ]\n}");
+
+        let options = DumperOptions::new().pretty(true).indent("\t").sort_keys(true).trailing_newline(true);
+        let out = String::from_utf8(to_json_bytes_with_options(&bytes, &options).unwrap()).unwrap();
+        assert_eq!(out, "{\n\t\"age\": 23,\n\t\"name\": \"Bob\",\n\t\"tags\": [\n\t\t\"a\",\n\t\t\"b\"\n\t]\n}\n");
+        let options = DumperOptions::new().sort_keys(true).trailing_newline(true);
+        assert_eq!(to_json_bytes_with_options(&bytes, &options).unwrap(), b"{\"age\":23,\"name\":\"Bob\",\"tags\":[\"a\",\"b\"]}\n");
+
+        let translator = Arc::new(AttributeTranslator::arangodb());
+        let options = DumperOptions::new().attribute_translator(translator);
+        assert_eq!(to_json_bytes_with_options(&[0x0b, 0x09, 0x02, 0x31, 0x31, 0x36, 0x32, 0x03, 0x05], &options).unwrap(), br#"{"_key":1,"6":2}"#);
+
+        assert_eq!(json(&[0x01]), "[]");
+        assert_eq!(json(&[0x0a]), "{}");
+        assert_eq!(json(&[0x13, 0x06, 0x31, 0x28, 0x10, 0x02]), "[1,16]");
+        assert_eq!(json(&[0x14, 0x06, 0x41, 0x61, 0x31, 0x01]), r#"{"a":1}"#);
+    }
+
+    #[test]
+    fn depth() {
+        // arrays nested `n` deep, around an empty array
+        let nested = |n: usize| {
+            let mut data = Vec::new();
+            for i in 0..n {
+                data.push(0x05);
+                data.extend_from_slice(&(9 * (n - i) as u64 + 1).to_le_bytes());
+            }
+            data.push(0x01);
+            data
+        };
+        let out = json(&nested(crate::DEFAULT_MAX_DEPTH - 1));
+        assert_eq!(out.len(), 2 * crate::DEFAULT_MAX_DEPTH);
+        assert_eq!(to_json_bytes(&nested(crate::DEFAULT_MAX_DEPTH)), Err(ErrorKind::DepthLimitExceeded.into()));
+        assert_eq!(to_json_bytes(&nested(100_000)), Err(ErrorKind::DepthLimitExceeded.into()));
+
+        let mut tags = [0xee, 0x01].repeat(100_000);
+        tags.push(0x35);
+        assert_eq!(json(&tags), "5");
+    }
+
+    #[test]
+    fn non_finite() {
+        let bytes = crate::to_bytes(&vec![f64::NAN, f64::INFINITY, f64::NEG_INFINITY]).unwrap();
+        assert_eq!(json(&bytes), "[null,null,null]");
+        let options = DumperOptions::new().non_finite(NonFinite::Allow);
+        assert_eq!(to_json_bytes_with_options(&bytes, &options).unwrap(), b"[NaN,Infinity,-Infinity]");
+        let options = DumperOptions::new().non_finite(NonFinite::Error);
+        assert_eq!(to_json_bytes_with_options(&bytes, &options), Err(ErrorKind::NonFiniteDouble.into()));
+    }
+
+    #[test]
+    fn bcd() {
+        assert_eq!(json(&[0xd0, 0x02, 0xfe, 0xff, 0xff, 0xff, 0x01, 0x25]), "-1.25");
+        assert_eq!(json(&[0x02, 0x09, 0xc8, 0x01, 0x2c, 0x01, 0x00, 0x00, 0x12]), "[12e300]");
+    }
+
+    #[test]
+    fn unsupported() {
+        assert_eq!(to_json_bytes(&[0xc0, 0x01, 0xff]), Err(ErrorKind::Unsupported(ValueType::Binary).into()));
+        assert_eq!(to_json_bytes(&[0x1e]), Err(ErrorKind::Unsupported(ValueType::MinKey).into()));
+        assert_eq!(to_json_bytes(&[0x15]), Err(ErrorKind::Unimplemented(0x15).into()));
+
+        let options = DumperOptions::new().unsupported(Unsupported::Null);
+        assert_eq!(to_json_bytes_with_options(&[0x02, 0x05, 0xc0, 0x01, 0xff], &options).unwrap(), b"[null]");
+        let options = DumperOptions::new().unsupported(Unsupported::Replace("(binary)"));
+        assert_eq!(to_json_bytes_with_options(&[0x02, 0x05, 0xc0, 0x01, 0xff], &options).unwrap(), br#"["(binary)"]"#);
+        assert_eq!(to_json_bytes_with_options(&[0xf0, 0x01], &options).unwrap(), br#""(binary)""#);
+
+        let date = [0x1c, 0x00, 0xe8, 0x66, 0x5e, 0x6f, 0x01, 0x00, 0x00];
+        let options = DumperOptions::new().dates_as_integers(false);
+        assert_eq!(to_json_bytes_with_options(&date, &options), Err(ErrorKind::Unsupported(ValueType::UtcDate).into()));
+        let options = options.unsupported(Unsupported::Null);
+        assert_eq!(to_json_bytes_with_options(&date, &options).unwrap(), b"null");
+    }
+
+    #[test]
+    fn matches_serde_json() {
+        let value = serde_json::json!({"a": [1, 2.5, -300, "x\u{7f}"], "b": {"c": null, "d": true}});
+        let bytes = crate::to_bytes(&value).unwrap();
+        let out: serde_json::Value = serde_json::from_slice(&to_json_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(out, value);
+    }
+}
@@ -1,17 +1,40 @@
 use std::fmt::{self, Display};
 use std::str::Utf8Error;
+use std::sync::Arc;
 
 use serde::{de, ser};
 
+use crate::value_type::ValueType;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Error raised while encoding, decoding or inspecting VelocyPack: an [`ErrorKind`] and, where
+/// known, where in the input it occurred and the error which caused it.
+#[derive(Clone, Debug)]
+pub struct Error {
+    // boxed to keep results small, as they're returned all through the deserializer's recursion
+    inner: Box<Inner>,
+}
+
+#[derive(Clone, Debug)]
+struct Inner {
+    kind: ErrorKind,
+    // header byte of the value which had a different type to the one expected
+    found: Option<u8>,
+    offset: Option<usize>,
+    path: Option<String>,
+    source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+/// What went wrong, see [`Error::kind`].
 #[derive(Clone, Debug, PartialEq)]
-pub enum Error {
-    // Variants created via the `ser::Error` and `de::Error` traits.
+#[non_exhaustive]
+pub enum ErrorKind {
+    // Kinds created via the `ser::Error` and `de::Error` traits.
 
     Message(String),
 
-    // Variants created directly by Serializer and Deserializer (format specific).
+    // Kinds created directly by Serializer and Deserializer (format specific).
 
     Eof,
     ExpectedNull,
@@ -19,52 +42,334 @@ pub enum Error {
     ExpectedInteger,
     ExpectedDouble,
     ExpectedString,
+    ExpectedBinary,
     ExpectedArray,
     ExpectedObject,
+    ExpectedTagged,
     NumberTooLarge,
-    InvalidUtf8(Utf8Error),
+    BudgetExceeded,
+    DepthLimitExceeded,
+    LimitExceeded(&'static str),
+    InvalidUtf8,
+    InvalidBcd,
+    Invalid(&'static str),
+    DuplicateKey(String),
+    UnknownField(String),
     TrailingBytes(usize),
     Unimplemented(u8),
+    Unsupported(ValueType),
+    NonFiniteDouble,
+    UnresolvedExternal(usize),
+    Io,
+}
+
+/// Broad category of an [`Error`], see [`Error::category`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// Input isn't valid VelocyPack, or is truncated.
+    Syntax,
+    /// A value exceeds what the target type or a configured limit allows.
+    Limit,
+    /// A value has a different type to the one expected, or one that isn't supported.
+    TypeMismatch,
+    /// Reading or writing failed.
+    Io,
+    /// Errors raised by `Serialize`/`Deserialize` implementations, and anything else.
+    Other,
+}
+
+impl ErrorKind {
+    /// Stable numeric code identifying the kind of error, for mapping errors across FFI or network
+    /// boundaries without matching on messages. Codes are grouped by category: 1xxx syntax, 2xxx
+    /// limits, 3xxx type mismatches, 4xxx io and 9xxx other. Codes are never reused or changed.
+    pub fn code(&self) -> u32 {
+        match *self {
+            ErrorKind::Eof => 1001,
+            ErrorKind::InvalidUtf8 => 1002,
+            ErrorKind::TrailingBytes(_) => 1003,
+            ErrorKind::Unimplemented(_) => 1004,
+            ErrorKind::InvalidBcd => 1005,
+            ErrorKind::Invalid(_) => 1006,
+            ErrorKind::DuplicateKey(_) => 1007,
+            ErrorKind::NumberTooLarge => 2001,
+            ErrorKind::BudgetExceeded => 2002,
+            ErrorKind::DepthLimitExceeded => 2003,
+            ErrorKind::LimitExceeded(_) => 2004,
+            ErrorKind::ExpectedNull => 3001,
+            ErrorKind::ExpectedBoolean => 3002,
+            ErrorKind::ExpectedInteger => 3003,
+            ErrorKind::ExpectedDouble => 3004,
+            ErrorKind::ExpectedString => 3005,
+            ErrorKind::ExpectedBinary => 3006,
+            ErrorKind::ExpectedArray => 3007,
+            ErrorKind::ExpectedObject => 3008,
+            ErrorKind::Unsupported(_) => 3009,
+            ErrorKind::NonFiniteDouble => 3010,
+            ErrorKind::ExpectedTagged => 3011,
+            ErrorKind::Io => 4001,
+            ErrorKind::Message(_) => 9001,
+            ErrorKind::UnresolvedExternal(_) => 9002,
+            ErrorKind::UnknownField(_) => 9003,
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self.code() / 1000 {
+            1 => ErrorCategory::Syntax,
+            2 => ErrorCategory::Limit,
+            3 => ErrorCategory::TypeMismatch,
+            4 => ErrorCategory::Io,
+            _ => ErrorCategory::Other,
+        }
+    }
+
+    // name of the type expected by `Expected*` kinds
+    fn expected(&self) -> Option<&'static str> {
+        let name = match self {
+            ErrorKind::ExpectedNull => "null",
+            ErrorKind::ExpectedBoolean => "boolean",
+            ErrorKind::ExpectedInteger => "integer",
+            ErrorKind::ExpectedDouble => "double",
+            ErrorKind::ExpectedString => "string",
+            ErrorKind::ExpectedBinary => "binary",
+            ErrorKind::ExpectedArray => "array",
+            ErrorKind::ExpectedObject => "object",
+            ErrorKind::ExpectedTagged => "tagged",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+impl Error {
+    pub fn kind(&self) -> &ErrorKind {
+        &self.inner.kind
+    }
+
+    /// See [`ErrorKind::code`].
+    pub fn code(&self) -> u32 {
+        self.inner.kind.code()
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        self.inner.kind.category()
+    }
+
+    /// Path within the document of the value which failed to decode, if known, e.g.
+    /// `friends[2].address.port`.
+    pub fn path(&self) -> Option<&str> {
+        self.inner.path.as_deref()
+    }
+
+    /// Header byte of the value which had a different type to the one expected, if known.
+    pub fn found(&self) -> Option<u8> {
+        self.inner.found
+    }
+
+    /// Offset within the input of the value the error occurred at, if known. Values external to
+    /// the input have no offset.
+    pub fn offset(&self) -> Option<usize> {
+        self.inner.offset
+    }
+
+    // the error as occurring at a value with header byte `found`, at `offset` within the input
+    pub(crate) fn at_value(mut self, found: u8, offset: Option<usize>) -> Error {
+        self.inner.found = Some(found);
+        self.inner.offset = offset;
+        self
+    }
+
+    // the error as caused by `source`
+    pub(crate) fn caused_by<E: std::error::Error + Send + Sync + 'static>(mut self, source: E) -> Error {
+        self.inner.source = Some(Arc::new(source));
+        self
+    }
+
+    // the error as occurring within the array item at `index`
+    pub(crate) fn at_index(self, index: usize) -> Error {
+        self.within(format!("[{}]", index))
+    }
+
+    // the error as occurring within the object member `key`
+    pub(crate) fn at_key(self, key: &str) -> Error {
+        self.within(key.to_owned())
+    }
+
+    fn within(mut self, mut segment: String) -> Error {
+        if let Some(path) = self.inner.path.take() {
+            if !path.starts_with('[') {
+                segment.push('.');
+            }
+            segment.push_str(&path);
+        }
+        self.inner.path = Some(segment);
+        self
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error { inner: Box::new(Inner { kind, found: None, offset: None, path: None, source: None }) }
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(e: Utf8Error) -> Self {
+        Error::from(ErrorKind::InvalidUtf8).caused_by(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::from(ErrorKind::Io).caused_by(e)
+    }
+}
+
+// sources are compared by message, as most errors can't be compared themselves
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (&self.inner, &other.inner);
+        a.kind == b.kind
+            && a.found == b.found
+            && a.offset == b.offset
+            && a.path == b.path
+            && a.source.as_ref().map(|e| e.to_string()) == b.source.as_ref().map(|e| e.to_string())
+    }
 }
 
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error::Message(msg.to_string())
+        ErrorKind::Message(msg.to_string()).into()
     }
 }
 
 impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error::Message(msg.to_string())
+        ErrorKind::Message(msg.to_string()).into()
     }
 }
 
-impl Display for Error {
+impl Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Error::Message(ref msg) => write!(f, "{}", msg),
-            Error::Eof => write!(f, "unexpected end of input"),
-            Error::ExpectedNull => write!(f, "expected null value in input"),
-            Error::ExpectedBoolean => write!(f, "expected boolean value in input"),
-            Error::ExpectedInteger => write!(f, "expected integer value in input"),
-            Error::ExpectedDouble => write!(f,"expected double value in input"),
-            Error::ExpectedString => write!(f, "expected string value in input"),
-            Error::ExpectedArray => write!(f, "expected array value in input"),
-            Error::ExpectedObject => write!(f, "expected object value in input"),
-            Error::NumberTooLarge => write!(f, "number was too large to parse into requested type"),
-            Error::InvalidUtf8(_utf8err) => write!(f, "invalid utf8 encountered when parsing string"),
-            Error::TrailingBytes(length) => write!(f, "found {} trailing bytes after parsing input", length),
-            Error::Unimplemented(b) => write!(f, "parsing for byte sequence starting 0x{:02x} is not implemented", b),
+            ErrorKind::Message(ref msg) => write!(f, "{}", msg),
+            ErrorKind::Eof => write!(f, "unexpected end of input"),
+            ErrorKind::ExpectedNull => write!(f, "expected null value in input"),
+            ErrorKind::ExpectedBoolean => write!(f, "expected boolean value in input"),
+            ErrorKind::ExpectedInteger => write!(f, "expected integer value in input"),
+            ErrorKind::ExpectedDouble => write!(f,"expected double value in input"),
+            ErrorKind::ExpectedString => write!(f, "expected string value in input"),
+            ErrorKind::ExpectedBinary => write!(f, "expected binary value in input"),
+            ErrorKind::ExpectedArray => write!(f, "expected array value in input"),
+            ErrorKind::ExpectedObject => write!(f, "expected object value in input"),
+            ErrorKind::ExpectedTagged => write!(f, "expected tagged value in input"),
+            ErrorKind::NumberTooLarge => write!(f, "number was too large to parse into requested type"),
+            ErrorKind::BudgetExceeded => write!(f, "decoding budget exceeded"),
+            ErrorKind::DepthLimitExceeded => write!(f, "values nested too deeply"),
+            ErrorKind::LimitExceeded(limit) => write!(f, "{} limit exceeded", limit),
+            ErrorKind::InvalidUtf8 => write!(f, "invalid utf8 encountered when parsing string"),
+            ErrorKind::InvalidBcd => write!(f, "invalid digit encountered when parsing bcd value"),
+            ErrorKind::Invalid(reason) => write!(f, "invalid VelocyPack: {}", reason),
+            ErrorKind::DuplicateKey(ref key) => write!(f, "duplicate object key `{}`", key),
+            ErrorKind::UnknownField(ref field) => write!(f, "unknown field `{}`", field),
+            ErrorKind::TrailingBytes(length) => write!(f, "found {} trailing bytes after parsing input", length),
+            ErrorKind::Unimplemented(b) => write!(f, "parsing for byte sequence starting 0x{:02x} is not implemented", b),
+            ErrorKind::Unsupported(t) => write!(f, "{} values are not supported", t),
+            ErrorKind::NonFiniteDouble => write!(f, "NaN and infinite doubles are not allowed"),
+            ErrorKind::UnresolvedExternal(ptr) => write!(f, "external value pointing to 0x{:x} could not be resolved", ptr),
+            ErrorKind::Io => write!(f, "io error"),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.inner.kind.expected(), self.inner.found) {
+            (Some(expected), Some(found)) => {
+                write!(f, "expected {}, found {} (0x{:02x})", expected, ValueType::from_header(found), found)?
+            },
+            _ => write!(f, "{}", self.inner.kind)?,
+        }
+        if let (ErrorKind::Io, Some(source)) = (&self.inner.kind, &self.inner.source) {
+            write!(f, ": {}", source)?;
+        }
+        if let Some(offset) = self.inner.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        if let Some(ref path) = self.inner.path {
+            write!(f, " at {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.inner.source {
+            Some(ref e) => Some(&**e),
+            None => None,
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
+    use std::error::Error as _;
+
+    use crate::error::{Error, ErrorCategory, ErrorKind};
+
     #[test]
     fn error() {
-        assert_eq!(&format!("{}", crate::error::Error::Message("foo".to_owned())), "foo");
+        assert_eq!(&format!("{}", Error::from(ErrorKind::Message("foo".to_owned()))), "foo");
+    }
+
+    #[test]
+    fn codes() {
+        assert_eq!(Error::from(ErrorKind::Eof).code(), 1001);
+        assert_eq!(Error::from(ErrorKind::Eof).category(), ErrorCategory::Syntax);
+        assert_eq!(ErrorKind::NumberTooLarge.category(), ErrorCategory::Limit);
+        assert_eq!(ErrorKind::ExpectedArray.code(), 3007);
+        assert_eq!(ErrorKind::ExpectedArray.category(), ErrorCategory::TypeMismatch);
+        assert_eq!(Error::from(std::io::Error::other("x")).category(), ErrorCategory::Io);
+        assert_eq!(ErrorKind::Message("foo".to_owned()).category(), ErrorCategory::Other);
+        assert_eq!(Error::from(ErrorKind::ExpectedArray).at_index(1).code(), 3007);
+    }
+
+    #[test]
+    fn path() {
+        let error = Error::from(ErrorKind::ExpectedInteger).at_key("port").at_key("address").at_index(2).at_key("friends");
+        assert_eq!(error.path(), Some("friends[2].address.port"));
+        assert_eq!(error.kind(), &ErrorKind::ExpectedInteger);
+        assert_eq!(error.to_string(), "expected integer value in input at friends[2].address.port");
+        assert_eq!(Error::from(ErrorKind::Eof).at_index(0).at_index(1).path(), Some("[1][0]"));
+    }
+
+    #[test]
+    fn source() {
+        let error = crate::from_bytes::<&str>(&[0x41, 0xff]).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidUtf8);
+        let utf8 = std::str::from_utf8(&[0x41, 0xff][1..]).unwrap_err();
+        assert_eq!(error.source().unwrap().to_string(), utf8.to_string());
+        assert_eq!(error.at_key("a").source().unwrap().to_string(), utf8.to_string());
+        assert!(Error::from(ErrorKind::Eof).source().is_none());
+
+        let error = Error::from(std::io::Error::other("x"));
+        assert_eq!(error.to_string(), "io error: x");
+        assert_eq!(error.source().unwrap().to_string(), "x");
+    }
+
+    #[test]
+    fn mismatch() {
+        let error = Error::from(ErrorKind::ExpectedInteger).at_value(0x4b, Some(132));
+        assert_eq!(error.to_string(), "expected integer, found string (0x4b) at offset 132");
+        assert_eq!(error.code(), 3003);
+        assert_eq!(error.found(), Some(0x4b));
+        assert_eq!(error.offset(), Some(132));
+        let error = error.at_key("port");
+        assert_eq!(error.kind(), &ErrorKind::ExpectedInteger);
+        assert_eq!(error.found(), Some(0x4b));
+
+        let error = Error::from(ErrorKind::ExpectedNull).at_value(0x1a, None);
+        assert_eq!(error.to_string(), "expected null, found bool (0x1a)");
     }
 }
@@ -0,0 +1,356 @@
+//! Ready-made adapters for use with serde's `with`, `serialize_with` and `deserialize_with`
+//! field attributes, covering commonly needed VelocyPack specific encodings, e.g.:
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Document {
+//!     #[serde(with = "velocypack::helpers::utcdate")]
+//!     created: SystemTime,
+//!     #[serde(with = "velocypack::helpers::i128_string")]
+//!     balance: i128,
+//! }
+//! ```
+
+use std::fmt;
+
+use serde::de::{self, Visitor};
+
+/// Bytes as a base64 encoded (standard alphabet, padded) string.
+///
+/// Deserialization also accepts VelocyPack binary values as-is.
+pub mod base64 {
+    use super::*;
+    use serde::{Deserializer, Serializer};
+
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    pub(crate) fn encode(bytes: &[u8]) -> String {
+        let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+        for chunk in bytes.chunks(3) {
+            let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+            let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+        out
+    }
+
+    pub(crate) fn decode(s: &str) -> Option<Vec<u8>> {
+        let s = s.as_bytes();
+        if !s.len().is_multiple_of(4) {
+            return None;
+        }
+
+        let mut out = Vec::with_capacity(s.len() / 4 * 3);
+        for (i, chunk) in s.chunks(4).enumerate() {
+            let last = i == s.len() / 4 - 1;
+            let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+            if padding > 2 || (padding > 0 && !last) {
+                return None;
+            }
+
+            let mut n: u32 = 0;
+            for &c in &chunk[..4 - padding] {
+                let v = ALPHABET.iter().position(|&a| a == c)? as u32;
+                n = n << 6 | v;
+            }
+            n <<= 6 * padding as u32;
+            out.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+        }
+        Some(out)
+    }
+
+    pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error> where
+        T: ?Sized + AsRef<[u8]>, S: Serializer {
+        serializer.serialize_str(&encode(bytes.as_ref()))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error> where
+        D: Deserializer<'de> {
+        struct Base64Visitor;
+
+        impl<'de> Visitor<'de> for Base64Visitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a base64 encoded string")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+                decode(v).ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+                Ok(v.to_vec())
+            }
+
+            // binary values are visited as a sequence of bytes by `deserialize_any`
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(b) = seq.next_element()? {
+                    bytes.push(b);
+                }
+                Ok(bytes)
+            }
+        }
+
+        deserializer.deserialize_any(Base64Visitor)
+    }
+}
+
+/// `SystemTime` or `i64` milliseconds since the epoch as a VelocyPack UTCDate (see
+/// [`UtcDate`](crate::UtcDate)), sub-millisecond precision of `SystemTime`s is truncated. Also
+/// available as `velocypack::utcdate`.
+///
+/// With serializers other than this crate's the value is encoded as an integer.
+pub mod utcdate {
+    use std::time::SystemTime;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::UtcDate;
+
+    /// Types which can be (de)serialized as a UTCDate.
+    pub trait Timestamp: Sized {
+        fn to_utc_date(&self) -> Option<UtcDate>;
+        fn from_utc_date(date: UtcDate) -> Option<Self>;
+    }
+
+    impl Timestamp for SystemTime {
+        fn to_utc_date(&self) -> Option<UtcDate> {
+            UtcDate::from_system_time(*self)
+        }
+
+        fn from_utc_date(date: UtcDate) -> Option<Self> {
+            date.to_system_time()
+        }
+    }
+
+    impl Timestamp for i64 {
+        fn to_utc_date(&self) -> Option<UtcDate> {
+            Some(UtcDate(*self))
+        }
+
+        fn from_utc_date(date: UtcDate) -> Option<Self> {
+            Some(date.0)
+        }
+    }
+
+    pub fn serialize<T, S>(t: &T, serializer: S) -> Result<S::Ok, S::Error> where
+        T: Timestamp, S: Serializer {
+        let date = t.to_utc_date().ok_or_else(|| serde::ser::Error::custom("date out of range"))?;
+        date.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error> where
+        T: Timestamp, D: Deserializer<'de> {
+        let date = UtcDate::deserialize(deserializer)?;
+        T::from_utc_date(date).ok_or_else(|| serde::de::Error::custom("date out of range"))
+    }
+}
+
+macro_rules! int_string {
+    ($name:ident, $ty:ty) => {
+        #[doc = concat!("`", stringify!($ty), "` as a decimal string, as VelocyPack has no 128 bit ",
+                        "integer type.\n\nDeserialization also accepts integer values.")]
+        pub mod $name {
+            use super::*;
+            use std::convert::TryFrom;
+            use serde::{Deserializer, Serializer};
+
+            pub fn serialize<S>(v: &$ty, serializer: S) -> Result<S::Ok, S::Error> where
+                S: Serializer {
+                serializer.collect_str(v)
+            }
+
+            pub fn deserialize<'de, D>(deserializer: D) -> Result<$ty, D::Error> where
+                D: Deserializer<'de> {
+                struct IntStringVisitor;
+
+                impl<'de> Visitor<'de> for IntStringVisitor {
+                    type Value = $ty;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str(concat!("a string containing a ", stringify!($ty)))
+                    }
+
+                    fn visit_str<E: de::Error>(self, v: &str) -> Result<$ty, E> {
+                        v.parse().map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+                    }
+
+                    fn visit_i64<E: de::Error>(self, v: i64) -> Result<$ty, E> {
+                        <$ty>::try_from(v).map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+                    }
+
+                    fn visit_u64<E: de::Error>(self, v: u64) -> Result<$ty, E> {
+                        <$ty>::try_from(v).map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+                    }
+                }
+
+                deserializer.deserialize_any(IntStringVisitor)
+            }
+        }
+    };
+}
+
+int_string!(i128_string, i128);
+int_string!(u128_string, u128);
+
+/// Maps with non-string keys (e.g. integers), translated to and from object attribute names
+/// using the key type's `Display` and `FromStr` implementations.
+pub mod string_keys {
+    use super::*;
+    use std::marker::PhantomData;
+    use std::str::FromStr;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde::de::MapAccess;
+
+    pub fn serialize<'a, M, K, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error> where
+        &'a M: IntoIterator<Item = (&'a K, &'a V)>, K: 'a + fmt::Display, V: 'a + Serialize, S: Serializer {
+        serializer.collect_map(map.into_iter().map(|(k, v)| (k.to_string(), v)))
+    }
+
+    pub fn deserialize<'de, M, K, V, D>(deserializer: D) -> Result<M, D::Error> where
+        M: Default + Extend<(K, V)>, K: FromStr, V: Deserialize<'de>, D: Deserializer<'de> {
+        struct StringKeysVisitor<M, K, V>(PhantomData<(M, K, V)>);
+
+        impl<'de, M, K, V> Visitor<'de> for StringKeysVisitor<M, K, V> where
+            M: Default + Extend<(K, V)>, K: FromStr, V: Deserialize<'de> {
+            type Value = M;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an object")
+            }
+
+            fn visit_map<A>(self, mut access: A) -> Result<M, A::Error> where
+                A: MapAccess<'de> {
+                let mut map = M::default();
+                while let Some(key) = access.next_key::<String>()? {
+                    let k = key.parse().map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&key), &self))?;
+                    map.extend(Some((k, access.next_value()?)));
+                }
+                Ok(map)
+            }
+        }
+
+        deserializer.deserialize_map(StringKeysVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, HashMap};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use serde::{Deserialize, Serialize};
+    use crate::{from_bytes, to_bytes};
+
+    #[test]
+    fn base64_codec() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"", ""), (b"f", "Zg=="), (b"fo", "Zm8="), (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="), (b"fooba", "Zm9vYmE="), (b"foobar", "Zm9vYmFy"),
+            (&[0xff, 0xfe, 0x00], "//4A"),
+        ];
+        for (bytes, encoded) in cases {
+            assert_eq!(base64::encode(bytes), *encoded);
+            assert_eq!(base64::decode(encoded).unwrap(), *bytes);
+        }
+        assert_eq!(base64::decode("Zm9"), None);
+        assert_eq!(base64::decode("Zg==Zg=="), None);
+        assert_eq!(base64::decode("Z==="), None);
+        assert_eq!(base64::decode("Zm9*"), None);
+    }
+
+    #[test]
+    fn base64_field() {
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct Blob {
+            #[serde(with = "base64")]
+            data: Vec<u8>,
+        }
+
+        let blob = Blob { data: vec![0x66, 0x6f, 0x6f] };
+        let bytes = to_bytes(&blob).unwrap();
+        assert_eq!(bytes, &[0x0b, 0x0e, 0x01, 0x44, 0x64, 0x61, 0x74, 0x61, 0x44, 0x5a, 0x6d, 0x39, 0x76, 0x03]);
+        assert_eq!(from_bytes::<Blob>(&bytes).unwrap(), blob);
+
+        // binary values are accepted as-is
+        let bytes = [0x0b, 0x0c, 0x01, 0x44, 0x64, 0x61, 0x74, 0x61, 0xc0, 0x01, 0xaa, 0x03];
+        assert_eq!(from_bytes::<Blob>(&bytes).unwrap(), Blob { data: vec![0xaa] });
+    }
+
+    #[test]
+    fn utcdate_field() {
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct Event {
+            #[serde(with = "utcdate")]
+            at: SystemTime,
+        }
+
+        // 2020-01-01T00:00:00Z
+        let event = Event { at: UNIX_EPOCH + Duration::from_millis(1577836800000) };
+        let bytes = to_bytes(&event).unwrap();
+        assert_eq!(bytes, &[0x0b, 0x10, 0x01, 0x42, 0x61, 0x74, 0x1c, 0x00, 0xe8, 0x66, 0x5e, 0x6f, 0x01, 0x00, 0x00, 0x03]);
+        assert_eq!(from_bytes::<Event>(&bytes).unwrap(), event);
+
+        let before = Event { at: UNIX_EPOCH - Duration::from_millis(1000) };
+        assert_eq!(from_bytes::<Event>(&to_bytes(&before).unwrap()).unwrap(), before);
+
+        // plain integers are accepted too
+        let bytes = [0x0b, 0x08, 0x01, 0x42, 0x61, 0x74, 0x35, 0x03];
+        assert_eq!(from_bytes::<Event>(&bytes).unwrap(), Event { at: UNIX_EPOCH + Duration::from_millis(5) });
+
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct Millis {
+            #[serde(with = "crate::utcdate")]
+            at: i64,
+        }
+        let bytes = to_bytes(&Millis { at: 1577836800000 }).unwrap();
+        assert_eq!(bytes, &[0x0b, 0x10, 0x01, 0x42, 0x61, 0x74, 0x1c, 0x00, 0xe8, 0x66, 0x5e, 0x6f, 0x01, 0x00, 0x00, 0x03]);
+        assert_eq!(from_bytes::<Millis>(&bytes).unwrap(), Millis { at: 1577836800000 });
+    }
+
+    #[test]
+    fn i128_string_field() {
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct Account {
+            #[serde(with = "i128_string")]
+            balance: i128,
+            #[serde(with = "u128_string")]
+            id: u128,
+        }
+
+        let account = Account { balance: i128::MIN, id: u128::MAX };
+        let bytes = to_bytes(&account).unwrap();
+        assert_eq!(from_bytes::<Account>(&bytes).unwrap(), account);
+        assert!(bytes.windows(40).any(|w| w == b"-170141183460469231731687303715884105728"));
+
+        let bytes = to_bytes(&[("balance", "12"), ("id", "x")].iter().cloned().collect::<HashMap<_, _>>()).unwrap();
+        assert!(from_bytes::<Account>(&bytes).is_err());
+    }
+
+    #[test]
+    fn string_keys_field() {
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct Counts {
+            #[serde(with = "string_keys")]
+            by_id: BTreeMap<u32, String>,
+        }
+
+        let mut by_id = BTreeMap::new();
+        by_id.insert(1, "one".to_owned());
+        by_id.insert(20, "twenty".to_owned());
+        let counts = Counts { by_id };
+
+        let bytes = to_bytes(&counts).unwrap();
+        assert_eq!(from_bytes::<Counts>(&bytes).unwrap(), counts);
+
+        let raw: HashMap<String, HashMap<String, String>> = from_bytes(&bytes).unwrap();
+        assert_eq!(raw["by_id"]["20"], "twenty");
+    }
+}
@@ -0,0 +1,235 @@
+use std::fmt::{self, Display, Write};
+
+use crate::error::{Error, ErrorKind};
+use crate::number::Number;
+use crate::raw;
+use crate::value_type::ValueType;
+
+// bytes shown per line, longer parts are cut short
+const MAX_BYTES: usize = 8;
+
+/// Formats an encoded buffer as an annotated hex dump, one line per header, value, padding and
+/// index table, with the items of arrays and objects indented below them:
+///
+/// ```text
+/// 00000000  0b 0b 02                    object, byte length 11, 2 members, 1 byte offsets
+/// 00000003  41 61                         string "a"
+/// 00000005  31                            small int 1
+/// 00000006  41 62                         string "b"
+/// 00000008  1a                            bool true
+/// 00000009  03 06                       index table
+/// ```
+///
+/// Dumping stops at the first invalid value, which is shown with the error decoding it, as are
+/// arrays, objects and tagged values nested more than [`DEFAULT_MAX_DEPTH`](crate::DEFAULT_MAX_DEPTH)
+/// deep. Bytes after the first value are shown as trailing bytes.
+pub struct HexDump<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> HexDump<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        HexDump { bytes }
+    }
+
+    fn line(&self, f: &mut fmt::Formatter, at: usize, length: usize, depth: usize, description: &str) -> fmt::Result {
+        let part = &self.bytes[at..at + length];
+        let mut hex = String::new();
+        for b in part.iter().take(MAX_BYTES) {
+            write!(hex, "{:02x} ", b)?;
+        }
+        if part.len() > MAX_BYTES {
+            hex.push_str("..");
+        }
+        writeln!(f, "{:08x}  {:<26}  {:indent$}{}", at, hex.trim_end(), "", description, indent = depth * 2)
+    }
+
+    // write the value at `at`, returning whether it was valid
+    fn value(&self, f: &mut fmt::Formatter, at: usize, depth: usize) -> Result<bool, fmt::Error> {
+        let bytes = &self.bytes[at..];
+        let length = match raw::value_byte_length(bytes) {
+            Ok(length) => length,
+            Err(e) => return self.invalid(f, at, bytes.len(), depth, e),
+        };
+        let value = &bytes[..length];
+        let header = value[0];
+        let description = match header {
+            0x01..=0x14 | 0xee | 0xef if depth >= crate::DEFAULT_MAX_DEPTH => {
+                return self.invalid(f, at, length, depth, ErrorKind::DepthLimitExceeded.into());
+            },
+            0x01..=0x14 => return self.container(f, at, value, depth),
+            0xee | 0xef => {
+                let n_bytes = if header == 0xee { 1 } else { 8 };
+                let tag = raw::read_uint(&value[1..], n_bytes).unwrap_or_default();
+                self.line(f, at, 1 + n_bytes, depth, &format!("tag {}", tag))?;
+                return self.value(f, at + 1 + n_bytes, depth + 1);
+            },
+            0x1b | 0x20..=0x3f => match Number::from_bytes(value) {
+                Ok(n) => format!("{} {}", ValueType::from_header(header), n),
+                Err(e) => return self.invalid(f, at, length, depth, e),
+            },
+            0x19 => "bool false".to_owned(),
+            0x1a => "bool true".to_owned(),
+            0x1c => format!("utc-date {}", raw::read_int(&value[1..], 8).unwrap_or_default()),
+            0x40..=0xbf => match raw::read_str(value) {
+                Ok(s) => format!("string {:?}", s),
+                Err(e) => return self.invalid(f, at, length, depth, e),
+            },
+            0xc8..=0xd7 => match raw::read_bcd(value) {
+                Ok(s) => format!("bcd {}", s),
+                Err(e) => return self.invalid(f, at, length, depth, e),
+            },
+            0xc0..=0xc7 | 0xf0..=0xff => format!("{}, {} bytes", ValueType::from_header(header), length),
+            _ => ValueType::from_header(header).to_string(),
+        };
+        self.line(f, at, length, depth, &description)?;
+        Ok(true)
+    }
+
+    fn container(&self, f: &mut fmt::Formatter, at: usize, value: &[u8], depth: usize) -> Result<bool, fmt::Error> {
+        let header = value[0];
+        let object = (0x0a..=0x12).contains(&header) || header == 0x14;
+        let kind = if object { "object" } else { "array" };
+        if header == 0x01 || header == 0x0a {
+            self.line(f, at, 1, depth, &format!("empty {}", kind))?;
+            return Ok(true);
+        }
+        let container = match raw::Container::new(value) {
+            Ok(container) => container,
+            Err(e) => return self.invalid(f, at, value.len(), depth, e),
+        };
+        let len = container.len();
+        let noun = match (object, len) {
+            (true, 1) => "member",
+            (true, _) => "members",
+            (false, 1) => "item",
+            (false, _) => "items",
+        };
+
+        // width of the byte length and number of items, and whether the number of items follows
+        // the header (rather than the index table, or not being stored at all)
+        let (n_bytes, header_len, offsets) = match header {
+            0x02..=0x05 => (1 << (header - 0x02), 1 << (header - 0x02), false),
+            0x06..=0x09 | 0x0b..=0x12 => {
+                let n_bytes = if header <= 0x09 { 1 << (header - 0x06) } else { 1 << ((header - 0x0b) % 4) };
+                (n_bytes, if n_bytes == 8 { 8 } else { 2 * n_bytes }, true)
+            },
+            _ => (0, raw::read_varint(&value[1..]).map(|(_, n)| n).unwrap_or_default(), false),
+        };
+        let mut description = match header {
+            0x13 | 0x14 => format!("compact {}, byte length {}, {} {}", kind, value.len(), len, noun),
+            _ => format!("{}, byte length {}, {} {}", kind, value.len(), len, noun),
+        };
+        if header == 0x0f || header == 0x10 || header == 0x11 || header == 0x12 {
+            description.insert_str(kind.len(), " (unsorted)");
+        }
+        if offsets {
+            write!(description, ", {} byte offsets", n_bytes)?;
+        }
+        self.line(f, at, 1 + header_len, depth, &description)?;
+
+        let data = container.data();
+        let data_start = at + (data.as_ptr() as usize - value.as_ptr() as usize);
+        if data_start > at + 1 + header_len {
+            self.line(f, at + 1 + header_len, data_start - at - 1 - header_len, depth + 1, "padding")?;
+        }
+        let mut position = data_start;
+        for _ in 0..len * if object { 2 } else { 1 } {
+            let length = raw::value_byte_length(&self.bytes[position..]).unwrap_or_default();
+            if !self.value(f, position, depth + 1)? {
+                return Ok(false);
+            }
+            position += length;
+        }
+
+        let end = at + value.len();
+        match header {
+            0x06..=0x09 | 0x0b..=0x12 => {
+                let table_end = if n_bytes == 8 { end - 8 } else { end };
+                if table_end > position {
+                    self.line(f, position, table_end - position, depth, "index table")?;
+                }
+                if n_bytes == 8 {
+                    self.line(f, table_end, 8, depth, &format!("{} {}", len, noun))?;
+                }
+            },
+            0x13 | 0x14 if end > position => self.line(f, position, end - position, depth, &format!("{} {}", len, noun))?,
+            _ => {},
+        }
+        Ok(true)
+    }
+
+    fn invalid(&self, f: &mut fmt::Formatter, at: usize, length: usize, depth: usize, e: Error) -> Result<bool, fmt::Error> {
+        self.line(f, at, length.min(self.bytes.len() - at), depth, &format!("invalid: {}", e))?;
+        Ok(false)
+    }
+}
+
+impl Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.bytes.is_empty() || !self.value(f, 0, 0)? {
+            return Ok(());
+        }
+        let length = raw::value_byte_length(self.bytes).unwrap_or_default();
+        if length < self.bytes.len() {
+            self.line(f, length, self.bytes.len() - length, 0, "trailing bytes")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn object() {
+        let bytes = crate::to_bytes(&serde_json::json!({"a": [1, 2], "b": true})).unwrap();
+        let expected = "\
+00000000  0b 0e 02                    object, byte length 14, 2 members, 1 byte offsets
+00000003  41 61                         string \"a\"
+00000005  02 04                         array, byte length 4, 2 items
+00000007  31                              small int 1
+00000008  32                              small int 2
+00000009  41 62                         string \"b\"
+0000000b  1a                            bool true
+0000000c  03 09                       index table
+";
+        assert_eq!(HexDump::new(&bytes).to_string(), expected);
+    }
+
+    #[test]
+    fn compact_and_tagged() {
+        let options = crate::SerializerOptions::new().compact_containers(true);
+        let bytes = crate::to_bytes_with_options(&vec![1], &options).unwrap();
+        let expected = "\
+00000000  13 04                       compact array, byte length 4, 1 item
+00000002  31                            small int 1
+00000003  01                          1 item
+";
+        assert_eq!(HexDump::new(&bytes).to_string(), expected);
+        let dump = HexDump::new(&[0xee, 0x05, 0x1c, 1, 0, 0, 0, 0, 0, 0, 0]).to_string();
+        assert_eq!(dump.lines().nth(1).unwrap(), "00000002  1c 01 00 00 00 00 00 00 ..    utc-date 1");
+    }
+
+    #[test]
+    fn invalid_and_trailing() {
+        let dump = HexDump::new(&[0x02, 0x04, 0x31, 0x43, 0x61]).to_string();
+        assert!(dump.lines().nth(2).unwrap().ends_with("  invalid: unexpected end of input"), "{}", dump);
+        let dump = HexDump::new(&[0x31, 0x32]).to_string();
+        assert_eq!(dump.lines().nth(1).unwrap(), format!("00000001  {:<26}  trailing bytes", "32"));
+        let dump = HexDump::new(&[0xbf, 0x0a, 0, 0, 0, 0, 0, 0, 0, 0x61]).to_string();
+        assert!(dump.starts_with("00000000  bf 0a 00 00 00 00 00 00 ..  invalid"), "{}", dump);
+    }
+
+    #[test]
+    fn depth() {
+        let mut tags = [0xee, 0x01].repeat(100_000);
+        tags.push(0x31);
+        let dump = HexDump::new(&tags).to_string();
+        assert_eq!(dump.lines().count(), crate::DEFAULT_MAX_DEPTH + 1);
+        let last = dump.lines().last().unwrap();
+        assert!(last.starts_with(&format!("{:08x}  ee 01 ee 01", 2 * crate::DEFAULT_MAX_DEPTH)), "{}", last);
+        assert!(last.ends_with(&format!("{:1$}invalid: values nested too deeply", "", 2 * crate::DEFAULT_MAX_DEPTH)), "{}", last);
+    }
+}
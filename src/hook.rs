@@ -0,0 +1,95 @@
+use serde::de::{self, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::value_type::ValueType;
+
+/// Replacement for a value, returned by a decode hook registered with
+/// [`Deserializer::with_hook`](crate::Deserializer::with_hook).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rewrite {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Double(f64),
+    String(String),
+    Binary(Vec<u8>),
+}
+
+impl Rewrite {
+    pub(crate) fn visit<'de, V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Rewrite::Null => visitor.visit_unit(),
+            Rewrite::Bool(v) => visitor.visit_bool(v),
+            Rewrite::Int(v) => visitor.visit_i64(v),
+            Rewrite::UInt(v) => visitor.visit_u64(v),
+            Rewrite::Double(v) => visitor.visit_f64(v),
+            Rewrite::String(v) => visitor.visit_string(v),
+            Rewrite::Binary(v) => visitor.visit_byte_buf(v),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Rewrite {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.visit(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Called with the encoded bytes of a value, returns a replacement for it, or `None` to decode
+/// the value as normal.
+pub(crate) type Hook = Box<dyn Fn(&[u8]) -> Option<Rewrite>>;
+
+/// Decode hooks registered on a deserializer, by value type.
+#[derive(Default)]
+pub(crate) struct Hooks {
+    hooks: Vec<(ValueType, Hook)>,
+}
+
+impl Hooks {
+    pub(crate) fn add(&mut self, value_type: ValueType, hook: Hook) {
+        self.hooks.push((value_type, hook));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Run hooks registered for the type of the value in `bytes` in the order they were added,
+    /// stopping at the first one which rewrites the value.
+    pub(crate) fn run(&self, bytes: &[u8]) -> Result<Option<Rewrite>> {
+        let value_type = ValueType::from_header(*bytes.first().ok_or(ErrorKind::Eof)?);
+        for (t, hook) in &self.hooks {
+            if *t == value_type {
+                if let Some(rewrite) = hook(bytes) {
+                    return Ok(Some(rewrite));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_in_order() {
+        let mut hooks = Hooks::default();
+        hooks.add(ValueType::String, Box::new(|_| None));
+        hooks.add(ValueType::String, Box::new(|b| Some(Rewrite::UInt(b.len() as u64))));
+        hooks.add(ValueType::String, Box::new(|_| Some(Rewrite::Null)));
+        assert_eq!(hooks.run(&[0x41, 0x61]).unwrap(), Some(Rewrite::UInt(2)));
+        assert_eq!(hooks.run(&[0x31]).unwrap(), None);
+        assert_eq!(hooks.run(&[]), Err(ErrorKind::Eof.into()));
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::error::Result;
+use crate::raw;
+
+/// Location of a single document within a buffer of concatenated VelocyPack values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexEntry {
+    /// Byte offset of the document from the start of the buffer.
+    pub offset: usize,
+    /// Number of bytes the document occupies.
+    pub length: usize,
+    /// Value of the key field, if one was configured and the document contains it as a string.
+    pub key: Option<String>,
+}
+
+/// Builds an [`Index`] over a buffer of concatenated VelocyPack values.
+#[derive(Clone, Debug, Default)]
+pub struct IndexBuilder {
+    key_field: Option<String>,
+}
+
+impl IndexBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the (string) value of this attribute for each document, so that documents can be
+    /// looked up by key, e.g. `_key` for ArangoDB documents.
+    pub fn key_field(mut self, field: &str) -> Self {
+        self.key_field = Some(field.to_owned());
+        self
+    }
+
+    /// Scan `data` once, recording where each document starts and ends. Zero bytes between
+    /// documents are treated as padding and skipped.
+    ///
+    /// `data` is typically a memory-mapped file (anything which derefs to `&[u8]` works), the
+    /// index only stores offsets so can be kept around after the mapping is dropped.
+    pub fn build(&self, data: &[u8]) -> Result<Index> {
+        let mut entries = Vec::new();
+        let mut keys = HashMap::new();
+
+        let mut offset = 0;
+        while offset < data.len() {
+            if data[offset] == 0x00 {
+                offset += 1;
+                continue;
+            }
+
+            let document = &data[offset..];
+            let length = raw::value_byte_length(document)?;
+
+            let key = match self.key_field {
+                Some(ref field) => match document[0] {
+                    // documents whose key isn't a string are indexed without one
+                    0x0a..=0x12 | 0x14 => raw::object_get(&document[..length], field)?
+                        .and_then(|v| raw::read_str(v).ok())
+                        .map(str::to_owned),
+                    _ => None,
+                },
+                None => None,
+            };
+
+            if let Some(ref key) = key {
+                // first occurrence of a key wins
+                keys.entry(key.clone()).or_insert(entries.len());
+            }
+
+            entries.push(IndexEntry { offset, length, key });
+            offset += length;
+        }
+
+        Ok(Index { entries, keys })
+    }
+}
+
+/// Offsets of all documents stored in a buffer of concatenated VelocyPack values, allowing
+/// random access by document number or key without re-scanning the buffer.
+#[derive(Clone, Debug, Default)]
+pub struct Index {
+    entries: Vec<IndexEntry>,
+    keys: HashMap<String, usize>,
+}
+
+impl Index {
+    pub fn builder() -> IndexBuilder {
+        IndexBuilder::new()
+    }
+
+    /// Index `data` without recording any key field.
+    pub fn build(data: &[u8]) -> Result<Self> {
+        IndexBuilder::new().build(data)
+    }
+
+    /// Number of documents in the index.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Get the entry of the `n`th document.
+    pub fn entry(&self, n: usize) -> Option<&IndexEntry> {
+        self.entries.get(n)
+    }
+
+    /// Get the document number of the document with the given key.
+    pub fn position(&self, key: &str) -> Option<usize> {
+        self.keys.get(key).copied()
+    }
+
+    /// Get the bytes of the `n`th document from the buffer the index was built from.
+    pub fn document<'a>(&self, data: &'a [u8], n: usize) -> Option<&'a [u8]> {
+        let entry = self.entries.get(n)?;
+        data.get(entry.offset..entry.offset + entry.length)
+    }
+
+    /// Get the bytes of the document with the given key from the buffer the index was built from.
+    pub fn document_by_key<'a>(&self, data: &'a [u8], key: &str) -> Option<&'a [u8]> {
+        self.document(data, self.position(key)?)
+    }
+
+    /// Deserialize the `n`th document from the buffer the index was built from.
+    pub fn get<'a, T: Deserialize<'a>>(&self, data: &'a [u8], n: usize) -> Result<Option<T>> {
+        match self.document(data, n) {
+            Some(bytes) => crate::from_bytes(bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Deserialize the document with the given key from the buffer the index was built from.
+    pub fn get_by_key<'a, T: Deserialize<'a>>(&self, data: &'a [u8], key: &str) -> Result<Option<T>> {
+        match self.position(key) {
+            Some(n) => self.get(data, n),
+            None => Ok(None),
+        }
+    }
+}
+
+impl std::ops::Index<usize> for Index {
+    type Output = IndexEntry;
+
+    fn index(&self, n: usize) -> &IndexEntry {
+        &self.entries[n]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use serde::Serialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Doc {
+        _key: String,
+        value: u64,
+    }
+
+    fn dump(docs: &[Doc]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for doc in docs {
+            data.extend(crate::to_bytes(doc).unwrap());
+        }
+        data
+    }
+
+    #[test]
+    fn index_documents() {
+        let docs = vec![
+            Doc { _key: "a".to_owned(), value: 1 },
+            Doc { _key: "b".to_owned(), value: 1000 },
+            Doc { _key: "c".to_owned(), value: 3 },
+        ];
+        let data = dump(&docs);
+
+        let index = Index::build(&data).unwrap();
+        assert_eq!(index.len(), 3);
+        assert_eq!(index[0].offset, 0);
+        assert_eq!(index[1].offset, index[0].length);
+        assert_eq!(index[2].offset + index[2].length, data.len());
+        assert_eq!(index[1].key, None);
+
+        for (n, doc) in docs.iter().enumerate() {
+            assert_eq!(&index.get::<Doc>(&data, n).unwrap().unwrap(), doc);
+        }
+        assert_eq!(index.get::<Doc>(&data, 3).unwrap(), None);
+    }
+
+    #[test]
+    fn index_by_key() {
+        let docs = vec![
+            Doc { _key: "x".to_owned(), value: 1 },
+            Doc { _key: "y".to_owned(), value: 2 },
+        ];
+        let data = dump(&docs);
+
+        let index = Index::builder().key_field("_key").build(&data).unwrap();
+        assert_eq!(index[0].key, Some("x".to_owned()));
+        assert_eq!(index.position("y"), Some(1));
+        assert_eq!(index.position("z"), None);
+        assert_eq!(index.get_by_key::<Doc>(&data, "y").unwrap(), Some(Doc { _key: "y".to_owned(), value: 2 }));
+        assert_eq!(index.document_by_key(&data, "x"), index.document(&data, 0));
+
+        // a document with a numeric key is still indexed, without one
+        let mut data = dump(&docs);
+        data.extend(crate::to_bytes(&serde_json::json!({"_key": 3, "value": 3})).unwrap());
+        let index = Index::builder().key_field("_key").build(&data).unwrap();
+        assert_eq!(index.len(), 3);
+        assert_eq!(index[2].key, None);
+        assert_eq!(index.position("y"), Some(1));
+    }
+
+    #[test]
+    fn index_mixed_values() {
+        // non-object values and padding between values
+        let mut data = vec![0x31, 0x00, 0x00];
+        data.extend(crate::to_bytes(&"foo").unwrap());
+        let mut m = HashMap::new();
+        m.insert("name", "bar");
+        data.extend(crate::to_bytes(&m).unwrap());
+
+        let index = Index::builder().key_field("name").build(&data).unwrap();
+        assert_eq!(index.entries().iter().map(|e| e.offset).collect::<Vec<_>>(), vec![0, 3, 7]);
+        assert_eq!(index[1].key, None);
+        assert_eq!(index[2].key, Some("bar".to_owned()));
+        assert_eq!(index.get::<String>(&data, 1).unwrap(), Some("foo".to_owned()));
+    }
+
+    #[test]
+    fn index_truncated() {
+        let mut data = dump(&[Doc { _key: "a".to_owned(), value: 1 }]);
+        data.pop();
+        assert_eq!(Index::build(&data).unwrap_err(), ErrorKind::Eof.into());
+        assert!(Index::build(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,216 @@
+use std::io::{Read, Write};
+
+use serde::Serialize;
+
+use crate::error::{ErrorKind, Result};
+use crate::raw;
+
+/// Number of bytes occupied by the first value in `bytes`, as given by its header, without
+/// decoding or checking it. Only the header and byte length need to be present, so this can be
+/// used to split a partially received buffer into messages: if the result is more than
+/// `bytes.len()`, the value is incomplete.
+///
+/// Returns `ErrorKind::Eof` if `bytes` is too short to tell the length.
+pub fn value_byte_length(bytes: &[u8]) -> Result<usize> {
+    match raw::encoded_length(bytes)? {
+        0 => Err(ErrorKind::Invalid("byte length of zero").into()),
+        length => Ok(length),
+    }
+}
+
+/// Skip over the first value in `bytes` using its header and byte length, without decoding it or
+/// anything nested in it, and return the bytes following it. Useful for stepping through values
+/// packed back to back, e.g. to find the n-th one, much faster than decoding each in turn.
+///
+/// Returns `ErrorKind::Eof` if `bytes` ends before the value does.
+pub fn skip_value(bytes: &[u8]) -> Result<&[u8]> {
+    let length = value_byte_length(bytes)?;
+    bytes.get(length..).ok_or_else(|| ErrorKind::Eof.into())
+}
+
+/// Read exactly one value from `reader`, using its header and byte length to read no further, so
+/// that the reader is left at the start of whatever follows (e.g. the next value of a stream).
+/// Returns `None` if the reader is already at its end.
+///
+/// Only the header and byte length are read one byte at a time, so `reader` doesn't need to be
+/// buffered.
+pub fn read_value<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut bytes = Vec::new();
+    loop {
+        match value_byte_length(&bytes) {
+            Ok(length) if length > bytes.len() => {
+                // grow the buffer only as bytes arrive, rather than trusting the byte length
+                let remaining = (length - bytes.len()) as u64;
+                reader.by_ref().take(remaining).read_to_end(&mut bytes)?;
+                if bytes.len() != length {
+                    return Err(ErrorKind::Eof.into());
+                }
+                return Ok(Some(bytes));
+            },
+            Ok(length) if length == bytes.len() => return Ok(Some(bytes)),
+            Ok(_) => return Err(ErrorKind::Eof.into()),
+            Err(ref e) if e.kind() == &ErrorKind::Eof => {
+                let mut b = [0x00];
+                if reader.read(&mut b)? == 0 {
+                    return if bytes.is_empty() { Ok(None) } else { Err(ErrorKind::Eof.into()) };
+                }
+                bytes.push(b[0]);
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Serialize `value` and write it to `writer`.
+pub fn write_value<W: Write, T: ?Sized + Serialize>(writer: &mut W, value: &T) -> Result<()> {
+    writer.write_all(&crate::to_bytes_with_options(value, &crate::SerializerOptions::default())?)?;
+    Ok(())
+}
+
+/// Splits values out of bytes pushed in as they arrive, e.g. from a nonblocking socket, without
+/// needing a whole message up front:
+///
+/// ```
+/// let mut parser = velocypack::ValueParser::new();
+/// assert_eq!(parser.feed(&[0x02, 0x04])?, None);
+/// assert_eq!(parser.feed(&[0x31, 0x32, 0x41])?, Some(4));
+/// assert_eq!(parser.next_value()?, Some(&[0x02, 0x04, 0x31, 0x32][..]));
+/// assert_eq!(parser.next_value()?, None);
+/// assert_eq!(parser.buffered(), &[0x41]);
+/// # Ok::<(), velocypack::Error>(())
+/// ```
+///
+/// Values are only split out using their headers, not checked. After an error the bytes
+/// buffered can't be split any further.
+#[derive(Debug, Default)]
+pub struct ValueParser {
+    buffer: Vec<u8>,
+    // bytes at the start of `buffer` already returned as values
+    consumed: usize,
+}
+
+impl ValueParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add bytes to the end of those buffered, returning the byte length of the next value if it
+    /// is now complete (see [`complete`](ValueParser::complete)).
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Option<usize>> {
+        if self.consumed > 0 {
+            self.buffer.drain(..self.consumed);
+            self.consumed = 0;
+        }
+        self.buffer.extend_from_slice(bytes);
+        self.complete()
+    }
+
+    /// The byte length of the next value if all of it has been buffered, or `None` if more bytes
+    /// are needed.
+    pub fn complete(&self) -> Result<Option<usize>> {
+        match value_byte_length(self.buffered()) {
+            Ok(length) if length <= self.buffered().len() => Ok(Some(length)),
+            Ok(_) => Ok(None),
+            Err(ref e) if e.kind() == &ErrorKind::Eof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Take the next value if it is complete.
+    pub fn next_value(&mut self) -> Result<Option<&[u8]>> {
+        match self.complete()? {
+            Some(length) => {
+                let start = self.consumed;
+                self.consumed += length;
+                Ok(Some(&self.buffer[start..self.consumed]))
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Bytes buffered which haven't been taken as values yet.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer[self.consumed..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_length() {
+        let bytes = crate::to_bytes(&vec!["a"; 300]).unwrap();
+        assert_eq!(value_byte_length(&bytes[..3]), Ok(bytes.len()));
+        assert_eq!(value_byte_length(&bytes[..2]), Err(ErrorKind::Eof.into()));
+        assert_eq!(value_byte_length(&[0x31, 0x32]), Ok(1));
+        assert_eq!(value_byte_length(&[0xee, 0x01, 0xbf, 0x10]), Err(ErrorKind::Eof.into()));
+        assert_eq!(value_byte_length(&[0xee, 0x01, 0x45]), Ok(8));
+        assert_eq!(value_byte_length(&[]), Err(ErrorKind::Eof.into()));
+        assert!(matches!(value_byte_length(&[0x02, 0x00]).unwrap_err().kind(), ErrorKind::Invalid(_)));
+    }
+
+    #[test]
+    fn skip() {
+        let mut bytes = crate::to_bytes(&vec![vec!["a"; 100]; 10]).unwrap();
+        bytes.extend_from_slice(&[0x31, 0xee, 0x01, 0x41, 0x61, 0x32]);
+        let rest = skip_value(&bytes).unwrap();
+        assert_eq!(rest, &[0x31, 0xee, 0x01, 0x41, 0x61, 0x32]);
+        assert_eq!(skip_value(&rest[1..]), Ok(&[0x32][..]));
+        assert_eq!(skip_value(&[0x32]), Ok(&[][..]));
+        assert_eq!(skip_value(&[0x02, 0x04, 0x31]), Err(ErrorKind::Eof.into()));
+        assert_eq!(skip_value(&[]), Err(ErrorKind::Eof.into()));
+    }
+
+    #[test]
+    fn read_write() {
+        let mut stream = Vec::new();
+        write_value(&mut stream, &vec!["a", "b"]).unwrap();
+        write_value(&mut stream, &1).unwrap();
+        stream.extend_from_slice(&[0xee, 0x01, 0x41, 0x61]);
+
+        let mut reader = &stream[..];
+        assert_eq!(read_value(&mut reader).unwrap().unwrap(), crate::to_bytes(&vec!["a", "b"]).unwrap());
+        assert_eq!(read_value(&mut reader).unwrap().unwrap(), &[0x31]);
+        assert_eq!(read_value(&mut reader).unwrap().unwrap(), &[0xee, 0x01, 0x41, 0x61]);
+        assert_eq!(read_value(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn parser() {
+        let mut stream = crate::to_bytes(&vec!["a"; 300]).unwrap();
+        stream.extend_from_slice(&[0x31, 0x32, 0xee, 0x01, 0x41, 0x61]);
+
+        let mut parser = ValueParser::new();
+        let mut values = Vec::new();
+        for chunk in stream.chunks(7) {
+            parser.feed(chunk).unwrap();
+            while let Some(value) = parser.next_value().unwrap() {
+                values.push(value.to_vec());
+            }
+        }
+        assert_eq!(values, vec![crate::to_bytes(&vec!["a"; 300]).unwrap(), vec![0x31], vec![0x32], vec![0xee, 0x01, 0x41, 0x61]]);
+        assert!(parser.buffered().is_empty());
+
+        let mut parser = ValueParser::new();
+        assert_eq!(parser.complete(), Ok(None));
+        assert_eq!(parser.feed(&[0x31, 0x15]), Ok(Some(1)));
+        parser.next_value().unwrap();
+        assert_eq!(parser.next_value(), Err(ErrorKind::Unimplemented(0x15).into()));
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(read_value(&mut &[0x02, 0x04, 0x31][..]), Err(ErrorKind::Eof.into()));
+        assert_eq!(read_value(&mut &[0xbf, 0x01][..]), Err(ErrorKind::Eof.into()));
+        assert_eq!(read_value(&mut &[0x15][..]), Err(ErrorKind::Unimplemented(0x15).into()));
+        // a huge byte length fails once the input runs out, without allocating it up front
+        assert_eq!(read_value(&mut &[0xbf, 0, 0, 0, 0, 0, 0, 0, 0x7f, 0x61][..]), Err(ErrorKind::Eof.into()));
+        assert_eq!(read_value(&mut &[0x05, 0, 0, 0, 0, 0, 0, 0xff, 0x0f, 0x31][..]), Err(ErrorKind::Eof.into()));
+
+        // nothing past the value is read
+        let mut reader = &[0x0b, 0x07, 0x01, 0x41, 0x61, 0x31, 0x03, 0xff][..];
+        read_value(&mut reader).unwrap();
+        assert_eq!(reader, &[0xff]);
+    }
+}
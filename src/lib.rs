@@ -1,14 +1,89 @@
-#[macro_use] extern crate bitvec;
-
+#[cfg(feature = "arangodump")]
+pub mod arangodump;
+#[cfg(feature = "tokio")]
+pub mod async_io;
+#[cfg(feature = "bytes")]
+mod buf;
+mod builder;
+mod canonical;
+mod column;
+mod compare;
+mod custom;
 mod de;
+mod dictionary;
+mod dumper;
 mod error;
+pub mod helpers;
+mod hexdump;
+mod hook;
+mod index;
+mod io;
+mod number;
+mod raw;
+mod raw_vpack;
 mod ser;
+mod shared;
+mod size;
+mod slice;
+mod tagged;
+mod translator;
+mod utc_date;
+mod validate;
+mod value;
+mod value_type;
+pub mod velocystream;
+
+#[cfg(feature = "bytes")]
+pub use buf::{from_buf, to_buf, to_buf_with_options};
+pub use builder::Builder;
+pub use canonical::canonicalize;
+pub use column::column;
+pub use compare::compare;
+pub use custom::{resolve_ids, CustomTypes};
+pub use de::{from_array_bytes, from_bytes, from_bytes_at, from_bytes_owned, from_bytes_with_options, first_from_bytes, iter_from_bytes, Deserializer, DeserializerOptions, DuplicateKeys, UnknownFields, DEFAULT_MAX_DEPTH};
+pub use dictionary::StringDictionary;
+pub use dumper::{to_json_bytes, to_json_bytes_with_options, DumperOptions, Unsupported};
+pub use error::{Error, ErrorCategory, ErrorKind, Result};
+pub use hexdump::HexDump;
+pub use hook::Rewrite;
+pub use index::{Index, IndexBuilder, IndexEntry};
+pub use io::{read_value, skip_value, value_byte_length, write_value, ValueParser};
+pub use number::{Number, NumberEncoding};
+pub use raw_vpack::{RawVPack, RawVPackBuf};
+#[cfg(feature = "rayon")]
+pub use ser::to_bytes_par;
+pub use ser::{to_bytes, to_bytes_aligned, to_bytes_from_iter, to_bytes_in, to_bytes_in_with_options, to_bytes_with_field_sizes, to_bytes_with_options, EmptyEncoding, FieldSizes, IndexWidth, NonFinite, NonStringKeys, Serializer, SerializerOptions, VariantEncoding};
+#[cfg(feature = "bytes")]
+pub use shared::from_bytes_shared;
+pub use shared::{from_shared, SharedBytes, SharedStr};
+pub use size::{serialized_size, serialized_size_with_options};
+pub use slice::Slice;
+pub use tagged::Tagged;
+pub use translator::AttributeTranslator;
+pub use utc_date::UtcDate;
+pub use validate::validate;
+pub use value::Value;
+pub use value_type::ValueType;
 
-pub use de::{from_bytes, first_from_bytes, Deserializer};
-pub use error::{Error, Result};
-pub use ser::{to_bytes, Serializer};
+pub use helpers::utcdate;
 
 pub(crate) const U8_SIZE: usize = std::mem::size_of::<u8>();
-pub(crate) const U16_SIZE: usize = std::mem::size_of::<u16>();
-pub(crate) const U32_SIZE: usize = std::mem::size_of::<u32>();
 pub(crate) const U64_SIZE: usize = std::mem::size_of::<u64>();
+
+// newtype struct name used to mark values which should be encoded as a VelocyPack UTCDate
+pub(crate) const UTC_DATE_TOKEN: &str = "$velocypack::private::UtcDate";
+
+// newtype struct name used to mark already encoded values, which are passed through as-is
+pub(crate) const RAW_TOKEN: &str = "$velocypack::private::RawVPack";
+
+// newtype struct name used by `Value` to have binary values deserialized as bytes, and other
+// values without a serde equivalent as maps with the single key `RAW_TOKEN` and their encoded
+// bytes as value
+pub(crate) const VALUE_TOKEN: &str = "$velocypack::private::Value";
+
+// newtype struct names (around unit) used to serialize min and max key values
+pub(crate) const MIN_KEY_TOKEN: &str = "$velocypack::private::MinKey";
+pub(crate) const MAX_KEY_TOKEN: &str = "$velocypack::private::MaxKey";
+
+// newtype struct name (around a `(tag, value)` tuple) used to serialize tagged values
+pub(crate) const TAGGED_TOKEN: &str = "$velocypack::private::Tagged";
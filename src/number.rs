@@ -0,0 +1,295 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+
+use crate::error::{ErrorKind, Result};
+use crate::raw;
+use crate::value_type::ValueType;
+
+/// How a [`Number`] was encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NumberEncoding {
+    /// 0x30-0x3f, a single byte integer from -6 to 9.
+    SmallInt,
+    /// Signed integer with the given number of bytes (1 to 8).
+    Int(usize),
+    /// Unsigned integer with the given number of bytes (1 to 8).
+    UInt(usize),
+    Double,
+}
+
+/// A number held by a [`Value`](crate::Value), keeping both its exact value (including `u64`s
+/// above `i64::MAX`) and, if it was decoded, how it was encoded, so that serializing it again
+/// reproduces the original bytes.
+///
+/// Numbers created from Rust primitives have no encoding and are serialized as usual. Two numbers
+/// are equal if they have the same value, whatever their encodings; integers never equal doubles.
+#[derive(Clone, Copy, Debug)]
+pub struct Number {
+    n: N,
+    encoding: Option<NumberEncoding>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum N {
+    Int(i64),
+    UInt(u64),
+    Double(f64),
+}
+
+impl Number {
+    /// Decode the integer or double at the start of `bytes`.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let header = *bytes.first().ok_or(ErrorKind::Eof)?;
+        let (n, encoding) = match header {
+            0x1b => (N::Double(f64::from_bits(raw::read_uint(&bytes[1..], 8)?)), NumberEncoding::Double),
+            0x20..=0x27 => {
+                let n_bytes = (header - 0x1f) as usize;
+                (N::Int(raw::read_int(&bytes[1..], n_bytes)?), NumberEncoding::Int(n_bytes))
+            },
+            0x28..=0x2f => {
+                let n_bytes = (header - 0x27) as usize;
+                (N::UInt(raw::read_uint(&bytes[1..], n_bytes)?), NumberEncoding::UInt(n_bytes))
+            },
+            0x30..=0x39 => (N::UInt((header - 0x30) as u64), NumberEncoding::SmallInt),
+            0x3a..=0x3f => (N::Int(header as i64 - 0x40), NumberEncoding::SmallInt),
+            _ => return Err(ErrorKind::ExpectedDouble.into()),
+        };
+        Ok(Number { n, encoding: Some(encoding) })
+    }
+
+    /// The encoding the number was decoded from, `None` for numbers created from primitives.
+    pub fn encoding(&self) -> Option<NumberEncoding> {
+        self.encoding
+    }
+
+    /// The same number without its original encoding, so that it's serialized in the shortest
+    /// form.
+    pub fn without_encoding(self) -> Self {
+        Number { encoding: None, ..self }
+    }
+
+    /// The type the number is, or would be, encoded as.
+    pub fn value_type(&self) -> ValueType {
+        match (self.encoding, self.n) {
+            (_, N::Double(_)) => ValueType::Double,
+            (Some(NumberEncoding::SmallInt), _) => ValueType::SmallInt,
+            (Some(NumberEncoding::Int(_)), _) => ValueType::Int,
+            (Some(NumberEncoding::UInt(_)), _) => ValueType::UInt,
+            _ if matches!(self.as_i64(), Some(-6..=9)) => ValueType::SmallInt,
+            (_, N::Int(v)) if v < 0 => ValueType::Int,
+            _ => ValueType::UInt,
+        }
+    }
+
+    pub fn is_i64(&self) -> bool {
+        self.as_i64().is_some()
+    }
+
+    pub fn is_u64(&self) -> bool {
+        self.as_u64().is_some()
+    }
+
+    pub fn is_f64(&self) -> bool {
+        matches!(self.n, N::Double(_))
+    }
+
+    /// Get an integer as an `i64`, if it fits.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.n {
+            N::Int(v) => Some(v),
+            N::UInt(v) => i64::try_from(v).ok(),
+            N::Double(_) => None,
+        }
+    }
+
+    /// Get an integer as a `u64`, if it isn't negative.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.n {
+            N::Int(v) => u64::try_from(v).ok(),
+            N::UInt(v) => Some(v),
+            N::Double(_) => None,
+        }
+    }
+
+    /// Get the number as an `f64`, possibly losing precision.
+    pub fn as_f64(&self) -> f64 {
+        match self.n {
+            N::Int(v) => v as f64,
+            N::UInt(v) => v as f64,
+            N::Double(v) => v,
+        }
+    }
+
+    // write the number with `encoding`, returning false if it can't hold the value
+    pub(crate) fn write_as(&self, encoding: NumberEncoding, output: &mut Vec<u8>) -> bool {
+        match encoding {
+            NumberEncoding::SmallInt => match self.as_i64() {
+                Some(v @ 0..=9) => output.push(0x30 + v as u8),
+                Some(v @ -6..=-1) => output.push((0x40 + v) as u8),
+                _ => return false,
+            },
+            NumberEncoding::Int(n_bytes @ 1..=8) => {
+                let v = match self.as_i64() {
+                    Some(v) => v,
+                    None => return false,
+                };
+                // the bytes left out must only repeat the sign bit
+                let rest = v >> (n_bytes * 8 - 1);
+                if rest != 0 && rest != -1 {
+                    return false;
+                }
+                output.push(0x1f + n_bytes as u8);
+                output.extend_from_slice(&v.to_le_bytes()[..n_bytes]);
+            },
+            NumberEncoding::UInt(n_bytes @ 1..=8) => {
+                let v = match self.as_u64() {
+                    Some(v) => v,
+                    None => return false,
+                };
+                if n_bytes < 8 && v >> (n_bytes * 8) != 0 {
+                    return false;
+                }
+                output.push(0x27 + n_bytes as u8);
+                output.extend_from_slice(&v.to_le_bytes()[..n_bytes]);
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        match (self.n, other.n) {
+            (N::Double(a), N::Double(b)) => a == b,
+            (N::Double(_), _) | (_, N::Double(_)) => false,
+            (N::Int(a), N::Int(b)) => a == b,
+            (N::UInt(a), N::UInt(b)) => a == b,
+            (N::Int(a), N::UInt(b)) | (N::UInt(b), N::Int(a)) => u64::try_from(a) == Ok(b),
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.n {
+            N::Int(v) => write!(f, "{}", v),
+            N::UInt(v) => write!(f, "{}", v),
+            N::Double(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+macro_rules! from_primitive {
+    ($variant:ident, $ty:ty, $($primitive:ty)*) => {
+        $(
+            impl From<$primitive> for Number {
+                fn from(v: $primitive) -> Self {
+                    Number { n: N::$variant(v as $ty), encoding: None }
+                }
+            }
+        )*
+    };
+}
+
+from_primitive!(Int, i64, i8 i16 i32 i64 isize);
+from_primitive!(UInt, u64, u8 u16 u32 u64 usize);
+from_primitive!(Double, f64, f32 f64);
+
+// newtype struct names used to have this crate's serializer write a number with an encoding
+const SMALL_INT_TOKEN: &str = "$velocypack::private::Number::SmallInt";
+const INT_TOKENS: [&str; 8] = [
+    "$velocypack::private::Number::Int1", "$velocypack::private::Number::Int2",
+    "$velocypack::private::Number::Int3", "$velocypack::private::Number::Int4",
+    "$velocypack::private::Number::Int5", "$velocypack::private::Number::Int6",
+    "$velocypack::private::Number::Int7", "$velocypack::private::Number::Int8",
+];
+const UINT_TOKENS: [&str; 8] = [
+    "$velocypack::private::Number::UInt1", "$velocypack::private::Number::UInt2",
+    "$velocypack::private::Number::UInt3", "$velocypack::private::Number::UInt4",
+    "$velocypack::private::Number::UInt5", "$velocypack::private::Number::UInt6",
+    "$velocypack::private::Number::UInt7", "$velocypack::private::Number::UInt8",
+];
+
+/// Get the encoding requested by a newtype struct name, if it's a number token.
+pub(crate) fn token_encoding(name: &str) -> Option<NumberEncoding> {
+    if name == SMALL_INT_TOKEN {
+        return Some(NumberEncoding::SmallInt);
+    }
+    if let Some(i) = INT_TOKENS.iter().position(|t| *t == name) {
+        return Some(NumberEncoding::Int(i + 1));
+    }
+    UINT_TOKENS.iter().position(|t| *t == name).map(|i| NumberEncoding::UInt(i + 1))
+}
+
+struct Plain(N);
+
+impl Serialize for Plain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self.0 {
+            N::Int(v) => serializer.serialize_i64(v),
+            N::UInt(v) => serializer.serialize_u64(v),
+            N::Double(v) => serializer.serialize_f64(v),
+        }
+    }
+}
+
+impl Serialize for Number {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let token = match self.encoding {
+            Some(NumberEncoding::SmallInt) => SMALL_INT_TOKEN,
+            Some(NumberEncoding::Int(n @ 1..=8)) => INT_TOKENS[n - 1],
+            Some(NumberEncoding::UInt(n @ 1..=8)) => UINT_TOKENS[n - 1],
+            _ => return Plain(self.n).serialize(serializer),
+        };
+        serializer.serialize_newtype_struct(token, &Plain(self.n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let n = Number::from_bytes(&[0x2f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap();
+        assert_eq!(n.as_u64(), Some(u64::MAX));
+        assert_eq!(n.as_i64(), None);
+        assert_eq!(n.encoding(), Some(NumberEncoding::UInt(8)));
+        assert_eq!(Number::from_bytes(&[0x3a]).unwrap().as_i64(), Some(-6));
+        assert_eq!(Number::from_bytes(&[0x21, 0x00, 0x80]).unwrap().encoding(), Some(NumberEncoding::Int(2)));
+        assert!(Number::from_bytes(&[0x1b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8, 0x3f]).unwrap().is_f64());
+        assert!(matches!(Number::from_bytes(&[0x18]).unwrap_err().kind(), ErrorKind::ExpectedDouble));
+        assert!(matches!(Number::from_bytes(&[0x29, 0x01]).unwrap_err().kind(), ErrorKind::Eof));
+    }
+
+    #[test]
+    fn eq() {
+        assert_eq!(Number::from(1_u8), Number::from(1_i64));
+        assert_eq!(Number::from_bytes(&[0x31]).unwrap(), Number::from(1));
+        assert_ne!(Number::from(-1), Number::from(u64::MAX));
+        assert_ne!(Number::from(1), Number::from(1.0));
+    }
+
+    #[test]
+    fn round_trip() {
+        for bytes in [
+            &[0x27, 0x05, 0, 0, 0, 0, 0, 0, 0][..],
+            &[0x20, 0x05],
+            &[0x22, 0xfe, 0xff, 0xff],
+            &[0x28, 0x00],
+            &[0x2b, 0xe8, 0x03, 0x00, 0x00],
+            &[0x2f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            &[0x35],
+            &[0x3d],
+        ] {
+            let n = Number::from_bytes(bytes).unwrap();
+            assert_eq!(crate::to_bytes(&n).unwrap(), bytes);
+            assert_eq!(crate::from_bytes::<i128>(&crate::to_bytes(&n.without_encoding()).unwrap()).unwrap(), crate::from_bytes::<i128>(bytes).unwrap());
+        }
+        assert_eq!(crate::to_bytes(&Number::from(5_u64)).unwrap(), &[0x35]);
+        assert_eq!(serde_json::to_string(&Number::from_bytes(&[0x2b, 0xe8, 0x03, 0x00, 0x00]).unwrap()).unwrap(), "1000");
+    }
+}
@@ -0,0 +1,736 @@
+// Helpers for working directly on encoded VelocyPack bytes, without decoding values through
+// serde. Used wherever only the layout of a value is needed (lengths, members, keys).
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+use crate::error::{Error, ErrorKind, Result};
+
+/// Read a little endian unsigned integer of `n_bytes` (1 to 8) bytes from the start of `bytes`.
+pub(crate) fn read_uint(bytes: &[u8], n_bytes: usize) -> Result<u64> {
+    let b = bytes.get(..n_bytes).ok_or(ErrorKind::Eof)?;
+    let mut le_bytes: [u8; 8] = [0; 8];
+    le_bytes[..n_bytes].copy_from_slice(b);
+    Ok(u64::from_le_bytes(le_bytes))
+}
+
+/// Read an `n_bytes` long little endian two's complement integer from the start of `bytes`.
+pub(crate) fn read_int(bytes: &[u8], n_bytes: usize) -> Result<i64> {
+    let v = read_uint(bytes, n_bytes)?;
+    // shift the sign bit up to bit 63 and back down again to sign extend
+    let shift = 64 - 8 * n_bytes as u32;
+    Ok(((v << shift) as i64) >> shift)
+}
+
+/// Read a variable length integer, as used by compact arrays and objects, from the start of
+/// `bytes`. Returns the value and the number of bytes it occupied.
+pub(crate) fn read_varint(bytes: &[u8]) -> Result<(u64, usize)> {
+    varint_from(bytes.iter())
+}
+
+/// Read a variable length integer which is stored backwards, ending at the last byte of `bytes`
+/// (the number of items in compact arrays and objects). Returns the value and the number of
+/// bytes it occupied.
+pub(crate) fn read_varint_reversed(bytes: &[u8]) -> Result<(u64, usize)> {
+    varint_from(bytes.iter().rev())
+}
+
+// 7 bits per byte, least significant first, until a byte without the high bit set. At most 10
+// bytes are read, enough for any u64.
+fn varint_from<'a, I: Iterator<Item = &'a u8>>(bytes: I) -> Result<(u64, usize)> {
+    let mut value: u64 = 0;
+    for (i, b) in bytes.take(10).enumerate() {
+        value |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(ErrorKind::Eof.into())
+}
+
+/// Append `v` as a variable length integer, 7 bits per byte with the high bit set on all but the
+/// last byte.
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push((v & 0x7f) as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+/// Number of bytes `write_varint` takes for `v`.
+pub(crate) fn varint_size(mut v: u64) -> usize {
+    let mut n = 1;
+    while v >= 0x80 {
+        v >>= 7;
+        n += 1;
+    }
+    n
+}
+
+/// Byte length of a compact array or object holding `items_size` bytes of items: the header, the
+/// byte length itself (whose size depends on its value), the items and the number of items.
+pub(crate) fn compact_byte_length(items_size: usize, n_items: usize) -> usize {
+    let data_size = 1 + items_size + varint_size(n_items as u64);
+    let mut byte_length = data_size + 1;
+    while data_size + varint_size(byte_length as u64) != byte_length {
+        byte_length = data_size + varint_size(byte_length as u64);
+    }
+    byte_length
+}
+
+pub(crate) fn to_usize(v: u64) -> Result<usize> {
+    usize::try_from(v).map_err(|_| ErrorKind::NumberTooLarge.into())
+}
+
+/// Number of bytes occupied by the value at the start of `bytes`, as given by its header.
+///
+/// Returns `ErrorKind::Eof` if `bytes` is shorter than the value claims to be.
+pub(crate) fn value_byte_length(bytes: &[u8]) -> Result<usize> {
+    let length = encoded_length(bytes)?;
+    if length == 0 || length > bytes.len() {
+        return Err(ErrorKind::Eof.into());
+    }
+    Ok(length)
+}
+
+/// Number of bytes the value at the start of `bytes` claims to occupy, which only needs its
+/// header and byte length (or, for tagged values, the tag and the tagged value's header) to be
+/// complete.
+pub(crate) fn encoded_length(bytes: &[u8]) -> Result<usize> {
+    let header = *bytes.first().ok_or(ErrorKind::Eof)?;
+    let length = match header {
+        0x01 | 0x0a | 0x17..=0x1a | 0x1e | 0x1f | 0x30..=0x3f => 1,
+        0x02..=0x09 => {
+            let n_bytes = 1 << ((header - 0x02) % 4);
+            to_usize(read_uint(&bytes[1..], n_bytes)?)?
+        },
+        0x0b..=0x12 => {
+            let n_bytes = 1 << ((header - 0x0b) % 4);
+            to_usize(read_uint(&bytes[1..], n_bytes)?)?
+        },
+        0x13 | 0x14 => to_usize(read_varint(&bytes[1..])?.0)?,
+        0x1b | 0x1c => 1 + 8,
+        0x1d => 1 + std::mem::size_of::<usize>(),
+        0x20..=0x27 => 1 + (header - 0x1f) as usize,
+        0x28..=0x2f => 1 + (header - 0x27) as usize,
+        0x40..=0xbe => 1 + (header - 0x40) as usize,
+        0xbf => to_usize(read_uint(&bytes[1..], 8)?)?.checked_add(1 + 8).ok_or(ErrorKind::NumberTooLarge)?,
+        0xc0..=0xc7 => {
+            let n_bytes = (header - 0xbf) as usize;
+            to_usize(read_uint(&bytes[1..], n_bytes)?)?.checked_add(1 + n_bytes).ok_or(ErrorKind::NumberTooLarge)?
+        },
+        0xc8..=0xd7 => {
+            // length of mantissa, 4 byte exponent, mantissa
+            let n_bytes = ((header - 0xc8) % 8 + 1) as usize;
+            to_usize(read_uint(&bytes[1..], n_bytes)?)?.checked_add(1 + n_bytes + 4).ok_or(ErrorKind::NumberTooLarge)?
+        },
+        0xee | 0xef => {
+            // tag numbers followed by the tagged value, looping over nested tags rather than
+            // recursing so that deeply nested ones can't overflow the stack
+            let mut tags_length = 0;
+            let mut inner = bytes;
+            while let Some(&tag @ (0xee | 0xef)) = inner.first() {
+                tags_length += if tag == 0xee { 1 + 1 } else { 1 + 8 };
+                inner = bytes.get(tags_length..).ok_or(ErrorKind::Eof)?;
+            }
+            encoded_length(inner)?.checked_add(tags_length).ok_or(ErrorKind::NumberTooLarge)?
+        },
+        0xf0..=0xf3 => 1 + (1 << (header - 0xf0)),
+        0xf4..=0xff => {
+            let n_bytes = 1 << ((header - 0xf4) / 3);
+            to_usize(read_uint(&bytes[1..], n_bytes)?)?.checked_add(1 + n_bytes).ok_or(ErrorKind::NumberTooLarge)?
+        },
+        b => return Err(ErrorKind::Unimplemented(b).into()),
+    };
+    Ok(length)
+}
+
+/// Split off the value at the start of `bytes`, returning it along with the remaining bytes.
+pub(crate) fn split_value(bytes: &[u8]) -> Result<(&[u8], &[u8])> {
+    let length = value_byte_length(bytes)?;
+    Ok(bytes.split_at(length))
+}
+
+/// Decode the string value at the start of `bytes`.
+pub(crate) fn read_str(bytes: &[u8]) -> Result<&str> {
+    let (start, length): (usize, usize) = match *bytes.first().ok_or(ErrorKind::Eof)? {
+        b @ 0x40..=0xbe => (1, (b - 0x40) as usize),
+        0xbf => (1 + 8, to_usize(read_uint(&bytes[1..], 8)?)?),
+        _ => return Err(ErrorKind::ExpectedString.into()),
+    };
+    let end = start.checked_add(length).ok_or(ErrorKind::NumberTooLarge)?;
+    let b = bytes.get(start..end).ok_or(ErrorKind::Eof)?;
+    std::str::from_utf8(b).map_err(Error::from)
+}
+
+/// Decode the object key at the start of `bytes`: a string, or an unsigned integer id (as used
+/// for translated attribute names), which is returned in decimal.
+pub(crate) fn read_key(bytes: &[u8]) -> Result<Cow<'_, str>> {
+    match *bytes.first().ok_or(ErrorKind::Eof)? {
+        0x28..=0x2f => Ok(Cow::Owned(read_uint(&bytes[1..], (bytes[0] - 0x27) as usize)?.to_string())),
+        b @ 0x30..=0x39 => Ok(Cow::Owned((b - 0x30).to_string())),
+        _ => read_str(bytes).map(Cow::Borrowed),
+    }
+}
+
+/// Order of encoded object keys in the index tables of sorted objects: strings by their bytes, as
+/// ArangoDB sorts them, after integer ids, which are ordered by value.
+pub(crate) fn key_order(a: &[u8], b: &[u8]) -> Ordering {
+    sort_key(a).cmp(&sort_key(b))
+}
+
+// compared for every pair of keys when sorting, so read without allocating or validating
+fn sort_key(key: &[u8]) -> (u8, u64, &[u8]) {
+    let string = |start: usize, length: Option<usize>| {
+        length.and_then(|length| key.get(start..start.checked_add(length)?)).unwrap_or(key)
+    };
+    match *key.first().unwrap_or(&0) {
+        b @ 0x28..=0x2f => (0, read_uint(&key[1..], (b - 0x27) as usize).unwrap_or_default(), &[]),
+        b @ 0x30..=0x39 => (0, (b - 0x30) as u64, &[]),
+        b @ 0x40..=0xbe => (1, 0, string(1, Some((b - 0x40) as usize))),
+        0xbf => (1, 0, string(1 + 8, read_uint(&key[1..], 8).ok().and_then(|l| usize::try_from(l).ok()))),
+        _ => (2, 0, key),
+    }
+}
+
+/// Decode the BCD value at the start of `bytes` into a decimal number string, e.g. `-1.25`.
+/// Exponents too large to write out in full are kept in exponent notation, e.g. `125e-300`.
+pub(crate) fn read_bcd(bytes: &[u8]) -> Result<String> {
+    let length = value_byte_length(bytes)?;
+    let header = bytes[0];
+    if !(0xc8..=0xd7).contains(&header) {
+        return Err(ErrorKind::ExpectedDouble.into());
+    }
+    let n_bytes = ((header - 0xc8) % 8 + 1) as usize;
+    let exponent = read_int(&bytes[1 + n_bytes..], 4)?;
+
+    // two digits per byte, most significant first
+    let mut digits = String::new();
+    for b in &bytes[1 + n_bytes + 4..length] {
+        for digit in [b >> 4, b & 0x0f] {
+            if digit > 9 {
+                return Err(ErrorKind::InvalidBcd.into());
+            }
+            if digit != 0 || !digits.is_empty() {
+                digits.push((b'0' + digit) as char);
+            }
+        }
+    }
+    if digits.is_empty() {
+        return Ok("0".to_owned());
+    }
+
+    let mut s = String::with_capacity(digits.len() + 2);
+    if header >= 0xd0 {
+        s.push('-');
+    }
+    let n_digits = digits.len() as i64;
+    match exponent {
+        0..=64 => {
+            s.push_str(&digits);
+            s.extend(std::iter::repeat_n('0', exponent as usize));
+        },
+        e if e < 0 && -e < n_digits => {
+            let point = (n_digits + e) as usize;
+            s.push_str(&digits[..point]);
+            s.push('.');
+            s.push_str(&digits[point..]);
+        },
+        e if e < 0 && -e <= n_digits + 64 => {
+            s.push_str("0.");
+            s.extend(std::iter::repeat_n('0', (-e - n_digits) as usize));
+            s.push_str(&digits);
+        },
+        e => {
+            s.push_str(&digits);
+            s.push('e');
+            s.push_str(&e.to_string());
+        },
+    }
+    Ok(s)
+}
+
+/// Members of an encoded array, or key/value pairs of an encoded object, stored back to back.
+pub(crate) struct Container<'a> {
+    data: &'a [u8],
+    len: usize,
+}
+
+impl<'a> Container<'a> {
+    /// Locate the members of the array or object at the start of `bytes`.
+    pub(crate) fn new(bytes: &'a [u8]) -> Result<Self> {
+        let header = *bytes.first().ok_or(ErrorKind::Eof)?;
+        let byte_length = value_byte_length(bytes)?;
+        let value = &bytes[..byte_length];
+
+        match header {
+            0x01 | 0x0a => Ok(Self { data: &[], len: 0 }),
+            0x02..=0x05 => {
+                // no index table, all items have the same length
+                let n_bytes = 1 << (header - 0x02);
+                let data = skip_padding(value.get(1 + n_bytes..).ok_or(ErrorKind::Eof)?);
+                let len = if data.is_empty() {
+                    0
+                } else {
+                    data.len() / value_byte_length(data)?
+                };
+                Ok(Self { data, len })
+            },
+            0x06..=0x09 | 0x0b..=0x12 => {
+                let n_bytes = if header <= 0x09 {
+                    1 << (header - 0x06)
+                } else {
+                    1 << ((header - 0x0b) % 4)
+                };
+                let (len, start, end) = if n_bytes == 8 {
+                    // number of items is stored after the index table
+                    let len = to_usize(read_uint(value.get(byte_length.checked_sub(8).ok_or(ErrorKind::Eof)?..).ok_or(ErrorKind::Eof)?, 8)?)?;
+                    (len, 1 + 8, byte_length.checked_sub(8).ok_or(ErrorKind::Eof)?)
+                } else {
+                    let len = to_usize(read_uint(value.get(1 + n_bytes..).ok_or(ErrorKind::Eof)?, n_bytes)?)?;
+                    (len, 1 + 2 * n_bytes, byte_length)
+                };
+                let index_size = len.checked_mul(n_bytes).ok_or(ErrorKind::NumberTooLarge)?;
+                let end = end.checked_sub(index_size).ok_or(ErrorKind::Eof)?;
+                let data = skip_padding(value.get(start..end).ok_or(ErrorKind::Eof)?);
+                Ok(Self { data, len })
+            },
+            0x13 | 0x14 => {
+                let (_, length_size) = read_varint(&value[1..])?;
+                let (len, len_size) = read_varint_reversed(value)?;
+                let end = byte_length.checked_sub(len_size).ok_or(ErrorKind::Eof)?;
+                let data = value.get(1 + length_size..end).ok_or(ErrorKind::Eof)?;
+                // every item takes at least one byte, so a larger count can't be right
+                let len = to_usize(len)?;
+                if len > data.len() {
+                    return Err(ErrorKind::Eof.into());
+                }
+                Ok(Self { data, len })
+            },
+            _ => Err(ErrorKind::ExpectedArray.into()),
+        }
+    }
+
+    /// Number of items of an array, or members of an object.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The encoded items, or keys and values, without any header, padding or index table.
+    pub(crate) fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Iterate over the items of an array.
+    pub(crate) fn items(&self) -> Items<'a> {
+        Items { data: self.data, remaining: self.len }
+    }
+
+    /// Iterate over the key/value pairs of an object.
+    pub(crate) fn members(&self) -> Members<'a> {
+        Members { data: self.data, remaining: self.len }
+    }
+}
+
+fn skip_padding(bytes: &[u8]) -> &[u8] {
+    let n = bytes.iter().take_while(|b| **b == 0x00).count();
+    &bytes[n..]
+}
+
+/// Iterator over the encoded items of an array.
+pub(crate) struct Items<'a> {
+    data: &'a [u8],
+    remaining: usize,
+}
+
+impl<'a> Iterator for Items<'a> {
+    type Item = Result<&'a [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        match split_value(self.data) {
+            Ok((item, rest)) => {
+                self.data = rest;
+                Some(Ok(item))
+            },
+            Err(e) => {
+                self.remaining = 0;
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+/// Iterator over the encoded keys and values of an object.
+pub(crate) struct Members<'a> {
+    data: &'a [u8],
+    remaining: usize,
+}
+
+impl<'a> Members<'a> {
+    fn next_pair(&mut self) -> Result<(&'a [u8], &'a [u8])> {
+        let (key, rest) = split_value(self.data)?;
+        let (value, rest) = split_value(rest)?;
+        self.data = rest;
+        Ok((key, value))
+    }
+}
+
+impl<'a> Iterator for Members<'a> {
+    type Item = Result<(&'a [u8], &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let pair = self.next_pair();
+        if pair.is_err() {
+            self.remaining = 0;
+        }
+        Some(pair)
+    }
+}
+
+/// Get the item at `index` of the array at the start of `bytes`, in constant time unless the
+/// array is compact.
+pub(crate) fn array_at(bytes: &[u8], index: usize) -> Result<Option<&[u8]>> {
+    let header = *bytes.first().ok_or(ErrorKind::Eof)?;
+    match header {
+        0x02..=0x05 => {
+            // all items have the same length
+            let n_bytes = 1 << (header - 0x02);
+            let value = &bytes[..value_byte_length(bytes)?];
+            let data = skip_padding(value.get(1 + n_bytes..).ok_or(ErrorKind::Eof)?);
+            let item_length = value_byte_length(data)?;
+            let start = match index.checked_mul(item_length) {
+                Some(start) if start < data.len() => start,
+                _ => return Ok(None),
+            };
+            Ok(Some(data.get(start..start + item_length).ok_or(ErrorKind::Eof)?))
+        },
+        0x06..=0x09 => {
+            let n_bytes = 1 << (header - 0x06);
+            let byte_length = value_byte_length(bytes)?;
+            let value = &bytes[..byte_length];
+            let (len, index_end) = if n_bytes == 8 {
+                let end = byte_length.checked_sub(8).ok_or(ErrorKind::Eof)?;
+                (to_usize(read_uint(&value[end..], 8)?)?, end)
+            } else {
+                (to_usize(read_uint(value.get(1 + n_bytes..).ok_or(ErrorKind::Eof)?, n_bytes)?)?, byte_length)
+            };
+            // offsets follow the items, at the end of the array, and can't overlap the header
+            let header_size = if n_bytes == 8 { 1 + 8 } else { 1 + 2 * n_bytes };
+            let index_start = len.checked_mul(n_bytes)
+                .and_then(|size| index_end.checked_sub(size))
+                .filter(|&start| start >= header_size)
+                .ok_or(ErrorKind::Eof)?;
+            if index >= len {
+                return Ok(None);
+            }
+            let entry = index_start + index * n_bytes;
+            let offset = to_usize(read_uint(&value[entry..], n_bytes)?)?;
+            let (item, _) = split_value(value.get(offset..).ok_or(ErrorKind::Eof)?)?;
+            Ok(Some(item))
+        },
+        0x01 | 0x13 => Container::new(bytes)?.items().nth(index).transpose(),
+        _ => Err(ErrorKind::ExpectedArray.into()),
+    }
+}
+
+/// Find the value stored under `key` in the object at the start of `bytes`, which also matches
+/// integer keys written in decimal. Takes logarithmic time for string keys of sorted objects,
+/// which are searched through their index table.
+pub(crate) fn object_get<'a>(bytes: &'a [u8], key: &str) -> Result<Option<&'a [u8]>> {
+    match bytes.first() {
+        Some(0x0b..=0x0e) => {
+            let found = sorted_object_find(bytes, key)?.map(|(_, value)| value);
+            if found.is_some() || key.parse::<u64>().is_err() {
+                return Ok(found);
+            }
+        },
+        Some(0x0a..=0x12) | Some(0x14) => {},
+        Some(_) => return Err(ErrorKind::ExpectedObject.into()),
+        None => return Err(ErrorKind::Eof.into()),
+    }
+
+    for member in Container::new(bytes)?.members() {
+        let (k, v) = member?;
+        if read_key(k)? == key {
+            return Ok(Some(v));
+        }
+    }
+    Ok(None)
+}
+
+/// Find the member `key` of the sorted object (0x0b to 0x0e) at the start of `bytes` with a binary
+/// search of its index table, returning the member's encoded key and value.
+pub(crate) fn sorted_object_find<'a>(bytes: &'a [u8], key: &str) -> Result<Option<(&'a [u8], &'a [u8])>> {
+    let header = *bytes.first().ok_or(ErrorKind::Eof)?;
+    if !(0x0b..=0x0e).contains(&header) {
+        return Err(ErrorKind::ExpectedObject.into());
+    }
+    let n_bytes = 1 << (header - 0x0b);
+    let byte_length = value_byte_length(bytes)?;
+    let value = &bytes[..byte_length];
+    let (len, index_end) = if n_bytes == 8 {
+        let end = byte_length.checked_sub(8).ok_or(ErrorKind::Eof)?;
+        (to_usize(read_uint(&value[end..], 8)?)?, end)
+    } else {
+        (to_usize(read_uint(value.get(1 + n_bytes..).ok_or(ErrorKind::Eof)?, n_bytes)?)?, byte_length)
+    };
+    let index_start = len.checked_mul(n_bytes).and_then(|size| index_end.checked_sub(size)).ok_or(ErrorKind::Eof)?;
+    let target = (1, 0, key.as_bytes());
+
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let offset = to_usize(read_uint(&value[index_start + mid * n_bytes..], n_bytes)?)?;
+        let member = value.get(offset..index_start).ok_or(ErrorKind::Eof)?;
+        match sort_key(member).cmp(&target) {
+            Ordering::Less => low = mid + 1,
+            Ordering::Greater => high = mid,
+            Ordering::Equal => {
+                let (key, rest) = split_value(member)?;
+                let (value, _) = split_value(rest)?;
+                return Ok(Some((key, value)));
+            },
+        }
+    }
+    Ok(None)
+}
+
+/// Encode an object of already encoded `(key, value)` members, with an index table of
+/// `offset_size` (1, 2, 4 or 8) byte offsets: test fixtures for offset sizes the serializer only
+/// produces for large objects.
+#[cfg(test)]
+pub(crate) fn object_with_offset_size(offset_size: usize, members: &[(&str, &[u8])]) -> Vec<u8> {
+    let header_size = if offset_size == 8 { 1 + 8 } else { 1 + 2 * offset_size };
+    let mut data = Vec::new();
+    let mut offsets = Vec::new();
+    for (k, v) in members {
+        offsets.push((*k, header_size + data.len()));
+        data.extend(crate::to_bytes(k).unwrap());
+        data.extend_from_slice(v);
+    }
+    offsets.sort_by_key(|(k, _)| *k);
+
+    let n_trailing = if offset_size == 8 { 8 } else { 0 };
+    let byte_length = header_size + data.len() + offsets.len() * offset_size + n_trailing;
+    let mut out = vec![0x0b + offset_size.trailing_zeros() as u8];
+    out.extend_from_slice(&byte_length.to_le_bytes()[..offset_size]);
+    if offset_size != 8 {
+        out.extend_from_slice(&members.len().to_le_bytes()[..offset_size]);
+    }
+    out.extend(data);
+    for (_, offset) in offsets {
+        out.extend_from_slice(&offset.to_le_bytes()[..offset_size]);
+    }
+    if offset_size == 8 {
+        out.extend_from_slice(&(members.len() as u64).to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_byte_members() {
+        let members: &[(&str, &[u8])] = &[("b", &[0x1a]), ("a", &[0x18]), ("c", &[0x19])];
+        for offset_size in [1, 2, 4, 8] {
+            let bytes = object_with_offset_size(offset_size, members);
+            assert_eq!(value_byte_length(&bytes).unwrap(), bytes.len());
+            let decoded: Vec<_> = Container::new(&bytes).unwrap().members()
+                .map(|m| m.map(|(k, v)| (read_str(k).unwrap().to_owned(), v.to_vec())).unwrap())
+                .collect();
+            assert_eq!(decoded, vec![("b".to_owned(), vec![0x1a]), ("a".to_owned(), vec![0x18]), ("c".to_owned(), vec![0x19])]);
+            assert_eq!(object_get(&bytes, "c").unwrap(), Some(&[0x19][..]));
+            assert_eq!(object_get(&bytes, "d").unwrap(), None);
+        }
+        assert_eq!(object_with_offset_size(1, members), &[0x0b, 0x0f, 0x03, 0x41, 0x62, 0x1a, 0x41, 0x61, 0x18, 0x41, 0x63, 0x19, 0x06, 0x03, 0x09]);
+    }
+
+    #[test]
+    fn sorted_find() {
+        let doc: std::collections::BTreeMap<String, u32> = (0..300).map(|i| (format!("k{}", i), i)).collect();
+        let bytes = crate::to_bytes(&doc).unwrap();
+        for i in [0, 1, 150, 299] {
+            let (key, value) = sorted_object_find(&bytes, &format!("k{}", i)).unwrap().unwrap();
+            assert_eq!(read_str(key).unwrap(), format!("k{}", i));
+            assert_eq!(crate::from_bytes::<u32>(value).unwrap(), i);
+        }
+        assert_eq!(sorted_object_find(&bytes, "k300").unwrap(), None);
+        assert_eq!(sorted_object_find(&bytes, "").unwrap(), None);
+
+        // integer keys sort before strings
+        let bytes = crate::to_bytes(&serde_json::json!({"a": 1, "b": 2})).unwrap();
+        let mut ids = vec![0x0b, 0x0c, 0x03, 0x31, 0x18, 0x41, 0x62, 0x32, 0x35, 0x1a, 0x03, 0x08, 0x05];
+        ids[1] = ids.len() as u8;
+        assert_eq!(sorted_object_find(&ids, "b").unwrap(), Some((&[0x41, 0x62][..], &[0x32][..])));
+        assert_eq!(sorted_object_find(&bytes, "a").unwrap(), Some((&[0x41, 0x61][..], &[0x31][..])));
+        assert!(sorted_object_find(&[0x0f, 0x03, 0x00], "a").is_err());
+    }
+
+    #[test]
+    fn array_at() {
+        // no index table, padded
+        let bytes = [0x03, 0x07, 0x00, 0x00, 0x31, 0x32, 0x33];
+        assert_eq!(super::array_at(&bytes, 2).unwrap(), Some(&[0x33][..]));
+        assert_eq!(super::array_at(&bytes, 3).unwrap(), None);
+        assert_eq!(super::array_at(&bytes, usize::MAX).unwrap(), None);
+
+        // 1 byte offsets: [1, "ab", 3]
+        let bytes = [0x06, 0x0b, 0x03, 0x31, 0x42, 0x61, 0x62, 0x33, 0x03, 0x04, 0x07];
+        assert_eq!(super::array_at(&bytes, 1).unwrap(), Some(&[0x42, 0x61, 0x62][..]));
+        assert_eq!(super::array_at(&bytes, 2).unwrap(), Some(&[0x33][..]));
+        assert_eq!(super::array_at(&bytes, 3).unwrap(), None);
+
+        // 8 byte offsets, number of items at the end
+        let mut bytes = vec![0x09, 0x1d, 0, 0, 0, 0, 0, 0, 0, 0x31, 0x32];
+        bytes.extend_from_slice(&9_u64.to_le_bytes());
+        bytes.extend_from_slice(&10_u64.to_le_bytes());
+        bytes.extend_from_slice(&2_u64.to_le_bytes());
+        bytes[1] = bytes.len() as u8;
+        assert_eq!(super::array_at(&bytes, 1).unwrap(), Some(&[0x32][..]));
+        assert_eq!(super::array_at(&bytes, 2).unwrap(), None);
+
+        // numbers of items whose index table doesn't fit in the array
+        let end = bytes.len() - 8;
+        for len in [3, u64::MAX / 4, u64::MAX] {
+            bytes[end..].copy_from_slice(&len.to_le_bytes());
+            assert_eq!(crate::Slice::new(&bytes).at(0), Err(ErrorKind::Eof.into()));
+        }
+        assert_eq!(super::array_at(&[0x06, 0x0b, 0x09, 0x31, 0x42, 0x61, 0x62, 0x33, 0x03, 0x04, 0x07], 0), Err(ErrorKind::Eof.into()));
+
+        let compact = [0x13, 0x06, 0x31, 0x41, 0x61, 0x02];
+        assert_eq!(super::array_at(&compact, 1).unwrap(), Some(&[0x41, 0x61][..]));
+        assert_eq!(super::array_at(&[0x01], 0).unwrap(), None);
+        assert_eq!(super::array_at(&[0x0a], 0), Err(ErrorKind::ExpectedArray.into()));
+        assert_eq!(super::array_at(&[0x06, 0x05, 0x01, 0x31, 0x09], 0), Err(ErrorKind::Eof.into()));
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(read_key(&[0x41, 0x61]).unwrap(), "a");
+        assert_eq!(read_key(&[0x31]).unwrap(), "1");
+        assert_eq!(read_key(&[0x29, 0xe8, 0x03]).unwrap(), "1000");
+        assert_eq!(read_key(&[0x3f]), Err(ErrorKind::ExpectedString.into()));
+        let object = [0x0b, 0x09, 0x02, 0x31, 0x1a, 0x32, 0x19, 0x03, 0x05];
+        assert_eq!(object_get(&object, "2").unwrap(), Some(&[0x19][..]));
+
+        // integer ids by value, before strings by their bytes, whatever follows the key
+        assert_eq!(key_order(&[0x39], &[0x29, 0xe8, 0x03]), Ordering::Less);
+        assert_eq!(key_order(&[0x29, 0xe8, 0x03], &[0x41, 0x61]), Ordering::Less);
+        assert_eq!(key_order(&[0x41, 0x62, 0x00], &[0x42, 0x61, 0x61]), Ordering::Greater);
+        let mut long = vec![0xbf, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x61];
+        assert_eq!(key_order(&long, &[0x41, 0x61, 0xff]), Ordering::Equal);
+        long.push(0x61);
+        assert_eq!(key_order(&long, &[0x41, 0x61]), Ordering::Equal);
+    }
+
+    #[test]
+    fn varints() {
+        for v in [0, 1, 0x7f, 0x80, 300, u32::MAX as u64, u64::MAX] {
+            let mut out = Vec::new();
+            write_varint(&mut out, v);
+            assert_eq!(read_varint(&out).unwrap(), (v, out.len()));
+            out.reverse();
+            assert_eq!(read_varint_reversed(&out).unwrap(), (v, out.len()));
+        }
+        let mut out = Vec::new();
+        write_varint(&mut out, 300);
+        assert_eq!(out, &[0xac, 0x02]);
+        assert_eq!(varint_size(300), 2);
+        assert_eq!(varint_size(u64::MAX), 10);
+
+        // unterminated, or longer than any u64 needs
+        assert_eq!(read_varint(&[0x80, 0x80]), Err(ErrorKind::Eof.into()));
+        assert_eq!(read_varint_reversed(&[0x80; 11]), Err(ErrorKind::Eof.into()));
+    }
+
+    #[test]
+    fn compact_lengths() {
+        // header, byte length, one item, number of items
+        assert_eq!(compact_byte_length(1, 1), 4);
+        assert_eq!(compact_byte_length(0, 0), 3);
+        // the byte length needs a second byte once it passes 127
+        assert_eq!(compact_byte_length(124, 1), 127);
+        assert_eq!(compact_byte_length(125, 1), 129);
+    }
+
+    #[test]
+    fn bcd() {
+        // mantissa length, exponent, mantissa
+        assert_eq!(read_bcd(&[0xc8, 0x02, 0xfe, 0xff, 0xff, 0xff, 0x01, 0x25]).unwrap(), "1.25");
+        assert_eq!(read_bcd(&[0xd0, 0x02, 0xfe, 0xff, 0xff, 0xff, 0x01, 0x25]).unwrap(), "-1.25");
+        assert_eq!(read_bcd(&[0xc8, 0x01, 0x03, 0x00, 0x00, 0x00, 0x12]).unwrap(), "12000");
+        assert_eq!(read_bcd(&[0xc8, 0x01, 0xfc, 0xff, 0xff, 0xff, 0x05]).unwrap(), "0.0005");
+        assert_eq!(read_bcd(&[0xc8, 0x01, 0x2c, 0x01, 0x00, 0x00, 0x12]).unwrap(), "12e300");
+        assert_eq!(read_bcd(&[0xc9, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]).unwrap(), "0");
+        assert_eq!(read_bcd(&[0xc8, 0x01, 0x00, 0x00, 0x00, 0x00, 0x1a]), Err(ErrorKind::InvalidBcd.into()));
+        assert_eq!(read_bcd(&[0xc8, 0x02, 0x00, 0x00, 0x00, 0x00, 0x12]), Err(ErrorKind::Eof.into()));
+        assert_eq!(read_bcd(&[0x31]), Err(ErrorKind::ExpectedDouble.into()));
+    }
+
+    #[test]
+    fn ints() {
+        assert_eq!(read_uint(&[0x01, 0x02, 0x03], 3).unwrap(), 0x030201);
+        assert_eq!(read_int(&[0xff], 1).unwrap(), -1);
+        assert_eq!(read_int(&[0x7f], 1).unwrap(), 127);
+        assert_eq!(read_int(&[0x00, 0x00, 0x80], 3).unwrap(), -0x800000);
+        assert_eq!(read_int(&[0xff; 8], 8).unwrap(), -1);
+        assert_eq!(read_int(&[0xff], 2), Err(ErrorKind::Eof.into()));
+    }
+
+    #[test]
+    fn byte_length_scalars() {
+        assert_eq!(value_byte_length(&[0x18]).unwrap(), 1);
+        assert_eq!(value_byte_length(&[0x1a]).unwrap(), 1);
+        assert_eq!(value_byte_length(&[0x35]).unwrap(), 1);
+        assert_eq!(value_byte_length(&[0x1b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f]).unwrap(), 9);
+        assert_eq!(value_byte_length(&[0x29, 0x39, 0x30, 0xff]).unwrap(), 3);
+        assert_eq!(value_byte_length(&[0x21, 0xc7, 0xcf]).unwrap(), 3);
+        assert_eq!(value_byte_length(&[0x43, 0x66, 0x6f, 0x6f, 0x18]).unwrap(), 4);
+        assert_eq!(value_byte_length(&[0xbf, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x61]).unwrap(), 10);
+        assert_eq!(value_byte_length(&[0xc0, 0x02, 0x01, 0x02]).unwrap(), 4);
+        assert_eq!(value_byte_length(&[0xee, 0x01, 0x31]).unwrap(), 3);
+        assert_eq!(value_byte_length(&[0xf1, 0x01, 0x02]).unwrap(), 3);
+        assert_eq!(value_byte_length(&[0xf4, 0x02, 0x01, 0x02]).unwrap(), 4);
+    }
+
+    #[test]
+    fn byte_length_containers() {
+        assert_eq!(value_byte_length(&[0x01]).unwrap(), 1);
+        assert_eq!(value_byte_length(&[0x02, 0x05, 0x31, 0x32, 0x33, 0x18]).unwrap(), 5);
+        assert_eq!(value_byte_length(&[0x06, 0x09, 0x02, 0x31, 0x29, 0x00, 0x01, 0x03, 0x04]).unwrap(), 9);
+        assert_eq!(value_byte_length(&[0x0b, 0x0b, 0x02, 0x41, 0x61, 0x31, 0x41, 0x62, 0x32, 0x03, 0x06]).unwrap(), 11);
+        assert_eq!(value_byte_length(&[0x13, 0x06, 0x31, 0x32, 0x33, 0x03]).unwrap(), 6);
+    }
+
+    #[test]
+    fn byte_length_truncated() {
+        assert_eq!(value_byte_length(&[]), Err(ErrorKind::Eof.into()));
+        assert_eq!(value_byte_length(&[0x43, 0x66]), Err(ErrorKind::Eof.into()));
+        assert_eq!(value_byte_length(&[0x1b, 0x00]), Err(ErrorKind::Eof.into()));
+        assert_eq!(value_byte_length(&[0x0b, 0x0b, 0x02, 0x41]), Err(ErrorKind::Eof.into()));
+        assert_eq!(value_byte_length(&[0x15]), Err(ErrorKind::Unimplemented(0x15).into()));
+    }
+
+    #[test]
+    fn object_lookup() {
+        let object = &[0x0b, 0x0b, 0x02, 0x41, 0x61, 0x31, 0x41, 0x62, 0x32, 0x03, 0x06];
+        assert_eq!(object_get(object, "a").unwrap(), Some(&[0x31][..]));
+        assert_eq!(object_get(object, "b").unwrap(), Some(&[0x32][..]));
+        assert_eq!(object_get(object, "c").unwrap(), None);
+        assert_eq!(object_get(&[0x14, 0x06, 0x41, 0x61, 0x31, 0x01], "a").unwrap(), Some(&[0x31][..]));
+        assert_eq!(object_get(&[0x0a], "a").unwrap(), None);
+        assert_eq!(object_get(&[0x31], "a"), Err(ErrorKind::ExpectedObject.into()));
+    }
+}
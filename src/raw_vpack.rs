@@ -0,0 +1,182 @@
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{ErrorKind, Result};
+use crate::raw;
+use crate::value_type::ValueType;
+
+/// A single encoded VelocyPack value, borrowed from the input it was deserialized from.
+///
+/// Deserializing into a `RawVPack` field records the value's bytes without decoding them, and
+/// serializing it writes the same bytes back out unchanged, preserving the exact encoding (integer
+/// widths, index tables, attribute order, padding etc.) of parts of a document which aren't
+/// modified. Only supported by this crate's serializer and deserializer, and not within
+/// `#[serde(flatten)]` structs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RawVPack<'a>(&'a [u8]);
+
+impl<'a> RawVPack<'a> {
+    /// Wrap `bytes`, which must contain exactly one value.
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self> {
+        let length = raw::value_byte_length(bytes)?;
+        if length != bytes.len() {
+            return Err(ErrorKind::TrailingBytes(bytes.len() - length).into());
+        }
+        Ok(RawVPack(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    pub fn value_type(&self) -> ValueType {
+        ValueType::from_header(self.0[0])
+    }
+
+    /// Decode the value.
+    pub fn deserialize<T: Deserialize<'a>>(&self) -> Result<T> {
+        crate::from_bytes(self.0)
+    }
+
+    pub fn to_raw_vpack_buf(&self) -> RawVPackBuf {
+        RawVPackBuf(self.0.to_vec())
+    }
+}
+
+/// Owned version of [`RawVPack`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RawVPackBuf(Vec<u8>);
+
+impl RawVPackBuf {
+    /// Wrap `bytes`, which must contain exactly one value.
+    pub fn from_vec(bytes: Vec<u8>) -> Result<Self> {
+        RawVPack::from_slice(&bytes)?;
+        Ok(RawVPackBuf(bytes))
+    }
+
+    pub fn as_raw_vpack(&self) -> RawVPack<'_> {
+        RawVPack(&self.0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl Serialize for RawVPack<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(crate::RAW_TOKEN, &Bytes(self.0))
+    }
+}
+
+impl Serialize for RawVPackBuf {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.as_raw_vpack().serialize(serializer)
+    }
+}
+
+struct RawVisitor;
+
+impl<'de> de::Visitor<'de> for RawVisitor {
+    type Value = RawVPack<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a raw VelocyPack value")
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> std::result::Result<Self::Value, E> {
+        RawVPack::from_slice(v).map_err(E::custom)
+    }
+}
+
+struct RawBufVisitor;
+
+impl<'de> de::Visitor<'de> for RawBufVisitor {
+    type Value = RawVPackBuf;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a raw VelocyPack value")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<Self::Value, E> {
+        RawVPackBuf::from_vec(v.to_vec()).map_err(E::custom)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawVPack<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(crate::RAW_TOKEN, RawVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawVPackBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(crate::RAW_TOKEN, RawBufVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Envelope<'a> {
+        version: u32,
+        #[serde(borrow)]
+        payload: RawVPack<'a>,
+    }
+
+    #[test]
+    fn round_trip_preserves_bytes() {
+        // non-canonical encodings: 8 byte uint for a small value, indexed array where an index
+        // table isn't needed, padding after the header
+        let payload = [0x06, 0x13, 0x02, 0x00, 0x00, 0x00, 0x00, 0x2f, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x31, 0x07, 0x10];
+        let payload = &payload[..];
+        let mut data = vec![0x0b, 0x00, 0x02, 0x47, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x32, 0x47, 0x70, 0x61, 0x79, 0x6c, 0x6f, 0x61, 0x64];
+        data.extend_from_slice(payload);
+        data.extend_from_slice(&[0x0c, 0x03]);
+        data[1] = data.len() as u8;
+
+        let mut envelope: Envelope = crate::from_bytes(&data).unwrap();
+        assert_eq!(envelope.version, 2);
+        assert_eq!(envelope.payload.as_bytes(), payload);
+        assert_eq!(envelope.payload.value_type(), ValueType::Array);
+        assert_eq!(envelope.payload.deserialize::<Vec<u64>>().unwrap(), vec![1, 1]);
+
+        envelope.version = 3;
+        let out = crate::to_bytes(&envelope).unwrap();
+        assert!(out.windows(payload.len()).any(|w| w == payload));
+        assert_eq!(crate::to_bytes(&envelope.payload).unwrap(), payload);
+    }
+
+    #[test]
+    fn owned() {
+        let data = crate::to_bytes(&vec!["a", "b"]).unwrap();
+        let raw: RawVPackBuf = crate::from_bytes(&data).unwrap();
+        assert_eq!(raw.as_bytes(), &data[..]);
+        assert_eq!(crate::to_bytes(&raw).unwrap(), data);
+        assert_eq!(raw.as_raw_vpack().to_raw_vpack_buf(), raw);
+        assert_eq!(raw.into_vec(), data);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(RawVPack::from_slice(&[0x31, 0x32]), Err(ErrorKind::TrailingBytes(1).into()));
+        assert_eq!(RawVPack::from_slice(&[0x43, 0x61]), Err(ErrorKind::Eof.into()));
+        assert_eq!(RawVPackBuf::from_vec(vec![]), Err(ErrorKind::Eof.into()));
+        assert!(serde_json::from_str::<RawVPackBuf>("[1]").is_err());
+    }
+}
@@ -1,29 +1,483 @@
 use std::fmt::{Display};
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::sync::Arc;
 use serde::{ser, Serialize};
 
-use crate::error::{Error, Result};
+use crate::error::{Error, ErrorKind, Result};
+use crate::custom::CustomTypes;
+use crate::dictionary::StringDictionary;
+use crate::number::Number;
+use crate::translator::AttributeTranslator;
+use crate::raw;
+
+/// Encoding used for empty arrays and objects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyEncoding {
+    /// 0x01 for arrays, 0x0a for objects.
+    #[default]
+    Short,
+    /// Compact array/object with no members, 0x13 0x03 0x00 and 0x14 0x03 0x00.
+    Compact,
+}
+
+/// Encoding used for unit enum variants.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VariantEncoding {
+    /// The variant's name as a string, as with JSON.
+    #[default]
+    Name,
+    /// The variant's index (position in the enum declaration) as an unsigned integer. Usually
+    /// a single byte, but not stable across reordering of variants.
+    Index,
+}
+
+/// Size of the byte length, number of items and index table offsets of arrays and objects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndexWidth {
+    /// The smallest size which fits, arrays with items of equal size have no index table.
+    #[default]
+    Smallest,
+    /// At least 4 bytes.
+    Four,
+    /// Always 8 bytes.
+    Eight,
+}
+
+impl IndexWidth {
+    pub(crate) fn min_bytes(self) -> usize {
+        match self {
+            IndexWidth::Smallest => 1,
+            IndexWidth::Four => 4,
+            IndexWidth::Eight => 8,
+        }
+    }
+}
+
+/// What to do with NaN and infinite doubles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonFinite {
+    /// Write them as they are.
+    #[default]
+    Allow,
+    /// Fail with `ErrorKind::NonFiniteDouble`.
+    Error,
+    /// Write null in their place.
+    Null,
+}
+
+/// What to do with map keys which aren't strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonStringKeys {
+    /// Write unsigned integers as integer keys (as used for translated attribute names, see
+    /// [`AttributeTranslator`]), fail for anything else.
+    #[default]
+    Integer,
+    /// Fail for anything but strings.
+    Error,
+    /// Write integers, doubles and bools as strings, like JSON. The deserializer parses such
+    /// keys back when decoding into number or bool keys.
+    Stringify,
+}
+
+/// Options controlling how values are encoded, for use with
+/// [`to_bytes_with_options`](crate::to_bytes_with_options) and the other `to_bytes_*` functions.
+/// The defaults give the same bytes as [`to_bytes`](crate::to_bytes):
+///
+/// ```
+/// use velocypack::{IndexWidth, NonFinite, SerializerOptions};
+///
+/// let options = SerializerOptions::new()
+///     .compact_containers(true)
+///     .omit_none_fields(true)
+///     .non_finite(NonFinite::Null);
+/// let bytes = velocypack::to_bytes_with_options(&vec![1.5, f64::NAN], &options)?;
+/// assert_eq!(velocypack::from_bytes::<Vec<Option<f64>>>(&bytes)?, vec![Some(1.5), None]);
+///
+/// // room to patch values in place later
+/// let options = SerializerOptions::new().index_width(IndexWidth::Eight).padding(true);
+/// # velocypack::to_bytes_with_options(&vec![1, 2], &options)?;
+/// # Ok::<(), velocypack::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SerializerOptions {
+    pub(crate) empty_collections: EmptyEncoding,
+    pub(crate) dictionary: Option<Arc<StringDictionary>>,
+    unit_variants: VariantEncoding,
+    pub(crate) omit_none_fields: bool,
+    unsorted_objects: bool,
+    pub(crate) compact_arrays: bool,
+    pub(crate) compact_objects: bool,
+    pub(crate) padding: bool,
+    pub(crate) index_width: IndexWidth,
+    non_finite: NonFinite,
+    integral_doubles_as_ints: bool,
+    attribute_translator: Option<Arc<AttributeTranslator>>,
+    non_string_keys: NonStringKeys,
+    pub(crate) custom_types: Option<Arc<CustomTypes>>,
+}
+
+impl SerializerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encoding of empty arrays and objects, whatever the other options.
+    pub fn empty_collections(mut self, encoding: EmptyEncoding) -> Self {
+        self.empty_collections = encoding;
+        self
+    }
+
+    /// Encode string values (but not object keys) found in `dictionary` as references to it.
+    pub fn dictionary(mut self, dictionary: Arc<StringDictionary>) -> Self {
+        self.dictionary = Some(dictionary);
+        self
+    }
+
+    /// Leave out struct fields set to `None`, rather than writing them as null. Doesn't affect
+    /// maps, or `None` values elsewhere.
+    pub fn omit_none_fields(mut self, enabled: bool) -> Self {
+        self.omit_none_fields = enabled;
+        self
+    }
+
+    /// Write objects with the unsorted headers (0x0f to 0x12), with the index table in the order
+    /// members were serialized, rather than sorted by key.
+    pub fn unsorted_objects(mut self, enabled: bool) -> Self {
+        self.unsorted_objects = enabled;
+        self
+    }
+
+    /// Write non-empty arrays and objects in the compact formats (0x13 and 0x14), which have no
+    /// index table, so are smaller but slower to look up members in. Compact objects keep
+    /// members in the order they were serialized.
+    pub fn compact_containers(mut self, enabled: bool) -> Self {
+        self.compact_arrays = enabled;
+        self.compact_objects = enabled;
+        self
+    }
+
+    /// Write non-empty arrays in the compact format (0x13), see
+    /// [`compact_containers`](Self::compact_containers).
+    pub fn compact_arrays(mut self, enabled: bool) -> Self {
+        self.compact_arrays = enabled;
+        self
+    }
+
+    /// Write non-empty objects in the compact format (0x14), see
+    /// [`compact_containers`](Self::compact_containers).
+    pub fn compact_objects(mut self, enabled: bool) -> Self {
+        self.compact_objects = enabled;
+        self
+    }
+
+    /// Pad the headers of arrays and objects with zero bytes, so that their items always start 9
+    /// bytes in, where they would be with 8 byte lengths. This is the layout the reference
+    /// implementation writes with `PaddingBehavior::UsePadding`. Doesn't apply to compact
+    /// containers.
+    pub fn padding(mut self, enabled: bool) -> Self {
+        self.padding = enabled;
+        self
+    }
+
+    /// Use (at least) 4 or 8 byte lengths and index tables for all non-empty arrays and objects,
+    /// so that values can later be patched in place without having to grow their containers'
+    /// headers. Doesn't apply to compact containers.
+    pub fn index_width(mut self, width: IndexWidth) -> Self {
+        self.index_width = width;
+        self
+    }
+
+    /// What to do with NaN and infinite doubles, which JSON can't represent.
+    pub fn non_finite(mut self, non_finite: NonFinite) -> Self {
+        self.non_finite = non_finite;
+        self
+    }
+
+    /// Write doubles with no fractional part, within the range where doubles can represent every
+    /// integer (±2^53), as (usually much smaller) integers instead. `-0.0` is kept as a double.
+    pub fn integral_doubles_as_ints(mut self, enabled: bool) -> Self {
+        self.integral_doubles_as_ints = enabled;
+        self
+    }
+
+    /// Write object keys found in `translator` as their integer ids.
+    pub fn attribute_translator(mut self, translator: Arc<AttributeTranslator>) -> Self {
+        self.attribute_translator = Some(translator);
+        self
+    }
+
+    /// What to do with map keys which aren't strings.
+    pub fn non_string_keys(mut self, policy: NonStringKeys) -> Self {
+        self.non_string_keys = policy;
+        self
+    }
+
+    /// Encode newtype structs with an encoder in `custom_types` as custom types.
+    pub fn custom_types(mut self, custom_types: Arc<CustomTypes>) -> Self {
+        self.custom_types = Some(custom_types);
+        self
+    }
+
+    /// Encoding of unit enum variants, the deserializer accepts either.
+    pub fn unit_variants(mut self, encoding: VariantEncoding) -> Self {
+        self.unit_variants = encoding;
+        self
+    }
+}
 
 #[derive(Default)]
 pub struct Serializer {
     // empty byte list, appended to as values are serialized
     output: Vec<u8>,
+    options: SerializerOptions,
+    // bytes are already encoded values, to be written as-is
+    raw_bytes: bool,
+    // value serialized was None, rather than any other null
+    wrote_none: bool,
 }
 
 // by convention, public API of a Serde serializer is one or more
 // `to_abc` functions, e.g. `to-string`, `to_bytes`, `to_writer` etc.
-pub fn to_bytes<T: Serialize>(value: &T) -> Result<Vec<u8>> {
-    let mut serializer = Serializer::default();
+pub fn to_bytes<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>> {
+    to_bytes_with_options(value, &SerializerOptions::default())
+}
+
+/// Serialize `value` with the encoding chosen by `options`.
+pub fn to_bytes_with_options<T: ?Sized + Serialize>(value: &T, options: &SerializerOptions) -> Result<Vec<u8>> {
+    let mut serializer = Serializer::with_options(options.clone());
     value.serialize(&mut serializer)?;
     Ok(serializer.output)
 }
 
+/// Serialize `value` onto the end of `buffer`, so that one buffer can be reused, or hold
+/// several values back to back (see [`first_from_bytes`](crate::first_from_bytes)). If
+/// serializing fails, `buffer` is left as it was.
+pub fn to_bytes_in<T: ?Sized + Serialize>(value: &T, buffer: &mut Vec<u8>) -> Result<()> {
+    to_bytes_in_with_options(value, buffer, &SerializerOptions::default())
+}
+
+/// Serialize `value` onto the end of `buffer` like [`to_bytes_in`], with the encoding chosen by
+/// `options`.
+pub fn to_bytes_in_with_options<T: ?Sized + Serialize>(value: &T, buffer: &mut Vec<u8>, options: &SerializerOptions) -> Result<()> {
+    let start = buffer.len();
+    let mut serializer = Serializer::with_options(options.clone()).append_to(std::mem::take(buffer));
+    let result = value.serialize(&mut serializer);
+    *buffer = serializer.output;
+    if result.is_err() {
+        buffer.truncate(start);
+    }
+    result
+}
+
+/// Serialize `value` onto the end of `buffer`, first padding it with zero bytes to a multiple of
+/// `alignment` (treated as 1 if 0), for embedding values inside other formats. Returns the range
+/// of `buffer` the value occupies.
+pub fn to_bytes_aligned<T: Serialize>(value: &T, buffer: &mut Vec<u8>, alignment: usize, options: &SerializerOptions) -> Result<Range<usize>> {
+    let original_len = buffer.len();
+    buffer.resize(original_len.next_multiple_of(alignment.max(1)), 0x00);
+    let start = buffer.len();
+    if let Err(e) = to_bytes_in_with_options(value, buffer, options) {
+        buffer.truncate(original_len);
+        return Err(e);
+    }
+    Ok(start..buffer.len())
+}
+
+// room left for an array header when streaming items, enough for the header and byte length
+// of any non-compact array
+const STREAM_HEADER_SIZE: usize = 1 + 8;
+
+/// Serialize the items of `iter` as an array, writing each item straight into the output rather
+/// than encoding it separately first, so that very large arrays only need their own bytes and
+/// an offset per item in memory. The header is chosen once all items have been written, giving
+/// the same bytes as serializing the items collected into a `Vec`.
+pub fn to_bytes_from_iter<I>(iter: I, options: &SerializerOptions) -> Result<Vec<u8>> where
+    I: IntoIterator,
+    I::Item: Serialize {
+    let iter = iter.into_iter();
+    let mut serializer = Serializer::with_options(options.clone());
+    serializer.output.resize(STREAM_HEADER_SIZE, 0x00);
+    // start of each item, relative to the first
+    let mut offsets = Vec::with_capacity(iter.size_hint().0);
+    for item in iter {
+        offsets.push(serializer.output.len() - STREAM_HEADER_SIZE);
+        item.serialize(&mut serializer)?;
+    }
+    let mut output = serializer.output;
+    finish_streamed_array(&mut output, &offsets, options);
+    Ok(output)
+}
+
+// fewest items encoded together by `to_bytes_par`, so that small arrays aren't split into chunks
+// too small to be worth handing to another thread
+#[cfg(feature = "rayon")]
+const PAR_MIN_CHUNK_LEN: usize = 1024;
+
+/// Serialize `items` as an array, encoding chunks of items in parallel on rayon's global thread
+/// pool, then joining them and writing the header and index table once all are done. Gives the
+/// same bytes as serializing `items` on one thread, so is only worth it for arrays of (many)
+/// thousands of items.
+#[cfg(feature = "rayon")]
+pub fn to_bytes_par<T: Serialize + Sync>(items: &[T], options: &SerializerOptions) -> Result<Vec<u8>> {
+    use rayon::prelude::*;
+
+    let chunk_len = (items.len() / (4 * rayon::current_num_threads())).max(PAR_MIN_CHUNK_LEN);
+    let chunks = items.par_chunks(chunk_len).map(|chunk| {
+        let mut serializer = Serializer::with_options(options.clone());
+        // start of each item, relative to the start of the chunk
+        let mut offsets = Vec::with_capacity(chunk.len());
+        for item in chunk {
+            offsets.push(serializer.output.len());
+            item.serialize(&mut serializer)?;
+        }
+        Ok((serializer.output, offsets))
+    }).collect::<Result<Vec<_>>>()?;
+
+    let items_size: usize = chunks.iter().map(|(bytes, _)| bytes.len()).sum();
+    let mut output = Vec::with_capacity(STREAM_HEADER_SIZE + items_size);
+    output.resize(STREAM_HEADER_SIZE, 0x00);
+    let mut offsets = Vec::with_capacity(items.len());
+    for (bytes, chunk_offsets) in chunks {
+        let chunk_start = output.len() - STREAM_HEADER_SIZE;
+        offsets.extend(chunk_offsets.into_iter().map(|offset| chunk_start + offset));
+        output.extend_from_slice(&bytes);
+    }
+    finish_streamed_array(&mut output, &offsets, options);
+    Ok(output)
+}
+
+// write the header (and index table) of an array whose items follow `STREAM_HEADER_SIZE` bytes
+// of room at the start of `output`, choosing the same layout as `ArraySerializer::end_array`
+fn finish_streamed_array(output: &mut Vec<u8>, offsets: &[usize], options: &SerializerOptions) {
+    let n_items = offsets.len();
+    let item_size = output.len() - STREAM_HEADER_SIZE;
+    if n_items == 0 {
+        output.clear();
+        match options.empty_collections {
+            EmptyEncoding::Short => output.push(0x01),
+            EmptyEncoding::Compact => output.extend_from_slice(&[0x13, 0x03, 0x00]),
+        }
+        return;
+    }
+    if options.compact_arrays {
+        let (byte_length_bytes, mut n_items_bytes) = compact_lengths(item_size, n_items);
+        let header = std::iter::once(0x13).chain(byte_length_bytes);
+        output.splice(..STREAM_HEADER_SIZE, header);
+        output.append(&mut n_items_bytes);
+        return;
+    }
+
+    let elem_len = offsets.get(1).copied().unwrap_or(item_size);
+    let same_length = options.index_width == IndexWidth::Smallest && elem_len * n_items == item_size
+        && offsets.iter().enumerate().all(|(i, &offset)| offset == i * elem_len);
+    let mut header = Vec::with_capacity(STREAM_HEADER_SIZE);
+    if same_length {
+        for n_bytes in [1, 2, 4, 8] {
+            let padding = if options.padding { 8 - n_bytes } else { 0 };
+            let needed_size = 1 + n_bytes + padding + item_size;
+            if n_bytes == 8 || needed_size < 1 << (n_bytes * 8) {
+                header.push(0x02 + n_bytes.trailing_zeros() as u8);
+                header.extend_from_slice(&(needed_size as u64).to_le_bytes()[..n_bytes]);
+                header.resize(header.len() + padding, 0x00);
+                break;
+            }
+        }
+        output.splice(..STREAM_HEADER_SIZE, header);
+        return;
+    }
+
+    let min_bytes = options.index_width.min_bytes();
+    for n_bytes in [1, 2, 4, 8].iter().copied().filter(|n| *n >= min_bytes) {
+        let header_size = if n_bytes == 8 { 1 + 8 } else { 1 + 2 * n_bytes };
+        let padding = if options.padding { 1 + 8 - header_size } else { 0 };
+        let needed_size = 1 + n_bytes + n_bytes + padding + item_size + n_items * n_bytes;
+        if n_bytes == 8 || needed_size < 1 << (n_bytes * 8) {
+            header.push(0x06 + n_bytes.trailing_zeros() as u8);
+            header.extend_from_slice(&(needed_size as u64).to_le_bytes()[..n_bytes]);
+            if n_bytes < 8 {
+                header.extend_from_slice(&(n_items as u64).to_le_bytes()[..n_bytes]);
+            }
+            header.resize(header.len() + padding, 0x00);
+            output.splice(..STREAM_HEADER_SIZE, header);
+            output.reserve(n_items * n_bytes + if n_bytes == 8 { 8 } else { 0 });
+            for offset in offsets {
+                output.extend_from_slice(&((header_size + padding + offset) as u64).to_le_bytes()[..n_bytes]);
+            }
+            if n_bytes == 8 {
+                output.extend_from_slice(&(n_items as u64).to_le_bytes());
+            }
+            break;
+        }
+    }
+}
+
+/// Encoded size in bytes of each top-level attribute of a document, by attribute name.
+pub type FieldSizes = Vec<(String, usize)>;
+
+/// Serialize `value`, also returning the number of bytes each top-level attribute (key and value)
+/// occupies in the output, in the order they were written. Useful for finding out which fields
+/// make up most of a document's size.
+///
+/// The remaining bytes are taken up by the object's header and index table. If `value` isn't
+/// serialized as an object, no sizes are returned.
+pub fn to_bytes_with_field_sizes<T: Serialize>(value: &T, options: &SerializerOptions) -> Result<(Vec<u8>, FieldSizes)> {
+    let bytes = to_bytes_with_options(value, options)?;
+    let mut sizes = Vec::new();
+    if let Some(0x0a..=0x12) | Some(0x14) = bytes.first() {
+        for member in raw::Container::new(&bytes)?.members() {
+            let (key, value) = member?;
+            sizes.push((raw::read_key(key)?.into_owned(), key.len() + value.len()));
+        }
+    }
+    Ok((bytes, sizes))
+}
+
 impl Serializer {
+    pub fn with_options(options: SerializerOptions) -> Self {
+        Self { output: Vec::new(), options, raw_bytes: false, wrote_none: false }
+    }
+
+    /// Serializer with default options and room for `capacity` bytes of output, e.g. the
+    /// expected size of a large document.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::default().append_to(Vec::with_capacity(capacity))
+    }
+
+    /// Serialize values after the existing contents of `buffer`, e.g. a protocol header, rather
+    /// than into a new buffer.
+    pub fn append_to(mut self, buffer: Vec<u8>) -> Self {
+        self.output = buffer;
+        self
+    }
+
+    /// The bytes of the values serialized so far, which are written one after another.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Take the bytes of the values serialized.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.output
+    }
+
+    /// Clear the values serialized so far, keeping the allocated buffer to serialize more values
+    /// into.
+    pub fn reset(&mut self) {
+        self.output.clear();
+        self.wrote_none = false;
+    }
+
+    fn child(options: &SerializerOptions) -> Self {
+        Self::with_options(options.clone())
+    }
+
     fn serialize_negative_int(&mut self, v: i64) {
         assert!(v < 0);
         match v {
             i if i > -7 => self.output.push((0x40 + i) as u8),
             i => {
-                let b = dbg!(i.to_le_bytes());
+                let b = i.to_le_bytes();
 
                 for bit in (0..8).rev() {
 
@@ -60,6 +514,132 @@ impl Serializer {
     }
 }
 
+/// Encode `key` as an object key, as the serializer does for map keys.
+pub(crate) fn encode_key<T: ?Sized + Serialize>(key: &T, options: &SerializerOptions) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut map = MapSerializer { keys: Items::default(), values: Items::default(), output: &mut output, options };
+    map.serialize_map_key(key)?;
+    Ok(map.keys.bytes)
+}
+
+/// Write an array of already encoded `items`.
+pub(crate) fn write_array(output: &mut Vec<u8>, items: Vec<Vec<u8>>, options: &SerializerOptions) -> Result<()> {
+    ArraySerializer { items: Items::from(items), output, options }.end_array()
+}
+
+/// Write an object of already encoded `keys` and `values`.
+pub(crate) fn write_object(output: &mut Vec<u8>, keys: Vec<Vec<u8>>, values: Vec<Vec<u8>>, options: &SerializerOptions) -> Result<()> {
+    MapSerializer { keys: Items::from(keys), values: Items::from(values), output, options }.end_map()
+}
+
+// the encoded items (or keys, or values) of an array or object being serialized, back to back in
+// one buffer, rather than each in a buffer of its own: most are only a few bytes, so allocating
+// for each of them would take longer than encoding them
+#[derive(Default)]
+struct Items {
+    bytes: Vec<u8>,
+    // end of each item in `bytes`
+    ends: Vec<usize>,
+}
+
+impl Items {
+    fn with_capacity(n_items: usize) -> Self {
+        Self { bytes: Vec::new(), ends: Vec::with_capacity(n_items) }
+    }
+
+    fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    // total byte length of the items
+    fn size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn get(&self, i: usize) -> &[u8] {
+        let start = if i == 0 { 0 } else { self.ends[i - 1] };
+        &self.bytes[start..self.ends[i]]
+    }
+
+    fn last(&self) -> &[u8] {
+        self.get(self.len() - 1)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        (0..self.len()).map(move |i| self.get(i))
+    }
+
+    // offset of each item in `bytes`
+    fn starts(&self) -> impl Iterator<Item = usize> + '_ {
+        std::iter::once(0).chain(self.ends.iter().copied()).take(self.len())
+    }
+
+    fn push(&mut self, item: &[u8]) {
+        self.bytes.extend_from_slice(item);
+        self.ends.push(self.bytes.len());
+    }
+
+    fn pop(&mut self) {
+        self.ends.pop();
+        self.bytes.truncate(self.ends.last().copied().unwrap_or(0));
+    }
+
+    // encode `value` as the next item with `serializer`, returning whether it was `None`
+    fn serialize<T: ?Sized + Serialize>(&mut self, mut serializer: Serializer, value: &T) -> Result<bool> {
+        let start = self.bytes.len();
+        serializer.output = std::mem::take(&mut self.bytes);
+        let result = value.serialize(&mut serializer);
+        self.bytes = serializer.output;
+        if let Err(e) = result {
+            self.bytes.truncate(start);
+            return Err(e);
+        }
+        self.ends.push(self.bytes.len());
+        Ok(serializer.wrote_none)
+    }
+}
+
+impl From<Vec<Vec<u8>>> for Items {
+    fn from(items: Vec<Vec<u8>>) -> Self {
+        let mut flat = Items::with_capacity(items.len());
+        flat.bytes.reserve(items.iter().map(Vec::len).sum());
+        for item in &items {
+            flat.push(item);
+        }
+        flat
+    }
+}
+
+// write a compact array or object: header, byte length, items back to back, then the number of
+// items, both lengths as variable length integers (the number of items stored backwards)
+fn write_compact<'b, I>(output: &mut Vec<u8>, header: u8, items: I, items_size: usize, n_items: usize) where
+    I: Iterator<Item = &'b [u8]> {
+    let (byte_length_bytes, n_items_bytes) = compact_lengths(items_size, n_items);
+    output.reserve(1 + byte_length_bytes.len() + items_size + n_items_bytes.len());
+    output.push(header);
+    output.extend_from_slice(&byte_length_bytes);
+    for item in items {
+        output.extend_from_slice(item);
+    }
+    output.extend_from_slice(&n_items_bytes);
+}
+
+// encoded byte length and (backwards) number of items of a compact container holding
+// `items_size` bytes of items
+fn compact_lengths(items_size: usize, n_items: usize) -> (Vec<u8>, Vec<u8>) {
+    let mut n_items_bytes = Vec::new();
+    raw::write_varint(&mut n_items_bytes, n_items as u64);
+    n_items_bytes.reverse();
+
+    let mut byte_length_bytes = Vec::new();
+    raw::write_varint(&mut byte_length_bytes, raw::compact_byte_length(items_size, n_items) as u64);
+    (byte_length_bytes, n_items_bytes)
+}
+
 impl<'a> ser::Serializer for &'a mut Serializer {
     type Ok = ();
 
@@ -109,7 +689,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         if v >= 0 {
             self.serialize_unsigned_int(v as u64);
         } else {
-            self.serialize_negative_int(v as i64);
+            self.serialize_negative_int(v);
         }
         Ok(())
     }
@@ -139,6 +719,18 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        if !v.is_finite() {
+            match self.options.non_finite {
+                NonFinite::Allow => {},
+                NonFinite::Error => return Err(ErrorKind::NonFiniteDouble.into()),
+                NonFinite::Null => return self.serialize_unit(),
+            }
+        }
+        const MAX_SAFE_INT: f64 = (1u64 << 53) as f64;
+        if self.options.integral_doubles_as_ints && v.trunc() == v && v.abs() <= MAX_SAFE_INT
+            && !(v == 0.0 && v.is_sign_negative()) {
+            return self.serialize_i64(v as i64);
+        }
         self.output.push(0x1b);
         self.output.extend_from_slice(&v.to_bits().to_le_bytes());
         Ok(())
@@ -149,6 +741,11 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        if let Some(encoded) = self.options.dictionary.as_ref().and_then(|d| d.encode(v)) {
+            self.output.extend(encoded);
+            return Ok(());
+        }
+
         if v.is_empty() {
             self.output.push(0x40);
         } else {
@@ -166,19 +763,27 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
-        let b = v.len().to_le_bytes();
-        for bit in (0..7).rev() {
-            if b[bit] != 0x00 {
-                self.output.push(0xc0 + bit as u8);
-                self.output.extend_from_slice(&b[..bit + 1]);
-                break;
+        if self.raw_bytes {
+            let length = raw::value_byte_length(v)?;
+            if length != v.len() {
+                return Err(ErrorKind::TrailingBytes(v.len() - length).into());
             }
+            self.output.extend_from_slice(v);
+            return Ok(());
         }
+
+        // 0xc0 to 0xc7 for 1 to 8 byte lengths, followed by the data
+        let b = (v.len() as u64).to_le_bytes();
+        let n_bytes = b.iter().rposition(|b| *b != 0x00).map_or(1, |i| i + 1);
+        self.output.push(0xbf + n_bytes as u8);
+        self.output.extend_from_slice(&b[..n_bytes]);
+        self.output.extend_from_slice(v);
         Ok(())
     }
 
     // use null to represent no value
     fn serialize_none(self) -> Result<Self::Ok> {
+        self.wrote_none = true;
         self.output.push(0x18);
         Ok(())
     }
@@ -202,19 +807,74 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(())
     }
 
-    // same behaviour as json
+    // same behaviour as json, unless encoding by index
     fn serialize_unit_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str
     ) -> Result<Self::Ok> {
-        self.serialize_str(variant)
+        match self.options.unit_variants {
+            VariantEncoding::Name => self.serialize_str(variant),
+            VariantEncoding::Index => self.serialize_u32(variant_index),
+        }
     }
 
     // serialise as insignificant wrapper around data contained
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok> where
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Self::Ok> where
         T: ?Sized + Serialize {
+        if name == crate::UTC_DATE_TOKEN {
+            // milliseconds since the epoch, as a signed 64 bit integer
+            let mut inner = Serializer::child(&self.options);
+            value.serialize(&mut inner)?;
+            let ms: i64 = crate::from_bytes(&inner.output)?;
+            self.output.push(0x1c);
+            self.output.extend_from_slice(&ms.to_le_bytes());
+            return Ok(());
+        }
+        if let Some(encoding) = crate::number::token_encoding(name) {
+            // a number which should keep the encoding it was decoded from
+            let mut inner = Serializer::child(&self.options);
+            value.serialize(&mut inner)?;
+            if !Number::from_bytes(&inner.output)?.write_as(encoding, &mut self.output) {
+                self.output.append(&mut inner.output);
+            }
+            return Ok(());
+        }
+        if name == crate::MIN_KEY_TOKEN || name == crate::MAX_KEY_TOKEN {
+            self.output.push(if name == crate::MIN_KEY_TOKEN { 0x1e } else { 0x1f });
+            return Ok(());
+        }
+        if name == crate::RAW_TOKEN {
+            let mut inner = Serializer::child(&self.options);
+            inner.raw_bytes = true;
+            value.serialize(&mut inner)?;
+            self.output.append(&mut inner.output);
+            return Ok(());
+        }
+        if name == crate::TAGGED_TOKEN {
+            // serialized as a (tag, value) tuple, which is unwrapped into the tag and value
+            let mut inner = Serializer::child(&self.options);
+            value.serialize(&mut inner)?;
+            let (tag, value): (u64, crate::RawVPack) = crate::from_bytes(&inner.output)?;
+            match u8::try_from(tag) {
+                Ok(tag) => self.output.extend_from_slice(&[0xee, tag]),
+                Err(_) => {
+                    self.output.push(0xef);
+                    self.output.extend_from_slice(&tag.to_le_bytes());
+                }
+            }
+            self.output.extend_from_slice(value.as_bytes());
+            return Ok(());
+        }
+        if let Some(custom_types) = self.options.custom_types.clone().filter(|c| c.encodes(name)) {
+            let mut inner = Serializer::child(&self.options);
+            value.serialize(&mut inner)?;
+            if let Some(encoded) = custom_types.encode(name, &inner.output) {
+                self.output.extend(encoded?);
+            }
+            return Ok(());
+        }
         value.serialize(self)
     }
 
@@ -224,10 +884,11 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         unimplemented!()
     }
 
-    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
         let array_ser = ArraySerializer {
-            items: Vec::new(),
+            items: Items::with_capacity(len.unwrap_or(0)),
             output: &mut self.output,
+            options: &self.options,
         };
         Ok(array_ser)
     }
@@ -246,11 +907,12 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         unimplemented!()
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
         let map_ser = MapSerializer {
-            keys: Vec::new(),
-            values: Vec::new(),
+            keys: Items::with_capacity(len.unwrap_or(0)),
+            values: Items::with_capacity(len.unwrap_or(0)),
             output: &mut self.output,
+            options: &self.options,
         };
         Ok(map_ser)
     }
@@ -263,14 +925,14 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         unimplemented!()
     }
 
-    fn collect_str<T: ?Sized>(self, _value: &T) -> Result<Self::Ok> where
-        T: Display {
-        unimplemented!()
+    fn collect_str<T>(self, value: &T) -> Result<Self::Ok> where
+        T: ?Sized + Display {
+        self.serialize_str(&value.to_string())
     }
 }
 
 // Same thing but for tuple structs.
-impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
+impl ser::SerializeTupleStruct for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -295,7 +957,7 @@ impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
 //
 // So the `end` method in this impl is responsible for closing both the `]` and
 // the `}`.
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl ser::SerializeTupleVariant for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -313,7 +975,7 @@ impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
 
 // Structs are like maps in which the keys are constrained to be compile-time
 // constant strings.
-impl<'a> ser::SerializeStruct for &'a mut Serializer {
+impl ser::SerializeStruct for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -331,7 +993,7 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
 
 // Similar to `SerializeTupleVariant`, here the `end` method is responsible for
 // closing both of the curly braces opened by `serialize_struct_variant`.
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+impl ser::SerializeStructVariant for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -348,41 +1010,81 @@ impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
 }
 
 pub struct MapSerializer<'a> {
-    keys: Vec<Vec<u8>>,
-    values: Vec<Vec<u8>>,
+    keys: Items,
+    values: Items,
     output: &'a mut Vec<u8>,
+    options: &'a SerializerOptions,
 }
 
 impl <'a> MapSerializer<'a> {
     fn serialize_map_key<T>(&mut self, key: &T) -> Result<()> where
         T: ?Sized + Serialize {
-        let mut serializer = Serializer::default();
-        key.serialize(&mut serializer)?;
-        let header = match serializer.output.first() {
-            Some(header) => header,
-            None => return Err(Error::Message("Empty serialization".to_owned())),
+        let mut serializer = Serializer::child(self.options);
+        serializer.options.dictionary = None;
+        self.keys.serialize(serializer, key)?;
+        let encoded = self.keys.last();
+        let header = match encoded.first() {
+            Some(header) => *header,
+            None => {
+                self.keys.pop();
+                return Err(ErrorKind::Message("Empty serialization".to_owned()).into());
+            },
         };
 
-        if *header >= 0x40_u8 && *header <= 0xbf_u8 {
-            self.keys.push(serializer.output);
-            Ok(())
-        } else {
-            Err(Error::Message(format!("Cannot serialize type to map key: {}", header)))
+        match header {
+            0x40..=0xbf => {
+                let id = self.options.attribute_translator.as_ref()
+                    .and_then(|t| t.id(raw::read_str(encoded).ok()?));
+                if let Some(id) = id {
+                    self.keys.pop();
+                    self.keys.push(&to_bytes(&id)?);
+                }
+                Ok(())
+            },
+            // attribute ids, see `AttributeTranslator`
+            0x28..=0x39 if self.options.non_string_keys == NonStringKeys::Integer => Ok(()),
+            0x19 | 0x1a | 0x1b | 0x20..=0x3f if self.options.non_string_keys == NonStringKeys::Stringify => {
+                let key = match header {
+                    0x19 => "false".to_owned(),
+                    0x1a => "true".to_owned(),
+                    0x1b => crate::from_bytes::<f64>(encoded)?.to_string(),
+                    0x28..=0x39 => crate::from_bytes::<u64>(encoded)?.to_string(),
+                    _ => crate::from_bytes::<i64>(encoded)?.to_string(),
+                };
+                self.keys.pop();
+                self.keys.push(&to_bytes(&key)?);
+                Ok(())
+            },
+            _ => {
+                self.keys.pop();
+                Err(ErrorKind::Message(format!("Cannot serialize type to map key: {}", header)).into())
+            },
         }
     }
 
-    fn serialize_map_value<T: ?Sized>(&mut self, value: &T) -> Result<()> where
-        T: Serialize {
-        let mut serializer = Serializer::default();
-        value.serialize(&mut serializer)?;
-        self.values.push(serializer.output);
+    fn serialize_map_value<T>(&mut self, value: &T) -> Result<()> where
+        T: ?Sized + Serialize {
+        self.values.serialize(Serializer::child(self.options), value)?;
         Ok(())
     }
 
-    fn end_map(mut self) -> Result<()> {
+    fn serialize_struct_field<T>(&mut self, key: &'static str, value: &T) -> Result<()> where
+        T: ?Sized + Serialize {
+        let wrote_none = self.values.serialize(Serializer::child(self.options), value)?;
+        if wrote_none && self.options.omit_none_fields {
+            self.values.pop();
+            return Ok(());
+        }
+        self.serialize_map_key(key)
+    }
+
+    fn end_map(self) -> Result<()> {
         if self.keys.is_empty() {
             assert!(self.values.is_empty());
-            self.output.push(0x0a);
+            match self.options.empty_collections {
+                EmptyEncoding::Short => self.output.push(0x0a),
+                EmptyEncoding::Compact => self.output.extend_from_slice(&[0x14, 0x03, 0x00]),
+            }
             return Ok(());
         }
 
@@ -393,73 +1095,71 @@ impl <'a> MapSerializer<'a> {
         // 1/2/4/8 bytes number of items
         // key/value pairs
         // 1/2/4/8 byte offsets indexing into total data structure
-        let mut item_size = 0;
-        for key in &self.keys {
-            item_size += key.len();
-        }
-        for value in &self.values {
-            item_size += value.len();
-        }
-
+        let item_size = self.keys.size() + self.values.size();
         let n_items = self.keys.len();
 
+        if self.options.compact_objects {
+            let members = self.keys.iter().zip(self.values.iter()).flat_map(|(key, value)| [key, value]);
+            write_compact(self.output, 0x14, members, item_size, n_items);
+            return Ok(());
+        }
+
         // try with 1 byte, then 2, then 4, then 8
-        for n_bytes in &[1, 2, 4, 8] {
-            // header, bytesize, nritems, <items>, <indexes>
-            let needed_size: usize = 1 + n_bytes + n_bytes + item_size + n_items * n_bytes;
+        let min_bytes = self.options.index_width.min_bytes();
+        for n_bytes in [1, 2, 4, 8].iter().filter(|n| **n >= min_bytes) {
+            // header, bytesize, nritems, <padding>, <items>, <indexes>, with nritems after the
+            // indexes for 8 bytes
+            let header_size = if *n_bytes == 8 { 1 + 8 } else { 1 + 2 * n_bytes };
+            let padding = if self.options.padding { 1 + 8 - header_size } else { 0 };
+            let needed_size: usize = 1 + n_bytes + n_bytes + padding + item_size + n_items * n_bytes;
+
+            if *n_bytes == 8 || needed_size < 1 << (n_bytes * 8) {
+                self.output.reserve(needed_size);
+                // unsorted headers are 4 after their sorted equivalents
+                let header_offset = if self.options.unsorted_objects { 4 } else { 0 };
 
-            if needed_size < 2_usize.pow((n_bytes * 8) as u32) {
                 // add header
                 match n_bytes {
                     1 => {
-                        self.output.push(0x0b);
+                        self.output.push(0x0b + header_offset);
                         self.output.extend_from_slice(&(needed_size as u8).to_le_bytes()); // byte size
                         self.output.extend_from_slice(&(n_items as u8).to_le_bytes()); // num items
                     },
                     2 => {
-                        self.output.push(0x0c);
+                        self.output.push(0x0c + header_offset);
                         self.output.extend_from_slice(&(needed_size as u16).to_le_bytes()); // byte size
                         self.output.extend_from_slice(&(n_items as u16).to_le_bytes()); // num items
                     },
                     4 => {
-                        self.output.push(0x0d);
+                        self.output.push(0x0d + header_offset);
                         self.output.extend_from_slice(&(needed_size as u32).to_le_bytes()); // byte size
                         self.output.extend_from_slice(&(n_items as u32).to_le_bytes()); // num items
                     },
                     8 => {
-                        self.output.push(0x0e);
+                        // num items is written after the index
+                        self.output.push(0x0e + header_offset);
                         self.output.extend_from_slice(&(needed_size as u64).to_le_bytes()); // byte size
-                        self.output.extend_from_slice(&(n_items as u64).to_le_bytes()); // num items
                     },
                     _ => panic!("Unexpected byte size"),
                 }
 
-                let sorted_offset_idx: Vec<usize> = {
-                    // build vec of keys and index, then sort them, use for indexing into values
-                    let mut sorted_keys: Vec<(usize, &Vec<u8>)> = self.keys
-                        .iter()
-                        .enumerate()
-                        .collect();
-                    sorted_keys.sort_by_key(|(_i, v)| v.clone());
-
-                    sorted_keys.iter()
-                        .map(|(i, _v)| *i)
-                        .collect()
-                };
+                // positions of the members in key order, comparing the keys in place
+                let mut sorted_offset_idx: Vec<usize> = (0..n_items).collect();
+                if !self.options.unsorted_objects {
+                    let keys = &self.keys;
+                    sorted_offset_idx.sort_by(|a, b| raw::key_order(keys.get(*a), keys.get(*b)));
+                }
 
+                self.output.resize(self.output.len() + padding, 0x00);
                 let mut offsets = Vec::with_capacity(n_items);
-
-                // header, byte size, nritems
-                let mut offset = 1 + 2 * n_bytes;
+                let mut offset = header_size + padding;
 
                 // write items in given order
-                for i in 0..n_items {
+                for (key, value) in self.keys.iter().zip(self.values.iter()) {
                     offsets.push(offset);
-                    let mut key = self.keys.get_mut(i).unwrap();
-                    let mut value = self.values.get_mut(i).unwrap();
                     offset += key.len() + value.len();
-                    self.output.append(&mut key);
-                    self.output.append(&mut value);
+                    self.output.extend_from_slice(key);
+                    self.output.extend_from_slice(value);
                 }
                 assert_eq!(offsets.len(), sorted_offset_idx.len());
 
@@ -484,6 +1184,7 @@ impl <'a> MapSerializer<'a> {
                         for idx in sorted_offset_idx {
                             self.output.extend_from_slice(&(offsets[idx] as u64).to_le_bytes()); // num items
                         }
+                        self.output.extend_from_slice(&(n_items as u64).to_le_bytes());
                     },
                     _ => panic!("Unexpected byte length"),
                 }
@@ -499,11 +1200,9 @@ impl <'a> ser::SerializeStruct for MapSerializer<'a> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<Self::Ok> where
-        T: Serialize {
-        self.serialize_map_key(key)?;
-        self.serialize_map_value(value)?;
-        Ok(())
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<Self::Ok> where
+        T: ?Sized + Serialize {
+        self.serialize_struct_field(key, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
@@ -520,8 +1219,8 @@ impl <'a> ser::SerializeMap for MapSerializer<'a> {
         self.serialize_map_key(key)
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<Self::Ok> where
-        T: Serialize {
+    fn serialize_value<T>(&mut self, value: &T) -> Result<Self::Ok> where
+        T: ?Sized + Serialize {
         self.serialize_map_value(value)
     }
 
@@ -532,46 +1231,47 @@ impl <'a> ser::SerializeMap for MapSerializer<'a> {
 
 
 pub struct ArraySerializer<'a> {
-    items: Vec<Vec<u8>>,
+    items: Items,
     output: &'a mut Vec<u8>,
+    options: &'a SerializerOptions,
 }
 
 impl<'a> ArraySerializer<'a> {
     fn serialize_array_element<T>(&mut self, value: &T) -> Result<()> where
         T: ?Sized + Serialize {
-        let mut serializer = Serializer::default();
-        value.serialize(&mut serializer)?;
-        self.items.push(serializer.output);
+        self.items.serialize(Serializer::child(self.options), value)?;
         Ok(())
     }
 
-    fn end_array(mut self) -> Result<()> {
+    fn end_array(self) -> Result<()> {
         if self.items.is_empty() {
-            self.output.push(0x01);
+            match self.options.empty_collections {
+                EmptyEncoding::Short => self.output.push(0x01),
+                EmptyEncoding::Compact => self.output.extend_from_slice(&[0x13, 0x03, 0x00]),
+            }
+        } else if self.options.compact_arrays {
+            write_compact(self.output, 0x13, self.items.iter(), self.items.size(), self.items.len());
         } else {
-            let elem_len = self.items[0].len();
-            let same_length = self.items
+            let elem_len = self.items.get(0).len();
+            let same_length = self.options.index_width == IndexWidth::Smallest && self.items
                 .iter()
-                .all(|ref v| v.len() == elem_len);
+                .all(|v| v.len() == elem_len);
             if same_length {
-                let byte_size = self.items.len() * elem_len;
-                if byte_size < 2_usize.pow(8) - 2 {
-                    self.output.push(0x02);
-                    self.output.extend_from_slice(&((byte_size + 2) as u8).to_le_bytes());
-                } else if byte_size < 2_usize.pow(16) - 3 {
-                    self.output.push(0x03);
-                    self.output.extend_from_slice(&((byte_size + 3) as u16).to_le_bytes());
-                } else if byte_size < 2_usize.pow(32) - 4 {
-                    self.output.push(0x04);
-                    self.output.extend_from_slice(&((byte_size + 4) as u32).to_le_bytes());
-                } else {
-                    self.output.push(0x05);
-                    self.output.extend_from_slice(&((byte_size + 5) as u64).to_le_bytes());
-                };
-
-                for item in &mut self.items.iter_mut() {
-                    self.output.append(item);
+                let byte_size = self.items.size();
+                for n_bytes in [1, 2, 4, 8] {
+                    // header, bytesize, <padding>, <items>
+                    let padding = if self.options.padding { 8 - n_bytes } else { 0 };
+                    let needed_size = 1 + n_bytes + padding + byte_size;
+                    if n_bytes == 8 || needed_size < 1 << (n_bytes * 8) {
+                        self.output.reserve(needed_size);
+                        self.output.push(0x02 + n_bytes.trailing_zeros() as u8);
+                        self.output.extend_from_slice(&(needed_size as u64).to_le_bytes()[..n_bytes]);
+                        self.output.resize(self.output.len() + padding, 0x00);
+                        break;
+                    }
                 }
+
+                self.output.extend_from_slice(&self.items.bytes);
             } else {
                 let n_items = self.items.len();
 
@@ -580,17 +1280,19 @@ impl<'a> ArraySerializer<'a> {
                 // 1/2/4/8 bytes number of items
                 // data items
                 // 1/2/4/8 byte offsets indexing into total data structure
-                let mut item_size = 0;
-                for item in &self.items {
-                    item_size += item.len();
-                }
+                let item_size = self.items.size();
 
                 // try with 1 byte, then 2, then 4, then 8
-                for n_bytes in &[1, 2, 4, 8] {
-                    // header, bytesize, nritems, <items>, <indexes>
-                    let needed_size: usize = 1 + n_bytes + n_bytes + item_size + n_items * n_bytes;
-
-                    if needed_size < 2_usize.pow((n_bytes * 8) as u32) {
+                let min_bytes = self.options.index_width.min_bytes();
+                for n_bytes in [1, 2, 4, 8].iter().filter(|n| **n >= min_bytes) {
+                    // header, bytesize, nritems, <padding>, <items>, <indexes>, with nritems after
+                    // the indexes for 8 bytes
+                    let header_size = if *n_bytes == 8 { 1 + 8 } else { 1 + 2 * n_bytes };
+                    let padding = if self.options.padding { 1 + 8 - header_size } else { 0 };
+                    let needed_size: usize = 1 + n_bytes + n_bytes + padding + item_size + n_items * n_bytes;
+
+                    if *n_bytes == 8 || needed_size < 1 << (n_bytes * 8) {
+                        self.output.reserve(needed_size);
                         // add header
                         match n_bytes {
                             1 => {
@@ -609,21 +1311,16 @@ impl<'a> ArraySerializer<'a> {
                                 self.output.extend_from_slice(&(n_items as u32).to_le_bytes()); // num items
                             },
                             8 => {
+                                // num items is written after the index
                                 self.output.push(0x09);
                                 self.output.extend_from_slice(&(needed_size as u64).to_le_bytes()); // byte size
-                                self.output.extend_from_slice(&(n_items as u64).to_le_bytes()); // num items
                             },
                             _ => panic!("Unexpected byte size"),
                         }
 
-                        let mut offsets = Vec::with_capacity(n_items);
-                        let mut offset = 1 + 2 * n_bytes;
-
-                        for item in &mut self.items.iter_mut() {
-                            offsets.push(offset);
-                            offset += item.len();
-                            self.output.append(item);
-                        }
+                        self.output.resize(self.output.len() + padding, 0x00);
+                        let offsets = self.items.starts().map(|start| header_size + padding + start);
+                        self.output.extend_from_slice(&self.items.bytes);
 
                         match n_bytes {
                             1 =>  {
@@ -645,6 +1342,7 @@ impl<'a> ArraySerializer<'a> {
                                 for offset in offsets {
                                     self.output.extend_from_slice(&(offset as u64).to_le_bytes()); // num items
                                 }
+                                self.output.extend_from_slice(&(n_items as u64).to_le_bytes());
                             },
                             _ => panic!("Unexpected byte length"),
                         }
@@ -741,8 +1439,8 @@ mod tests {
         assert_eq!(to_bytes(&9i8).unwrap(), &[0x39]);
 
         // signed int, little endian, 1 byte
-        assert_eq!(to_bytes(&std::i8::MIN).unwrap(), &[0x20, 0x80]);
-        assert_eq!(to_bytes(&std::i8::MAX).unwrap(), &[0x28, 0x7f]);
+        assert_eq!(to_bytes(&i8::MIN).unwrap(), &[0x20, 0x80]);
+        assert_eq!(to_bytes(&i8::MAX).unwrap(), &[0x28, 0x7f]);
         assert_eq!(to_bytes(&-7i8).unwrap(), &[0x20, 0xf9]);
         assert_eq!(to_bytes(&10i8).unwrap(), &[0x28, 0x0a]);
     }
@@ -770,14 +1468,14 @@ mod tests {
         assert_eq!(to_bytes(&9i16).unwrap(), &[0x39]);
 
         // signed int, little endian, 1 byte
-        assert_eq!(to_bytes(&(std::i8::MIN as i16)).unwrap(), &[0x20, 0x80]);
-        assert_eq!(to_bytes(&(std::i8::MAX as i16)).unwrap(), &[0x28, 0x7f]);
+        assert_eq!(to_bytes(&(i8::MIN as i16)).unwrap(), &[0x20, 0x80]);
+        assert_eq!(to_bytes(&(i8::MAX as i16)).unwrap(), &[0x28, 0x7f]);
         assert_eq!(to_bytes(&-7i16).unwrap(), &[0x20, 0xf9]);
         assert_eq!(to_bytes(&10i16).unwrap(), &[0x28, 0x0a]);
 
         // signed int, little endian, 2 bytes
-        assert_eq!(to_bytes(&std::i16::MIN).unwrap(), &[0x21, 0x00, 0x80]);
-        assert_eq!(to_bytes(&std::i16::MAX).unwrap(), &[0x29, 0xff, 0x7f]);
+        assert_eq!(to_bytes(&i16::MIN).unwrap(), &[0x21, 0x00, 0x80]);
+        assert_eq!(to_bytes(&i16::MAX).unwrap(), &[0x29, 0xff, 0x7f]);
         assert_eq!(to_bytes(&-12345i16).unwrap(), &[0x21, 0xc7, 0xcf]);
         assert_eq!(to_bytes(&12345i16).unwrap(), &[0x29, 0x39, 0x30]);
     }
@@ -805,14 +1503,14 @@ mod tests {
         assert_eq!(to_bytes(&9i32).unwrap(), &[0x39]);
 
         // signed int, little endian, 1 byte
-        assert_eq!(to_bytes(&(std::i8::MIN as i32)).unwrap(), &[0x20, 0x80]);
-        assert_eq!(to_bytes(&(std::i8::MAX as i32)).unwrap(), &[0x28, 0x7f]);
+        assert_eq!(to_bytes(&(i8::MIN as i32)).unwrap(), &[0x20, 0x80]);
+        assert_eq!(to_bytes(&(i8::MAX as i32)).unwrap(), &[0x28, 0x7f]);
         assert_eq!(to_bytes(&-7i32).unwrap(), &[0x20, 0xf9]);
         assert_eq!(to_bytes(&10i32).unwrap(), &[0x28, 0x0a]);
 
         // signed int, little endian, 2 bytes
-        assert_eq!(to_bytes(&std::i16::MIN).unwrap(), &[0x21, 0x00, 0x80]);
-        assert_eq!(to_bytes(&std::i16::MAX).unwrap(), &[0x29, 0xff, 0x7f]);
+        assert_eq!(to_bytes(&i16::MIN).unwrap(), &[0x21, 0x00, 0x80]);
+        assert_eq!(to_bytes(&i16::MAX).unwrap(), &[0x29, 0xff, 0x7f]);
         assert_eq!(to_bytes(&-12345i32).unwrap(), &[0x21, 0xc7, 0xcf]);
         assert_eq!(to_bytes(&12345i32).unwrap(), &[0x29, 0x39, 0x30]);
 
@@ -821,8 +1519,8 @@ mod tests {
         assert_eq!(to_bytes(&I24_MIN).unwrap(), &[0x22, 0x00, 0x00, 0x80]);
 
         // signed int, little endian, 4 bytes
-        assert_eq!(to_bytes(&std::i32::MIN).unwrap(), &[0x23, 0x00, 0x00, 0x00, 0x80]);
-        assert_eq!(to_bytes(&std::i32::MAX).unwrap(), &[0x2b, 0xff, 0xff, 0xff, 0x7f]);
+        assert_eq!(to_bytes(&i32::MIN).unwrap(), &[0x23, 0x00, 0x00, 0x00, 0x80]);
+        assert_eq!(to_bytes(&i32::MAX).unwrap(), &[0x2b, 0xff, 0xff, 0xff, 0x7f]);
     }
 
     #[test]
@@ -848,14 +1546,14 @@ mod tests {
         assert_eq!(to_bytes(&9i64).unwrap(), &[0x39]);
 
         // signed int, little endian, 1 byte
-        assert_eq!(to_bytes(&(std::i8::MIN as i64)).unwrap(), &[0x20, 0x80]);
-        assert_eq!(to_bytes(&(std::i8::MAX as i64)).unwrap(), &[0x28, 0x7f]);
+        assert_eq!(to_bytes(&(i8::MIN as i64)).unwrap(), &[0x20, 0x80]);
+        assert_eq!(to_bytes(&(i8::MAX as i64)).unwrap(), &[0x28, 0x7f]);
         assert_eq!(to_bytes(&-7i64).unwrap(), &[0x20, 0xf9]);
         assert_eq!(to_bytes(&10i64).unwrap(), &[0x28, 0x0a]);
 
         // signed int, little endian, 2 bytes
-        assert_eq!(to_bytes(&std::i16::MIN).unwrap(), &[0x21, 0x00, 0x80]);
-        assert_eq!(to_bytes(&std::i16::MAX).unwrap(), &[0x29, 0xff, 0x7f]);
+        assert_eq!(to_bytes(&i16::MIN).unwrap(), &[0x21, 0x00, 0x80]);
+        assert_eq!(to_bytes(&i16::MAX).unwrap(), &[0x29, 0xff, 0x7f]);
         assert_eq!(to_bytes(&-12345i64).unwrap(), &[0x21, 0xc7, 0xcf]);
         assert_eq!(to_bytes(&12345i64).unwrap(), &[0x29, 0x39, 0x30]);
 
@@ -864,8 +1562,8 @@ mod tests {
         assert_eq!(to_bytes(&I24_MAX).unwrap(), &[0x2a, 0xff, 0xff, 0x7f]);
 
         // signed int, little endian, 4 bytes
-        assert_eq!(to_bytes(&std::i32::MIN).unwrap(), &[0x23, 0x00, 0x00, 0x00, 0x80]);
-        assert_eq!(to_bytes(&std::i32::MAX).unwrap(), &[0x2b, 0xff, 0xff, 0xff, 0x7f]);
+        assert_eq!(to_bytes(&i32::MIN).unwrap(), &[0x23, 0x00, 0x00, 0x00, 0x80]);
+        assert_eq!(to_bytes(&i32::MAX).unwrap(), &[0x2b, 0xff, 0xff, 0xff, 0x7f]);
 
         // signed int, little endian, 5 bytes
         assert_eq!(to_bytes(&I40_MIN).unwrap(), &[0x24, 0x00, 0x00, 0x00, 0x00, 0x80]);
@@ -880,8 +1578,8 @@ mod tests {
         assert_eq!(to_bytes(&I56_MAX).unwrap(), &[0x2e, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
 
         // signed int, little endian, 8 bytes
-        assert_eq!(to_bytes(&std::i64::MIN).unwrap(), &[0x27, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80]);
-        assert_eq!(to_bytes(&std::i64::MAX).unwrap(), &[0x2f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
+        assert_eq!(to_bytes(&i64::MIN).unwrap(), &[0x27, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80]);
+        assert_eq!(to_bytes(&i64::MAX).unwrap(), &[0x2f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
     }
 
     #[test]
@@ -900,7 +1598,7 @@ mod tests {
 
         // uint, little endian, 1 byte
         assert_eq!(to_bytes(&10u8).unwrap(), &[0x28, 0x0a]);
-        assert_eq!(to_bytes(&std::u8::MAX).unwrap(), &[0x28, 0xff]);
+        assert_eq!(to_bytes(&u8::MAX).unwrap(), &[0x28, 0xff]);
     }
 
     #[test]
@@ -918,11 +1616,11 @@ mod tests {
         assert_eq!(to_bytes(&9u16).unwrap(), &[0x39]);
 
         // uint, little endian, 1 byte
-        assert_eq!(to_bytes(&(std::u8::MAX as u16)).unwrap(), &[0x28, 0xff]);
+        assert_eq!(to_bytes(&(u8::MAX as u16)).unwrap(), &[0x28, 0xff]);
         assert_eq!(to_bytes(&10u16).unwrap(), &[0x28, 0x0a]);
 
         // uint, little endian, 2 bytes
-        assert_eq!(to_bytes(&std::u16::MAX).unwrap(), &[0x29, 0xff, 0xff]);
+        assert_eq!(to_bytes(&u16::MAX).unwrap(), &[0x29, 0xff, 0xff]);
         assert_eq!(to_bytes(&12345u16).unwrap(), &[0x29, 0x39, 0x30]);
     }
 
@@ -941,18 +1639,18 @@ mod tests {
         assert_eq!(to_bytes(&9u32).unwrap(), &[0x39]);
 
         // uint, little endian, 1 byte
-        assert_eq!(to_bytes(&(std::u8::MAX as u32)).unwrap(), &[0x28, 0xff]);
+        assert_eq!(to_bytes(&(u8::MAX as u32)).unwrap(), &[0x28, 0xff]);
         assert_eq!(to_bytes(&10u32).unwrap(), &[0x28, 0x0a]);
 
         // uint, little endian, 2 bytes
-        assert_eq!(to_bytes(&std::u16::MAX).unwrap(), &[0x29, 0xff, 0xff]);
+        assert_eq!(to_bytes(&u16::MAX).unwrap(), &[0x29, 0xff, 0xff]);
         assert_eq!(to_bytes(&12345u32).unwrap(), &[0x29, 0x39, 0x30]);
 
         // uint, little endian, 3 bytes
         assert_eq!(to_bytes(&I24_MAX).unwrap(), &[0x2a, 0xff, 0xff, 0x7f]);
 
         // uint, little endian, 4 bytes
-        assert_eq!(to_bytes(&std::u32::MAX).unwrap(), &[0x2b, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(to_bytes(&u32::MAX).unwrap(), &[0x2b, 0xff, 0xff, 0xff, 0xff]);
     }
 
     #[test]
@@ -970,18 +1668,18 @@ mod tests {
         assert_eq!(to_bytes(&9u64).unwrap(), &[0x39]);
 
         // uint, little endian, 1 byte
-        assert_eq!(to_bytes(&(std::u8::MAX as u64)).unwrap(), &[0x28, 0xff]);
+        assert_eq!(to_bytes(&(u8::MAX as u64)).unwrap(), &[0x28, 0xff]);
         assert_eq!(to_bytes(&10u64).unwrap(), &[0x28, 0x0a]);
 
         // uint, little endian, 2 bytes
-        assert_eq!(to_bytes(&std::u16::MAX).unwrap(), &[0x29, 0xff, 0xff]);
+        assert_eq!(to_bytes(&u16::MAX).unwrap(), &[0x29, 0xff, 0xff]);
         assert_eq!(to_bytes(&12345u64).unwrap(), &[0x29, 0x39, 0x30]);
 
         // uint, little endian, 3 bytes
         assert_eq!(to_bytes(&U24_MAX).unwrap(), &[0x2a, 0xff, 0xff, 0xff]);
 
         // uint, little endian, 4 bytes
-        assert_eq!(to_bytes(&std::u32::MAX).unwrap(), &[0x2b, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(to_bytes(&u32::MAX).unwrap(), &[0x2b, 0xff, 0xff, 0xff, 0xff]);
 
         // uint, little endian, 5 bytes
         assert_eq!(to_bytes(&U40_MAX).unwrap(), &[0x2c, 0xff, 0xff, 0xff, 0xff, 0xff]);
@@ -993,7 +1691,7 @@ mod tests {
         assert_eq!(to_bytes(&U56_MAX).unwrap(), &[0x2e, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
 
         // uint, little endian, 8 bytes
-        assert_eq!(to_bytes(&std::u64::MAX).unwrap(), &[0x2f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(to_bytes(&u64::MAX).unwrap(), &[0x2f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
     }
 
     #[test]
@@ -1068,7 +1766,14 @@ mod tests {
 
     #[test]
     fn unit_variant() {
-        // TODO
+        #[derive(Debug, Serialize, serde::Deserialize, PartialEq)]
+        enum Status { Active, Inactive }
+        assert_eq!(to_bytes(&Status::Inactive).unwrap(), &[0x48, 0x49, 0x6e, 0x61, 0x63, 0x74, 0x69, 0x76, 0x65]);
+
+        let options = SerializerOptions::new().unit_variants(VariantEncoding::Index);
+        let data = to_bytes_with_options(&vec![Status::Active, Status::Inactive], &options).unwrap();
+        assert_eq!(data, &[0x02, 0x04, 0x30, 0x31]);
+        assert_eq!(crate::from_bytes::<Vec<Status>>(&data).unwrap(), vec![Status::Active, Status::Inactive]);
     }
 
     #[test]
@@ -1129,6 +1834,402 @@ mod tests {
         assert_eq!(to_bytes(&a).unwrap(), &[0x06, 0x08, 0x02, 0x31, 0x41, 0x61, 0x03, 0x04]);
     }
 
+    #[test]
+    fn empty_collections_compact() {
+        let options = SerializerOptions::new().empty_collections(EmptyEncoding::Compact);
+        let a: Vec<u32> = Vec::new();
+        assert_eq!(to_bytes_with_options(&a, &options).unwrap(), &[0x13, 0x03, 0x00]);
+        let m: HashMap<String, u32> = HashMap::new();
+        assert_eq!(to_bytes_with_options(&m, &options).unwrap(), &[0x14, 0x03, 0x00]);
+
+        // applies to nested values too
+        let a: Vec<Vec<u32>> = vec![Vec::new(), Vec::new()];
+        assert_eq!(to_bytes_with_options(&a, &options).unwrap(), &[0x02, 0x08, 0x13, 0x03, 0x00, 0x13, 0x03, 0x00]);
+        assert_eq!(crate::from_bytes::<Vec<Vec<u32>>>(&to_bytes_with_options(&a, &options).unwrap()).unwrap(), a);
+    }
+
+    #[test]
+    fn attribute_translator() {
+        let translator = Arc::new(AttributeTranslator::arangodb());
+        let options = SerializerOptions::new().attribute_translator(translator.clone());
+        let bytes = to_bytes_with_options(&json!({"_key": "a", "name": "b"}), &options).unwrap();
+        assert_eq!(bytes, &[0x0b, 0x0f, 0x02, 0x31, 0x41, 0x61, 0x44, 0x6e, 0x61, 0x6d, 0x65, 0x41, 0x62, 0x03, 0x06]);
+        assert_eq!(crate::to_json_bytes(&bytes).unwrap(), br#"{"1":"a","name":"b"}"#);
+
+        let mut de = crate::Deserializer::from_bytes(&bytes).with_attribute_translator(translator);
+        let value = <serde_json::Value as serde::Deserialize>::deserialize(&mut de).unwrap();
+        assert_eq!(value, json!({"_key": "a", "name": "b"}));
+
+        // integer keys are written as they are
+        let map: HashMap<u8, bool> = vec![(1, true), (200, false)].into_iter().collect();
+        let bytes = to_bytes(&map).unwrap();
+        assert_eq!(crate::from_bytes::<HashMap<u8, bool>>(&bytes).unwrap(), map);
+        assert!(to_bytes(&vec![(-1, true)].into_iter().collect::<HashMap<i8, bool>>()).is_err());
+    }
+
+    #[test]
+    fn non_string_keys() {
+        let map: HashMap<u64, &str> = vec![(1, "a"), (1000, "b")].into_iter().collect();
+        let bytes = to_bytes(&map).unwrap();
+        assert_eq!(crate::to_json_bytes(&bytes).unwrap().len(), r#"{"1":"a","1000":"b"}"#.len());
+        assert_eq!(crate::from_bytes::<HashMap<u64, &str>>(&bytes).unwrap(), map);
+
+        let options = SerializerOptions::new().non_string_keys(NonStringKeys::Error);
+        assert!(to_bytes_with_options(&map, &options).is_err());
+
+        let options = SerializerOptions::new().non_string_keys(NonStringKeys::Stringify);
+        let bytes = to_bytes_with_options(&map, &options).unwrap();
+        assert_eq!(crate::from_bytes::<HashMap<String, &str>>(&bytes).unwrap()["1000"], "b");
+        assert_eq!(crate::from_bytes::<HashMap<u64, &str>>(&bytes).unwrap(), map);
+
+        let map: HashMap<i32, bool> = vec![(-5, true), (7, false)].into_iter().collect();
+        assert!(to_bytes(&map).is_err());
+        let bytes = to_bytes_with_options(&map, &options).unwrap();
+        assert!(crate::from_bytes::<HashMap<String, bool>>(&bytes).unwrap()["-5"]);
+        assert_eq!(crate::from_bytes::<HashMap<i32, bool>>(&bytes).unwrap(), map);
+
+        let map: HashMap<bool, u8> = vec![(true, 1)].into_iter().collect();
+        let bytes = to_bytes_with_options(&map, &options).unwrap();
+        assert_eq!(crate::to_json_bytes(&bytes).unwrap(), br#"{"true":1}"#);
+        assert_eq!(crate::from_bytes::<HashMap<bool, u8>>(&bytes).unwrap(), map);
+        assert!(crate::from_bytes::<HashMap<u8, u8>>(&bytes).is_err());
+
+        let map: std::collections::BTreeMap<String, f64> = vec![("2.5".to_owned(), 1.0)].into_iter().collect();
+        let bytes = to_bytes(&map).unwrap();
+        assert_eq!(crate::from_bytes::<HashMap<String, f64>>(&bytes).unwrap()["2.5"], 1.0);
+    }
+
+    #[test]
+    fn integral_doubles_as_ints() {
+        let options = SerializerOptions::new().integral_doubles_as_ints(true);
+        assert_eq!(to_bytes_with_options(&3.0f64, &options).unwrap(), &[0x33]);
+        assert_eq!(to_bytes_with_options(&-2.0f64, &options).unwrap(), &[0x3e]);
+        assert_eq!(to_bytes_with_options(&1000.0f32, &options).unwrap(), &[0x29, 0xe8, 0x03]);
+        assert_eq!(to_bytes_with_options(&-1000.0f64, &options).unwrap(), &[0x21, 0x18, 0xfc]);
+        assert_eq!(to_bytes_with_options(&1.5f64, &options).unwrap(), to_bytes(&1.5f64).unwrap());
+        assert_eq!(to_bytes_with_options(&-0.0f64, &options).unwrap(), to_bytes(&-0.0f64).unwrap());
+        assert_eq!(to_bytes_with_options(&1e300f64, &options).unwrap(), to_bytes(&1e300f64).unwrap());
+        assert_eq!(to_bytes_with_options(&f64::INFINITY, &options).unwrap(), to_bytes(&f64::INFINITY).unwrap());
+        assert_eq!(to_bytes_with_options(&9007199254740992.0f64, &options).unwrap()[0], 0x2e);
+
+        let values = vec![0.0, 12.0, -7.0, 2.5, 9007199254740992.0];
+        let bytes = to_bytes_with_options(&values, &options).unwrap();
+        assert!(bytes.len() < to_bytes(&values).unwrap().len());
+        assert_eq!(crate::from_bytes::<Vec<f64>>(&bytes).unwrap(), values);
+    }
+
+    #[test]
+    fn non_finite() {
+        let values = vec![1.5, f64::NAN, f64::INFINITY, f64::NEG_INFINITY];
+        let bytes = to_bytes(&values).unwrap();
+        let decoded: Vec<f64> = crate::from_bytes(&bytes).unwrap();
+        assert!(decoded[1].is_nan());
+        assert_eq!(decoded[2..], [f64::INFINITY, f64::NEG_INFINITY]);
+
+        let options = SerializerOptions::new().non_finite(NonFinite::Null);
+        let bytes = to_bytes_with_options(&values, &options).unwrap();
+        assert_eq!(crate::from_bytes::<Vec<Option<f64>>>(&bytes).unwrap(), vec![Some(1.5), None, None, None]);
+
+        let options = SerializerOptions::new().non_finite(NonFinite::Error);
+        assert_eq!(to_bytes_with_options(&values, &options), Err(ErrorKind::NonFiniteDouble.into()));
+        assert_eq!(to_bytes_with_options(&1.5f32, &options).unwrap(), to_bytes(&1.5f64).unwrap());
+        assert_eq!(to_bytes_with_options(&f32::NAN, &options), Err(ErrorKind::NonFiniteDouble.into()));
+    }
+
+    #[test]
+    fn index_width() {
+        let options = SerializerOptions::new().index_width(IndexWidth::Four);
+        let bytes = to_bytes_with_options(&vec![1, 2], &options).unwrap();
+        assert_eq!(bytes, &[0x08, 0x13, 0, 0, 0, 0x02, 0, 0, 0, 0x31, 0x32, 0x09, 0, 0, 0, 0x0a, 0, 0, 0]);
+        assert_eq!(crate::from_bytes::<Vec<u8>>(&bytes).unwrap(), vec![1, 2]);
+
+        let bytes = to_bytes_with_options(&json!({"a": 1}), &options).unwrap();
+        assert_eq!(bytes, &[0x0d, 0x10, 0, 0, 0, 0x01, 0, 0, 0, 0x41, 0x61, 0x31, 0x09, 0, 0, 0]);
+
+        // number of items comes after the index table
+        let options = SerializerOptions::new().index_width(IndexWidth::Eight);
+        let bytes = to_bytes_with_options(&vec![1, 2], &options).unwrap();
+        assert_eq!(bytes, &[0x09, 0x23, 0, 0, 0, 0, 0, 0, 0, 0x31, 0x32, 0x09, 0, 0, 0, 0, 0, 0, 0, 0x0a, 0, 0, 0, 0, 0, 0, 0, 0x02, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(crate::from_bytes::<Vec<u8>>(&bytes).unwrap(), vec![1, 2]);
+
+        let value = json!({"a": [1, {"b": "c"}], "d": null});
+        for options in [options.clone(), options.unsorted_objects(true)] {
+            let bytes = to_bytes_with_options(&value, &options).unwrap();
+            assert_eq!(bytes[0] & 0x03, 0x02);
+            assert_eq!(crate::from_bytes::<serde_json::Value>(&bytes).unwrap(), value);
+            assert_eq!(crate::to_json_bytes(&bytes).unwrap(), br#"{"a":[1,{"b":"c"}],"d":null}"#);
+        }
+    }
+
+    #[test]
+    fn bytes_in() {
+        let mut buffer = vec![0xff];
+        to_bytes_in(&1, &mut buffer).unwrap();
+        to_bytes_in("a", &mut buffer).unwrap();
+        assert_eq!(buffer, &[0xff, 0x31, 0x41, 0x61]);
+        assert_eq!(crate::first_from_bytes::<String>(&buffer[2..]).unwrap().0, "a");
+
+        let mut map = std::collections::HashMap::new();
+        map.insert(vec![1], 1);
+        assert!(to_bytes_in(&map, &mut buffer).is_err());
+        assert_eq!(buffer, &[0xff, 0x31, 0x41, 0x61]);
+
+        let options = SerializerOptions::new().compact_arrays(true);
+        to_bytes_in_with_options(&vec![1], &mut buffer, &options).unwrap();
+        assert_eq!(buffer, &[0xff, 0x31, 0x41, 0x61, 0x13, 0x04, 0x31, 0x01]);
+    }
+
+    #[test]
+    fn reuse() {
+        let mut serializer = Serializer::default();
+        1.serialize(&mut serializer).unwrap();
+        "a".serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.as_slice(), &[0x31, 0x41, 0x61]);
+        serializer.reset();
+        assert!(serializer.as_slice().is_empty());
+        vec![true].serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_inner(), &[0x02, 0x03, 0x1a]);
+
+        let mut serializer = Serializer::with_capacity(64);
+        assert!(serializer.output.capacity() >= 64);
+        vec![1, 2].serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.as_slice(), &[0x02, 0x04, 0x31, 0x32]);
+
+        let mut serializer = Serializer::default().append_to(vec![0xff, 0xfe]);
+        1.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_inner(), &[0xff, 0xfe, 0x31]);
+    }
+
+    #[test]
+    fn compact_arrays_or_objects() {
+        let value = json!({"a": [1, 2]});
+        let bytes = to_bytes_with_options(&value, &SerializerOptions::new().compact_arrays(true)).unwrap();
+        assert_eq!(bytes, &[0x0b, 0x0b, 0x01, 0x41, 0x61, 0x13, 0x05, 0x31, 0x32, 0x02, 0x03]);
+        let bytes = to_bytes_with_options(&value, &SerializerOptions::new().compact_objects(true)).unwrap();
+        assert_eq!(bytes, &[0x14, 0x09, 0x41, 0x61, 0x02, 0x04, 0x31, 0x32, 0x01]);
+    }
+
+    #[test]
+    fn padding() {
+        let options = SerializerOptions::new().padding(true);
+        assert_eq!(to_bytes_with_options(&vec![1, 2], &options).unwrap(), &[0x02, 0x0b, 0, 0, 0, 0, 0, 0, 0, 0x31, 0x32]);
+        assert_eq!(to_bytes_with_options(&json!([1, "a"]), &options).unwrap(), &[0x06, 0x0e, 0x02, 0, 0, 0, 0, 0, 0, 0x31, 0x41, 0x61, 0x09, 0x0a]);
+        assert_eq!(to_bytes_with_options(&json!({"a": 1}), &options).unwrap(), &[0x0b, 0x0d, 0x01, 0, 0, 0, 0, 0, 0, 0x41, 0x61, 0x31, 0x09]);
+        // 8 byte headers need no padding
+        let eight = options.clone().index_width(IndexWidth::Eight);
+        assert_eq!(to_bytes_with_options(&json!([1, "a"]), &eight).unwrap(), to_bytes_with_options(&json!([1, "a"]), &SerializerOptions::new().index_width(IndexWidth::Eight)).unwrap());
+
+        let value = json!({"list": [1, "two", [3.5]], "nested": {"x": null, "y": [true, false]}});
+        let bytes = to_bytes_with_options(&value, &options).unwrap();
+        crate::validate(&bytes).unwrap();
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&bytes).unwrap(), value);
+        let list = crate::Slice::new(&bytes).get("list").unwrap().unwrap();
+        assert_eq!(list.at(1).unwrap().unwrap().as_str().unwrap(), "two");
+        assert_eq!(crate::to_json_bytes(&bytes).unwrap(), serde_json::to_vec(&value).unwrap());
+    }
+
+    #[test]
+    fn from_iter() {
+        let long = "x".repeat(300);
+        let arrays = vec![
+            vec![],
+            vec![json!(1)],
+            vec![json!(1), json!(2), json!(3)],
+            vec![json!(1), json!("a"), json!(null), json!({"b": [1.5]})],
+            vec![json!(long); 3],
+            vec![json!(long), json!(1)],
+            (0..100_000).map(|i| json!(i)).collect(),
+        ];
+        for options in [
+            SerializerOptions::new(),
+            SerializerOptions::new().padding(true),
+            SerializerOptions::new().compact_arrays(true),
+            SerializerOptions::new().index_width(IndexWidth::Four),
+            SerializerOptions::new().index_width(IndexWidth::Eight).padding(true),
+            SerializerOptions::new().empty_collections(EmptyEncoding::Compact),
+        ] {
+            for array in &arrays {
+                assert_eq!(to_bytes_from_iter(array, &options).unwrap(), to_bytes_with_options(array, &options).unwrap());
+            }
+        }
+
+        let bytes = to_bytes_from_iter((0..1000).map(|i| i.to_string()), &SerializerOptions::new()).unwrap();
+        assert_eq!(crate::from_bytes::<Vec<String>>(&bytes).unwrap()[999], "999");
+        let options = SerializerOptions::new().non_finite(NonFinite::Error);
+        assert_eq!(to_bytes_from_iter([1.0, f64::NAN], &options), Err(ErrorKind::NonFiniteDouble.into()));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_arrays() {
+        let arrays: Vec<Vec<serde_json::Value>> = vec![
+            vec![],
+            vec![json!(1), json!("a")],
+            (0..100_000).map(|i| json!(i)).collect(),
+            (0..10_000).map(|i| json!({"id": i, "name": i.to_string()})).collect(),
+        ];
+        for options in [
+            SerializerOptions::new(),
+            SerializerOptions::new().compact_arrays(true),
+            SerializerOptions::new().index_width(IndexWidth::Four).padding(true),
+        ] {
+            for array in &arrays {
+                assert_eq!(to_bytes_par(array, &options).unwrap(), to_bytes_with_options(array, &options).unwrap());
+            }
+        }
+
+        let mut doubles = vec![1.0; 10_000];
+        doubles[9_000] = f64::NAN;
+        let options = SerializerOptions::new().non_finite(NonFinite::Error);
+        assert_eq!(to_bytes_par(&doubles, &options), Err(ErrorKind::NonFiniteDouble.into()));
+    }
+
+    #[test]
+    fn compact_containers() {
+        let options = SerializerOptions::new().compact_containers(true);
+        // examples from the spec
+        assert_eq!(to_bytes_with_options(&vec![1, 16], &options).unwrap(), &[0x13, 0x06, 0x31, 0x28, 0x10, 0x02]);
+        let bytes = to_bytes_with_options(&json!({"a": 12, "b": true, "c": "xyz"}), &options).unwrap();
+        assert_eq!(bytes, &[0x14, 0x10, 0x41, 0x61, 0x28, 0x0c, 0x41, 0x62, 0x1a, 0x41, 0x63, 0x43, 0x78, 0x79, 0x7a, 0x03]);
+        assert_eq!(to_bytes_with_options(&Vec::<u8>::new(), &options).unwrap(), &[0x01]);
+
+        let value = json!({"list": [1, "two", [3.5]], "nested": {"x": null, "y": [true, false]}});
+        let bytes = to_bytes_with_options(&value, &options).unwrap();
+        assert_eq!(bytes[0], 0x14);
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&bytes).unwrap(), value);
+        assert!(bytes.len() < to_bytes(&value).unwrap().len());
+
+        // byte length and number of items needing multi-byte varints
+        let strings: Vec<String> = (0..200).map(|i| format!("item {}", i)).collect();
+        let bytes = to_bytes_with_options(&strings, &options).unwrap();
+        assert_eq!(crate::from_bytes::<Vec<String>>(&bytes).unwrap(), strings);
+        assert_eq!(crate::to_json_bytes(&bytes).unwrap(), serde_json::to_vec(&strings).unwrap());
+    }
+
+    #[test]
+    fn unsorted_objects() {
+        #[derive(Serialize)]
+        struct Doc {
+            b: u8,
+            a: u8,
+        }
+        assert_eq!(to_bytes(&Doc { b: 1, a: 2 }).unwrap(), &[0x0b, 0x0b, 0x02, 0x41, 0x62, 0x31, 0x41, 0x61, 0x32, 0x06, 0x03]);
+
+        let options = SerializerOptions::new().unsorted_objects(true);
+        let bytes = to_bytes_with_options(&Doc { b: 1, a: 2 }, &options).unwrap();
+        assert_eq!(bytes, &[0x0f, 0x0b, 0x02, 0x41, 0x62, 0x31, 0x41, 0x61, 0x32, 0x03, 0x06]);
+        assert_eq!(crate::to_json_bytes(&bytes).unwrap(), br#"{"b":1,"a":2}"#);
+        assert_eq!(crate::from_bytes::<HashMap<String, u8>>(&bytes).unwrap()["a"], 2);
+
+        let big: Vec<(String, u64)> = (0..100).rev().map(|i| (format!("k{}", i), i)).collect();
+        let map: serde_json::Map<String, serde_json::Value> = big.iter().map(|(k, v)| (k.clone(), json!(v))).collect();
+        let bytes = to_bytes_with_options(&map, &options).unwrap();
+        assert_eq!(bytes[0], 0x10);
+        assert_eq!(crate::from_bytes::<serde_json::Map<String, serde_json::Value>>(&bytes).unwrap(), map);
+    }
+
+    #[test]
+    fn items() {
+        let options = SerializerOptions::new();
+        let mut items = Items::default();
+        items.serialize(Serializer::child(&options), "abc").unwrap();
+        assert!(items.serialize(Serializer::child(&options), &None::<u8>).unwrap());
+        items.pop();
+        items.serialize(Serializer::child(&options), &300).unwrap();
+        let options = SerializerOptions::new().non_finite(NonFinite::Error);
+        assert!(items.serialize(Serializer::child(&options), &[1.0, f64::NAN]).is_err());
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items.iter().collect::<Vec<_>>(), vec![&[0x43, 0x61, 0x62, 0x63][..], &[0x29, 0x2c, 0x01]]);
+        assert_eq!(items.starts().collect::<Vec<_>>(), vec![0, 4]);
+        assert_eq!(items.size(), 7);
+    }
+
+    #[test]
+    fn omit_none_fields() {
+        #[derive(Serialize)]
+        struct Doc {
+            a: Option<u8>,
+            b: Option<u8>,
+            c: (),
+            d: Option<Option<u8>>,
+        }
+        let doc = Doc { a: None, b: Some(1), c: (), d: Some(None) };
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&to_bytes(&doc).unwrap()).unwrap(), json!({"a": null, "b": 1, "c": null, "d": null}));
+
+        let options = SerializerOptions::new().omit_none_fields(true);
+        let bytes = to_bytes_with_options(&doc, &options).unwrap();
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&bytes).unwrap(), json!({"b": 1, "c": null}));
+
+        let all_none = Doc { a: None, b: None, c: (), d: None };
+        let bytes = to_bytes_with_options(&vec![all_none], &options).unwrap();
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&bytes).unwrap(), json!([{"c": null}]));
+
+        // maps are left as they are
+        let map: HashMap<&str, Option<u8>> = vec![("a", None)].into_iter().collect();
+        assert_eq!(to_bytes_with_options(&map, &options).unwrap(), to_bytes(&map).unwrap());
+    }
+
+    #[test]
+    fn bytes() {
+        struct Bytes<'a>(&'a [u8]);
+        impl Serialize for Bytes<'_> {
+            fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+
+        assert_eq!(to_bytes(&Bytes(&[])).unwrap(), &[0xc0, 0x00]);
+        assert_eq!(to_bytes(&Bytes(&[0x01, 0xff])).unwrap(), &[0xc0, 0x02, 0x01, 0xff]);
+
+        let data = vec![0xab; 300];
+        let out = to_bytes(&Bytes(&data)).unwrap();
+        assert_eq!(&out[..3], &[0xc1, 0x2c, 0x01]);
+        assert_eq!(out.len(), 3 + 300);
+        assert_eq!(crate::from_bytes::<&[u8]>(&out).unwrap(), &data[..]);
+
+        #[derive(Serialize)]
+        struct Doc<'a> {
+            data: Bytes<'a>,
+        }
+        let out = to_bytes(&Doc { data: Bytes(&[0x01, 0x02]) }).unwrap();
+        assert_eq!(crate::from_bytes::<HashMap<&str, &[u8]>>(&out).unwrap()["data"], &[0x01, 0x02]);
+    }
+
+    #[test]
+    fn aligned() {
+        let options = SerializerOptions::new();
+        let mut buffer = vec![0xaa; 3];
+        assert_eq!(to_bytes_aligned(&1.5f64, &mut buffer, 8, &options).unwrap(), 8..17);
+        assert_eq!(&buffer[..8], &[0xaa, 0xaa, 0xaa, 0, 0, 0, 0, 0]);
+        assert_eq!(to_bytes_aligned(&"a", &mut buffer, 1, &options).unwrap(), 17..19);
+        assert_eq!(to_bytes_aligned(&true, &mut buffer, 0, &options).unwrap(), 19..20);
+        assert_eq!(to_bytes_aligned(&(), &mut buffer, 4, &options).unwrap(), 20..21);
+        assert_eq!(buffer.len(), 21);
+    }
+
+    #[test]
+    fn field_sizes() {
+        #[derive(Serialize)]
+        struct Doc {
+            small: u8,
+            big: String,
+            list: Vec<u32>,
+        }
+
+        let doc = Doc { small: 1, big: "x".repeat(200), list: vec![1, 2, 3] };
+        let (bytes, sizes) = to_bytes_with_field_sizes(&doc, &SerializerOptions::new()).unwrap();
+        assert_eq!(bytes, to_bytes(&doc).unwrap());
+        assert_eq!(sizes, vec![
+            ("small".to_owned(), 6 + 1),
+            ("big".to_owned(), 4 + 9 + 200),
+            ("list".to_owned(), 5 + 5),
+        ]);
+
+        let (_, sizes) = to_bytes_with_field_sizes(&[1, 2], &SerializerOptions::new()).unwrap();
+        assert!(sizes.is_empty());
+    }
+
     #[test]
     fn object_empty() {
         let a: HashMap<i32, String> = HashMap::new();
@@ -1163,7 +2264,7 @@ mod tests {
             friends: vec![Person { name: "Alice".to_owned(), age: 42, friends: Vec::new() }]
         };
         println!("{:x?}", to_bytes(&p).unwrap());
-        let expected: Vec<u8> = vec![0x0b, 0x3f, 0x03, 0x44, 0x6e, 0x61, 0x6d, 0x65, 0x43, 0x42, 0x6f, 0x62, 0x43, 0x61, 0x67, 0x65, 0x28, 0x17, 0x47, 0x66, 0x72, 0x69, 0x65, 0x6e, 0x64, 0x73, 0x02, 0x22, 0x0b, 0x20, 0x03, 0x44, 0x6e, 0x61, 0x6d, 0x65, 0x45, 0x41, 0x6c, 0x69, 0x63, 0x65, 0x43, 0x61, 0x67, 0x65, 0x28, 0x2a, 0x47, 0x66, 0x72, 0x69, 0x65, 0x6e, 0x64, 0x73, 0x01, 0x0e, 0x03, 0x14, 0x0c, 0x03, 0x12];
+        let expected: Vec<u8> = vec![0x0b, 0x3f, 0x03, 0x44, 0x6e, 0x61, 0x6d, 0x65, 0x43, 0x42, 0x6f, 0x62, 0x43, 0x61, 0x67, 0x65, 0x28, 0x17, 0x47, 0x66, 0x72, 0x69, 0x65, 0x6e, 0x64, 0x73, 0x02, 0x22, 0x0b, 0x20, 0x03, 0x44, 0x6e, 0x61, 0x6d, 0x65, 0x45, 0x41, 0x6c, 0x69, 0x63, 0x65, 0x43, 0x61, 0x67, 0x65, 0x28, 0x2a, 0x47, 0x66, 0x72, 0x69, 0x65, 0x6e, 0x64, 0x73, 0x01, 0x0e, 0x14, 0x03, 0x0c, 0x12, 0x03];
         assert_eq!(to_bytes(&p).unwrap(), expected);
     }
 }
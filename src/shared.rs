@@ -0,0 +1,297 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::error::Result;
+
+thread_local! {
+    // buffer being decoded by `from_shared` on this thread, which shared fields point into
+    static BUFFER: RefCell<Option<Buffer>> = const { RefCell::new(None) };
+}
+
+// an owned buffer which shared fields keep alive
+#[derive(Clone)]
+enum Buffer {
+    Arc(Arc<[u8]>),
+    #[cfg(feature = "bytes")]
+    Bytes(bytes::Bytes),
+}
+
+impl Buffer {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Buffer::Arc(buffer) => buffer,
+            #[cfg(feature = "bytes")]
+            Buffer::Bytes(buffer) => buffer,
+        }
+    }
+}
+
+/// Deserialize a single VelocyPack's bytes into `T`, with any [`SharedBytes`] and [`SharedStr`]
+/// fields pointing into `buffer` rather than copying out of it. The decoded value doesn't borrow
+/// from `buffer`, so its fields can be handed to other threads, each keeping the buffer alive.
+pub fn from_shared<T: DeserializeOwned>(buffer: &Arc<[u8]>) -> Result<T> {
+    decode_shared(Buffer::Arc(buffer.clone()))
+}
+
+/// Deserialize a single VelocyPack's bytes into `T` like [`from_shared`], with any
+/// [`SharedBytes`] and [`SharedStr`] fields pointing into `bytes`, e.g. a frame received by a
+/// network stack working in `Bytes`. Needs the `bytes` feature.
+#[cfg(feature = "bytes")]
+pub fn from_bytes_shared<T: DeserializeOwned>(bytes: bytes::Bytes) -> Result<T> {
+    decode_shared(Buffer::Bytes(bytes))
+}
+
+fn decode_shared<T: DeserializeOwned>(buffer: Buffer) -> Result<T> {
+    // restores the previous buffer even if deserializing panics, for nested calls
+    struct Restore(Option<Buffer>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            BUFFER.with(|b| *b.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore = Restore(BUFFER.with(|b| b.replace(Some(buffer.clone()))));
+    crate::from_bytes(buffer.as_slice())
+}
+
+// share `bytes` if they lie within the buffer being decoded, otherwise copy them
+fn share(bytes: &[u8]) -> (Buffer, Range<usize>) {
+    let shared = BUFFER.with(|b| {
+        let buffer = b.borrow();
+        let buffer = buffer.as_ref()?;
+        let start = (bytes.as_ptr() as usize).checked_sub(buffer.as_slice().as_ptr() as usize)?;
+        let end = start + bytes.len();
+        if end <= buffer.as_slice().len() {
+            Some((buffer.clone(), start..end))
+        } else {
+            None
+        }
+    });
+    shared.unwrap_or_else(|| (Buffer::Arc(Arc::from(bytes)), 0..bytes.len()))
+}
+
+/// Binary data deserialized by [`from_shared`] without copying, which shares ownership of the
+/// decoded buffer. With other deserializers the data is copied into a buffer of its own.
+#[derive(Clone)]
+pub struct SharedBytes {
+    buffer: Buffer,
+    range: Range<usize>,
+}
+
+impl SharedBytes {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer.as_slice()[self.range.clone()]
+    }
+}
+
+/// Without copying if decoded by [`from_bytes_shared`].
+#[cfg(feature = "bytes")]
+impl From<SharedBytes> for bytes::Bytes {
+    fn from(shared: SharedBytes) -> Self {
+        match shared.buffer {
+            Buffer::Bytes(buffer) => buffer.slice(shared.range),
+            Buffer::Arc(_) => bytes::Bytes::copy_from_slice(shared.as_bytes()),
+        }
+    }
+}
+
+impl Deref for SharedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl From<&[u8]> for SharedBytes {
+    fn from(bytes: &[u8]) -> Self {
+        SharedBytes { buffer: Buffer::Arc(Arc::from(bytes)), range: 0..bytes.len() }
+    }
+}
+
+impl fmt::Debug for SharedBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_bytes(), f)
+    }
+}
+
+impl PartialEq for SharedBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for SharedBytes {}
+
+impl std::hash::Hash for SharedBytes {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
+}
+
+impl Serialize for SharedBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.as_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for SharedBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = SharedBytes;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("binary data")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<SharedBytes, E> {
+                let (buffer, range) = share(v);
+                Ok(SharedBytes { buffer, range })
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<SharedBytes, E> {
+                self.visit_bytes(v.as_bytes())
+            }
+        }
+
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
+
+/// A string deserialized by [`from_shared`] without copying, which shares ownership of the
+/// decoded buffer. With other deserializers the string is copied into a buffer of its own.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct SharedStr(SharedBytes);
+
+impl SharedStr {
+    pub fn as_str(&self) -> &str {
+        // checked when decoded, so this can't fail (and is cheap next to copying)
+        std::str::from_utf8(self.0.as_bytes()).unwrap_or_default()
+    }
+}
+
+impl Deref for SharedStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for SharedStr {
+    fn from(s: &str) -> Self {
+        SharedStr(SharedBytes::from(s.as_bytes()))
+    }
+}
+
+impl fmt::Debug for SharedStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for SharedStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for SharedStr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SharedStr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct StrVisitor;
+
+        impl<'de> Visitor<'de> for StrVisitor {
+            type Value = SharedStr;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<SharedStr, E> {
+                let (buffer, range) = share(v.as_bytes());
+                Ok(SharedStr(SharedBytes { buffer, range }))
+            }
+        }
+
+        deserializer.deserialize_str(StrVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Doc {
+        name: SharedStr,
+        data: SharedBytes,
+        tags: Vec<SharedStr>,
+    }
+
+    #[test]
+    fn shared() {
+        let mut builder = crate::Builder::new();
+        builder.open_object().unwrap();
+        builder.add("name", "Bob").unwrap();
+        builder.add("data", &SharedBytes::from(&[1, 2, 3][..])).unwrap();
+        builder.add("tags", &["a", "bc"]).unwrap();
+        builder.close().unwrap();
+        let bytes = builder.finish().unwrap();
+        let buffer: Arc<[u8]> = Arc::from(bytes);
+        let doc: Doc = from_shared(&buffer).unwrap();
+        assert_eq!(doc.name.as_str(), "Bob");
+        assert_eq!(&*doc.data, &[1, 2, 3]);
+        assert_eq!(doc.tags, vec![SharedStr::from("a"), SharedStr::from("bc")]);
+        assert!(matches!(&doc.name.0.buffer, Buffer::Arc(b) if Arc::ptr_eq(b, &buffer)));
+        assert!(matches!(&doc.data.buffer, Buffer::Arc(b) if Arc::ptr_eq(b, &buffer)));
+
+        let name = doc.name.clone();
+        drop(doc);
+        drop(buffer);
+        assert_eq!(std::thread::spawn(move || name.to_string()).join().unwrap(), "Bob");
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn shared_bytes() {
+        let mut builder = crate::Builder::new();
+        builder.open_object().unwrap();
+        builder.add("name", "Bob").unwrap();
+        builder.add("data", &SharedBytes::from(&[1, 2, 3][..])).unwrap();
+        builder.add("tags", &["a"]).unwrap();
+        builder.close().unwrap();
+        let bytes = bytes::Bytes::from(builder.finish().unwrap());
+        let doc: Doc = from_bytes_shared(bytes.clone()).unwrap();
+        assert_eq!(doc.name.as_str(), "Bob");
+        assert_eq!(doc.tags, vec![SharedStr::from("a")]);
+        let data = bytes::Bytes::from(doc.data);
+        assert_eq!(&data[..], &[1, 2, 3]);
+        // a view of the same memory
+        assert!(bytes.as_ptr_range().contains(&data.as_ptr()));
+        assert_eq!(bytes::Bytes::from(SharedBytes::from(&[4][..])), bytes::Bytes::from_static(&[4]));
+        assert!(BUFFER.with(|b| b.borrow().is_none()));
+    }
+
+    #[test]
+    fn copied() {
+        // decoded without a shared buffer
+        let name: SharedStr = crate::from_bytes(&[0x43, 0x42, 0x6f, 0x62]).unwrap();
+        assert_eq!(&*name, "Bob");
+        let name: SharedStr = serde_json::from_str("\"Bob\"").unwrap();
+        assert_eq!(crate::to_bytes(&name).unwrap(), &[0x43, 0x42, 0x6f, 0x62]);
+        assert!(BUFFER.with(|b| b.borrow().is_none()));
+    }
+}
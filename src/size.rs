@@ -0,0 +1,345 @@
+use serde::ser::{self, Serialize};
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::raw;
+use crate::ser::{EmptyEncoding, IndexWidth, Serializer, SerializerOptions};
+
+/// Number of bytes [`to_bytes`](crate::to_bytes) would encode `value` in, without producing the
+/// encoding, e.g. to size a buffer or check a size limit before serializing.
+pub fn serialized_size<T: ?Sized + Serialize>(value: &T) -> Result<usize> {
+    serialized_size_with_options(value, &SerializerOptions::default())
+}
+
+/// Number of bytes [`to_bytes_with_options`](crate::to_bytes_with_options) would encode `value`
+/// in.
+pub fn serialized_size_with_options<T: ?Sized + Serialize>(value: &T, options: &SerializerOptions) -> Result<usize> {
+    let mut sizer = Sizer { options, scratch: Serializer::with_options(options.clone()), wrote_none: false };
+    value.serialize(&mut sizer)
+}
+
+// adds up the sizes of values, only encoding scalars (into a reused buffer) where their size
+// isn't simple to work out
+struct Sizer<'a> {
+    options: &'a SerializerOptions,
+    scratch: Serializer,
+    // value sized was None, rather than any other null
+    wrote_none: bool,
+}
+
+impl Sizer<'_> {
+    fn encoded<F: FnOnce(&mut Serializer) -> Result<()>>(&mut self, f: F) -> Result<usize> {
+        self.scratch.reset();
+        f(&mut self.scratch)?;
+        Ok(self.scratch.as_slice().len())
+    }
+
+    fn value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(usize, bool)> {
+        self.wrote_none = false;
+        let size = value.serialize(&mut *self)?;
+        Ok((size, self.wrote_none))
+    }
+}
+
+fn indexed_size(data_size: usize, n_items: usize, options: &SerializerOptions) -> usize {
+    let min_bytes = options.index_width.min_bytes();
+    for n_bytes in [1, 2, 4, 8].iter().filter(|n| **n >= min_bytes) {
+        let header_size = if *n_bytes == 8 { 1 + 8 } else { 1 + 2 * n_bytes };
+        let padding = if options.padding { 1 + 8 - header_size } else { 0 };
+        let needed_size = 1 + n_bytes + n_bytes + padding + data_size + n_items * n_bytes;
+        if *n_bytes == 8 || needed_size < 1 << (n_bytes * 8) {
+            return needed_size;
+        }
+    }
+    unreachable!()
+}
+
+fn empty_size(options: &SerializerOptions) -> usize {
+    match options.empty_collections {
+        EmptyEncoding::Short => 1,
+        EmptyEncoding::Compact => 3,
+    }
+}
+
+fn array_size(items: &[usize], options: &SerializerOptions) -> usize {
+    let data_size = items.iter().sum();
+    if items.is_empty() {
+        return empty_size(options);
+    }
+    if options.compact_arrays {
+        return raw::compact_byte_length(data_size, items.len());
+    }
+    if options.index_width == IndexWidth::Smallest && items.iter().all(|n| *n == items[0]) {
+        for n_bytes in [1, 2, 4, 8] {
+            let padding = if options.padding { 8 - n_bytes } else { 0 };
+            let needed_size = 1 + n_bytes + padding + data_size;
+            if n_bytes == 8 || needed_size < 1 << (n_bytes * 8) {
+                return needed_size;
+            }
+        }
+    }
+    indexed_size(data_size, items.len(), options)
+}
+
+fn object_size(data_size: usize, n_members: usize, options: &SerializerOptions) -> usize {
+    if n_members == 0 {
+        empty_size(options)
+    } else if options.compact_objects {
+        raw::compact_byte_length(data_size, n_members)
+    } else {
+        indexed_size(data_size, n_members, options)
+    }
+}
+
+fn unsupported(kind: &str) -> Error {
+    ErrorKind::Message(format!("{} are not supported", kind)).into()
+}
+
+macro_rules! encoded {
+    ($($method:ident($ty:ty))*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<usize> {
+                self.encoded(|s| ser::Serializer::$method(s, v))
+            }
+        )*
+    };
+}
+
+impl<'a, 'b> ser::Serializer for &'b mut Sizer<'a> {
+    type Ok = usize;
+    type Error = Error;
+
+    type SerializeSeq = SeqSizer<'a, 'b>;
+    type SerializeTuple = SeqSizer<'a, 'b>;
+    type SerializeTupleStruct = ser::Impossible<usize, Error>;
+    type SerializeTupleVariant = ser::Impossible<usize, Error>;
+    type SerializeMap = MapSizer<'a, 'b>;
+    type SerializeStruct = MapSizer<'a, 'b>;
+    type SerializeStructVariant = ser::Impossible<usize, Error>;
+
+    encoded! {
+        serialize_bool(bool) serialize_i8(i8) serialize_i16(i16) serialize_i32(i32) serialize_i64(i64)
+        serialize_u8(u8) serialize_u16(u16) serialize_u32(u32) serialize_u64(u64)
+        serialize_f32(f32) serialize_f64(f64) serialize_char(char)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<usize> {
+        if self.options.dictionary.is_some() {
+            return self.encoded(|s| ser::Serializer::serialize_str(s, v));
+        }
+        Ok(if v.len() <= 126 { 1 + v.len() } else { 1 + 8 + v.len() })
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<usize> {
+        let n_bytes = (v.len() as u64).to_le_bytes().iter().rposition(|b| *b != 0x00).map_or(1, |i| i + 1);
+        Ok(1 + n_bytes + v.len())
+    }
+
+    fn serialize_none(self) -> Result<usize> {
+        self.wrote_none = true;
+        Ok(1)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<usize> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<usize> {
+        Ok(1)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<usize> {
+        Ok(1)
+    }
+
+    fn serialize_unit_variant(self, name: &'static str, variant_index: u32, variant: &'static str) -> Result<usize> {
+        self.encoded(|s| ser::Serializer::serialize_unit_variant(s, name, variant_index, variant))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, name: &'static str, value: &T) -> Result<usize> {
+        // this crate's own types, e.g. numbers keeping their encoding and raw values, and custom
+        // types
+        if name.starts_with("$velocypack::private::") || self.options.custom_types.as_ref().is_some_and(|c| c.encodes(name)) {
+            return self.encoded(|s| ser::Serializer::serialize_newtype_struct(s, name, value));
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, _variant_index: u32, _variant: &'static str, _value: &T) -> Result<usize> {
+        Err(unsupported("newtype variants"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SeqSizer { sizer: self, items: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct> {
+        Err(unsupported("tuple structs"))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _variant_index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant> {
+        Err(unsupported("tuple variants"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(MapSizer { sizer: self, data_size: 0, n_members: 0 })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _variant_index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant> {
+        Err(unsupported("struct variants"))
+    }
+}
+
+struct SeqSizer<'a, 'b> {
+    sizer: &'b mut Sizer<'a>,
+    items: Vec<usize>,
+}
+
+impl ser::SerializeSeq for SeqSizer<'_, '_> {
+    type Ok = usize;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        let (size, _) = self.sizer.value(value)?;
+        self.items.push(size);
+        Ok(())
+    }
+
+    fn end(self) -> Result<usize> {
+        Ok(array_size(&self.items, self.sizer.options))
+    }
+}
+
+impl ser::SerializeTuple for SeqSizer<'_, '_> {
+    type Ok = usize;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<usize> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct MapSizer<'a, 'b> {
+    sizer: &'b mut Sizer<'a>,
+    // total size of keys and values
+    data_size: usize,
+    n_members: usize,
+}
+
+impl ser::SerializeMap for MapSizer<'_, '_> {
+    type Ok = usize;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        self.data_size += crate::ser::encode_key(key, self.sizer.options)?.len();
+        self.n_members += 1;
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.data_size += self.sizer.value(value)?.0;
+        Ok(())
+    }
+
+    fn end(self) -> Result<usize> {
+        Ok(object_size(self.data_size, self.n_members, self.sizer.options))
+    }
+}
+
+impl ser::SerializeStruct for MapSizer<'_, '_> {
+    type Ok = usize;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<()> {
+        let (size, none) = self.sizer.value(value)?;
+        if none && self.sizer.options.omit_none_fields {
+            return Ok(());
+        }
+        ser::SerializeMap::serialize_key(self, key)?;
+        self.data_size += size;
+        Ok(())
+    }
+
+    fn end(self) -> Result<usize> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{to_bytes_with_options, RawVPack, StringDictionary, UtcDate, Value};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[derive(serde::Serialize)]
+    struct Doc {
+        name: &'static str,
+        missing: Option<u8>,
+        date: UtcDate,
+        raw: RawVPack<'static>,
+        #[serde(with = "serde_bytes_like")]
+        data: Vec<u8>,
+    }
+
+    mod serde_bytes_like {
+        pub fn serialize<S: serde::Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(v)
+        }
+    }
+
+    #[test]
+    fn exact() {
+        let strings: Vec<String> = (0..300).map(|i| "x".repeat(i % 140)).collect();
+        let values = [
+            json!(null), json!(1), json!(-300), json!(u64::MAX), json!(1.5), json!("a"), json!([]), json!({}),
+            json!([1, 2, 3]), json!([1, "two", [3.5]]), json!({"b": [true, null], "a": {"c": "d".repeat(200)}}),
+            json!(strings), json!((0..70000).collect::<Vec<u32>>()),
+        ];
+        let dictionary = Arc::new(StringDictionary::new(1, vec!["two"]));
+        let options = [
+            SerializerOptions::new(),
+            SerializerOptions::new().compact_containers(true),
+            SerializerOptions::new().compact_arrays(true),
+            SerializerOptions::new().padding(true),
+            SerializerOptions::new().index_width(IndexWidth::Four),
+            SerializerOptions::new().index_width(IndexWidth::Eight).padding(true),
+            SerializerOptions::new().empty_collections(EmptyEncoding::Compact),
+            SerializerOptions::new().dictionary(dictionary),
+        ];
+        for options in &options {
+            for value in &values {
+                let expected = to_bytes_with_options(value, options).unwrap().len();
+                assert_eq!(serialized_size_with_options(value, options).unwrap(), expected, "{} with {:?}", value, options);
+            }
+        }
+    }
+
+    #[test]
+    fn types() {
+        let doc = Doc {
+            name: "doc",
+            missing: None,
+            date: UtcDate::from_millis(1),
+            raw: RawVPack::from_slice(&[0x02, 0x04, 0x31, 0x32]).unwrap(),
+            data: vec![0; 300],
+        };
+        for options in [SerializerOptions::new(), SerializerOptions::new().omit_none_fields(true)] {
+            assert_eq!(serialized_size_with_options(&doc, &options).unwrap(), to_bytes_with_options(&doc, &options).unwrap().len());
+        }
+        // numbers keeping their wider encodings
+        let value = Value::from_bytes(&[0x02, 0x06, 0x28, 0x01, 0x20, 0xff]).unwrap();
+        assert_eq!(serialized_size(&value).unwrap(), 6);
+    }
+}
@@ -0,0 +1,412 @@
+use std::borrow::Cow;
+
+use serde::de::{self, Deserialize, Visitor};
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::number::Number;
+use crate::raw;
+use crate::raw_vpack::RawVPackBuf;
+use crate::value::Value;
+use crate::value_type::ValueType;
+
+/// A view of the VelocyPack value at the start of a byte slice, answering questions about it and
+/// navigating into arrays and objects without decoding anything else.
+///
+/// Creating a `Slice` doesn't check the value, which happens as it's accessed: methods return
+/// `ErrorKind::Eof` if the value is truncated. Bytes after the value are ignored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Slice<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Slice<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Slice { bytes }
+    }
+
+    /// The bytes the slice was created from, from the start of the value.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// The header byte of the value, 0x00 (which is no valid value) for an empty slice.
+    pub fn head(&self) -> u8 {
+        self.bytes.first().copied().unwrap_or(0x00)
+    }
+
+    /// Number of bytes the value occupies, including its header.
+    pub fn byte_size(&self) -> Result<usize> {
+        raw::value_byte_length(self.bytes)
+    }
+
+    /// The bytes of the value only.
+    pub fn value_bytes(&self) -> Result<&'a [u8]> {
+        Ok(&self.bytes[..self.byte_size()?])
+    }
+
+    /// Copy the value into its own buffer, e.g. to forward one member of a large document without
+    /// decoding it. Values of any kind, including compact and tagged ones, don't refer to bytes
+    /// outside themselves, so the copy is a valid document on its own.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        Ok(self.value_bytes()?.to_vec())
+    }
+
+    /// Copy the value into a [`RawVPackBuf`], which serializes as the value unchanged.
+    pub fn to_raw_vpack_buf(&self) -> Result<RawVPackBuf> {
+        RawVPackBuf::from_vec(self.to_vec()?)
+    }
+
+    /// The tag of a tagged value (0xee, 0xef), the outermost one if it has several.
+    pub fn tag(&self) -> Result<Option<u64>> {
+        match self.bytes.first() {
+            Some(0xee) => raw::read_uint(&self.bytes[1..], 1).map(Some),
+            Some(0xef) => raw::read_uint(&self.bytes[1..], 8).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// The value wrapped by any tags (0xee, 0xef), or the value itself if it isn't tagged.
+    pub fn untagged(&self) -> Result<Slice<'a>> {
+        let mut bytes = self.bytes;
+        loop {
+            let n_bytes = match bytes.first() {
+                Some(0xee) => 1,
+                Some(0xef) => 8,
+                _ => return Ok(Slice::new(bytes)),
+            };
+            if bytes.len() < 1 + n_bytes {
+                return Err(ErrorKind::Eof.into());
+            }
+            bytes = &bytes[1 + n_bytes..];
+        }
+    }
+
+    /// The bytes following the value, e.g. the next value of a stream of values.
+    pub fn rest(&self) -> Result<&'a [u8]> {
+        Ok(&self.bytes[self.byte_size()?..])
+    }
+
+    /// Type of the value, `ValueType::None` for an empty slice.
+    pub fn value_type(&self) -> ValueType {
+        self.bytes.first().map_or(ValueType::None, |h| ValueType::from_header(*h))
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.value_type() == ValueType::Null
+    }
+
+    pub fn is_bool(&self) -> bool {
+        self.value_type() == ValueType::Bool
+    }
+
+    /// Whether the value is an integer or a double.
+    pub fn is_number(&self) -> bool {
+        self.is_integer() || self.is_double()
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self.value_type(), ValueType::Int | ValueType::UInt | ValueType::SmallInt)
+    }
+
+    pub fn is_double(&self) -> bool {
+        self.value_type() == ValueType::Double
+    }
+
+    pub fn is_string(&self) -> bool {
+        self.value_type() == ValueType::String
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.value_type() == ValueType::Binary
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.value_type() == ValueType::Array
+    }
+
+    pub fn is_object(&self) -> bool {
+        self.value_type() == ValueType::Object
+    }
+
+    pub fn as_bool(&self) -> Result<bool> {
+        match self.bytes.first() {
+            Some(0x19) => Ok(false),
+            Some(0x1a) => Ok(true),
+            Some(_) => Err(ErrorKind::ExpectedBoolean.into()),
+            None => Err(ErrorKind::Eof.into()),
+        }
+    }
+
+    /// Get an integer value as an `i64`, failing with `ErrorKind::NumberTooLarge` if it doesn't fit.
+    pub fn as_i64(&self) -> Result<i64> {
+        self.integer()?.as_i64().ok_or(ErrorKind::NumberTooLarge.into())
+    }
+
+    /// Get an integer value as a `u64`, failing with `ErrorKind::NumberTooLarge` if it's negative.
+    pub fn as_u64(&self) -> Result<u64> {
+        self.integer()?.as_u64().ok_or(ErrorKind::NumberTooLarge.into())
+    }
+
+    /// Get any number as an `f64`, possibly losing precision.
+    pub fn as_f64(&self) -> Result<f64> {
+        Ok(Number::from_bytes(self.bytes)?.as_f64())
+    }
+
+    fn integer(&self) -> Result<Number> {
+        match self.bytes.first() {
+            Some(0x20..=0x3f) => Number::from_bytes(self.bytes),
+            Some(_) => Err(ErrorKind::ExpectedInteger.into()),
+            None => Err(ErrorKind::Eof.into()),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&'a str> {
+        match self.bytes.first() {
+            Some(0x40..=0xbf) => raw::read_str(self.bytes),
+            Some(_) => Err(ErrorKind::ExpectedString.into()),
+            None => Err(ErrorKind::Eof.into()),
+        }
+    }
+
+    /// Number of items of an array, or members of an object.
+    pub fn items_len(&self) -> Result<usize> {
+        Ok(self.container()?.len())
+    }
+
+    /// Same as [`Slice::items_len`].
+    pub fn length(&self) -> Result<usize> {
+        self.items_len()
+    }
+
+    /// Get the member `key` of an object.
+    pub fn get(&self, key: &str) -> Result<Option<Slice<'a>>> {
+        Ok(raw::object_get(self.bytes, key)?.map(Slice::new))
+    }
+
+    /// Get the item at `index` of an array. Takes constant time, except for compact arrays which
+    /// have to be scanned.
+    pub fn at(&self, index: usize) -> Result<Option<Slice<'a>>> {
+        self.array()?;
+        Ok(raw::array_at(self.bytes, index)?.map(Slice::new))
+    }
+
+    /// Iterate over the items of an array.
+    pub fn items(&self) -> Result<impl Iterator<Item = Result<Slice<'a>>>> {
+        self.array()?;
+        Ok(self.container()?.items().map(|item| item.map(Slice::new)))
+    }
+
+    /// Iterate over the keys and values of an object.
+    pub fn members(&self) -> Result<impl Iterator<Item = Result<(Cow<'a, str>, Slice<'a>)>>> {
+        match self.value_type() {
+            ValueType::Object => {},
+            ValueType::None => return Err(ErrorKind::Eof.into()),
+            _ => return Err(ErrorKind::ExpectedObject.into()),
+        }
+        Ok(self.container()?.members().map(|member| {
+            let (key, value) = member?;
+            Ok((raw::read_key(key)?, Slice::new(value)))
+        }))
+    }
+
+    /// Check the value is well formed, see [`validate`](crate::validate). Bytes after the value
+    /// aren't checked.
+    pub fn validate(&self) -> Result<()> {
+        crate::validate::validate_value(self.bytes, 0).map(|_| ())
+    }
+
+    /// Decode the value.
+    pub fn to_value(&self) -> Result<Value> {
+        Value::from_bytes(self.bytes)
+    }
+
+    /// Deserialize the value, e.g. a sub-document found with [`Slice::get`] and [`Slice::at`].
+    pub fn deserialize<T: Deserialize<'a>>(&self) -> Result<T> {
+        T::deserialize(*self)
+    }
+
+    /// Convert the value to JSON, see [`to_json_bytes`](crate::to_json_bytes).
+    pub fn to_json_bytes(&self) -> Result<Vec<u8>> {
+        crate::to_json_bytes(self.bytes)
+    }
+
+    fn array(&self) -> Result<()> {
+        match self.value_type() {
+            ValueType::Array => Ok(()),
+            ValueType::None => Err(ErrorKind::Eof.into()),
+            _ => Err(ErrorKind::ExpectedArray.into()),
+        }
+    }
+
+    fn container(&self) -> Result<raw::Container<'a>> {
+        match self.value_type() {
+            ValueType::Array | ValueType::Object => raw::Container::new(self.bytes),
+            ValueType::None => Err(ErrorKind::Eof.into()),
+            _ => Err(ErrorKind::ExpectedArray.into()),
+        }
+    }
+}
+
+// run a deserialize method on a deserializer for the value only
+macro_rules! forward_to_deserializer {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value> {
+                let mut de = crate::Deserializer::from_bytes(self.value_bytes()?);
+                de::Deserializer::$method(&mut de, $($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Slice<'de> {
+    type Error = Error;
+
+    forward_to_deserializer! {
+        deserialize_any() deserialize_bool() deserialize_i8() deserialize_i16() deserialize_i32()
+        deserialize_i64() deserialize_i128() deserialize_u8() deserialize_u16() deserialize_u32()
+        deserialize_u64() deserialize_u128() deserialize_f32() deserialize_f64() deserialize_char()
+        deserialize_str() deserialize_string() deserialize_bytes() deserialize_byte_buf()
+        deserialize_option() deserialize_unit() deserialize_unit_struct(name: &'static str)
+        deserialize_newtype_struct(name: &'static str) deserialize_seq()
+        deserialize_tuple(len: usize) deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_map() deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_identifier() deserialize_ignored_any()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document() -> Vec<u8> {
+        crate::to_bytes(&serde_json::json!({
+            "friends": [{"name": "Alice", "age": 42}, {"name": "Bob", "age": -3}],
+            "big": u64::MAX,
+            "ok": true,
+        })).unwrap()
+    }
+
+    #[test]
+    fn navigate() {
+        let bytes = document();
+        let doc = Slice::new(&bytes);
+        assert!(doc.is_object());
+        assert_eq!(doc.length().unwrap(), 3);
+
+        let friends = doc.get("friends").unwrap().unwrap();
+        assert!(friends.is_array());
+        assert_eq!(friends.length().unwrap(), 2);
+        let bob = friends.at(1).unwrap().unwrap();
+        assert_eq!(bob.get("name").unwrap().unwrap().as_str().unwrap(), "Bob");
+        assert_eq!(bob.get("age").unwrap().unwrap().as_i64().unwrap(), -3);
+        assert!(friends.at(2).unwrap().is_none());
+        assert!(doc.get("missing").unwrap().is_none());
+
+        let names: Vec<&str> = friends.items().unwrap()
+            .map(|f| f.unwrap().get("name").unwrap().unwrap().as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["Alice", "Bob"]);
+        let keys: Vec<String> = doc.members().unwrap().map(|m| m.unwrap().0.into_owned()).collect();
+        assert_eq!(keys, ["big", "friends", "ok"]);
+    }
+
+    #[test]
+    fn scalars() {
+        let bytes = document();
+        let doc = Slice::new(&bytes);
+        let big = doc.get("big").unwrap().unwrap();
+        assert!(big.is_integer() && big.is_number());
+        assert_eq!(big.as_u64().unwrap(), u64::MAX);
+        assert_eq!(big.as_i64(), Err(ErrorKind::NumberTooLarge.into()));
+        assert!(doc.get("ok").unwrap().unwrap().as_bool().unwrap());
+        assert_eq!(Slice::new(&[0x1b, 0, 0, 0, 0, 0, 0, 0xf8, 0x3f]).as_f64().unwrap(), 1.5);
+        assert_eq!(Slice::new(&[0x35]).as_f64().unwrap(), 5.0);
+        assert_eq!(Slice::new(&[0x43, 0x61]).as_str(), Err(ErrorKind::Eof.into()));
+        assert_eq!(doc.get("friends").unwrap().unwrap().to_json_bytes().unwrap(), br#"[{"age":42,"name":"Alice"},{"age":-3,"name":"Bob"}]"#);
+    }
+
+    #[test]
+    fn framing() {
+        let mut stream = crate::to_bytes(&vec![1, 2, 3]).unwrap();
+        stream.extend(crate::to_bytes(&"foo").unwrap());
+        stream.push(0x43);
+
+        let first = Slice::new(&stream);
+        assert_eq!(first.head(), 0x02);
+        assert_eq!(first.byte_size().unwrap(), 5);
+        assert_eq!(first.items_len().unwrap(), 3);
+        assert_eq!(first.value_bytes().unwrap(), &stream[..5]);
+
+        let second = Slice::new(first.rest().unwrap());
+        assert_eq!(second.head(), 0x43);
+        assert_eq!(second.byte_size().unwrap(), 4);
+        assert_eq!(second.items_len(), Err(ErrorKind::ExpectedArray.into()));
+
+        let third = Slice::new(second.rest().unwrap());
+        assert_eq!(third.byte_size(), Err(ErrorKind::Eof.into()));
+        assert_eq!(Slice::new(third.rest().unwrap_or_default()).head(), 0x00);
+    }
+
+    #[test]
+    fn extract() {
+        let bytes = crate::to_bytes_with_options(&serde_json::json!({"a": {"b": [1, "x"]}, "c": 2}), &crate::SerializerOptions::new().compact_containers(true)).unwrap();
+        let doc = Slice::new(&bytes);
+        let a = doc.get("a").unwrap().unwrap().to_vec().unwrap();
+        assert_eq!(a[0], 0x14);
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&a).unwrap(), serde_json::json!({"b": [1, "x"]}));
+        let raw = doc.get("c").unwrap().unwrap().to_raw_vpack_buf().unwrap();
+        assert_eq!(raw.as_bytes(), &[0x32]);
+
+        let tagged = [0xee, 0x01, 0xef, 0x02, 0, 0, 0, 0, 0, 0, 0, 0x41, 0x61];
+        let inner = Slice::new(&tagged).untagged().unwrap();
+        assert_eq!(inner.to_vec().unwrap(), &[0x41, 0x61]);
+        assert_eq!(Slice::new(&tagged).to_vec().unwrap(), &tagged);
+        assert_eq!(Slice::new(&[0xef, 0x01]).untagged(), Err(ErrorKind::Eof.into()));
+        assert_eq!(Slice::new(&tagged).tag(), Ok(Some(1)));
+        assert_eq!(Slice::new(&[0xef, 0x01]).tag(), Err(ErrorKind::Eof.into()));
+        assert_eq!(Slice::new(&[0x31]).tag(), Ok(None));
+        assert_eq!(Slice::new(&[0x31]).untagged().unwrap().head(), 0x31);
+        Slice::new(&tagged[..3]).validate().unwrap_err();
+        Slice::new(&tagged).validate().unwrap();
+    }
+
+    #[test]
+    fn deserialize() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Friend<'a> {
+            name: &'a str,
+            age: i8,
+        }
+
+        let bytes = document();
+        let doc = Slice::new(&bytes);
+        let bob: Friend = doc.get("friends").unwrap().unwrap().at(1).unwrap().unwrap().deserialize().unwrap();
+        assert_eq!(bob, Friend { name: "Bob", age: -3 });
+        let friends: Vec<Friend> = doc.get("friends").unwrap().unwrap().deserialize().unwrap();
+        assert_eq!(friends.len(), 2);
+        assert_eq!(doc.get("big").unwrap().unwrap().deserialize::<u64>().unwrap(), u64::MAX);
+        assert!(doc.get("ok").unwrap().unwrap().deserialize::<String>().is_err());
+        // trailing bytes belong to the next value
+        assert_eq!(Slice::new(&[0x31, 0x32]).deserialize::<u8>().unwrap(), 1);
+    }
+
+    #[test]
+    fn type_errors() {
+        let bytes = document();
+        let doc = Slice::new(&bytes);
+        assert_eq!(doc.at(0), Err(ErrorKind::ExpectedArray.into()));
+        assert_eq!(doc.as_str(), Err(ErrorKind::ExpectedString.into()));
+        assert_eq!(Slice::new(&[0x31]).get("a"), Err(ErrorKind::ExpectedObject.into()));
+        assert_eq!(Slice::new(&[0x31]).length(), Err(ErrorKind::ExpectedArray.into()));
+        assert_eq!(Slice::new(&[0x01]).members().err(), Some(ErrorKind::ExpectedObject.into()));
+        assert_eq!(Slice::new(&[]).value_type(), ValueType::None);
+        assert_eq!(Slice::new(&[]).length(), Err(ErrorKind::Eof.into()));
+        assert_eq!(Slice::new(&[0x1a]).as_u64(), Err(ErrorKind::ExpectedInteger.into()));
+    }
+}
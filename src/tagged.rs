@@ -0,0 +1,98 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeTuple, Serializer};
+use serde::{Deserialize, Serialize};
+
+/// A value with a tag (0xee, or 0xef for tags above 255), an application specific number saying
+/// how to interpret it.
+///
+/// Deserializing other types decodes tagged values as the value they tag, so a `Tagged` is only
+/// needed to read or write the tag itself. Other formats see it as the array `[tag, value]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Tagged<T> {
+    pub tag: u64,
+    pub value: T,
+}
+
+impl<T> Tagged<T> {
+    pub fn new(tag: u64, value: T) -> Self {
+        Tagged { tag, value }
+    }
+}
+
+// the tag and value, as passed to this crate's serializer within a `TAGGED_TOKEN` newtype
+struct Parts<'a, T>(u64, &'a T);
+
+impl<T: Serialize> Serialize for Parts<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(&self.0)?;
+        tuple.serialize_element(self.1)?;
+        tuple.end()
+    }
+}
+
+impl<T: Serialize> Serialize for Tagged<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(crate::TAGGED_TOKEN, &Parts(self.tag, &self.value))
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Tagged<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct TaggedVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for TaggedVisitor<T> {
+            type Value = Tagged<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a tagged value")
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<Tagged<T>, D::Error> {
+                deserializer.deserialize_tuple(2, self)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Tagged<T>, A::Error> {
+                let tag = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let value = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                Ok(Tagged { tag, value })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(crate::TAGGED_TOKEN, TaggedVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let bytes = crate::to_bytes(&Tagged::new(5, "a")).unwrap();
+        assert_eq!(bytes, &[0xee, 0x05, 0x41, 0x61]);
+        assert_eq!(crate::from_bytes::<Tagged<String>>(&bytes).unwrap(), Tagged::new(5, "a".to_owned()));
+        // the tag is skipped when not asked for
+        assert_eq!(crate::from_bytes::<String>(&bytes).unwrap(), "a");
+
+        let bytes = crate::to_bytes(&vec![Tagged::new(300, 1_u8)]).unwrap();
+        assert_eq!(bytes, &[0x02, 0x0c, 0xef, 0x2c, 0x01, 0, 0, 0, 0, 0, 0, 0x31]);
+        assert_eq!(crate::from_bytes::<Vec<Tagged<u8>>>(&bytes).unwrap(), vec![Tagged::new(300, 1)]);
+        assert_eq!(crate::from_bytes::<Vec<u8>>(&bytes).unwrap(), vec![1]);
+        assert_eq!(crate::serialized_size(&vec![Tagged::new(300, 1_u8)]).unwrap(), bytes.len());
+
+        // nested tags
+        let nested = Tagged::new(1, Tagged::new(2, true));
+        let bytes = crate::to_bytes(&nested).unwrap();
+        assert_eq!(bytes, &[0xee, 0x01, 0xee, 0x02, 0x1a]);
+        assert_eq!(crate::from_bytes::<Tagged<Tagged<bool>>>(&bytes).unwrap(), nested);
+        assert!(crate::from_bytes::<bool>(&bytes).unwrap());
+
+        assert!(crate::from_bytes::<Tagged<bool>>(&[0x1a]).is_err());
+        assert_eq!(serde_json::to_string(&Tagged::new(5, "a")).unwrap(), r#"[5,"a"]"#);
+        assert_eq!(serde_json::from_str::<Tagged<String>>(r#"[5,"a"]"#).unwrap(), Tagged::new(5, "a".to_owned()));
+    }
+}
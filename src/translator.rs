@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+/// Mapping between attribute names and the integer ids used in their place as object keys.
+///
+/// VelocyPack allows object keys to be unsigned integers referencing a table of attribute names,
+/// which ArangoDB uses for its system attributes (see [`AttributeTranslator::arangodb`]). When
+/// set with
+/// [`SerializerOptions::attribute_translator`](crate::SerializerOptions::attribute_translator),
+/// keys found in the translator are written as their ids, and a deserializer using
+/// [`Deserializer::with_attribute_translator`](crate::Deserializer::with_attribute_translator)
+/// decodes ids back into names.
+#[derive(Clone, Debug, Default)]
+pub struct AttributeTranslator {
+    names: HashMap<u64, String>,
+    ids: HashMap<String, u64>,
+}
+
+impl AttributeTranslator {
+    /// Create a translator from `(id, name)` pairs. Later duplicates of an id or name are ignored.
+    pub fn new<I, S>(attributes: I) -> Self where
+        I: IntoIterator<Item = (u64, S)>, S: Into<String> {
+        let mut translator = Self::default();
+        for (id, name) in attributes {
+            let name = name.into();
+            if !translator.names.contains_key(&id) && !translator.ids.contains_key(&name) {
+                translator.ids.insert(name.clone(), id);
+                translator.names.insert(id, name);
+            }
+        }
+        translator
+    }
+
+    /// The ids ArangoDB uses for system attributes: `_key`, `_rev`, `_id`, `_from` and `_to` as
+    /// 1 to 5.
+    pub fn arangodb() -> Self {
+        Self::new(vec![(1, "_key"), (2, "_rev"), (3, "_id"), (4, "_from"), (5, "_to")])
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Get the id of attribute `name`.
+    pub fn id(&self, name: &str) -> Option<u64> {
+        self.ids.get(name).copied()
+    }
+
+    /// Get the name of the attribute with id `id`.
+    pub fn name(&self, id: u64) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let translator = AttributeTranslator::new(vec![(1, "a"), (2, "b"), (1, "c"), (3, "a")]);
+        assert_eq!(translator.len(), 2);
+        assert_eq!(translator.id("b"), Some(2));
+        assert_eq!(translator.id("c"), None);
+        assert_eq!(translator.name(1), Some("a"));
+        assert_eq!(translator.name(3), None);
+        assert_eq!(AttributeTranslator::arangodb().id("_from"), Some(4));
+    }
+}
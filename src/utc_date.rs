@@ -0,0 +1,117 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A point in time, as milliseconds since the Unix epoch, encoded as a VelocyPack UTCDate (0x1c)
+/// rather than a plain integer. ArangoDB's date functions and filters only work with the native
+/// type.
+///
+/// With serializers other than this crate's the value is encoded as an integer. Deserializing
+/// also accepts plain integers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UtcDate(pub i64);
+
+impl UtcDate {
+    pub fn from_millis(ms: i64) -> Self {
+        UtcDate(ms)
+    }
+
+    pub fn millis(&self) -> i64 {
+        self.0
+    }
+
+    /// Convert a `SystemTime`, truncating sub-millisecond precision. Returns `None` if the time
+    /// is out of range.
+    pub fn from_system_time(t: SystemTime) -> Option<Self> {
+        let ms = match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => i64::try_from(d.as_millis()).ok()?,
+            Err(e) => -i64::try_from(e.duration().as_millis()).ok()?,
+        };
+        Some(UtcDate(ms))
+    }
+
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        let d = Duration::from_millis(self.0.unsigned_abs());
+        if self.0 >= 0 {
+            UNIX_EPOCH.checked_add(d)
+        } else {
+            UNIX_EPOCH.checked_sub(d)
+        }
+    }
+}
+
+impl From<i64> for UtcDate {
+    fn from(ms: i64) -> Self {
+        UtcDate(ms)
+    }
+}
+
+impl From<UtcDate> for i64 {
+    fn from(date: UtcDate) -> Self {
+        date.0
+    }
+}
+
+impl Serialize for UtcDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(crate::UTC_DATE_TOKEN, &self.0)
+    }
+}
+
+struct UtcDateVisitor;
+
+impl<'de> de::Visitor<'de> for UtcDateVisitor {
+    type Value = UtcDate;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a utc date")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<UtcDate, E> {
+        Ok(UtcDate(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<UtcDate, E> {
+        i64::try_from(v).map(UtcDate).map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<UtcDate, D::Error> where
+        D: Deserializer<'de> {
+        i64::deserialize(deserializer).map(UtcDate)
+    }
+}
+
+impl<'de> Deserialize<'de> for UtcDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(crate::UTC_DATE_TOKEN, UtcDateVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_bytes, to_bytes};
+
+    #[test]
+    fn round_trip() {
+        // 2020-01-01T00:00:00Z
+        let date = UtcDate(1577836800000);
+        let bytes = to_bytes(&date).unwrap();
+        assert_eq!(bytes, &[0x1c, 0x00, 0xe8, 0x66, 0x5e, 0x6f, 0x01, 0x00, 0x00]);
+        assert_eq!(from_bytes::<UtcDate>(&bytes).unwrap(), date);
+        assert_eq!(from_bytes::<UtcDate>(&to_bytes(&UtcDate(-1)).unwrap()).unwrap(), UtcDate(-1));
+        assert_eq!(from_bytes::<UtcDate>(&[0x35]).unwrap(), UtcDate(5));
+        assert!(from_bytes::<UtcDate>(&[0x43, 0x66, 0x6f, 0x6f]).is_err());
+        assert_eq!(serde_json::to_string(&date).unwrap(), "1577836800000");
+    }
+
+    #[test]
+    fn system_time() {
+        let t = UNIX_EPOCH + Duration::from_micros(1500);
+        assert_eq!(UtcDate::from_system_time(t), Some(UtcDate(1)));
+        assert_eq!(UtcDate(-1000).to_system_time(), Some(UNIX_EPOCH - Duration::from_secs(1)));
+        assert_eq!(UtcDate::from_system_time(UNIX_EPOCH - Duration::from_millis(5)), Some(UtcDate(-5)));
+    }
+}
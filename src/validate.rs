@@ -0,0 +1,187 @@
+use std::cmp::Ordering;
+
+use crate::error::{ErrorKind, Result};
+use crate::raw;
+use crate::value_type::ValueType;
+
+// containers and tags nested deeper than this are rejected, so that validating can't overflow
+// the stack
+const MAX_DEPTH: usize = 128;
+
+/// Check that `bytes` holds exactly one well formed value, without decoding it: headers must be
+/// known, byte lengths must fit, index tables must point at the items (in key order for sorted
+/// objects), strings must be UTF-8 and object keys strings or integers. Containers may be nested
+/// at most 128 deep.
+///
+/// External values (0x1d) are rejected, since they point outside the buffer.
+pub fn validate(bytes: &[u8]) -> Result<()> {
+    let length = validate_value(bytes, 0)?;
+    match bytes.len() - length {
+        0 => Ok(()),
+        n => Err(ErrorKind::TrailingBytes(n).into()),
+    }
+}
+
+// validate the value at the start of `bytes`, returning its length
+pub(crate) fn validate_value(bytes: &[u8], depth: usize) -> Result<usize> {
+    if depth > MAX_DEPTH {
+        return Err(ErrorKind::Invalid("values nested too deeply").into());
+    }
+    let length = raw::value_byte_length(bytes)?;
+    let value = &bytes[..length];
+    let header = value[0];
+    match ValueType::from_header(header) {
+        ValueType::Array => validate_array(value, &|item| validate_value(item, depth + 1))?,
+        ValueType::Object => validate_object(value, &|item| validate_value(item, depth + 1))?,
+        ValueType::External => return Err(ErrorKind::Unsupported(ValueType::External).into()),
+        ValueType::String => {
+            raw::read_str(value)?;
+        },
+        ValueType::Bcd => {
+            raw::read_bcd(value)?;
+        },
+        ValueType::Tagged => {
+            let n_bytes = if header == 0xee { 1 } else { 8 };
+            validate_value(&value[1 + n_bytes..], depth + 1)?;
+        },
+        _ => {},
+    }
+    Ok(length)
+}
+
+/// Check the array or object `value` without validating its items: that the index table points at
+/// the items, and that sorted objects list their keys in order.
+pub(crate) fn validate_container(value: &[u8]) -> Result<()> {
+    match value.first() {
+        Some(0x01..=0x09 | 0x13) => validate_array(value, &raw::value_byte_length),
+        Some(0x0a..=0x12 | 0x14) => validate_object(value, &raw::value_byte_length),
+        Some(_) => Err(ErrorKind::ExpectedArray.into()),
+        None => Err(ErrorKind::Eof.into()),
+    }
+}
+
+// validate an array, using `item_length` to validate each item and get its length
+fn validate_array(value: &[u8], item_length: &dyn Fn(&[u8]) -> Result<usize>) -> Result<()> {
+    let container = raw::Container::new(value)?;
+    let (offsets, rest) = walk(value, &container, false, item_length)?;
+    if !rest.is_empty() {
+        return Err(ErrorKind::Invalid("unexpected bytes after array items").into());
+    }
+    let valid = match value[0] {
+        // without index table, all items have the same length
+        0x02..=0x05 => {
+            let item_length = offsets.get(1).map(|&o| o - offsets[0]);
+            item_length.is_none_or(|n| offsets.windows(2).all(|w| w[1] - w[0] == n))
+        },
+        header @ 0x06..=0x09 => index_table(value, 1 << (header - 0x06), offsets.len())? == offsets,
+        _ => true,
+    };
+    if !valid {
+        return Err(ErrorKind::Invalid("array items don't match their index table or lengths").into());
+    }
+    Ok(())
+}
+
+fn validate_object(value: &[u8], item_length: &dyn Fn(&[u8]) -> Result<usize>) -> Result<()> {
+    let container = raw::Container::new(value)?;
+    let (mut offsets, rest) = walk(value, &container, true, item_length)?;
+    if !rest.is_empty() {
+        return Err(ErrorKind::Invalid("unexpected bytes after object members").into());
+    }
+    if let header @ 0x0b..=0x12 = value[0] {
+        // sorted objects index their members in key order, so only compare the sets of offsets
+        let index = index_table(value, 1 << ((header - 0x0b) % 4), offsets.len())?;
+        let mut sorted = index.clone();
+        sorted.sort_unstable();
+        offsets.sort_unstable();
+        if sorted != offsets {
+            return Err(ErrorKind::Invalid("object index table doesn't match members").into());
+        }
+        if header <= 0x0e && index.windows(2).any(|w| raw::key_order(&value[w[0]..], &value[w[1]..]) == Ordering::Greater) {
+            return Err(ErrorKind::Invalid("sorted object keys are out of order").into());
+        }
+    }
+    Ok(())
+}
+
+// validate the items, or keys and values, of a container, returning their offsets from the start
+// of the container and any bytes left over after them
+fn walk<'a>(value: &[u8], container: &raw::Container<'a>, object: bool, item_length: &dyn Fn(&[u8]) -> Result<usize>) -> Result<(Vec<usize>, &'a [u8])> {
+    let mut data = container.data();
+    let mut offsets = Vec::with_capacity(container.len());
+    for _ in 0..container.len() {
+        offsets.push(data.as_ptr() as usize - value.as_ptr() as usize);
+        if object {
+            match data.first() {
+                Some(0x28..=0x39 | 0x40..=0xbf) => {},
+                Some(_) => return Err(ErrorKind::Invalid("object key isn't a string or integer").into()),
+                None => return Err(ErrorKind::Eof.into()),
+            }
+            data = &data[item_length(data)?..];
+        }
+        data = &data[item_length(data)?..];
+    }
+    Ok((offsets, data))
+}
+
+// read the offsets of an index table of `len` entries of `n_bytes` each
+fn index_table(value: &[u8], n_bytes: usize, len: usize) -> Result<Vec<usize>> {
+    let end = if n_bytes == 8 { value.len() - 8 } else { value.len() };
+    let start = end.checked_sub(len * n_bytes).ok_or(ErrorKind::Eof)?;
+    value[start..end].chunks(n_bytes)
+        .map(|entry| raw::read_uint(entry, n_bytes).and_then(raw::to_usize))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{to_bytes, to_bytes_with_options, IndexWidth, SerializerOptions};
+    use serde_json::json;
+
+    #[test]
+    fn valid() {
+        let doc = json!({"a": [1, "foo", [], {}], "b": {"c": null, "d": 1.5}, "e": [1, 2, 3], "f": u64::MAX});
+        for options in [
+            SerializerOptions::new(),
+            SerializerOptions::new().unsorted_objects(true),
+            SerializerOptions::new().compact_containers(true),
+            SerializerOptions::new().index_width(IndexWidth::Eight),
+        ] {
+            validate(&to_bytes_with_options(&doc, &options).unwrap()).unwrap();
+        }
+        validate(&[0xee, 0x01, 0x31]).unwrap();
+        validate(&[0x0b, 0x07, 0x01, 0x31, 0x41, 0x61, 0x03]).unwrap();
+        validate(&[0xc8, 0x01, 0, 0, 0, 0, 0x12]).unwrap();
+    }
+
+    #[test]
+    fn invalid() {
+        let mut bytes = to_bytes(&json!([1, "foo", 300])).unwrap();
+        validate(&bytes).unwrap();
+        assert_eq!(validate(&bytes[..bytes.len() - 1]), Err(ErrorKind::Eof.into()));
+        bytes.push(0x18);
+        assert_eq!(validate(&bytes), Err(ErrorKind::TrailingBytes(1).into()));
+
+        // index table not matching the items
+        validate(&[0x06, 0x07, 0x02, 0x31, 0x32, 0x03, 0x04]).unwrap();
+        assert!(matches!(validate(&[0x06, 0x07, 0x02, 0x31, 0x32, 0x04, 0x03]).unwrap_err().kind(), ErrorKind::Invalid(_)));
+        // items of differing length without index table
+        assert!(matches!(validate(&[0x02, 0x08, 0x41, 0x61, 0x31, 0x42, 0x62, 0x63]).unwrap_err().kind(), ErrorKind::Invalid(_)));
+        assert!(matches!(validate(&[0x02, 0x05, 0x41, 0x61, 0x31]).unwrap_err().kind(), ErrorKind::Invalid(_)));
+        // integer value as key
+        assert!(matches!(validate(&[0x0b, 0x07, 0x01, 0x20, 0x01, 0x31, 0x03]).unwrap_err().kind(), ErrorKind::Invalid(_)));
+        assert!(matches!(validate(&[0x42, 0xff, 0xfe]).unwrap_err().kind(), ErrorKind::InvalidUtf8));
+        assert_eq!(validate(&[0x15]), Err(ErrorKind::Unimplemented(0x15).into()));
+        assert_eq!(validate(&[0x1d, 0, 0, 0, 0, 0, 0, 0, 0]), Err(ErrorKind::Unsupported(ValueType::External).into()));
+        assert_eq!(validate(&[0xc8, 0x01, 0, 0, 0, 0, 0x1a]), Err(ErrorKind::InvalidBcd.into()));
+
+        // sorted object with "b" indexed before "a"
+        validate(&[0x0f, 0x0b, 0x02, 0x41, 0x62, 0x31, 0x41, 0x61, 0x32, 0x03, 0x06]).unwrap();
+        assert_eq!(validate(&[0x0b, 0x0b, 0x02, 0x41, 0x62, 0x31, 0x41, 0x61, 0x32, 0x03, 0x06]), Err(ErrorKind::Invalid("sorted object keys are out of order").into()));
+
+        let mut nested = [0xee, 0x01].repeat(MAX_DEPTH + 1);
+        nested.push(0x18);
+        assert!(matches!(validate(&nested).unwrap_err().kind(), ErrorKind::Invalid(_)));
+    }
+}
@@ -0,0 +1,1082 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use crate::dumper::write_json_str;
+use crate::error::{ErrorKind, Result};
+use crate::number::Number;
+use crate::raw;
+use crate::raw_vpack::RawVPack;
+use crate::ser::SerializerOptions;
+use crate::tagged::Tagged;
+use crate::utc_date::UtcDate;
+use crate::value_type::ValueType;
+
+/// An owned VelocyPack value of any type, for documents without a fixed schema.
+///
+/// Decoding maps BCD numbers to `String` and illegal values to `Null`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Binary(Vec<u8>),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+    UtcDate(UtcDate),
+    MinKey,
+    MaxKey,
+    /// A custom type value, including its header byte.
+    Custom(Vec<u8>),
+    /// A value with a tag, see [`Tagged`](crate::Tagged).
+    Tagged(u64, Box<Value>),
+}
+
+impl Value {
+    /// Decode the VelocyPack value at the start of `bytes`.
+    ///
+    /// Fails with `ErrorKind::DepthLimitExceeded` on arrays, objects and tagged values nested
+    /// more than [`DEFAULT_MAX_DEPTH`](crate::DEFAULT_MAX_DEPTH) deep.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Value::parse(bytes, 0)
+    }
+
+    fn parse(bytes: &[u8], depth: usize) -> Result<Self> {
+        // chains of tags are collected here rather than recursed into
+        let mut bytes = bytes;
+        let mut depth = depth;
+        let mut tags = Vec::new();
+        while let Some(&header @ (0xee | 0xef)) = bytes.first() {
+            depth = nested(depth)?;
+            let n_bytes = if header == 0xee { 1 } else { 8 };
+            tags.push(raw::read_uint(&bytes[1..], n_bytes)?);
+            bytes = bytes.get(1 + n_bytes..).ok_or(ErrorKind::Eof)?;
+        }
+
+        let header = *bytes.first().ok_or(ErrorKind::Eof)?;
+        let value = match header {
+            0x01..=0x09 | 0x13 => {
+                let depth = nested(depth)?;
+                let items = raw::Container::new(bytes)?.items();
+                Value::Array(items.map(|item| Value::parse(item?, depth)).collect::<Result<_>>()?)
+            },
+            0x0a..=0x12 | 0x14 => {
+                let depth = nested(depth)?;
+                let mut object = BTreeMap::new();
+                for member in raw::Container::new(bytes)?.members() {
+                    let (key, value) = member?;
+                    object.insert(raw::read_key(key)?.into_owned(), Value::parse(value, depth)?);
+                }
+                Value::Object(object)
+            },
+            0x17 | 0x18 => Value::Null,
+            0x1e => Value::MinKey,
+            0x1f => Value::MaxKey,
+            0x19 => Value::Bool(false),
+            0x1a => Value::Bool(true),
+            0x1b | 0x20..=0x3f => Value::Number(Number::from_bytes(bytes)?),
+            0x1c => Value::UtcDate(UtcDate(raw::read_int(&bytes[1..], 8)?)),
+            0x40..=0xbf => Value::String(raw::read_str(bytes)?.to_owned()),
+            0xc8..=0xd7 => Value::String(raw::read_bcd(bytes)?),
+            0xc0..=0xc7 => {
+                let (value, _) = raw::split_value(bytes)?;
+                Value::Binary(value[1 + (header - 0xbf) as usize..].to_vec())
+            },
+            0xf0..=0xff => Value::Custom(raw::split_value(bytes)?.0.to_vec()),
+            b => match ValueType::from_header(b) {
+                ValueType::None => return Err(ErrorKind::Unimplemented(b).into()),
+                value_type => return Err(ErrorKind::Unsupported(value_type).into()),
+            },
+        };
+        Ok(tags.into_iter().rev().fold(value, |value, tag| Value::Tagged(tag, Box::new(value))))
+    }
+
+    /// Encode the value with default options. Null and bools, which no option affects, are
+    /// written as their header byte without going through the serializer.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if let Some(header) = self.single_byte() {
+            return Ok(vec![header]);
+        }
+        crate::to_bytes(self)
+    }
+
+    pub fn to_bytes_with_options(&self, options: &SerializerOptions) -> Result<Vec<u8>> {
+        if let Some(header) = self.single_byte() {
+            return Ok(vec![header]);
+        }
+        crate::to_bytes_with_options(self, options)
+    }
+
+    // the only byte of values encoded the same whatever the options
+    fn single_byte(&self) -> Option<u8> {
+        match self {
+            Value::Null => Some(0x18),
+            Value::Bool(false) => Some(0x19),
+            Value::Bool(true) => Some(0x1a),
+            _ => None,
+        }
+    }
+
+    /// Encode the value after modifying it, copying the parts which are unchanged from `original`,
+    /// the bytes it was decoded from, rather than encoding them again. Only arrays and objects on
+    /// the way to modified values are rebuilt, which is much faster for large documents with a
+    /// few changes, and keeps the exact encoding of everything else.
+    pub fn to_bytes_reusing(&self, original: &[u8]) -> Result<Vec<u8>> {
+        let (original, _) = raw::split_value(original)?;
+        crate::to_bytes(&Reusing { value: self, original })
+    }
+
+    // whether `bytes` encodes this value, with the same number encodings
+    fn encoded_by(&self, bytes: &[u8]) -> Result<bool> {
+        let header = bytes[0];
+        let same = match (self, header) {
+            (Value::Null, 0x18) | (Value::MinKey, 0x1e) | (Value::MaxKey, 0x1f) => true,
+            (Value::Bool(v), 0x19 | 0x1a) => *v == (header == 0x1a),
+            (Value::Number(n), 0x1b | 0x20..=0x3f) => {
+                let m = Number::from_bytes(bytes)?;
+                *n == m && n.encoding() == m.encoding() && n.as_f64().to_bits() == m.as_f64().to_bits()
+            },
+            (Value::String(v), 0x40..=0xbf) => raw::read_str(bytes)? == v,
+            (Value::Binary(v), 0xc0..=0xc7) => bytes[1 + (header - 0xbf) as usize..] == v[..],
+            (Value::UtcDate(d), 0x1c) => raw::read_int(&bytes[1..], 8)? == d.millis(),
+            (Value::Custom(v), 0xf0..=0xff) => bytes == &v[..],
+            (Value::Tagged(tag, v), 0xee | 0xef) => {
+                let n_bytes = if header == 0xee { 1 } else { 8 };
+                raw::read_uint(&bytes[1..], n_bytes)? == *tag && v.encoded_by(&bytes[1 + n_bytes..])?
+            },
+            (Value::Array(items), 0x01..=0x09 | 0x13) => {
+                let mut n = 0;
+                for item in raw::Container::new(bytes)?.items() {
+                    match items.get(n) {
+                        Some(value) if value.encoded_by(item?)? => n += 1,
+                        _ => return Ok(false),
+                    }
+                }
+                n == items.len()
+            },
+            (Value::Object(members), 0x0a..=0x12 | 0x14) => {
+                let mut n = 0;
+                for member in raw::Container::new(bytes)?.members() {
+                    let (key, value) = member?;
+                    match members.get(&*raw::read_key(key)?) {
+                        Some(v) if v.encoded_by(value)? => n += 1,
+                        _ => return Ok(false),
+                    }
+                }
+                n == members.len()
+            },
+            _ => false,
+        };
+        Ok(same)
+    }
+
+    /// Encode the value in the normal form of [`canonicalize`](crate::canonicalize).
+    pub fn canonicalize(&self) -> Result<Vec<u8>> {
+        crate::canonicalize(&self.to_bytes()?)
+    }
+
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::Null => ValueType::Null,
+            Value::Bool(_) => ValueType::Bool,
+            Value::Number(n) => n.value_type(),
+            Value::String(_) => ValueType::String,
+            Value::Binary(_) => ValueType::Binary,
+            Value::Array(_) => ValueType::Array,
+            Value::Object(_) => ValueType::Object,
+            Value::UtcDate(_) => ValueType::UtcDate,
+            Value::MinKey => ValueType::MinKey,
+            Value::MaxKey => ValueType::MaxKey,
+            Value::Custom(_) => ValueType::Custom,
+            Value::Tagged(..) => ValueType::Tagged,
+        }
+    }
+
+    /// Get the tag of a tagged value.
+    pub fn tag(&self) -> Option<u64> {
+        match self {
+            Value::Tagged(tag, _) => Some(*tag),
+            _ => None,
+        }
+    }
+
+    /// Get the value without any tags.
+    pub fn untagged(&self) -> &Value {
+        match self {
+            Value::Tagged(_, v) => v.untagged(),
+            v => v,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Get an integer value as an `i64`, if it fits.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Number(n) => n.as_i64(),
+            _ => None,
+        }
+    }
+
+    /// Get an integer value as a `u64`, if it isn't negative.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(n) => n.as_u64(),
+            _ => None,
+        }
+    }
+
+    /// Get any number as an `f64`, possibly losing precision.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(n.as_f64()),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_binary(&self) -> Option<&[u8]> {
+        match self {
+            Value::Binary(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_utc_date(&self) -> Option<UtcDate> {
+        match self {
+            Value::UtcDate(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Object(o) => Some(o),
+            _ => None,
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_mut(&mut self) -> Option<&mut BTreeMap<String, Value>> {
+        match self {
+            Value::Object(o) => Some(o),
+            _ => None,
+        }
+    }
+
+    /// Get the member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_object()?.get(key)
+    }
+
+    /// Get the item at `index` of an array.
+    pub fn get_index(&self, index: usize) -> Option<&Value> {
+        self.as_array()?.get(index)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.as_object_mut()?.get_mut(key)
+    }
+
+    pub fn get_index_mut(&mut self, index: usize) -> Option<&mut Value> {
+        self.as_array_mut()?.get_mut(index)
+    }
+
+    /// Merge `patch` into the value following RFC 7386 (JSON Merge Patch), like ArangoDB's
+    /// `MERGE_RECURSIVE`: members of a patch object are merged into an object value, recursively,
+    /// with `null` members removing the member. Any other patch replaces the value entirely.
+    pub fn merge(&mut self, patch: Value) {
+        self.merge_with(patch, true)
+    }
+
+    /// Like [`Value::merge`] but only for top-level members, like ArangoDB's `MERGE`: members of
+    /// a patch object replace members of the same name as a whole, and `null` members remove them.
+    pub fn merge_shallow(&mut self, patch: Value) {
+        self.merge_with(patch, false)
+    }
+
+    fn merge_with(&mut self, patch: Value, recursive: bool) {
+        let patch = match patch {
+            Value::Object(patch) => patch,
+            patch => {
+                *self = patch;
+                return;
+            },
+        };
+        if !matches!(self, Value::Object(_)) {
+            *self = Value::Object(BTreeMap::new());
+        }
+        if let Value::Object(members) = self {
+            for (key, value) in patch {
+                if value.is_null() {
+                    members.remove(&key);
+                } else if recursive {
+                    members.entry(key).or_insert(Value::Null).merge_with(value, true);
+                } else {
+                    members.insert(key, value);
+                }
+            }
+        }
+    }
+
+    /// Look up a nested value by a JSON Pointer (RFC 6901) such as `/friends/0/name`, where `~1`
+    /// and `~0` escape `/` and `~`. The empty pointer refers to the value itself.
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        let mut value = self;
+        for token in pointer_tokens(pointer)? {
+            value = match value {
+                Value::Object(members) => members.get(&*token)?,
+                Value::Array(items) => items.get(pointer_index(&token)?)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Like [`Value::pointer`], returning a mutable reference.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        let mut value = self;
+        for token in pointer_tokens(pointer)? {
+            value = match value {
+                Value::Object(members) => members.get_mut(&*token)?,
+                Value::Array(items) => items.get_mut(pointer_index(&token)?)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+}
+
+/// Renders the value as JSON, indented by two spaces per level with `{:#}`. Binary and custom
+/// values are written as base64 strings, UTC dates as milliseconds since the epoch, min/max keys
+/// and NaN/infinite doubles as `null`, and tagged values as the value they tag.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_json(f, self, 0)
+    }
+}
+
+fn write_json(f: &mut fmt::Formatter, value: &Value, depth: usize) -> fmt::Result {
+    let newline = |f: &mut fmt::Formatter, depth: usize| -> fmt::Result {
+        if f.alternate() {
+            f.write_char('\n')?;
+            for _ in 0..depth {
+                f.write_str("  ")?;
+            }
+        }
+        Ok(())
+    };
+
+    match value {
+        Value::Null => f.write_str("null"),
+        Value::Bool(v) => write!(f, "{}", v),
+        Value::Number(n) if n.as_f64().is_finite() => write!(f, "{}", n),
+        Value::Number(_) => f.write_str("null"),
+        Value::String(v) => write_json_str(f, v, false),
+        Value::Binary(v) | Value::Custom(v) => write_json_str(f, &crate::helpers::base64::encode(v), false),
+        Value::UtcDate(d) => write!(f, "{}", d.millis()),
+        Value::MinKey | Value::MaxKey => f.write_str("null"),
+        Value::Tagged(_, v) => write_json(f, v, depth),
+        Value::Array(items) => {
+            f.write_char('[')?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    f.write_char(',')?;
+                }
+                newline(f, depth + 1)?;
+                write_json(f, item, depth + 1)?;
+            }
+            if !items.is_empty() {
+                newline(f, depth)?;
+            }
+            f.write_char(']')
+        },
+        Value::Object(members) => {
+            f.write_char('{')?;
+            for (i, (key, value)) in members.iter().enumerate() {
+                if i > 0 {
+                    f.write_char(',')?;
+                }
+                newline(f, depth + 1)?;
+                write_json_str(f, key, false)?;
+                f.write_str(if f.alternate() { ": " } else { ":" })?;
+                write_json(f, value, depth + 1)?;
+            }
+            if !members.is_empty() {
+                newline(f, depth)?;
+            }
+            f.write_char('}')
+        },
+    }
+}
+
+// depth inside one more level of nesting, failing past the limit
+fn nested(depth: usize) -> Result<usize> {
+    if depth >= crate::DEFAULT_MAX_DEPTH {
+        return Err(ErrorKind::DepthLimitExceeded.into());
+    }
+    Ok(depth + 1)
+}
+
+// unescaped reference tokens of a JSON pointer, `None` if it doesn't start with a `/`
+fn pointer_tokens(pointer: &str) -> Option<impl Iterator<Item = Cow<'_, str>>> {
+    let tokens = if pointer.is_empty() { None } else { Some(pointer.strip_prefix('/')?) };
+    Some(tokens.into_iter().flat_map(|tokens| tokens.split('/')).map(|token| {
+        if token.contains('~') {
+            Cow::Owned(token.replace("~1", "/").replace("~0", "~"))
+        } else {
+            Cow::Borrowed(token)
+        }
+    }))
+}
+
+// array index of a reference token, rejecting signs and leading zeros
+fn pointer_index(token: &str) -> Option<usize> {
+    if token.starts_with('+') || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+
+impl From<Number> for Value {
+    fn from(v: Number) -> Self {
+        Value::Number(v)
+    }
+}
+
+macro_rules! from_number {
+    ($($primitive:ty)*) => {
+        $(
+            impl From<$primitive> for Value {
+                fn from(v: $primitive) -> Self {
+                    Value::Number(v.into())
+                }
+            }
+        )*
+    };
+}
+
+from_number!(i8 i16 i32 i64 isize u8 u16 u32 u64 usize f32 f64);
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::String(v.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::String(v)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(v: Vec<Value>) -> Self {
+        Value::Array(v)
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Value {
+    fn from(v: BTreeMap<String, Value>) -> Self {
+        Value::Object(v)
+    }
+}
+
+impl PartialEq<str> for Value {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == Some(other)
+    }
+}
+
+impl PartialEq<&str> for Value {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == Some(*other)
+    }
+}
+
+impl PartialEq<String> for Value {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == Some(other.as_str())
+    }
+}
+
+impl PartialEq<Value> for str {
+    fn eq(&self, other: &Value) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<Value> for &str {
+    fn eq(&self, other: &Value) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<Value> for String {
+    fn eq(&self, other: &Value) -> bool {
+        other == self
+    }
+}
+
+// comparisons with primitives, converting the value with `$conv` and the primitive to `$ty`
+macro_rules! partial_eq_primitive {
+    ($conv:ident, $ty:ty, $($primitive:ty)*) => {
+        $(
+            impl PartialEq<$primitive> for Value {
+                fn eq(&self, other: &$primitive) -> bool {
+                    self.$conv() == Some(*other as $ty)
+                }
+            }
+
+            impl PartialEq<Value> for $primitive {
+                fn eq(&self, other: &Value) -> bool {
+                    other == self
+                }
+            }
+        )*
+    };
+}
+
+partial_eq_primitive!(as_i64, i64, i8 i16 i32 i64 isize);
+partial_eq_primitive!(as_u64, u64, u8 u16 u32 u64 usize);
+partial_eq_primitive!(as_f64, f64, f32 f64);
+partial_eq_primitive!(as_bool, bool, bool);
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Bool(v) => serializer.serialize_bool(*v),
+            Value::Number(n) => n.serialize(serializer),
+            Value::String(v) => serializer.serialize_str(v),
+            Value::Binary(v) => serializer.serialize_bytes(v),
+            Value::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            },
+            Value::Object(members) => {
+                let mut map = serializer.serialize_map(Some(members.len()))?;
+                for (key, value) in members {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            },
+            Value::UtcDate(d) => d.serialize(serializer),
+            Value::MinKey => serializer.serialize_newtype_struct(crate::MIN_KEY_TOKEN, &()),
+            Value::MaxKey => serializer.serialize_newtype_struct(crate::MAX_KEY_TOKEN, &()),
+            Value::Custom(v) => RawVPack::from_slice(v).map_err(ser::Error::custom)?.serialize(serializer),
+            Value::Tagged(tag, v) => Tagged::new(*tag, &**v).serialize(serializer),
+        }
+    }
+}
+
+// serializes a value, copying unchanged parts from the encoded `original`
+struct Reusing<'a> {
+    value: &'a Value,
+    original: &'a [u8],
+}
+
+impl Serialize for Reusing<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        if self.value.encoded_by(self.original).map_err(ser::Error::custom)? {
+            return RawVPack::from_slice(self.original).map_err(ser::Error::custom)?.serialize(serializer);
+        }
+        match (self.value, self.original[0]) {
+            (Value::Array(items), 0x01..=0x09 | 0x13) => {
+                let container = raw::Container::new(self.original).map_err(ser::Error::custom)?;
+                let mut originals = container.items();
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    match originals.next() {
+                        Some(Ok(original)) => seq.serialize_element(&Reusing { value: item, original })?,
+                        _ => seq.serialize_element(item)?,
+                    }
+                }
+                seq.end()
+            },
+            (Value::Object(members), 0x0a..=0x12 | 0x14) => {
+                let container = raw::Container::new(self.original).map_err(ser::Error::custom)?;
+                let mut originals = HashMap::new();
+                for member in container.members() {
+                    let (key, value) = member.map_err(ser::Error::custom)?;
+                    originals.insert(raw::read_key(key).map_err(ser::Error::custom)?, value);
+                }
+                let mut map = serializer.serialize_map(Some(members.len()))?;
+                for (key, value) in members {
+                    match originals.get(key.as_str()) {
+                        Some(original) => map.serialize_entry(key, &Reusing { value, original })?,
+                        None => map.serialize_entry(key, value)?,
+                    }
+                }
+                map.end()
+            },
+            (value, _) => value.serialize(serializer),
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> de::Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> std::result::Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> std::result::Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> std::result::Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<Value, E> {
+        Ok(Value::Binary(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> std::result::Result<Value, E> {
+        Ok(Value::Binary(v))
+    }
+
+    fn visit_none<E: de::Error>(self) -> std::result::Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> std::result::Result<Value, A::Error> {
+        let mut members = BTreeMap::new();
+        let first: Option<String> = map.next_key()?;
+        if first.as_deref() == Some(crate::RAW_TOKEN) {
+            // value without a serde equivalent passed along by this crate's deserializer
+            let Encoded(value) = map.next_value()?;
+            return Ok(value);
+        }
+        if first.as_deref() == Some(crate::TAGGED_TOKEN) {
+            let Tagged { tag, value } = map.next_value()?;
+            return Ok(Value::Tagged(tag, Box::new(value)));
+        }
+        if let Some(key) = first {
+            members.insert(key, map.next_value()?);
+        }
+        while let Some((key, value)) = map.next_entry()? {
+            members.insert(key, value);
+        }
+        Ok(Value::Object(members))
+    }
+}
+
+struct Encoded(Value);
+
+impl<'de> Deserialize<'de> for Encoded {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct EncodedVisitor;
+
+        impl<'de> de::Visitor<'de> for EncodedVisitor {
+            type Value = Encoded;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an encoded value")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<Encoded, E> {
+                Value::from_bytes(v).map(Encoded).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_bytes(EncodedVisitor)
+    }
+}
+
+/// Any value can be deserialized into a `Value`. With this crate's deserializer VelocyPack binary
+/// values become `Value::Binary`, rather than an array of bytes.
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(crate::VALUE_TOKEN, ValueVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut object = BTreeMap::new();
+        object.insert("a".to_owned(), Value::Array(vec![Value::Null, true.into(), (-3_i64).into(), 1000_u64.into()]));
+        object.insert("b".to_owned(), Value::from(1.5));
+        object.insert("c".to_owned(), "foo".into());
+        object.insert("d".to_owned(), Value::Binary(vec![0x01, 0x02]));
+        object.insert("e".to_owned(), Value::Object(BTreeMap::new()));
+        let value = Value::Object(object);
+
+        let bytes = value.to_bytes().unwrap();
+        assert_eq!(Value::from_bytes(&bytes).unwrap(), value);
+        assert_eq!(value.get("c").and_then(Value::as_str), Some("foo"));
+        assert_eq!(value.get("a").and_then(|a| a.get_index(3)).and_then(Value::as_i64), Some(1000));
+        assert_eq!(value.get("z"), None);
+    }
+
+    #[test]
+    fn pointer() {
+        let bytes = crate::to_bytes(&serde_json::json!({
+            "friends": [{"name": "Alice"}, {"name": "Bob"}],
+            "a/b": {"~": 1},
+        })).unwrap();
+        let mut value = Value::from_bytes(&bytes).unwrap();
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("/friends/1/name"), Some(&Value::from("Bob")));
+        assert_eq!(value.pointer("/a~1b/~0"), Some(&Value::from(1)));
+        assert_eq!(value.pointer("friends"), None);
+        assert_eq!(value.pointer("/friends/01"), None);
+        assert_eq!(value.pointer("/friends/2"), None);
+        assert_eq!(value.pointer("/friends/0/name/x"), None);
+
+        *value.pointer_mut("/friends/0/name").unwrap() = "Carol".into();
+        assert_eq!(value.pointer("/friends/0/name").and_then(Value::as_str), Some("Carol"));
+        assert!(value.pointer_mut("/missing").is_none());
+    }
+
+    #[test]
+    fn display() {
+        let value = Value::from_bytes(&crate::to_bytes(&serde_json::json!({
+            "a": [1, -2.5, null],
+            "b": "x\"y",
+            "c": {},
+        })).unwrap()).unwrap();
+        assert_eq!(value.to_string(), r#"{"a":[1,-2.5,null],"b":"x\"y","c":{}}"#);
+        assert_eq!(format!("{:#}", value), "{\n  \"a\": [\n    1,\n    -2.5,\n    null\n  ],\n  \"b\": \"x\\\"y\",\n  \"c\": {}\n}");
+        assert_eq!(Value::Binary(b"foo".to_vec()).to_string(), r#""Zm9v""#);
+        assert_eq!(Value::from(f64::NAN).to_string(), "null");
+        assert_eq!(Value::from(u64::MAX).to_string(), "18446744073709551615");
+    }
+
+    #[test]
+    fn eq_primitives() {
+        let foo = Value::from("foo");
+        assert!(foo == "foo");
+        assert!("foo" == foo);
+        assert!(foo == *"foo");
+        let owned = "foo".to_owned();
+        assert!(foo == owned);
+        assert!(owned == foo);
+        assert!(foo != "bar");
+        assert!(foo != 1);
+
+        let (int, uint) = (Value::from(42), Value::from(42_u64));
+        assert!(int == 42 && uint == 42_i8 && 42_u16 == int);
+        let max = Value::from(u64::MAX);
+        assert!(max == u64::MAX && max != -1);
+        assert!(Value::from(-1) != u64::MAX);
+
+        let double = Value::from(2.0);
+        assert!(double == 2.0 && double != 2);
+        assert!(Value::from(2) == 2.0_f32);
+
+        let yes = Value::Bool(true);
+        assert!(yes == true && Value::Null != false);
+    }
+
+    #[test]
+    fn merge() {
+        let value = |json: serde_json::Value| Value::from_bytes(&crate::to_bytes(&json).unwrap()).unwrap();
+        let doc = value(serde_json::json!({"a": {"b": 1, "c": 2}, "d": [1, 2], "e": "x"}));
+        let patch = value(serde_json::json!({"a": {"b": null, "z": {"y": null}}, "d": [3], "e": null, "f": true}));
+
+        let mut merged = doc.clone();
+        merged.merge(patch.clone());
+        assert_eq!(merged, value(serde_json::json!({"a": {"c": 2, "z": {}}, "d": [3], "f": true})));
+
+        let mut merged = doc.clone();
+        merged.merge_shallow(patch);
+        assert_eq!(merged, value(serde_json::json!({"a": {"b": null, "z": {"y": null}}, "d": [3], "f": true})));
+
+        let mut merged = doc;
+        merged.merge(Value::from("replaced"));
+        assert_eq!(merged, "replaced");
+        merged.merge(value(serde_json::json!({"a": 1})));
+        assert_eq!(merged, value(serde_json::json!({"a": 1})));
+    }
+
+    #[test]
+    fn canonicalize() {
+        let value = Value::from_bytes(&crate::to_bytes(&serde_json::json!({"bb": 1, "a": [2, "x"]})).unwrap()).unwrap();
+        let options = SerializerOptions::new().unsorted_objects(true).compact_containers(true);
+        let bytes = value.to_bytes_with_options(&options).unwrap();
+        assert_eq!(crate::canonicalize(&bytes).unwrap(), value.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn serde() {
+        #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+        struct Document {
+            id: u32,
+            extra: Value,
+        }
+
+        let extra = Value::from_bytes(&crate::to_bytes(&serde_json::json!({"a": [1, -2, 1.5], "b": "c"})).unwrap()).unwrap();
+        let mut doc = Document { id: 1, extra };
+        let bytes = crate::to_bytes(&doc).unwrap();
+        assert_eq!(crate::from_bytes::<Document>(&bytes).unwrap(), doc);
+        assert_eq!(crate::from_bytes::<Value>(&bytes).unwrap(), Value::from_bytes(&bytes).unwrap());
+
+        doc.extra = Value::Array(vec![Value::Binary(vec![0x01, 0xff]), Value::Null]);
+        let bytes = crate::to_bytes(&doc).unwrap();
+        assert_eq!(crate::from_bytes::<Document>(&bytes).unwrap(), doc);
+        assert_eq!(crate::from_bytes::<Value>(&[0xf0, 0xab]).unwrap(), Value::Custom(vec![0xf0, 0xab]));
+        // outside of `Value`s binary values still decode as arrays
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&bytes).unwrap()["extra"][0], serde_json::json!([1, 255]));
+
+        let json = r#"{"extra":{"x":[true,null,"y"]},"id":2}"#;
+        let doc: Document = serde_json::from_str(json).unwrap();
+        assert_eq!(doc.extra.pointer("/x/2"), Some(&Value::from("y")));
+        assert_eq!(serde_json::to_string(&doc.extra).unwrap(), r#"{"x":[true,null,"y"]}"#);
+        let bytes = crate::to_bytes(&[Value::from(1_u64)]).unwrap();
+        assert_eq!(crate::from_bytes::<Vec<Value>>(&bytes).unwrap(), vec![Value::from(1)]);
+    }
+
+    #[test]
+    fn numbers_keep_encoding() {
+        // 1 as an 8 byte signed int, u64::MAX, 1000 as a 4 byte uint, -1 as a 2 byte int
+        let bytes = [
+            0x13, 0x1d,
+            0x27, 0x01, 0, 0, 0, 0, 0, 0, 0,
+            0x2f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0x2b, 0xe8, 0x03, 0x00, 0x00,
+            0x21, 0xff, 0xff,
+            0x04,
+        ];
+        let value = Value::from_bytes(&bytes).unwrap();
+        assert_eq!(value.get_index(1).and_then(Value::as_u64), Some(u64::MAX));
+        assert_eq!(value.get_index(3), Some(&Value::from(-1)));
+        let options = SerializerOptions::new().compact_containers(true);
+        assert_eq!(value.to_bytes_with_options(&options).unwrap(), &bytes[..]);
+        assert_eq!(crate::from_bytes::<Value>(&bytes).unwrap().to_bytes_with_options(&options).unwrap(), &bytes[..]);
+        let plain = vec![Value::from(1), Value::from(u64::MAX), Value::from(1000), Value::from(-1)];
+        assert_eq!(value.canonicalize().unwrap(), crate::to_bytes(&plain).unwrap());
+        assert_eq!(serde_json::to_string(&value).unwrap(), "[1,18446744073709551615,1000,-1]");
+    }
+
+    #[test]
+    fn velocypack_types() {
+        let value = Value::Array(vec![
+            Value::UtcDate(UtcDate(1577836800000)),
+            Value::MinKey,
+            Value::MaxKey,
+            Value::Custom(vec![0xf1, 0x01, 0x02]),
+            Value::Binary(vec![0x03]),
+        ]);
+        let bytes = value.to_bytes().unwrap();
+        assert_eq!(&bytes[3..], &[
+            0x1c, 0x00, 0xe8, 0x66, 0x5e, 0x6f, 0x01, 0x00, 0x00,
+            0x1e,
+            0x1f,
+            0xf1, 0x01, 0x02,
+            0xc0, 0x01, 0x03,
+            0x03, 0x0c, 0x0d, 0x0e, 0x11,
+        ]);
+        assert_eq!(Value::from_bytes(&bytes).unwrap(), value);
+        assert_eq!(crate::from_bytes::<Value>(&bytes).unwrap(), value);
+        assert_eq!(value.to_string(), r#"[1577836800000,null,null,"8QEC","Aw=="]"#);
+        assert_eq!(serde_json::to_string(&value).unwrap(), "[1577836800000,null,null,[241,1,2],[3]]");
+        assert!(Value::Custom(vec![0xf1]).to_bytes().is_err());
+    }
+
+    #[test]
+    fn mutable_access() {
+        let mut value = Value::from_bytes(&crate::to_bytes(&serde_json::json!({"a": [1, 2], "b": {"c": 3}})).unwrap()).unwrap();
+        *value.get_mut("a").and_then(|a| a.get_index_mut(1)).unwrap() = Value::from(20);
+        value.get_mut("b").and_then(Value::as_object_mut).unwrap().insert("d".to_owned(), Value::Null);
+        value.get_mut("a").and_then(Value::as_array_mut).unwrap().push(Value::from("x"));
+        assert_eq!(value.to_string(), r#"{"a":[1,20,"x"],"b":{"c":3,"d":null}}"#);
+        assert!(value.get_mut("z").is_none());
+        assert!(value.get_index_mut(0).is_none());
+    }
+
+    #[test]
+    fn to_bytes_reusing() {
+        // {"counter": 1, "history": [1 as an 8 byte uint, "x"], "name": "foo"}, unsorted compact
+        let history = [0x13, 0x0e, 0x2f, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x41, 0x78, 0x02];
+        let mut original = vec![0x14, 0x00, 0x47];
+        original.extend_from_slice(b"counter");
+        original.push(0x31);
+        original.push(0x47);
+        original.extend_from_slice(b"history");
+        original.extend_from_slice(&history);
+        original.push(0x44);
+        original.extend_from_slice(b"name");
+        original.extend_from_slice(&[0x43, 0x66, 0x6f, 0x6f, 0x03]);
+        original[1] = original.len() as u8;
+
+        let mut value = Value::from_bytes(&original).unwrap();
+        assert_eq!(value.to_bytes_reusing(&original).unwrap(), original);
+
+        *value.get_mut("counter").unwrap() = Value::from(2);
+        let bytes = value.to_bytes_reusing(&original).unwrap();
+        assert!(bytes.windows(history.len()).any(|w| w == history));
+        assert_eq!(Value::from_bytes(&bytes).unwrap(), value);
+
+        *value.pointer_mut("/history/1").unwrap() = Value::from("y");
+        value.as_object_mut().unwrap().remove("name");
+        let bytes = value.to_bytes_reusing(&original).unwrap();
+        assert!(bytes.windows(9).any(|w| w == &history[2..11]));
+        assert_eq!(Value::from_bytes(&bytes).unwrap(), value);
+        assert!(Value::Null.to_bytes_reusing(&[]).is_err());
+    }
+
+    #[test]
+    fn from_bytes() {
+        assert_eq!(Value::from_bytes(&[0x17]).unwrap(), Value::Null);
+        assert_eq!(Value::from_bytes(&[0x3f]).unwrap(), Value::from(-1));
+        assert_eq!(Value::from_bytes(&[0x28, 0xff]).unwrap(), Value::from(255));
+        let tagged = Value::from_bytes(&[0xee, 0x01, 0x35]).unwrap();
+        assert_eq!(tagged, Value::Tagged(1, Box::new(Value::from(5))));
+        assert_eq!((tagged.tag(), tagged.untagged(), tagged.to_string()), (Some(1), &Value::from(5), "5".to_owned()));
+        assert_eq!(crate::from_bytes::<Value>(&[0xee, 0x01, 0x35]).unwrap(), tagged);
+        assert_eq!(tagged.to_bytes().unwrap(), &[0xee, 0x01, 0x35]);
+        assert_eq!(crate::from_bytes::<Value>(&[0x02, 0x0c, 0xef, 0x2c, 0x01, 0, 0, 0, 0, 0, 0, 0x31]).unwrap(),
+                   Value::Array(vec![Value::Tagged(300, Box::new(Value::from(1)))]));
+        assert_eq!(Value::from_bytes(&[0xc0, 0x01, 0xab]).unwrap(), Value::Binary(vec![0xab]));
+        assert_eq!(Value::from_bytes(&[0xc8, 0x01, 0x00, 0x00, 0x00, 0x00, 0x12]).unwrap(), Value::String("12".to_owned()));
+        // object with attribute id key
+        assert_eq!(Value::from_bytes(&[0x14, 0x05, 0x31, 0x18, 0x01]).unwrap().get("1"), Some(&Value::Null));
+        assert!(matches!(Value::from_bytes(&[0x1d]).unwrap_err().kind(), ErrorKind::Unsupported(ValueType::External)));
+        assert!(matches!(Value::from_bytes(&[0x00]).unwrap_err().kind(), ErrorKind::Unimplemented(0x00)));
+        assert!(Value::from_bytes(&[0x02, 0x04, 0x18]).is_err());
+    }
+
+    #[test]
+    fn single_bytes() {
+        let options = SerializerOptions::new().index_width(crate::IndexWidth::Eight).compact_containers(true);
+        for (value, header) in [(Value::Null, 0x18), (Value::Bool(false), 0x19), (Value::Bool(true), 0x1a)] {
+            assert_eq!(value.to_bytes().unwrap(), [header]);
+            assert_eq!(value.to_bytes_with_options(&options).unwrap(), [header]);
+            assert_eq!(crate::to_bytes(&value).unwrap(), [header]);
+            assert_eq!(Value::from_bytes(&[header]).unwrap(), value);
+        }
+
+        // objects of only single byte members, with each size of offsets
+        let members: &[(&str, &[u8])] = &[("b", &[0x1a]), ("a", &[0x18]), ("c", &[0x19])];
+        let mut object = BTreeMap::new();
+        object.insert("a".to_owned(), Value::Null);
+        object.insert("b".to_owned(), Value::Bool(true));
+        object.insert("c".to_owned(), Value::Bool(false));
+        let object = Value::Object(object);
+        for offset_size in [1, 2, 4, 8] {
+            let bytes = raw::object_with_offset_size(offset_size, members);
+            assert_eq!(Value::from_bytes(&bytes).unwrap(), object);
+            assert_eq!(crate::from_bytes::<Value>(&bytes).unwrap(), object);
+            assert_eq!(object.to_bytes_reusing(&bytes).unwrap(), bytes);
+        }
+        for width in [crate::IndexWidth::Smallest, crate::IndexWidth::Four, crate::IndexWidth::Eight] {
+            let bytes = object.to_bytes_with_options(&SerializerOptions::new().index_width(width)).unwrap();
+            assert_eq!(Value::from_bytes(&bytes).unwrap(), object);
+        }
+    }
+
+    #[test]
+    fn from_bytes_depth() {
+        // arrays nested `n` deep, around an empty array
+        let nested = |n: usize| {
+            let mut data = Vec::new();
+            for i in 0..n {
+                data.push(0x05);
+                data.extend_from_slice(&(9 * (n - i) as u64 + 1).to_le_bytes());
+            }
+            data.push(0x01);
+            data
+        };
+        assert!(Value::from_bytes(&nested(crate::DEFAULT_MAX_DEPTH - 1)).is_ok());
+        assert_eq!(Value::from_bytes(&nested(crate::DEFAULT_MAX_DEPTH)).unwrap_err().kind(), &ErrorKind::DepthLimitExceeded);
+        assert_eq!(Value::from_bytes(&nested(100_000)).unwrap_err().kind(), &ErrorKind::DepthLimitExceeded);
+
+        let tags = |n: usize| {
+            let mut data = [0xee, 0x01].repeat(n);
+            data.push(0x18);
+            data
+        };
+        let value = Value::from_bytes(&tags(3)).unwrap();
+        assert_eq!(value, Value::Tagged(1, Box::new(Value::Tagged(1, Box::new(Value::Tagged(1, Box::new(Value::Null)))))));
+        assert_eq!(value, crate::from_bytes::<Value>(&tags(3)).unwrap());
+        assert!(Value::from_bytes(&tags(crate::DEFAULT_MAX_DEPTH)).is_ok());
+        assert_eq!(Value::from_bytes(&tags(100_000)).unwrap_err().kind(), &ErrorKind::DepthLimitExceeded);
+    }
+}
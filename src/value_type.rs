@@ -0,0 +1,124 @@
+use std::fmt::{self, Display};
+
+/// Type of a VelocyPack value, as determined by its header byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    /// 0x00, and reserved header bytes.
+    None,
+    Illegal,
+    Null,
+    Bool,
+    Array,
+    Object,
+    Double,
+    UtcDate,
+    External,
+    MinKey,
+    MaxKey,
+    Int,
+    UInt,
+    SmallInt,
+    String,
+    Binary,
+    Bcd,
+    Tagged,
+    Custom,
+}
+
+impl ValueType {
+    /// Get the type of a value from its header byte.
+    pub const fn from_header(header: u8) -> Self {
+        TYPES[header as usize]
+    }
+}
+
+// type of every header byte, so that dispatching on the type of a value (when decoding,
+// validating or inspecting slices) is a single lookup rather than a chain of range comparisons
+static TYPES: [ValueType; 256] = {
+    let mut types = [ValueType::None; 256];
+    let mut header = 0;
+    while header < 256 {
+        types[header] = match header as u8 {
+            0x01..=0x09 | 0x13 => ValueType::Array,
+            0x0a..=0x12 | 0x14 => ValueType::Object,
+            0x17 => ValueType::Illegal,
+            0x18 => ValueType::Null,
+            0x19 | 0x1a => ValueType::Bool,
+            0x1b => ValueType::Double,
+            0x1c => ValueType::UtcDate,
+            0x1d => ValueType::External,
+            0x1e => ValueType::MinKey,
+            0x1f => ValueType::MaxKey,
+            0x20..=0x27 => ValueType::Int,
+            0x28..=0x2f => ValueType::UInt,
+            0x30..=0x3f => ValueType::SmallInt,
+            0x40..=0xbf => ValueType::String,
+            0xc0..=0xc7 => ValueType::Binary,
+            0xc8..=0xd7 => ValueType::Bcd,
+            0xee | 0xef => ValueType::Tagged,
+            0xf0..=0xff => ValueType::Custom,
+            _ => ValueType::None,
+        };
+        header += 1;
+    }
+    types
+};
+
+impl Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            ValueType::None => "none",
+            ValueType::Illegal => "illegal",
+            ValueType::Null => "null",
+            ValueType::Bool => "bool",
+            ValueType::Array => "array",
+            ValueType::Object => "object",
+            ValueType::Double => "double",
+            ValueType::UtcDate => "utc-date",
+            ValueType::External => "external",
+            ValueType::MinKey => "min-key",
+            ValueType::MaxKey => "max-key",
+            ValueType::Int => "int",
+            ValueType::UInt => "uint",
+            ValueType::SmallInt => "small int",
+            ValueType::String => "string",
+            ValueType::Binary => "binary",
+            ValueType::Bcd => "bcd",
+            ValueType::Tagged => "tagged",
+            ValueType::Custom => "custom",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_header() {
+        assert_eq!(ValueType::from_header(0x00), ValueType::None);
+        assert_eq!(ValueType::from_header(0x01), ValueType::Array);
+        assert_eq!(ValueType::from_header(0x13), ValueType::Array);
+        assert_eq!(ValueType::from_header(0x0b), ValueType::Object);
+        assert_eq!(ValueType::from_header(0x14), ValueType::Object);
+        assert_eq!(ValueType::from_header(0x15), ValueType::None);
+        assert_eq!(ValueType::from_header(0x1a), ValueType::Bool);
+        assert_eq!(ValueType::from_header(0x1c), ValueType::UtcDate);
+        assert_eq!(ValueType::from_header(0x27), ValueType::Int);
+        assert_eq!(ValueType::from_header(0x28), ValueType::UInt);
+        assert_eq!(ValueType::from_header(0x3f), ValueType::SmallInt);
+        assert_eq!(ValueType::from_header(0xbf), ValueType::String);
+        assert_eq!(ValueType::from_header(0xc7), ValueType::Binary);
+        assert_eq!(ValueType::from_header(0xd7), ValueType::Bcd);
+        assert_eq!(ValueType::from_header(0xd8), ValueType::None);
+        assert_eq!(ValueType::from_header(0xee), ValueType::Tagged);
+        assert_eq!(ValueType::from_header(0xff), ValueType::Custom);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(ValueType::UtcDate.to_string(), "utc-date");
+        assert_eq!(ValueType::SmallInt.to_string(), "small int");
+    }
+}
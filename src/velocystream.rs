@@ -0,0 +1,676 @@
+//! Chunking and headers of messages for
+//! [VelocyStream](https://github.com/arangodb/velocystream) (version 1.1), the binary protocol
+//! ArangoDB serves over TCP, whose messages are one or more VelocyPack values sent back to back
+//! in chunks.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeTuple, Serializer};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, ErrorKind, Result};
+
+/// Bytes taken up by the header of every chunk: chunk length, chunk number, message id and
+/// message length.
+pub const CHUNK_HEADER_SIZE: usize = 4 + 4 + 8 + 8;
+
+/// Largest size of a chunk (including its header) ArangoDB sends by default.
+pub const DEFAULT_MAX_CHUNK_SIZE: usize = 30 * 1024;
+
+/// Split `message` (e.g. a request header followed by its body, see
+/// [`first_from_bytes`](crate::first_from_bytes)) into the chunks to send for message
+/// `message_id`, each no larger than `max_chunk_size` bytes including its header.
+///
+/// The first chunk holds the number of chunks, later ones their position, as VelocyStream 1.1
+/// expects. An empty message is sent as one chunk without data.
+pub fn chunk_message(message_id: u64, message: &[u8], max_chunk_size: usize) -> Result<Vec<Vec<u8>>> {
+    if max_chunk_size <= CHUNK_HEADER_SIZE {
+        return Err(ErrorKind::Message(format!("chunks must be larger than their {} byte header", CHUNK_HEADER_SIZE)).into());
+    }
+    let data_size = max_chunk_size - CHUNK_HEADER_SIZE;
+    let n_chunks = message.len().div_ceil(data_size).max(1);
+    // chunk numbers are stored shifted left by one, next to the first chunk bit
+    let n_chunks = u32::try_from(n_chunks).ok().filter(|n| *n < 1 << 31).ok_or(ErrorKind::NumberTooLarge)?;
+
+    let mut chunks = Vec::with_capacity(n_chunks as usize);
+    for i in 0..n_chunks {
+        let start = i as usize * data_size;
+        let data = &message[start.min(message.len())..(start + data_size).min(message.len())];
+        let chunk_x = if i == 0 { n_chunks << 1 | 1 } else { i << 1 };
+
+        let mut chunk = Vec::with_capacity(CHUNK_HEADER_SIZE + data.len());
+        chunk.extend_from_slice(&((CHUNK_HEADER_SIZE + data.len()) as u32).to_le_bytes());
+        chunk.extend_from_slice(&chunk_x.to_le_bytes());
+        chunk.extend_from_slice(&message_id.to_le_bytes());
+        chunk.extend_from_slice(&(message.len() as u64).to_le_bytes());
+        chunk.extend_from_slice(data);
+        chunks.push(chunk);
+    }
+    Ok(chunks)
+}
+
+/// Assembles whole messages from the chunks received on a connection, and chunks the messages
+/// sent on it, so that a driver only deals with whole messages. Not tied to any I/O runtime:
+/// bytes received are passed to [`feed`](MessageCodec::feed), and the bytes returned by
+/// [`encode`](MessageCodec::encode) written out as they are.
+///
+/// Chunks of different messages may be interleaved, and the chunks of a message may arrive in
+/// any order.
+///
+/// With the `tokio` feature, it is also a `tokio_util::codec` `Decoder` and `Encoder` of
+/// `(message_id, message)` pairs, for use with `Framed`.
+#[derive(Debug)]
+pub struct MessageCodec {
+    max_chunk_size: usize,
+    // bytes received, of which the first `consumed` have been decoded
+    buffer: Vec<u8>,
+    consumed: usize,
+    // chunks received of messages not yet complete, by message id
+    partial: HashMap<u64, Partial>,
+}
+
+#[derive(Debug, Default)]
+struct Partial {
+    // known once the first chunk has been received
+    n_chunks: Option<u32>,
+    message_length: u64,
+    chunks: BTreeMap<u32, Vec<u8>>,
+}
+
+impl Default for MessageCodec {
+    fn default() -> Self {
+        MessageCodec { max_chunk_size: DEFAULT_MAX_CHUNK_SIZE, buffer: Vec::new(), consumed: 0, partial: HashMap::new() }
+    }
+}
+
+impl MessageCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Largest size of chunks written by [`encode`](MessageCodec::encode), including their
+    /// header, [`DEFAULT_MAX_CHUNK_SIZE`] by default.
+    pub fn max_chunk_size(mut self, size: usize) -> Self {
+        self.max_chunk_size = size;
+        self
+    }
+
+    /// The chunks of message `message_id`, back to back, ready to be written.
+    pub fn encode(&self, message_id: u64, message: &[u8]) -> Result<Vec<u8>> {
+        Ok(chunk_message(message_id, message, self.max_chunk_size)?.concat())
+    }
+
+    /// Add bytes received to those buffered.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        if self.consumed > 0 {
+            self.buffer.drain(..self.consumed);
+            self.consumed = 0;
+        }
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Decode the chunks buffered until a message is complete, returning its id and bytes, or
+    /// `None` if more bytes are needed.
+    pub fn decode(&mut self) -> Result<Option<(u64, Vec<u8>)>> {
+        loop {
+            let buffered = &self.buffer[self.consumed..];
+            if buffered.len() < CHUNK_HEADER_SIZE {
+                return Ok(None);
+            }
+            let chunk_length = u32::from_le_bytes([buffered[0], buffered[1], buffered[2], buffered[3]]) as usize;
+            if chunk_length < CHUNK_HEADER_SIZE {
+                return Err(ErrorKind::Message(format!("chunk length {} shorter than chunk header", chunk_length)).into());
+            }
+            if buffered.len() < chunk_length {
+                return Ok(None);
+            }
+            let chunk_x = u32::from_le_bytes([buffered[4], buffered[5], buffered[6], buffered[7]]);
+            let message_id = crate::raw::read_uint(&buffered[8..], 8)?;
+            let message_length = crate::raw::read_uint(&buffered[16..], 8)?;
+            let data = buffered[CHUNK_HEADER_SIZE..chunk_length].to_vec();
+            self.consumed += chunk_length;
+
+            let partial = self.partial.entry(message_id).or_default();
+            let index = if chunk_x & 1 == 1 {
+                if chunk_x >> 1 == 0 {
+                    return Err(ErrorKind::Message(format!("message {} has no chunks", message_id)).into());
+                }
+                partial.n_chunks = Some(chunk_x >> 1);
+                0
+            } else {
+                chunk_x >> 1
+            };
+            partial.message_length = message_length;
+            partial.chunks.insert(index, data);
+
+            if partial.n_chunks == Some(partial.chunks.len() as u32) {
+                let partial = self.partial.remove(&message_id).unwrap_or_default();
+                // chunks are numbered from 0, so the last one shows whether any are missing
+                if partial.chunks.keys().next_back().map(|&i| i + 1) != partial.n_chunks {
+                    return Err(ErrorKind::Message(format!("chunks of message {} numbered out of range", message_id)).into());
+                }
+                let message = partial.chunks.into_values().collect::<Vec<_>>().concat();
+                if message.len() as u64 != partial.message_length {
+                    return Err(ErrorKind::Message(format!("message {} of {} bytes, expected {}", message_id, message.len(), partial.message_length)).into());
+                }
+                return Ok(Some((message_id, message)));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio_util::codec::Decoder for MessageCodec {
+    type Item = (u64, Vec<u8>);
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>> {
+        // chunks of incomplete messages are kept by the codec, so take everything buffered
+        self.feed(src);
+        src.clear();
+        MessageCodec::decode(self)
+    }
+
+    fn decode_eof(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>> {
+        match tokio_util::codec::Decoder::decode(self, src)? {
+            None if self.consumed < self.buffer.len() || !self.partial.is_empty() => Err(ErrorKind::Eof.into()),
+            message => Ok(message),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio_util::codec::Encoder<(u64, Vec<u8>)> for MessageCodec {
+    type Error = Error;
+
+    fn encode(&mut self, (message_id, message): (u64, Vec<u8>), dst: &mut bytes::BytesMut) -> Result<()> {
+        for chunk in chunk_message(message_id, &message, self.max_chunk_size)? {
+            dst.extend_from_slice(&chunk);
+        }
+        Ok(())
+    }
+}
+
+// message type of requests, in their header
+const REQUEST_TYPE: u64 = 1;
+
+/// HTTP method of a request, encoded as a number in its header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RequestType {
+    Delete = 0,
+    Get = 1,
+    Post = 2,
+    Put = 3,
+    Head = 4,
+    Patch = 5,
+    Options = 6,
+}
+
+impl TryFrom<u64> for RequestType {
+    type Error = Error;
+
+    fn try_from(n: u64) -> Result<Self> {
+        Ok(match n {
+            0 => RequestType::Delete,
+            1 => RequestType::Get,
+            2 => RequestType::Post,
+            3 => RequestType::Put,
+            4 => RequestType::Head,
+            5 => RequestType::Patch,
+            6 => RequestType::Options,
+            _ => return Err(ErrorKind::Message(format!("unknown request type {}", n)).into()),
+        })
+    }
+}
+
+impl Serialize for RequestType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u64(*self as u64)
+    }
+}
+
+impl<'de> Deserialize<'de> for RequestType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        RequestType::try_from(u64::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+/// Header of a request message, which is (de)serialized as the array
+/// `[version, 1, database, requestType, request, parameters, meta]` ArangoDB expects, and is
+/// followed by the request body (if any) in the same message:
+///
+/// ```
+/// use velocypack::velocystream::{RequestHeader, RequestType};
+///
+/// let header = RequestHeader::new("_system", RequestType::Get, "/_api/version").parameter("details", "true");
+/// let message = velocypack::to_bytes(&header)?;
+/// assert_eq!(velocypack::from_bytes::<RequestHeader>(&message)?, header);
+/// # Ok::<(), velocypack::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestHeader {
+    pub version: u64,
+    pub database: String,
+    pub request_type: RequestType,
+    /// Path of the request, e.g. `/_api/document/users/1`.
+    pub request: String,
+    /// Query string parameters.
+    pub parameters: BTreeMap<String, String>,
+    /// HTTP style headers, e.g. `content-type`.
+    pub meta: BTreeMap<String, String>,
+}
+
+impl RequestHeader {
+    pub fn new(database: impl Into<String>, request_type: RequestType, request: impl Into<String>) -> Self {
+        RequestHeader {
+            version: 1,
+            database: database.into(),
+            request_type,
+            request: request.into(),
+            parameters: BTreeMap::new(),
+            meta: BTreeMap::new(),
+        }
+    }
+
+    pub fn parameter(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.parameters.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn meta(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.meta.insert(name.into(), value.into());
+        self
+    }
+}
+
+impl Serialize for RequestHeader {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(7)?;
+        tuple.serialize_element(&self.version)?;
+        tuple.serialize_element(&REQUEST_TYPE)?;
+        tuple.serialize_element(&self.database)?;
+        tuple.serialize_element(&self.request_type)?;
+        tuple.serialize_element(&self.request)?;
+        tuple.serialize_element(&self.parameters)?;
+        tuple.serialize_element(&self.meta)?;
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for RequestHeader {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct HeaderVisitor;
+
+        impl<'de> Visitor<'de> for HeaderVisitor {
+            type Value = RequestHeader;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a request header array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<RequestHeader, A::Error> {
+                let version = next(&mut seq, 0, "7 elements")?;
+                let message_type: u64 = next(&mut seq, 1, "7 elements")?;
+                if message_type != REQUEST_TYPE {
+                    return Err(de::Error::custom(format!("expected request message type 1, got {}", message_type)));
+                }
+                Ok(RequestHeader {
+                    version,
+                    database: next(&mut seq, 2, "7 elements")?,
+                    request_type: next(&mut seq, 3, "7 elements")?,
+                    request: next(&mut seq, 4, "7 elements")?,
+                    parameters: next(&mut seq, 5, "7 elements")?,
+                    meta: next(&mut seq, 6, "7 elements")?,
+                })
+            }
+        }
+
+        deserializer.deserialize_tuple(7, HeaderVisitor)
+    }
+}
+
+// message types of responses, in their header: 2 for a (final) response, 3 for a response
+// chunk
+const RESPONSE_TYPES: [u64; 2] = [2, 3];
+
+/// Header of a response message, (de)serialized as the array
+/// `[version, type, responseCode, meta]`, and followed by the response body (if any) in the same
+/// message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResponseHeader {
+    pub version: u64,
+    /// 2 for a response, 3 for a response chunk.
+    pub message_type: u64,
+    /// HTTP status code of the response.
+    pub response_code: u16,
+    /// HTTP style headers, e.g. `content-type`.
+    pub meta: BTreeMap<String, String>,
+}
+
+impl ResponseHeader {
+    /// Decode the header at the start of `message`, returning it along with the bytes of the
+    /// body, which is empty or one or more values (see [`body_values`](Self::body_values)).
+    pub fn from_message(message: &[u8]) -> Result<(Self, &[u8])> {
+        crate::first_from_bytes(message)
+    }
+
+    /// Split the body returned by [`from_message`](Self::from_message) into its values.
+    pub fn body_values(body: &[u8]) -> impl Iterator<Item = Result<crate::Slice<'_>>> {
+        let mut rest = body;
+        std::iter::from_fn(move || {
+            if rest.is_empty() {
+                return None;
+            }
+            match crate::raw::split_value(rest) {
+                Ok((value, remaining)) => {
+                    rest = remaining;
+                    Some(Ok(crate::Slice::new(value)))
+                },
+                Err(e) => {
+                    rest = &[];
+                    Some(Err(e))
+                },
+            }
+        })
+    }
+
+    /// Whether the response code is 2xx.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.response_code)
+    }
+
+    /// Whether the response code is 4xx or 5xx, in which case the body usually holds an object
+    /// with `errorNum` and `errorMessage` attributes.
+    pub fn is_error(&self) -> bool {
+        self.response_code >= 400
+    }
+}
+
+impl Serialize for ResponseHeader {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(4)?;
+        tuple.serialize_element(&self.version)?;
+        tuple.serialize_element(&self.message_type)?;
+        tuple.serialize_element(&self.response_code)?;
+        tuple.serialize_element(&self.meta)?;
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ResponseHeader {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct HeaderVisitor;
+
+        impl<'de> Visitor<'de> for HeaderVisitor {
+            type Value = ResponseHeader;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a response header array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<ResponseHeader, A::Error> {
+                let version = next(&mut seq, 0, "4 elements")?;
+                let message_type = next(&mut seq, 1, "4 elements")?;
+                if !RESPONSE_TYPES.contains(&message_type) {
+                    return Err(de::Error::custom(format!("expected response message type 2 or 3, got {}", message_type)));
+                }
+                Ok(ResponseHeader {
+                    version,
+                    message_type,
+                    response_code: next(&mut seq, 2, "4 elements")?,
+                    // not sent by all server versions
+                    meta: seq.next_element()?.unwrap_or_default(),
+                })
+            }
+        }
+
+        deserializer.deserialize_tuple(4, HeaderVisitor)
+    }
+}
+
+// message type of authentication requests
+const AUTHENTICATION_TYPE: u64 = 1000;
+
+/// Authentication message, sent as a message of its own before any requests, and serialized as
+/// `[1, 1000, "plain", user, password]` or `[1, 1000, "jwt", token]`. The server answers with a
+/// [`ResponseHeader`], whose response code is 401 if authentication failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Authentication {
+    Plain { user: String, password: String },
+    Jwt { token: String },
+}
+
+impl Authentication {
+    pub fn plain(user: impl Into<String>, password: impl Into<String>) -> Self {
+        Authentication::Plain { user: user.into(), password: password.into() }
+    }
+
+    pub fn jwt(token: impl Into<String>) -> Self {
+        Authentication::Jwt { token: token.into() }
+    }
+}
+
+impl Serialize for Authentication {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Authentication::Plain { user, password } => {
+                let mut tuple = serializer.serialize_tuple(5)?;
+                tuple.serialize_element(&1)?;
+                tuple.serialize_element(&AUTHENTICATION_TYPE)?;
+                tuple.serialize_element("plain")?;
+                tuple.serialize_element(user)?;
+                tuple.serialize_element(password)?;
+                tuple.end()
+            },
+            Authentication::Jwt { token } => {
+                let mut tuple = serializer.serialize_tuple(4)?;
+                tuple.serialize_element(&1)?;
+                tuple.serialize_element(&AUTHENTICATION_TYPE)?;
+                tuple.serialize_element("jwt")?;
+                tuple.serialize_element(token)?;
+                tuple.end()
+            },
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Authentication {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct AuthenticationVisitor;
+
+        impl<'de> Visitor<'de> for AuthenticationVisitor {
+            type Value = Authentication;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an authentication message array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Authentication, A::Error> {
+                let _version: u64 = next(&mut seq, 0, "4 or 5 elements")?;
+                let message_type: u64 = next(&mut seq, 1, "4 or 5 elements")?;
+                if message_type != AUTHENTICATION_TYPE {
+                    return Err(de::Error::custom(format!("expected authentication message type 1000, got {}", message_type)));
+                }
+                let encryption: String = next(&mut seq, 2, "4 or 5 elements")?;
+                match encryption.as_str() {
+                    "plain" => Ok(Authentication::Plain {
+                        user: next(&mut seq, 3, "5 elements")?,
+                        password: next(&mut seq, 4, "5 elements")?,
+                    }),
+                    "jwt" => Ok(Authentication::Jwt { token: next(&mut seq, 3, "4 elements")? }),
+                    _ => Err(de::Error::unknown_variant(&encryption, &["plain", "jwt"])),
+                }
+            }
+        }
+
+        deserializer.deserialize_seq(AuthenticationVisitor)
+    }
+}
+
+// next element of a message array, at position `i`
+fn next<'de, A: SeqAccess<'de>, T: Deserialize<'de>>(seq: &mut A, i: usize, expected: &'static str) -> std::result::Result<T, A::Error> {
+    seq.next_element()?.ok_or_else(|| de::Error::invalid_length(i, &expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn header(chunk: &[u8]) -> (u32, u32, u64, u64) {
+        (
+            u32::from_le_bytes(chunk[..4].try_into().unwrap()),
+            u32::from_le_bytes(chunk[4..8].try_into().unwrap()),
+            u64::from_le_bytes(chunk[8..16].try_into().unwrap()),
+            u64::from_le_bytes(chunk[16..24].try_into().unwrap()),
+        )
+    }
+
+    #[test]
+    fn chunks() {
+        let message: Vec<u8> = (0..100).collect();
+        let chunks = chunk_message(7, &message, CHUNK_HEADER_SIZE + 40).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(header(&chunks[0]), (64, 3 << 1 | 1, 7, 100));
+        assert_eq!(header(&chunks[1]), (64, 1 << 1, 7, 100));
+        assert_eq!(header(&chunks[2]), (44, 2 << 1, 7, 100));
+        let data: Vec<u8> = chunks.iter().flat_map(|c| c[CHUNK_HEADER_SIZE..].to_vec()).collect();
+        assert_eq!(data, message);
+
+        let chunks = chunk_message(1, &message, DEFAULT_MAX_CHUNK_SIZE).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(header(&chunks[0]), (124, 1 << 1 | 1, 1, 100));
+        let chunks = chunk_message(1, &message, CHUNK_HEADER_SIZE + 50).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].len(), CHUNK_HEADER_SIZE + 50);
+    }
+
+    #[test]
+    fn codec() {
+        let codec = MessageCodec::new().max_chunk_size(CHUNK_HEADER_SIZE + 10);
+        let first: Vec<u8> = (0..35).collect();
+        let second = crate::to_bytes(&"hello").unwrap();
+        let mut stream = codec.encode(1, &first).unwrap();
+        stream.extend(codec.encode(2, &second).unwrap());
+        stream.extend(codec.encode(3, &[]).unwrap());
+
+        let mut codec = codec;
+        let mut messages = Vec::new();
+        for bytes in stream.chunks(5) {
+            codec.feed(bytes);
+            while let Some(message) = codec.decode().unwrap() {
+                messages.push(message);
+            }
+        }
+        assert_eq!(messages, vec![(1, first.clone()), (2, second.clone()), (3, Vec::new())]);
+
+        // interleaved, and out of order
+        let chunks = chunk_message(4, &first, CHUNK_HEADER_SIZE + 10).unwrap();
+        for chunk in [&chunks[3], &chunks[1]] {
+            codec.feed(chunk);
+        }
+        codec.feed(&chunk_message(5, &second, 100).unwrap()[0]);
+        assert_eq!(codec.decode().unwrap(), Some((5, second)));
+        codec.feed(&chunks[2]);
+        codec.feed(&chunks[0]);
+        assert_eq!(codec.decode().unwrap(), Some((4, first)));
+        assert_eq!(codec.decode().unwrap(), None);
+
+        let mut codec = MessageCodec::new();
+        codec.feed(&[4, 0, 0, 0]);
+        codec.feed(&[0; 20]);
+        assert!(codec.decode().is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn tokio_codec() {
+        use bytes::BytesMut;
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let mut codec = MessageCodec::new().max_chunk_size(CHUNK_HEADER_SIZE + 10);
+        let first: Vec<u8> = (0..35).collect();
+        let second = crate::to_bytes(&"hello").unwrap();
+        let mut stream = BytesMut::new();
+        Encoder::encode(&mut codec, (1, first.clone()), &mut stream).unwrap();
+        Encoder::encode(&mut codec, (2, second.clone()), &mut stream).unwrap();
+        assert_eq!(stream, [codec.encode(1, &first).unwrap(), codec.encode(2, &second).unwrap()].concat());
+
+        let mut messages = Vec::new();
+        let mut src = BytesMut::new();
+        for bytes in stream.chunks(7) {
+            src.extend_from_slice(bytes);
+            while let Some(message) = Decoder::decode(&mut codec, &mut src).unwrap() {
+                messages.push(message);
+            }
+        }
+        assert_eq!(messages, vec![(1, first.clone()), (2, second)]);
+        assert_eq!(codec.decode_eof(&mut src).unwrap(), None);
+
+        // the stream ends part way through a message
+        let mut src = BytesMut::from(&codec.encode(3, &first).unwrap()[..40]);
+        assert_eq!(Decoder::decode(&mut codec, &mut src).unwrap(), None);
+        assert_eq!(codec.decode_eof(&mut src), Err(ErrorKind::Eof.into()));
+    }
+
+    #[test]
+    fn request_header() {
+        let header = RequestHeader::new("_system", RequestType::Post, "/_api/cursor").meta("content-type", "application/x-velocypack");
+        let bytes = crate::to_bytes(&header).unwrap();
+        let expected = serde_json::json!([1, 1, "_system", 2, "/_api/cursor", {}, {"content-type": "application/x-velocypack"}]);
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&bytes).unwrap(), expected);
+        assert_eq!(crate::from_bytes::<RequestHeader>(&bytes).unwrap(), header);
+
+        let response = crate::to_bytes(&serde_json::json!([1, 2, 200, {}])).unwrap();
+        assert!(crate::from_bytes::<RequestHeader>(&response).is_err());
+        let unknown = crate::to_bytes(&serde_json::json!([1, 1, "_system", 9, "/", {}, {}])).unwrap();
+        assert!(crate::from_bytes::<RequestHeader>(&unknown).is_err());
+    }
+
+    #[test]
+    fn response_header() {
+        let mut message = crate::to_bytes(&serde_json::json!([1, 2, 404, {"content-type": "application/x-velocypack"}])).unwrap();
+        message.extend(crate::to_bytes(&serde_json::json!({"error": true, "errorNum": 1202})).unwrap());
+        message.push(0x18);
+        let (header, body) = ResponseHeader::from_message(&message).unwrap();
+        assert_eq!(header.response_code, 404);
+        assert!(header.is_error() && !header.is_success());
+        assert_eq!(header.meta["content-type"], "application/x-velocypack");
+        let values: Vec<_> = ResponseHeader::body_values(body).collect::<Result<_>>().unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].get("errorNum").unwrap().unwrap().deserialize::<u64>().unwrap(), 1202);
+        assert_eq!(crate::from_bytes::<ResponseHeader>(&crate::to_bytes(&header).unwrap()).unwrap(), header);
+
+        let message = crate::to_bytes(&(1, 3, 200)).unwrap();
+        let (header, body) = ResponseHeader::from_message(&message).unwrap();
+        assert!(header.is_success() && header.meta.is_empty() && body.is_empty());
+        assert!(ResponseHeader::from_message(&crate::to_bytes(&(1, 1, 200, ())).unwrap()).is_err());
+        assert!(ResponseHeader::body_values(&[0x31, 0x15]).nth(1).unwrap().is_err());
+    }
+
+    #[test]
+    fn authentication() {
+        let plain = Authentication::plain("root", "secret");
+        let bytes = crate::to_bytes(&plain).unwrap();
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&bytes).unwrap(), serde_json::json!([1, 1000, "plain", "root", "secret"]));
+        assert_eq!(crate::from_bytes::<Authentication>(&bytes).unwrap(), plain);
+
+        let jwt = Authentication::jwt("eyJhbGciOi");
+        let bytes = crate::to_bytes(&jwt).unwrap();
+        assert_eq!(crate::from_bytes::<serde_json::Value>(&bytes).unwrap(), serde_json::json!([1, 1000, "jwt", "eyJhbGciOi"]));
+        assert_eq!(crate::from_bytes::<Authentication>(&bytes).unwrap(), jwt);
+
+        assert!(crate::from_bytes::<Authentication>(&crate::to_bytes(&(1, 1000, "basic", "x")).unwrap()).is_err());
+        assert!(crate::from_bytes::<Authentication>(&crate::to_bytes(&(1, 1000, "plain", "root")).unwrap()).is_err());
+    }
+
+    #[test]
+    fn empty_and_too_small() {
+        let chunks = chunk_message(2, &[], 100).unwrap();
+        assert_eq!(chunks, vec![[24, 0, 0, 0, 3, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0].to_vec()]);
+        assert!(chunk_message(2, &[0x18], CHUNK_HEADER_SIZE).is_err());
+    }
+}